@@ -0,0 +1,156 @@
+use rolypoly::archive::ArchiveManager;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+const ENTRY_COUNT: usize = 200_000;
+
+/// How much faster streaming the central directory must be than reading it through the
+/// zip crate's index; override with ROLYPOLY_LIST_MIN_SPEEDUP on slow or noisy machines
+const MIN_SPEEDUP: f64 = 1.3;
+/// Streaming peak memory must stay below this fraction of the indexed read's; override
+/// with ROLYPOLY_LIST_MAX_MEMORY_RATIO
+const MAX_MEMORY_RATIO: f64 = 1.0;
+
+/// System allocator that tracks live bytes and the peak since the last reset
+struct CountingAllocator;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(live, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Build an archive with many tiny stored entries spread over nested directories
+fn create_large_index_archive(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for i in 0..ENTRY_COUNT {
+        zip.start_file(format!("dir_{}/sub_{}/file_{i}.txt", i % 100, i % 7), options)?;
+        zip.write_all(b"x")?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Run `f`, printing and returning how long it took and the most memory it had
+/// allocated at once beyond what was live beforehand
+fn measure<T>(label: &str, f: impl FnOnce() -> T) -> (T, Duration, usize) {
+    let baseline = LIVE.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+    let start = Instant::now();
+    let value = f();
+    let elapsed = start.elapsed();
+    let peak = PEAK.load(Ordering::Relaxed) - baseline;
+    println!(
+        "{label:<24} {:>8.1} ms {:>10.1} MiB peak",
+        elapsed.as_secs_f64() * 1000.0,
+        peak as f64 / (1024.0 * 1024.0)
+    );
+    (value, elapsed, peak)
+}
+
+fn env_threshold(name: &str, default: f64) -> Result<f64, Box<dyn std::error::Error>> {
+    match std::env::var(name) {
+        Ok(value) => Ok(value.parse().map_err(|e| format!("{name}={value}: {e}"))?),
+        Err(_) => Ok(default),
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let min_speedup = env_threshold("ROLYPOLY_LIST_MIN_SPEEDUP", MIN_SPEEDUP)?;
+    let max_memory_ratio = env_threshold("ROLYPOLY_LIST_MAX_MEMORY_RATIO", MAX_MEMORY_RATIO)?;
+    let temp_dir = TempDir::new()?;
+    let archive_path = temp_dir.path().join("large_index.zip");
+
+    println!("Creating archive with {ENTRY_COUNT} entries...");
+    create_large_index_archive(&archive_path)?;
+
+    let manager = ArchiveManager::new();
+    let (listed, list_time, list_peak) = measure("list", || manager.list_archive(&archive_path));
+    let (counted, callback_time, callback_peak) = measure("list (callback)", || {
+        let mut count = 0;
+        manager.list_archive_with_callback(&archive_path, |_| {
+            count += 1;
+            Ok(())
+        })?;
+        anyhow::Ok(count)
+    });
+    assert_eq!(listed?.len(), ENTRY_COUNT);
+    assert_eq!(counted?, ENTRY_COUNT);
+
+    let (buffered, buffered_time, buffered_peak) =
+        measure("stats (buffered)", || manager.get_archive_stats(&archive_path));
+    let (streaming, streaming_time, streaming_peak) =
+        measure("stats (streaming)", || manager.get_archive_stats_streaming(&archive_path));
+    let (buffered, streaming) = (buffered?, streaming?);
+    assert_eq!(buffered.file_count, streaming.file_count);
+    assert_eq!(buffered.total_compressed_size, streaming.total_compressed_size);
+
+    let limits = (min_speedup, max_memory_ratio);
+    check("listing", (list_time, list_peak), (callback_time, callback_peak), limits)?;
+    check(
+        "stats",
+        (buffered_time, buffered_peak),
+        (streaming_time, streaming_peak),
+        limits,
+    )
+}
+
+/// Fail unless the `streamed` run beat the `indexed` one by the required speedup and
+/// stayed below the allowed share of its peak memory
+fn check(
+    label: &str,
+    (indexed_time, indexed_peak): (Duration, usize),
+    (streamed_time, streamed_peak): (Duration, usize),
+    (min_speedup, max_memory_ratio): (f64, f64),
+) -> Result<(), Box<dyn std::error::Error>> {
+    let speedup = indexed_time.as_secs_f64() / streamed_time.as_secs_f64();
+    let memory_ratio = streamed_peak as f64 / indexed_peak as f64;
+    println!("{label}: streaming speedup {speedup:.2}x, peak memory ratio {memory_ratio:.3}");
+    if speedup < min_speedup {
+        return Err(format!(
+            "{label}: streaming the central directory is only {speedup:.2}x faster than the \
+             indexed read (need {min_speedup}x, ROLYPOLY_LIST_MIN_SPEEDUP)"
+        )
+        .into());
+    }
+    if memory_ratio >= max_memory_ratio {
+        return Err(format!(
+            "{label}: streaming the central directory peaked at {memory_ratio:.3}x the memory \
+             of the indexed read (must stay below {max_memory_ratio}, \
+             ROLYPOLY_LIST_MAX_MEMORY_RATIO)"
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Benchmark failed: {}", e);
+        std::process::exit(1);
+    }
+}
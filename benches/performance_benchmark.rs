@@ -1,3 +1,4 @@
+use rolypoly::bench::{BenchmarkResult, write_summary};
 use std::env;
 use std::fs;
 use std::path::Path;
@@ -5,48 +6,6 @@ use std::process::Command;
 use std::time::Instant;
 use tempfile::TempDir;
 
-#[derive(Debug)]
-struct BenchmarkResult {
-    operation: String,
-    tool: String,
-    file_count: usize,
-    total_size_mb: f64,
-    time_ms: u128,
-    throughput_mbps: f64,
-    compression_ratio: Option<f64>,
-}
-
-impl BenchmarkResult {
-    fn new(
-        operation: String,
-        tool: String,
-        file_count: usize,
-        total_size_mb: f64,
-        time_ms: u128,
-    ) -> Self {
-        let throughput_mbps = if time_ms > 0 {
-            (total_size_mb * 1000.0) / time_ms as f64
-        } else {
-            0.0
-        };
-
-        Self {
-            operation,
-            tool,
-            file_count,
-            total_size_mb,
-            time_ms,
-            throughput_mbps,
-            compression_ratio: None,
-        }
-    }
-
-    fn with_compression_ratio(mut self, ratio: f64) -> Self {
-        self.compression_ratio = Some(ratio);
-        self
-    }
-}
-
 fn create_test_files(dir: &Path) -> Result<f64, Box<dyn std::error::Error>> {
     let mut total_size = 0u64;
 
@@ -311,44 +270,7 @@ fn run_benchmarks() -> Result<Vec<BenchmarkResult>, Box<dyn std::error::Error>>
 }
 
 fn print_summary(results: &[BenchmarkResult]) {
-    println!("\n{}", "=".repeat(80));
-    println!("PERFORMANCE BENCHMARK SUMMARY");
-    println!("{}", "=".repeat(80));
-
-    println!(
-        "{:<12} {:<12} {:<8} {:<10} {:<8} {:<10} {:<10}",
-        "Tool", "Operation", "Files", "Size(MB)", "Time(ms)", "MB/s", "Ratio"
-    );
-    println!("{}", "-".repeat(80));
-
-    for result in results {
-        let ratio_str = if let Some(ratio) = result.compression_ratio {
-            format!("{:.1}%", ratio * 100.0)
-        } else {
-            "-".to_string()
-        };
-
-        println!(
-            "{:<12} {:<12} {:<8} {:<10.2} {:<8} {:<10.2} {:<10}",
-            result.tool,
-            result.operation,
-            result.file_count,
-            result.total_size_mb,
-            result.time_ms,
-            result.throughput_mbps,
-            ratio_str
-        );
-    }
-
-    println!("\nSystem Information:");
-    println!("- Platform: {}", std::env::consts::OS);
-    println!("- Architecture: {}", std::env::consts::ARCH);
-
-    if let Ok(output) = Command::new("sysctl").args(["-n", "hw.ncpu"]).output()
-        && let Ok(cpu_count) = String::from_utf8(output.stdout)
-    {
-        println!("- CPU Cores: {}", cpu_count.trim());
-    }
+    write_summary(&mut std::io::stdout(), results).expect("write summary");
 }
 
 #[test]
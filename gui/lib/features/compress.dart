@@ -125,6 +125,11 @@ class _CompressScreenState extends State<CompressScreen> {
     }
   }
 
+  Future<void> _reveal(String path) async {
+    final r = await _cli.reveal(path);
+    if (r.exitCode != 0) setState(() => _error = (r.stderr as String).trim());
+  }
+
   @override
   Widget build(BuildContext context) {
     return Padding(
@@ -204,6 +209,12 @@ class _CompressScreenState extends State<CompressScreen> {
           const SizedBox(height: 8),
           Row(children: [
             Expanded(child: Text(_status)),
+            if (!kIsWeb && _status == 'Done' && _archivePath != null)
+              TextButton.icon(
+                onPressed: () => _reveal(_archivePath!),
+                icon: const Icon(Icons.folder_open),
+                label: const Text('Show in Folder'),
+              ),
             if (((!kIsWeb && _inputs.isNotEmpty) || (kIsWeb && _inputsWeb.isNotEmpty)) && !_running)
               TextButton.icon(
                 onPressed: () => setState(() {
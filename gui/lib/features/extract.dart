@@ -92,6 +92,11 @@ class _ExtractScreenState extends State<ExtractScreen> {
     }
   }
 
+  Future<void> _reveal(String path) async {
+    final r = await _cli.reveal(path);
+    if (r.exitCode != 0) setState(() => _error = (r.stderr as String).trim());
+  }
+
   @override
   Widget build(BuildContext context) {
     return Padding(
@@ -192,7 +197,15 @@ class _ExtractScreenState extends State<ExtractScreen> {
           const SizedBox(height: 12),
           if (!kIsWeb) LinearProgressIndicator(value: _running ? null : (_pct == 0 ? null : _pct)),
           const SizedBox(height: 8),
-          Text(_status),
+          Row(children: [
+            Expanded(child: Text(_status)),
+            if (!kIsWeb && _status == 'Done' && _outDir != null)
+              TextButton.icon(
+                onPressed: () => _reveal(_outDir!),
+                icon: const Icon(Icons.folder_open),
+                label: const Text('Open Folder'),
+              ),
+          ]),
           if (_error != null) Text(_error!, style: const TextStyle(color: Colors.red)),
         ]),
       );
@@ -93,6 +93,12 @@ class RolyPolyCli {
     return Process.run(binary, args);
   }
 
+  /// Show [path] in the system file manager once an operation has finished
+  Future<ProcessResult> reveal(String path) {
+    if (kIsWeb) throw UnsupportedError('Process execution is unavailable on web');
+    return Process.run(binary, ['reveal', path]);
+  }
+
   /// Example of streaming progress (when `--json --progress` is implemented in CLI)
   Stream<Map<String, dynamic>> streamCreate(String archive, List<String> files) async* {
     if (kIsWeb) throw UnsupportedError('Streaming process is unavailable on web');
@@ -1,13 +1,348 @@
-use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::central_dir::{self, TrimmedArchive};
+use crate::progress::{JsonProgress, ProgressReporter};
+use crate::split::VolumeReader;
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufReader, Read};
-use std::path::Path;
-use std::time::Instant;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 use walkdir::WalkDir;
-use zip::write::SimpleFileOptions;
-use zip::{ZipArchive, ZipWriter};
+use zip::read::ZipFile;
+use zip::write::{FileOptionExtension, FileOptions, FullFileOptions, SimpleFileOptions};
+use zip::{AesMode, HasZipMetadata, ZipArchive, ZipWriter};
+
+/// How to resolve archive entries that share the same name
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DuplicatePolicy {
+    /// Use the last entry with a given name (matches most unzip tools)
+    #[default]
+    KeepLast,
+    /// Use the first entry with a given name
+    KeepFirst,
+    /// Refuse to process archives with repeated names
+    Error,
+}
+
+/// Archive failures that callers may want to handle specifically
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// The destination ran out of space; any partial output has been removed
+    DiskFull {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The progress reporter asked for the operation to stop
+    Cancelled,
+    /// Extracted files whose SHA-256 differs from the archive's `SHA256SUMS`
+    ChecksumMismatch { names: Vec<String> },
+    /// An extracted path is longer than the platform or file system allows
+    PathTooLong {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+impl ArchiveError {
+    /// Stable machine-readable code for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            ArchiveError::DiskFull { .. } => "DISK_FULL",
+            ArchiveError::Cancelled => "CANCELLED",
+            ArchiveError::ChecksumMismatch { .. } => "CHECKSUM_MISMATCH",
+            ArchiveError::PathTooLong { .. } => "PATH_TOO_LONG",
+        }
+    }
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveError::DiskFull { path, source } => {
+                write!(f, "No space left while writing {}: {}", path.display(), source)
+            }
+            ArchiveError::Cancelled => write!(f, "Operation cancelled"),
+            ArchiveError::ChecksumMismatch { names } => {
+                write!(f, "Checksum mismatch for {}", names.join(", "))
+            }
+            ArchiveError::PathTooLong { path, source } => write!(
+                f,
+                "Path too long to extract ({} characters): {}: {}. Extract to a shorter output \
+                 directory, or use --flatten to drop the entries' folders",
+                path.as_os_str().len(),
+                path.display(),
+                source
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ArchiveError::DiskFull { source, .. } | ArchiveError::PathTooLong { source, .. } => {
+                Some(source)
+            }
+            ArchiveError::Cancelled | ArchiveError::ChecksumMismatch { .. } => None,
+        }
+    }
+}
+
+/// What to do when an extracted file would overwrite one already written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConflictPolicy {
+    /// Stop with an error
+    #[default]
+    Error,
+    /// Write to a new name with `-1`, `-2`, ... appended to the file stem
+    Rename,
+    /// Keep the existing file and skip the entry
+    Skip,
+}
+
+/// Encryption scheme applied to an archive entry
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    clap::ValueEnum,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum EncryptionMethod {
+    /// Legacy PKWARE encryption; weak, only for tools that can't open AES entries
+    #[value(name = "zipcrypto")]
+    ZipCrypto,
+    Aes128,
+    Aes192,
+    Aes256,
+}
+
+impl EncryptionMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EncryptionMethod::ZipCrypto => "zipcrypto",
+            EncryptionMethod::Aes128 => "aes128",
+            EncryptionMethod::Aes192 => "aes192",
+            EncryptionMethod::Aes256 => "aes256",
+        }
+    }
+
+    fn aes_mode(&self) -> Option<AesMode> {
+        match self {
+            EncryptionMethod::ZipCrypto => None,
+            EncryptionMethod::Aes128 => Some(AesMode::Aes128),
+            EncryptionMethod::Aes192 => Some(AesMode::Aes192),
+            EncryptionMethod::Aes256 => Some(AesMode::Aes256),
+        }
+    }
+}
+
+/// Checksum used by the `hash` command
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum HashAlgorithm {
+    /// SHA-256, as 64 hex characters
+    #[default]
+    Sha256,
+    /// CRC-32 as stored in ZIP headers, as 8 hex characters
+    Crc32,
+}
+
+impl HashAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Crc32 => "crc32",
+        }
+    }
+}
+
+/// How the `hash` command renders digest bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum HashEncoding {
+    /// Lowercase hexadecimal
+    #[default]
+    Hex,
+    /// Standard padded base64 of the raw digest, as used by Subresource Integrity
+    Base64,
+}
+
+impl HashEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashEncoding::Hex => "hex",
+            HashEncoding::Base64 => "base64",
+        }
+    }
+
+    pub fn encode(&self, digest: &[u8]) -> String {
+        use base64::Engine;
+
+        match self {
+            HashEncoding::Hex => digest.iter().map(|b| format!("{b:02x}")).collect(),
+            HashEncoding::Base64 => base64::engine::general_purpose::STANDARD.encode(digest),
+        }
+    }
+}
+
+/// Formats that are already compressed, so deflating them again wastes time
+pub const DEFAULT_STORE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "webp", "heic", "avif", "mp3", "m4a", "aac", "ogg", "flac", "mp4",
+    "m4v", "mov", "mkv", "webm", "avi", "zip", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "jar",
+    "apk", "docx", "xlsx", "pptx", "odt", "epub",
+];
+
+/// Entry added by `embed_checksums`, in the format read by `sha256sum -c`
+pub const CHECKSUMS_ENTRY: &str = "SHA256SUMS";
+
+/// File in the output directory listing entries finished by a `resume` extraction,
+/// one name per line; removed once the extraction completes
+pub const RESUME_STATE_FILE: &str = ".rolypoly-resume";
+
+/// Code page for entry names stored without the UTF-8 flag
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NameEncoding {
+    /// The ZIP specification's default
+    #[default]
+    Cp437,
+    /// A legacy code page used by the tool that wrote the archive, e.g. Shift_JIS or GBK
+    Legacy(&'static encoding_rs::Encoding),
+}
+
+impl NameEncoding {
+    /// Look up an encoding by WHATWG label (`shift_jis`, `gbk`, `windows-1251`, ...) or
+    /// `cp437`
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.to_ascii_lowercase().as_str() {
+            "cp437" | "ibm437" | "437" => Some(NameEncoding::Cp437),
+            other => encoding_rs::Encoding::for_label(other.as_bytes()).map(NameEncoding::Legacy),
+        }
+    }
+
+    /// Decode a name stored without the UTF-8 flag. Names that are valid UTF-8 are
+    /// taken as such, since many tools write UTF-8 without setting the flag.
+    pub fn decode<'a>(&self, raw: &'a [u8]) -> Cow<'a, str> {
+        if let Ok(name) = std::str::from_utf8(raw) {
+            return Cow::Borrowed(name);
+        }
+        match self {
+            NameEncoding::Cp437 => Cow::Owned(
+                raw.iter()
+                    .map(|&b| {
+                        if b < 0x80 {
+                            b as char
+                        } else {
+                            CP437_HIGH[b as usize - 0x80]
+                        }
+                    })
+                    .collect(),
+            ),
+            NameEncoding::Legacy(encoding) => encoding.decode_without_bom_handling(raw).0,
+        }
+    }
+}
+
+/// Characters for CP437 bytes 0x80-0xFF; the lower half is ASCII
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', 'É', 'æ', 'Æ',
+    'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', 'á', 'í', 'ó', 'ú', 'ñ', 'Ñ',
+    'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕',
+    '╣', '║', '╗', '╝', '╜', '╛', '┐', '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦',
+    '╠', '═', '╬', '╧', '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐',
+    '▀', 'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', '≡', '±',
+    '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// Named compression settings for `create --preset`; an explicit level wins
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum CompressionPreset {
+    /// Deflate at level 1
+    Fast,
+    /// Deflate at level 6
+    Balanced,
+    /// Deflate at level 9
+    Max,
+}
+
+impl CompressionPreset {
+    pub fn level(self) -> i32 {
+        match self {
+            CompressionPreset::Fast => 1,
+            CompressionPreset::Balanced => 6,
+            CompressionPreset::Max => 9,
+        }
+    }
+}
+
+/// One line of a `create --rules` file: entries whose name matches `pattern`
+/// (gitignore-style, so `*.txt` matches at any depth) are written with `method` and,
+/// for deflate, `level`
+#[derive(Clone, Debug)]
+pub struct CompressionRule {
+    pub pattern: String,
+    pub method: zip::CompressionMethod,
+    pub level: Option<i64>,
+    matcher: Gitignore,
+}
+
+impl CompressionRule {
+    fn matches(&self, name: &str) -> bool {
+        self.matcher.matched(name, false).is_ignore()
+    }
+}
+
+/// Parse compression rules, one `<glob> <store|deflate> [level]` per line. Blank
+/// lines and lines starting with `#` are skipped.
+pub fn parse_compression_rules(text: &str) -> Result<Vec<CompressionRule>> {
+    let mut rules = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: &str| anyhow::anyhow!("Rule {}: {reason}: {line}", number + 1);
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (method, level) = match fields[1..] {
+            ["store"] => (zip::CompressionMethod::Stored, None),
+            ["deflate"] => (zip::CompressionMethod::Deflated, None),
+            ["deflate", level] => match level.parse::<i64>() {
+                Ok(level @ 0..=9) => (zip::CompressionMethod::Deflated, Some(level)),
+                _ => return Err(invalid("deflate levels run from 0 to 9")),
+            },
+            ["store", _] => return Err(invalid("store takes no level")),
+            _ => return Err(invalid("expected `<glob> <store|deflate> [level]`")),
+        };
+        let mut builder = GitignoreBuilder::new("");
+        builder.add_line(None, fields[0]).map_err(|e| invalid(&e.to_string()))?;
+        rules.push(CompressionRule {
+            pattern: fields[0].to_string(),
+            method,
+            level,
+            matcher: builder.build()?,
+        });
+    }
+    Ok(rules)
+}
+
+/// Whether extraction replaces files that already exist in the output directory
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OverwritePolicy {
+    /// Always replace existing files
+    #[default]
+    Always,
+    /// Keep existing files and skip their entries
+    Never,
+    /// Replace a file only when the entry's modification time is newer
+    IfNewer,
+}
 
 #[derive(Clone, Debug)]
 pub struct ArchiveOptions {
@@ -15,8 +350,109 @@ pub struct ArchiveOptions {
     pub auto_store: bool,
     // if estimated entropy > threshold -> store
     pub store_entropy_threshold: f64,
+    // if deflating a file's prefix keeps at least this fraction of its size -> store
+    pub store_ratio_threshold: f64,
     // buffer size used for I/O copies
     pub io_buffer_size: usize,
+    // policy for repeated entry names when reading archives
+    pub on_duplicate: DuplicatePolicy,
+    // encrypt new entries and decrypt existing ones with this password
+    pub password: Option<String>,
+    // scheme used when encrypting new entries
+    pub encryption: EncryptionMethod,
+    // warn after creation when entry names differ only by case
+    pub warn_case_collisions: bool,
+    // refuse to extract archives whose entry names differ only by case
+    pub check_case_collisions: bool,
+    // drop directory components and write every file directly into the output dir
+    pub flatten: bool,
+    // policy for flattened files that land on an existing path
+    pub on_conflict: ConflictPolicy,
+    // skip dotfiles (and Windows hidden files) found while walking directories
+    pub exclude_hidden: bool,
+    // name file inputs by the path given (minus any root, `.` and `..` parts) instead
+    // of their file name alone
+    pub store_full_path: bool,
+    // gitignore-style patterns, relative to each directory input, pruned while walking
+    pub exclude_patterns: Vec<String>,
+    // also honour `.gitignore` files found while walking, each applying below its directory
+    pub respect_gitignore: bool,
+    // only walk this many levels below each directory input; 1 keeps its direct children
+    pub max_depth: Option<usize>,
+    // per-entry method and level; the first rule matching an entry name wins over the
+    // global level, `store_extensions` and `auto_store`
+    pub compression_rules: Vec<CompressionRule>,
+    // store entries for empty directories so they survive a round trip
+    pub preserve_empty_dirs: bool,
+    // write explicit directory entries at all; without them the tree is implied by
+    // file names alone and empty directories are lost
+    pub dir_entries: bool,
+    // extra attempts for file opens that fail with transient errors
+    pub retries: u32,
+    // fixed Unix modes for extracted files and directories, overriding stored bits
+    pub file_mode: Option<u32>,
+    pub dir_mode: Option<u32>,
+    // leave out `__MACOSX/` and `._*` AppleDouble entries when listing or extracting
+    pub skip_mac_metadata: bool,
+    // only archive files modified strictly after / before these times
+    pub newer_than: Option<SystemTime>,
+    pub older_than: Option<SystemTime>,
+    // stamp every created entry with this time instead of its source's modification time
+    pub mtime: Option<SystemTime>,
+    // only archive files whose size in bytes falls within these bounds (inclusive)
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    // extensions (without the dot, case-insensitive) that are always stored uncompressed
+    pub store_extensions: Vec<String>,
+    // deflate files that are already archives by their magic bytes (ZIP, gzip, ...)
+    // instead of storing them
+    pub force_recompress: bool,
+    // skip reading holes in sparse files (Linux; elsewhere files are read normally)
+    pub sparse: bool,
+    // whether extracted files replace ones already on disk
+    pub overwrite: OverwritePolicy,
+    // extract or list only these entries (names ending in `/` select everything below);
+    // empty = all
+    pub entries: Vec<String>,
+    // archive comment written when creating
+    pub comment: Option<String>,
+    // append a SHA256SUMS entry covering every file once an archive is created
+    pub embed_checksums: bool,
+    // leave out input files that can't be opened, reporting them, instead of failing
+    pub keep_going: bool,
+    // store each input's uid/gid when creating and give extracted files those owners
+    // again (Unix; restoring them normally needs root)
+    pub preserve_owner: bool,
+    // store files whose content was already archived as symlinks to the first copy
+    pub dedup: bool,
+    // archive what a symlink named as an input points to instead of the link itself
+    pub dereference: bool,
+    // fail validation on suspicious entry names instead of only warning about them, and
+    // on local headers that disagree with the central directory
+    pub strict: bool,
+    // cut created archives into volumes of at most this many bytes (see `split`)
+    pub split_size: Option<u64>,
+    // check extracted files against the archive's SHA256SUMS entry, if it has one
+    pub verify_checksums: bool,
+    // record finished entries in RESUME_STATE_FILE and skip them on a re-run when the
+    // file on disk still matches the entry's CRC
+    pub resume: bool,
+    // extract into a temporary directory next to the output and move the result into
+    // place only once every entry has been written
+    pub atomic: bool,
+    // accept data before the ZIP (self-extractor stubs, signing blocks) in strict
+    // validation
+    pub allow_prepended: bool,
+    // code page of entry names that lack the UTF-8 flag
+    pub name_encoding: NameEncoding,
+    // flag non-ASCII entry names as UTF-8 when creating; when off, such names are
+    // refused, since the writer has no other way to store them
+    pub utf8_names: bool,
+    // create missing directories above a new archive instead of failing
+    pub create_parents: bool,
+    // read archives by the EOCD record that closes their central directory, ignoring
+    // bytes after it and a recorded comment length that runs past the end of the file
+    pub ignore_comment_mismatch: bool,
 }
 
 impl Default for ArchiveOptions {
@@ -25,7 +461,53 @@ impl Default for ArchiveOptions {
             compression_level: None,
             auto_store: true,
             store_entropy_threshold: 7.8,
+            store_ratio_threshold: 0.9,
             io_buffer_size: 256 * 1024,
+            on_duplicate: DuplicatePolicy::default(),
+            password: None,
+            encryption: EncryptionMethod::Aes256,
+            warn_case_collisions: false,
+            check_case_collisions: false,
+            flatten: false,
+            on_conflict: ConflictPolicy::default(),
+            exclude_hidden: false,
+            store_full_path: false,
+            exclude_patterns: Vec::new(),
+            respect_gitignore: false,
+            max_depth: None,
+            compression_rules: Vec::new(),
+            preserve_empty_dirs: true,
+            dir_entries: true,
+            retries: 0,
+            file_mode: None,
+            dir_mode: None,
+            skip_mac_metadata: false,
+            newer_than: None,
+            older_than: None,
+            mtime: None,
+            min_size: None,
+            max_size: None,
+            store_extensions: DEFAULT_STORE_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+            force_recompress: false,
+            sparse: false,
+            overwrite: OverwritePolicy::default(),
+            entries: Vec::new(),
+            comment: None,
+            embed_checksums: false,
+            split_size: None,
+            strict: false,
+            keep_going: false,
+            preserve_owner: false,
+            dedup: false,
+            dereference: false,
+            verify_checksums: false,
+            resume: false,
+            atomic: false,
+            allow_prepended: false,
+            name_encoding: NameEncoding::default(),
+            utf8_names: true,
+            create_parents: false,
+            ignore_comment_mismatch: false,
         }
     }
 }
@@ -53,128 +535,422 @@ impl ArchiveManager {
 
     /// Validate the integrity of a ZIP archive
     pub fn validate_archive<P: AsRef<Path>>(&self, archive_path: P) -> Result<bool> {
-        let file = File::open(archive_path.as_ref())?;
+        let mut reporter = crate::progress::default_reporter();
+        self.validate_archive_with_reporter(archive_path, reporter.as_mut())
+    }
+
+    /// Validate the integrity of a ZIP archive, reporting progress to `reporter`
+    pub fn validate_archive_with_reporter<P: AsRef<Path>>(
+        &self,
+        archive_path: P,
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<bool> {
+        let _span =
+            tracing::info_span!("validate", archive = %archive_path.as_ref().display()).entered();
+        let file = self.open_archive(archive_path.as_ref())?;
         let mut archive = ZipArchive::new(BufReader::new(file))?;
 
         let mode = crate::progress::output_mode();
         println!("→ Validating: {}", archive_path.as_ref().display());
         let start = Instant::now();
         let total = archive.len() as u64;
-        let pb = if mode.progress && !mode.json {
-            let pb = ProgressBar::new(total);
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template(
-                        "{spinner:.green} [{elapsed_precise}] {wide_bar:.cyan/blue} {pos:>5}/{len:<5} {percent:>3}% {eta_precise} | {msg}"
-                    )
-                    .unwrap()
-                    .progress_chars("█· "),
-            );
-            Some(pb)
-        } else {
-            if mode.json {
-                crate::progress::print_json(&serde_json::json!({
-                    "event":"start","op":"validate","archive": archive_path.as_ref().display().to_string(),"total": total
-                }));
-            }
-            None
+        tracing::debug!(entries = total, "validating archive");
+        reporter.start(total);
+        if mode.json {
+            crate::progress::print_json(&serde_json::json!({
+                "event":"start","op":"validate","archive": archive_path.as_ref().display().to_string(),"total": total
+            }));
+        }
+        let mut json_progress = match mode.json {
+            true => Some(JsonProgress::new(total, uncompressed_total(&mut archive)?)),
+            false => None,
         };
+        let mut suspicious_names = 0;
+        let mut corrupt = 0;
+        // Data ahead of the archive is normal for self-extractors, but hides what a
+        // strict check should see unless it was expected
+        let prepended = archive.offset();
+        let unexpected_prefix = self.opts.strict && !self.opts.allow_prepended && prepended > 0;
+        if unexpected_prefix {
+            eprintln!(
+                "warning: {prepended} bytes precede the ZIP data; pass --allow-prepended if \
+                 this is a self-extractor or signed archive"
+            );
+        }
 
         for i in 0..archive.len() {
-            let file = archive.by_index(i)?;
-            if let Some(pb) = &pb {
-                pb.set_message(format!("Validating: {}", file.name()));
+            let problems = entry_name_problems(archive.by_index_raw(i)?.name_raw());
+            if !problems.is_empty() {
+                let name = archive.by_index_raw(i)?.name().escape_debug().to_string();
+                eprintln!("warning: entry \"{name}\" {}", problems.join(", "));
+                suspicious_names += 1;
             }
-            if mode.json {
-                crate::progress::print_json(&serde_json::json!({
-                    "event":"progress","op":"validate","file": file.name(),
-                    "current": i + 1, "total": total, "pct": ((i+1) as f64 / total as f64)
-                }));
+            // Without a password, encrypted entries can only be checked at the metadata level
+            let checkable = self.opts.password.is_some() || !archive.by_index_raw(i)?.encrypted();
+            let mut file = match checkable {
+                true => self.open_entry(&mut archive, i)?,
+                false => archive.by_index_raw(i)?,
+            };
+            reporter.message(&format!("Validating: {}", file.name()));
+            if let Some(progress) = &mut json_progress
+                && let Some(event) =
+                    progress.event("validate", file.name(), i as u64 + 1, file.size())
+            {
+                crate::progress::print_json(&event);
             }
 
-            // The zip crate automatically validates CRC32 when reading
-            // If there's a CRC mismatch, it will return an error
+            // The zip crate checks the CRC-32 and size once an entry is read to the end
+            if checkable && let Err(err) = std::io::copy(&mut file, &mut std::io::sink()) {
+                eprintln!("error: entry \"{}\" {err}", file.name().escape_debug());
+                corrupt += 1;
+            }
             drop(file);
-            if let Some(pb) = &pb {
-                pb.inc(1);
+            reporter.inc(1);
+        }
+        // A thorough check also holds every local header to its central directory record.
+        // Otherwise only data descriptors are compared, since for streamed entries they
+        // are what the CRC and sizes above were first recorded in.
+        let mut header_mismatches = 0;
+        let mut reader = BufReader::new(self.open_archive(archive_path.as_ref())?);
+        if self.opts.strict {
+            for mismatch in central_dir::local_header_mismatches(&mut reader)? {
+                eprintln!("warning: {mismatch}");
+                header_mismatches += 1;
+            }
+        } else {
+            for mismatch in central_dir::data_descriptor_mismatches(&mut reader)? {
+                eprintln!("error: {mismatch}");
+                corrupt += 1;
             }
         }
 
         let elapsed = start.elapsed();
-        if let Some(pb) = &pb {
-            pb.finish_with_message(format!("✓ Validation completed in {:.2?}", elapsed));
-        }
+        tracing::info!(
+            entries = total,
+            elapsed_ms = elapsed.as_millis() as u64,
+            "validation finished"
+        );
+        reporter.message(&format!("✓ Validation completed in {:.2?}", elapsed));
+        reporter.finish();
         if mode.json {
             crate::progress::print_json(&serde_json::json!({
                 "event":"done","op":"validate","archive": archive_path.as_ref().display().to_string(),
-                "elapsed_ms": elapsed.as_millis()
+                "elapsed_ms": elapsed.as_millis(), "suspicious_names": suspicious_names,
+                "header_mismatches": header_mismatches, "corrupt_entries": corrupt,
+                "prepended_bytes": prepended
             }));
         }
-        Ok(true)
+        Ok(corrupt == 0
+            && !unexpected_prefix
+            && !(self.opts.strict && suspicious_names + header_mismatches > 0))
+    }
+
+    /// Validate an archive and every `.zip` entry inside it, up to `limits.max_depth`
+    /// levels deep. Nested archives are read into memory, bounded by `limits.max_bytes`
+    /// in total, and every entry inside them is read so CRCs are checked.
+    pub fn validate_archive_recursive<P: AsRef<Path>>(
+        &self,
+        archive_path: P,
+        limits: &NestedLimits,
+    ) -> Result<ValidationReport> {
+        let archive_path = archive_path.as_ref();
+        let valid = self.validate_archive(archive_path)?;
+        let file = self.open_archive(archive_path)?;
+        let mut archive = ZipArchive::new(BufReader::new(file))?;
+        let mut budget = limits.max_bytes;
+        let nested = self
+            .validate_entries(&mut archive, 1, limits, &mut budget, false)
+            .map_err(|err| anyhow::anyhow!(err))?;
+        Ok(ValidationReport {
+            name: archive_path.display().to_string(),
+            valid: valid && nested.iter().all(|n| n.valid),
+            error: None,
+            nested,
+        })
+    }
+
+    /// Descend into the `.zip` entries of `archive` (whose entries sit at `depth`),
+    /// reading the other entries to check their CRCs when `check_data` is set
+    fn validate_entries<R: Read + Seek>(
+        &self,
+        archive: &mut ZipArchive<R>,
+        depth: usize,
+        limits: &NestedLimits,
+        budget: &mut u64,
+        check_data: bool,
+    ) -> std::result::Result<Vec<ValidationReport>, String> {
+        let mut reports = Vec::new();
+        for i in 0..archive.len() {
+            let (name, is_zip, readable) = {
+                let raw = archive.by_index_raw(i).map_err(|e| e.to_string())?;
+                let is_zip = !raw.is_dir()
+                    && Path::new(raw.name())
+                        .extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+                // Without a password, encrypted entries can only be checked at the metadata level
+                let readable = !(raw.encrypted() && self.opts.password.is_none());
+                (raw.name().to_string(), is_zip, readable)
+            };
+            if !readable {
+                continue;
+            }
+            if is_zip && depth <= limits.max_depth {
+                reports.push(self.validate_nested(archive, i, name, depth, limits, budget));
+            } else if check_data {
+                let mut entry = self.open_entry(archive, i).map_err(|e| format!("{name}: {e}"))?;
+                std::io::copy(&mut entry, &mut std::io::sink())
+                    .map_err(|e| format!("{name}: {e}"))?;
+            }
+        }
+        Ok(reports)
+    }
+
+    /// Read entry `index` into memory and validate it as an archive
+    fn validate_nested<R: Read + Seek>(
+        &self,
+        archive: &mut ZipArchive<R>,
+        index: usize,
+        name: String,
+        depth: usize,
+        limits: &NestedLimits,
+        budget: &mut u64,
+    ) -> ValidationReport {
+        let mut check = || -> std::result::Result<Vec<ValidationReport>, String> {
+            let mut entry = self.open_entry(archive, index).map_err(|e| e.to_string())?;
+            let mut bytes = Vec::new();
+            (&mut entry)
+                .take(*budget + 1)
+                .read_to_end(&mut bytes)
+                .map_err(|e| e.to_string())?;
+            if bytes.len() as u64 > *budget {
+                *budget = 0;
+                return Err(format!(
+                    "nested archives exceed the {} byte extraction limit",
+                    limits.max_bytes
+                ));
+            }
+            *budget -= bytes.len() as u64;
+            drop(entry);
+            let mut inner = ZipArchive::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+            self.validate_entries(&mut inner, depth + 1, limits, budget, true)
+        };
+        match check() {
+            Ok(nested) => ValidationReport {
+                name,
+                valid: nested.iter().all(|n| n.valid),
+                error: None,
+                nested,
+            },
+            Err(error) => {
+                tracing::debug!(entry = %name, %error, "nested archive failed validation");
+                ValidationReport {
+                    name,
+                    valid: false,
+                    error: Some(error),
+                    nested: Vec::new(),
+                }
+            }
+        }
     }
 
     /// Calculate SHA256 hash of a file
     pub fn calculate_file_hash<P: AsRef<Path>>(&self, file_path: P) -> Result<String> {
+        self.calculate_file_hash_with(file_path, HashAlgorithm::Sha256)
+    }
+
+    /// Calculate a file's checksum with `algo`, as lowercase hex
+    pub fn calculate_file_hash_with<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+        algo: HashAlgorithm,
+    ) -> Result<String> {
+        let digest = self.calculate_file_digest(file_path, algo)?;
+        Ok(HashEncoding::Hex.encode(&digest))
+    }
+
+    /// Calculate a file's raw digest bytes; CRC-32 is big-endian
+    pub fn calculate_file_digest<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+        algo: HashAlgorithm,
+    ) -> Result<Vec<u8>> {
+        Ok(self.calculate_file_digest_with_stats(file_path, algo)?.0)
+    }
+
+    /// Calculate a file's raw digest bytes, along with how much was read and how long
+    /// reading and hashing took
+    pub fn calculate_file_digest_with_stats<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+        algo: HashAlgorithm,
+    ) -> Result<(Vec<u8>, HashStats)> {
+        let start = Instant::now();
         let mut file = File::open(file_path)?;
-        let mut hasher = Sha256::new();
+        let mut sha256 = Sha256::new();
+        let mut crc32 = crc32fast::Hasher::new();
         let mut buffer = [0; 8192];
+        let mut bytes = 0u64;
 
         loop {
             let bytes_read = file.read(&mut buffer)?;
             if bytes_read == 0 {
                 break;
             }
-            hasher.update(&buffer[..bytes_read]);
+            bytes += bytes_read as u64;
+            match algo {
+                HashAlgorithm::Sha256 => sha256.update(&buffer[..bytes_read]),
+                HashAlgorithm::Crc32 => crc32.update(&buffer[..bytes_read]),
+            }
+        }
+
+        let digest = match algo {
+            HashAlgorithm::Sha256 => sha256.finalize().to_vec(),
+            HashAlgorithm::Crc32 => crc32.finalize().to_be_bytes().to_vec(),
+        };
+        let stats = HashStats {
+            bytes,
+            elapsed: start.elapsed(),
+        };
+        Ok((digest, stats))
+    }
+
+    /// Checksum every file in `paths`, walking directories, sorted by name. Names are
+    /// relative to `relative_to` with `/` separators when it is given, and the paths as
+    /// walked otherwise; a file outside `relative_to` is an error. Names come from the
+    /// paths as walked, so a symlink inside the root is named where it is rather than
+    /// where it points.
+    pub fn checksum_manifest(
+        &self,
+        paths: &[PathBuf],
+        relative_to: Option<&Path>,
+        algo: HashAlgorithm,
+    ) -> Result<Vec<ManifestEntry>> {
+        let absolute = |path: &Path| -> Result<PathBuf> {
+            let absolute = std::path::absolute(path)
+                .with_context(|| format!("Failed to resolve {}", path.display()))?;
+            Ok(normalize_lexically(&absolute))
+        };
+        let root = relative_to.map(absolute).transpose()?;
+        let mut entries = Vec::new();
+        for path in paths {
+            if !path.exists() {
+                anyhow::bail!("File or directory does not exist: {}", path.display());
+            }
+            for entry in WalkDir::new(path).follow_links(true) {
+                let entry = entry?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let name = match &root {
+                    Some(root) => {
+                        let full = absolute(entry.path())?;
+                        let relative = full.strip_prefix(root).map_err(|_| {
+                            anyhow::anyhow!(
+                                "{} is not inside {}",
+                                entry.path().display(),
+                                root.display()
+                            )
+                        })?;
+                        let parts: Vec<_> = relative
+                            .components()
+                            .map(|c| c.as_os_str().to_string_lossy())
+                            .collect();
+                        parts.join("/")
+                    }
+                    None => portable_entry_name(&entry.path().to_string_lossy()).into_owned(),
+                };
+                let hash = self.calculate_file_hash_with(entry.path(), algo)?;
+                entries.push(ManifestEntry { name, hash });
+            }
         }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
 
-        Ok(format!("{:x}", hasher.finalize()))
+    /// How many bytes precede the ZIP data, e.g. a self-extractor stub; 0 for a plain archive
+    pub fn prepended_bytes<P: AsRef<Path>>(&self, archive_path: P) -> Result<u64> {
+        let file = self.open_archive(archive_path.as_ref())?;
+        Ok(ZipArchive::new(BufReader::new(file))?.offset())
+    }
+
+    /// The archive-level comment, empty when there is none
+    pub fn read_comment<P: AsRef<Path>>(&self, archive_path: P) -> Result<String> {
+        let file = self.open_archive(archive_path.as_ref())?;
+        let archive = ZipArchive::new(BufReader::new(file))?;
+        Ok(String::from_utf8_lossy(archive.comment()).into_owned())
     }
 
     /// Get archive statistics
     pub fn get_archive_stats<P: AsRef<Path>>(&self, archive_path: P) -> Result<ArchiveStats> {
-        let file = File::open(archive_path.as_ref())?;
+        let file = self.open_archive(archive_path.as_ref())?;
         let mut archive = ZipArchive::new(BufReader::new(file))?;
 
-        let mut total_uncompressed_size = 0u64;
-        let mut total_compressed_size = 0u64;
-        let mut file_count = 0;
-        let mut dir_count = 0;
-
+        let mut stats = ArchiveStats::empty();
         for i in 0..archive.len() {
-            let file = archive.by_index(i)?;
-
-            if file.is_dir() {
-                dir_count += 1;
+            // Raw access reads metadata only, so encrypted archives need no password here
+            let file = archive.by_index_raw(i)?;
+            let (is_dir, size, compressed_size) =
+                (file.is_dir(), file.size(), file.compressed_size());
+            let encrypted = file.encrypted();
+            drop(file);
+            let encryption = if encrypted {
+                Some(entry_encryption(&mut archive, i)?)
             } else {
-                file_count += 1;
-                total_uncompressed_size += file.size();
-                total_compressed_size += file.compressed_size();
-            }
+                None
+            };
+            stats.record(is_dir, size, compressed_size, encryption);
         }
+        if !archive.comment().is_empty() {
+            stats.comment = Some(String::from_utf8_lossy(archive.comment()).into_owned());
+        }
+        stats.prepended_bytes = archive.offset();
+        Ok(stats.finish())
+    }
 
-        let compression_ratio = if total_uncompressed_size > 0 {
-            (total_compressed_size as f64 / total_uncompressed_size as f64) * 100.0
-        } else {
-            0.0
-        };
-
-        Ok(ArchiveStats {
-            file_count,
-            dir_count,
-            total_uncompressed_size,
-            total_compressed_size,
-            compression_ratio,
-        })
+    /// Compute the same aggregates as [`get_archive_stats`](Self::get_archive_stats) by
+    /// streaming the central directory, so memory use stays flat however many entries exist
+    pub fn get_archive_stats_streaming<P: AsRef<Path>>(
+        &self,
+        archive_path: P,
+    ) -> Result<ArchiveStats> {
+        let mut reader = BufReader::new(self.open_archive(archive_path.as_ref())?);
+        let mut stats = ArchiveStats::empty();
+        stats.prepended_bytes = central_dir::find_eocd(&mut reader)?.prefix_len();
+        central_dir::for_each_entry(&mut reader, |entry| {
+            stats.record(
+                entry.name.ends_with(b"/"),
+                entry.uncompressed_size,
+                entry.compressed_size,
+                raw_entry_encryption(&entry),
+            );
+            Ok(())
+        })?;
+        Ok(stats.finish())
     }
 
     /// Create a new ZIP archive with the specified files
     pub fn create_archive<P: AsRef<Path>>(&self, archive_path: P, files: &[P]) -> Result<()> {
-        let file = File::create(archive_path.as_ref())?;
-        let mut zip = ZipWriter::new(file);
-        let base_options = SimpleFileOptions::default();
+        let mut reporter = crate::progress::default_reporter();
+        self.create_archive_with_reporter(archive_path, files, reporter.as_mut())
+    }
 
-        // Count total files for progress bar
-        let mut total_files = 0;
+    /// Create a new ZIP archive, reporting progress to `reporter`
+    pub fn create_archive_with_reporter<P: AsRef<Path>>(
+        &self,
+        archive_path: P,
+        files: &[P],
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<()> {
+        self.create_archive_with_summary(archive_path, files, reporter).map(drop)
+    }
+
+    /// Create a new ZIP archive, returning the files left out under `keep_going`
+    pub fn create_archive_with_summary<P: AsRef<Path>>(
+        &self,
+        archive_path: P,
+        files: &[P],
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<CreateSummary> {
         for file_path in files {
             let path = file_path.as_ref();
             if !path.exists() {
@@ -183,76 +959,182 @@ impl ArchiveManager {
                     path.display()
                 ));
             }
-            if path.is_file() {
-                total_files += 1;
+        }
+        let parent = archive_path.as_ref().parent().filter(|p| !p.as_os_str().is_empty());
+        if let Some(parent) = parent.filter(|p| !p.exists()) {
+            if !self.opts.create_parents {
+                anyhow::bail!(
+                    "Directory {} does not exist; pass --create-parents to create it",
+                    parent.display()
+                );
+            }
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        crate::split::remove_volumes(archive_path.as_ref())?;
+        let file = self.create_file(archive_path.as_ref())?;
+        self.create_archive_into(archive_path.as_ref(), file, files, reporter)
+    }
+
+    /// Build an archive from `(name, contents)` pairs entirely in memory. Names ending
+    /// in `/` become directory entries. Compression level, encryption and comment
+    /// follow the options; nothing is read from or written to disk.
+    pub fn create_archive_in_memory(&self, entries: &[(&str, &[u8])]) -> Result<Vec<u8>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = self.entry_time(
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated),
+            None,
+        );
+        let options = match self.opts.compression_level {
+            Some(level) => options.compression_level(Some(level as i64)),
+            None => options,
+        };
+        for (name, contents) in entries {
+            let name = portable_entry_name(name);
+            if name.ends_with('/') {
+                self.check_entry_name(&name)?;
+                zip.add_directory(name.trim_end_matches('/'), options)?;
+            } else {
+                self.start_entry(&mut zip, &name, options)?;
+                zip.write_all(contents)?;
+            }
+        }
+        if let Some(comment) = &self.opts.comment {
+            zip.set_comment(comment.clone());
+        }
+        let bytes = zip.finish()?.into_inner();
+        match self.zipcrypto_password() {
+            Some(password) => {
+                let mut encrypted = Vec::with_capacity(bytes.len());
+                crate::zipcrypto::encrypt_archive(
+                    &mut Cursor::new(bytes),
+                    &mut encrypted,
+                    password,
+                )?;
+                Ok(encrypted)
+            }
+            None => Ok(bytes),
+        }
+    }
+
+    /// Write the archive to `writer`, removing the partial file at `archive_path` on failure
+    fn create_archive_into<W: Write + Seek, P: AsRef<Path>>(
+        &self,
+        archive_path: &Path,
+        writer: W,
+        files: &[P],
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<CreateSummary> {
+        let mut summary = CreateSummary::default();
+        let result = self
+            .write_archive(archive_path, ZipWriter::new(writer), files, reporter)
+            .map(|written| summary = written)
+            .and_then(|()| match self.opts.embed_checksums {
+                true => self.embed_checksums(archive_path),
+                false => Ok(()),
+            })
+            .and_then(|()| match self.zipcrypto_password() {
+                Some(password) => self.encrypt_zipcrypto(archive_path, password),
+                None => Ok(()),
+            })
+            .and_then(|()| match self.opts.split_size {
+                Some(size) => crate::split::split_archive(archive_path, size).map(drop),
+                None => Ok(()),
+            });
+        if let Err(err) = result {
+            // The writer has been dropped by now, so the file can be removed on every platform
+            tracing::debug!(error = %err, "archive creation failed; removing partial output");
+            let _ = std::fs::remove_file(archive_path);
+            return Err(classify_write_error(err, archive_path));
+        }
+        if self.opts.warn_case_collisions {
+            self.report_case_collisions(archive_path, "create", false)?;
+        }
+        Ok(summary)
+    }
+
+    /// Add `files` to `zip`, which may already hold entries, and finish it. Returns the
+    /// files left out under `keep_going` and the duplicates found under `dedup`.
+    fn write_archive<W: Write + Seek, P: AsRef<Path>>(
+        &self,
+        archive_path: &Path,
+        mut zip: ZipWriter<W>,
+        files: &[P],
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<CreateSummary> {
+        let _span = tracing::info_span!("create", archive = %archive_path.display()).entered();
+        let base_options = SimpleFileOptions::default();
+
+        // Count total files and bytes for progress reporting
+        let mut total_files = 0;
+        let mut total_bytes = 0;
+        for file_path in files {
+            let path = file_path.as_ref();
+            if self.stores_link(path) {
+                if self.include_link(path)? {
+                    total_files += 1;
+                }
+            } else if path.is_file() {
+                if self.include_file(path)? {
+                    total_files += 1;
+                    total_bytes += path.metadata().map_or(0, |m| m.len());
+                }
             } else if path.is_dir() {
-                total_files += WalkDir::new(path)
-                    .into_iter()
-                    .filter_map(|e| e.ok())
-                    .filter(|e| e.path().is_file())
-                    .count();
+                for entry in self.walk_dir(path)?.filter_map(|e| e.ok()) {
+                    if entry.path().is_file() && self.include_file(entry.path())? {
+                        total_files += 1;
+                        total_bytes += entry.metadata().map_or(0, |m| m.len());
+                    }
+                }
             }
         }
 
         let mode = crate::progress::output_mode();
-        println!("→ Creating: {}", archive_path.as_ref().display());
+        println!("→ Creating: {}", archive_path.display());
         let start = Instant::now();
         let total = total_files as u64;
-        let pb = if mode.progress && !mode.json {
-            let pb = ProgressBar::new(total);
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template(
-                        "{spinner:.green} [{elapsed_precise}] {wide_bar:.cyan/blue} {pos:>5}/{len:<5} {percent:>3}% {eta_precise} | {msg}"
-                    )
-                    .unwrap()
-                    .progress_chars("█· "),
-            );
-            Some(pb)
-        } else {
-            if mode.json {
-                crate::progress::print_json(&serde_json::json!({
-                    "event":"start","op":"create","archive": archive_path.as_ref().display().to_string(),"total": total
-                }));
-            }
-            None
-        };
+        tracing::debug!(files = total, "creating archive");
+        reporter.start(total);
+        if mode.json {
+            crate::progress::print_json(&serde_json::json!({
+                "event":"start","op":"create","archive": archive_path.display().to_string(),"total": total
+            }));
+        }
+        let mut json_progress = mode.json.then(|| JsonProgress::new(total, total_bytes));
 
         let mut processed: u64 = 0;
+        let mut state = CreateState::default();
         for file_path in files {
             let path = file_path.as_ref();
-            if path.is_file() {
-                if let Some(pb) = &pb {
-                    pb.set_message(format!("Adding: {}", path.display()));
+            if reporter.is_cancelled() {
+                return Err(ArchiveError::Cancelled.into());
+            }
+            if self.stores_link(path) {
+                if !self.include_link(path)? {
+                    continue;
                 }
+                reporter.message(&format!("Adding: {}", path.display()));
                 processed += 1;
-                if mode.json {
-                    let pct = if total > 0 {
-                        (processed as f64) / (total as f64)
-                    } else {
-                        0.0
-                    };
-                    crate::progress::print_json(&serde_json::json!({
-                        "event":"progress","op":"create","file": path.display().to_string(),
-                        "current": processed, "total": total, "pct": pct
-                    }));
-                }
-                // Choose method per-file
-                let method = if self.opts.auto_store
-                    && is_incompressible(path, self.opts.store_entropy_threshold)?
-                {
-                    zip::CompressionMethod::Stored
-                } else {
-                    zip::CompressionMethod::Deflated
-                };
-                let mut options = base_options.compression_method(method);
-                if let Some(level) = self.opts.compression_level {
-                    options = options.compression_level(Some(level as i64));
+                if let Some(progress) = &mut json_progress {
+                    let file = path.display().to_string();
+                    if let Some(event) = progress.event("create", &file, processed, 0) {
+                        crate::progress::print_json(&event);
+                    }
                 }
-                self.add_file_to_zip(&mut zip, path, &options, self.opts.io_buffer_size)?;
-                if let Some(pb) = &pb {
-                    pb.inc(1);
+                self.add_link_to_zip(&mut zip, path, base_options)?;
+                reporter.inc(1);
+            } else if path.is_file() && self.include_file(path)? {
+                reporter.message(&format!("Adding: {}", path.display()));
+                processed += 1;
+                if let Some(progress) = &mut json_progress {
+                    let size = path.metadata()?.len();
+                    let file = path.display().to_string();
+                    if let Some(event) = progress.event("create", &file, processed, size) {
+                        crate::progress::print_json(&event);
+                    }
                 }
+                self.add_file_to_zip(&mut zip, path, base_options, &mut state)?;
+                reporter.inc(1);
             } else if path.is_dir() {
                 let mut options = base_options.compression_method(zip::CompressionMethod::Deflated);
                 if let Some(level) = self.opts.compression_level {
@@ -262,449 +1144,4712 @@ impl ArchiveManager {
                     &mut zip,
                     path,
                     &options,
-                    &pb,
-                    mode.json,
-                    total,
+                    reporter,
+                    &mut json_progress,
                     &mut processed,
+                    &mut state,
                     self.opts.clone(),
                 )?;
             }
         }
 
         let elapsed = start.elapsed();
-        if let Some(pb) = &pb {
-            pb.finish_with_message(format!("✓ Created {} files in {:.2?}", total_files, elapsed));
-        }
+        tracing::info!(files = total, elapsed_ms = elapsed.as_millis() as u64, "archive created");
+        reporter.message(&format!("✓ Created {} files in {:.2?}", total_files, elapsed));
+        reporter.finish();
         if mode.json {
             crate::progress::print_json(&serde_json::json!({
-                "event":"done","op":"create","archive": archive_path.as_ref().display().to_string(),
+                "event":"done","op":"create","archive": archive_path.display().to_string(),
                 "elapsed_ms": elapsed.as_millis()
             }));
         }
+        if let Some(comment) = &self.opts.comment {
+            zip.set_comment(comment.clone());
+        }
         zip.finish()?;
-        Ok(())
+        Ok(state.summary)
     }
 
-    /// Extract a ZIP archive to the specified directory
-    pub fn extract_archive<P: AsRef<Path>>(&self, archive_path: P, output_dir: P) -> Result<()> {
-        let file = File::open(archive_path.as_ref())?;
-        let mut archive = ZipArchive::new(BufReader::new(file))?;
-
-        let mode = crate::progress::output_mode();
-        println!(
-            "→ Extracting: {} → {}",
-            archive_path.as_ref().display(),
-            output_dir.as_ref().display()
-        );
-        let start = Instant::now();
-        let total = archive.len() as u64;
-        let pb = if mode.progress && !mode.json {
-            let pb = ProgressBar::new(total);
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template(
-                        "{spinner:.green} [{elapsed_precise}] {wide_bar:.cyan/blue} {pos:>5}/{len:<5} {percent:>3}% {eta_precise} | {msg}"
-                    )
-                    .unwrap()
-                    .progress_chars("█· "),
-            );
-            Some(pb)
-        } else {
-            if mode.json {
-                crate::progress::print_json(&serde_json::json!({
-                    "event":"start","op":"extract","archive": archive_path.as_ref().display().to_string(),
-                    "total": total, "output": output_dir.as_ref().display().to_string()
-                }));
-            }
-            None
-        };
-
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            let output_path = output_dir.as_ref().join(file.name());
-            if let Some(pb) = &pb {
-                pb.set_message(format!("Extracting: {}", file.name()));
-            }
-            if mode.json {
-                crate::progress::print_json(&serde_json::json!({
-                    "event":"progress","op":"extract","file": file.name(),
-                    "current": i + 1, "total": total, "pct": ((i+1) as f64 / total as f64)
-                }));
+    /// Append a [`CHECKSUMS_ENTRY`] listing `<sha256>  <name>` for every file entry
+    fn embed_checksums(&self, archive_path: &Path) -> Result<()> {
+        let mut sums = String::new();
+        {
+            let mut archive = ZipArchive::new(BufReader::new(self.open_file(archive_path)?))?;
+            if archive.index_for_name(CHECKSUMS_ENTRY).is_some() {
+                anyhow::bail!("The inputs already contain a {CHECKSUMS_ENTRY} entry");
             }
-
-            if file.is_dir() {
-                std::fs::create_dir_all(&output_path)?;
-            } else {
-                if let Some(parent) = output_path.parent() {
-                    std::fs::create_dir_all(parent)?;
+            for i in 0..archive.len() {
+                let mut entry = self.open_entry(&mut archive, i)?;
+                if entry.is_dir() {
+                    continue;
                 }
-                let mut output_file = File::create(&output_path)?;
-                std::io::copy(&mut file, &mut output_file)?;
-            }
-            if let Some(pb) = &pb {
-                pb.inc(1);
+                let mut hasher = Sha256::new();
+                std::io::copy(&mut entry, &mut hasher)?;
+                sums.push_str(&format!("{:x}  {}\n", hasher.finalize(), entry.name()));
             }
         }
+        let file = File::options().read(true).write(true).open(archive_path)?;
+        let mut zip = ZipWriter::new_append(file)?;
+        let options = self.entry_time(
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated),
+            None,
+        );
+        self.start_entry(&mut zip, CHECKSUMS_ENTRY, options)?;
+        zip.write_all(sums.as_bytes())?;
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// Add files and directories to an existing archive, keeping its current entries.
+    /// The archive is rewritten through a temporary copy, so it is left untouched on failure.
+    pub fn add_to_archive<P: AsRef<Path>>(&self, archive_path: P, files: &[P]) -> Result<()> {
+        let mut reporter = crate::progress::default_reporter();
+        self.add_to_archive_with_reporter(archive_path, files, reporter.as_mut())
+    }
+
+    /// Add files to an existing archive, reporting progress to `reporter`
+    pub fn add_to_archive_with_reporter<P: AsRef<Path>>(
+        &self,
+        archive_path: P,
+        files: &[P],
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<()> {
+        let archive_path = archive_path.as_ref();
+        for file_path in files {
+            let path = file_path.as_ref();
+            if !path.exists() {
+                anyhow::bail!("File or directory does not exist: {}", path.display());
+            }
+        }
+        crate::split::ensure_single_volume(archive_path)?;
+        self.check_zipcrypto_unsupported()?;
+        self.replace_via_temp(archive_path, |temp| {
+            std::fs::copy(archive_path, temp)?;
+            let file = File::options().read(true).write(true).open(temp)?;
+            let zip = ZipWriter::new_append(file)?;
+            self.write_archive(archive_path, zip, files, reporter)
+                .map(drop)
+                .map_err(|err| classify_write_error(err, archive_path))
+        })
+    }
+
+    /// Add files to an archive, replacing entries of the same name. Files new to the
+    /// archive are appended in place; when any replaces an entry, the archive is
+    /// rewritten through a temporary copy that carries the other entries over
+    /// without recompressing them. Returns how many entries were replaced.
+    pub fn update_archive<P: AsRef<Path>>(
+        &self,
+        archive_path: &Path,
+        files: &[P],
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<usize> {
+        crate::split::ensure_single_volume(archive_path)?;
+        self.check_zipcrypto_unsupported()?;
+        let names: HashSet<String> = files
+            .iter()
+            .map(|f| f.as_ref())
+            .filter(|path| path.is_file())
+            .map(|path| self.input_entry_name(path).into_owned())
+            .collect();
+        let mut archive = ZipArchive::new(BufReader::new(self.open_file(archive_path)?))?;
+        let replaced = archive.file_names().filter(|name| names.contains(*name)).count();
+        if replaced == 0 {
+            drop(archive);
+            self.append_in_place(archive_path, files, reporter)?;
+            return Ok(0);
+        }
+        self.replace_via_temp(archive_path, |temp| {
+            let mut zip = ZipWriter::new(self.create_file(temp)?);
+            for i in 0..archive.len() {
+                let entry = archive.by_index_raw(i)?;
+                if !names.contains(entry.name()) {
+                    zip.raw_copy_file(entry)?;
+                }
+            }
+            zip.set_raw_comment(archive.comment().to_vec().into());
+            self.write_archive(archive_path, zip, files, reporter)
+                .map_err(|err| classify_write_error(err, archive_path))?;
+            Ok(replaced)
+        })
+    }
+
+    /// Append `files` to the archive at `archive_path` without copying it. The new
+    /// entries overwrite the central directory, which is kept in memory and written
+    /// back if the append fails, so the archive is never left without one.
+    fn append_in_place<P: AsRef<Path>>(
+        &self,
+        archive_path: &Path,
+        files: &[P],
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<()> {
+        let mut file = File::options().read(true).write(true).open(archive_path)?;
+        let eocd = central_dir::find_eocd(&mut file)?;
+        let cd_start = eocd.cd_end - eocd.cd_size;
+        let mut tail = Vec::new();
+        file.seek(SeekFrom::Start(cd_start))?;
+        file.read_to_end(&mut tail)?;
+        let result = file
+            .try_clone()
+            .map_err(anyhow::Error::from)
+            .and_then(|writer| Ok(ZipWriter::new_append(writer)?))
+            .and_then(|zip| self.write_archive(archive_path, zip, files, reporter));
+        if let Err(err) = result {
+            file.set_len(cd_start)?;
+            file.seek(SeekFrom::Start(cd_start))?;
+            file.write_all(&tail)?;
+            return Err(classify_write_error(err, archive_path));
+        }
+        Ok(())
+    }
+
+    /// Remove the named entries from an archive, returning how many were dropped.
+    /// Naming a directory (`dir/`) also removes everything under it.
+    pub fn remove_from_archive<P: AsRef<Path>>(
+        &self,
+        archive_path: P,
+        names: &[&str],
+    ) -> Result<usize> {
+        let archive_path = archive_path.as_ref();
+        crate::split::ensure_single_volume(archive_path)?;
+        let mut archive = ZipArchive::new(BufReader::new(self.open_file(archive_path)?))?;
+        let missing = missing_entries(&archive, names);
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "Entries not found in {}: {}",
+                archive_path.display(),
+                missing.join(", ")
+            );
+        }
+        self.replace_via_temp(archive_path, |temp| {
+            let mut zip = ZipWriter::new(self.create_file(temp)?);
+            let mut count = 0;
+            for i in 0..archive.len() {
+                let entry = archive.by_index_raw(i)?;
+                if names.iter().any(|n| entry_selected(entry.name(), n)) {
+                    count += 1;
+                    continue;
+                }
+                zip.raw_copy_file(entry)?;
+            }
+            zip.set_raw_comment(archive.comment().to_vec().into());
+            zip.finish()?;
+            Ok(count)
+        })
+    }
+
+    /// Build a replacement for `path` in a sibling temporary file and move it into
+    /// place only when `build` succeeds
+    fn replace_via_temp<T>(
+        &self,
+        path: &Path,
+        build: impl FnOnce(&Path) -> Result<T>,
+    ) -> Result<T> {
+        let temp = sibling_temp_path(path);
+        match build(&temp) {
+            Ok(value) => {
+                std::fs::rename(&temp, path).with_context(|| {
+                    format!("Failed to replace {} with its updated copy", path.display())
+                })?;
+                Ok(value)
+            }
+            Err(err) => {
+                let _ = std::fs::remove_file(&temp);
+                Err(err)
+            }
+        }
+    }
+
+    /// Extract a ZIP archive to the specified directory
+    pub fn extract_archive<P: AsRef<Path>>(&self, archive_path: P, output_dir: P) -> Result<()> {
+        let mut reporter = crate::progress::default_reporter();
+        self.extract_archive_with_reporter(archive_path, output_dir, reporter.as_mut())
+    }
+
+    /// Extract a ZIP archive, reporting progress to `reporter`
+    pub fn extract_archive_with_reporter<P: AsRef<Path>>(
+        &self,
+        archive_path: P,
+        output_dir: P,
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<()> {
+        self.extract_archive_with_summary(archive_path, output_dir, reporter).map(drop)
+    }
+
+    /// Extract a ZIP archive, returning how many entries were written and skipped
+    pub fn extract_archive_with_summary<P: AsRef<Path>>(
+        &self,
+        archive_path: P,
+        output_dir: P,
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<ExtractSummary> {
+        let (archive_path, output_dir) = (archive_path.as_ref(), output_dir.as_ref());
+        if self.opts.atomic {
+            return self.extract_atomically(archive_path, output_dir, reporter);
+        }
+        self.extract_into(archive_path, output_dir, output_dir, reporter)
+    }
+
+    /// Extract into a sibling temporary directory, then move the result into
+    /// `output_dir`, so it never holds a partial extraction
+    fn extract_atomically(
+        &self,
+        archive_path: &Path,
+        output_dir: &Path,
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<ExtractSummary> {
+        let target = match output_dir.exists() {
+            true => output_dir.canonicalize()?,
+            false => std::path::absolute(output_dir)?,
+        };
+        let staging = sibling_temp_path(&target);
+        if staging.exists() {
+            // Left behind by a run that was killed
+            std::fs::remove_dir_all(&staging)?;
+        }
+        let extracted = self
+            .extract_into(archive_path, &staging, output_dir, reporter)
+            .and_then(|summary| move_into_place(&staging, &target).map(|()| summary));
+        if extracted.is_err() && staging.exists() {
+            let _ = std::fs::remove_dir_all(&staging);
+        }
+        extracted
+    }
+
+    /// Extract into `output_dir`, applying the overwrite policy to the files that
+    /// already exist in `target` and naming `target` in messages
+    fn extract_into(
+        &self,
+        archive_path: &Path,
+        output_dir: &Path,
+        target: &Path,
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<ExtractSummary> {
+        let _span = tracing::info_span!(
+            "extract",
+            archive = %archive_path.display(),
+            output = %target.display()
+        )
+        .entered();
+        let file = self.open_archive(archive_path)?;
+        let archive = ZipArchive::new(BufReader::new(file))?;
+
+        let mode = crate::progress::output_mode();
+        println!("→ Extracting: {} → {}", archive_path.display(), target.display());
+        if self.opts.check_case_collisions {
+            self.report_case_collisions(archive_path, "extract", true)?;
+        }
+        let mut archive = self.apply_duplicate_policy(archive_path, archive)?;
+        let checksums = match self.opts.verify_checksums {
+            true => self.read_checksums(&mut archive)?,
+            false => None,
+        };
+        if self.opts.verify_checksums && checksums.is_none() {
+            eprintln!("warning: {CHECKSUMS_ENTRY} not found; extracted files are not verified");
+        }
+        // Files written this run, as (entry name, path), for checksum verification
+        let mut written_files = Vec::new();
+        let start = Instant::now();
+        let total = archive.len() as u64;
+        tracing::debug!(entries = total, "extracting archive");
+        reporter.start(total);
+        if mode.json {
+            crate::progress::print_json(&serde_json::json!({
+                "event":"start","op":"extract","archive": archive_path.display().to_string(),
+                "total": total, "output": target.display().to_string()
+            }));
+        }
+        let mut json_progress = match mode.json {
+            true => Some(JsonProgress::new(total, uncompressed_total(&mut archive)?)),
+            false => None,
+        };
+
+        std::fs::create_dir_all(output_dir)?;
+        let root = output_dir.canonicalize()?;
+        // Directory modes are applied last so read-only modes don't block writing children
+        let mut extracted_dirs: std::collections::BTreeSet<PathBuf> = Default::default();
+        let mut kept = 0u64;
+        let mut resume = match self.opts.resume {
+            true => Some(ResumeState::open(&root)?),
+            false => None,
+        };
+        let mut resumed = 0u64;
+        // Extracted paths with the owners stored for them, restored at the end
+        let mut owners = Vec::new();
+        let mut summary = ExtractSummary::default();
+
+        for i in 0..archive.len() {
+            if reporter.is_cancelled() {
+                return Err(ArchiveError::Cancelled.into());
+            }
+            let mut file = self.open_entry(&mut archive, i)?;
+            let name = self.entry_name(&file).into_owned();
+            let is_dir = entry_is_dir(&file);
+            // Skipped entries count too, so the final event comes whatever is skipped
+            if let Some(progress) = &mut json_progress
+                && let Some(event) = progress.event("extract", &name, i as u64 + 1, file.size())
+            {
+                crate::progress::print_json(&event);
+            }
+            if !self.selects(&name) {
+                reporter.inc(1);
+                continue;
+            }
+            if self.opts.skip_mac_metadata && is_mac_metadata(&name) {
+                summary.skipped += 1;
+                reporter.inc(1);
+                continue;
+            }
+            let output_path = if self.opts.flatten {
+                if is_dir {
+                    summary.skipped += 1;
+                    reporter.inc(1);
+                    continue;
+                }
+                match self.flattened_path(output_dir, &name)? {
+                    Some(path) => path,
+                    None => {
+                        summary.skipped += 1;
+                        reporter.inc(1);
+                        continue;
+                    }
+                }
+            } else {
+                let relative = enclosed_path(&name).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Refusing to extract '{name}': path escapes the output directory"
+                    )
+                })?;
+                output_dir.join(relative)
+            };
+            // Links extracted earlier could redirect this path outside the root
+            ensure_within_root(&root, &output_path, &name)?;
+            reporter.message(&format!("Extracting: {name}"));
+            tracing::trace!(entry = name.as_str(), path = %output_path.display(), "extracting entry");
+
+            if self.opts.dir_mode.is_some() {
+                let dirs = output_path.ancestors().skip(usize::from(!is_dir));
+                let output_root = output_dir;
+                extracted_dirs
+                    .extend(dirs.take_while(|d| *d != output_root).map(Path::to_path_buf));
+            }
+
+            let owner = match self.opts.preserve_owner {
+                true => file.extra_data().and_then(parse_owner_field),
+                false => None,
+            };
+            if is_dir {
+                std::fs::create_dir_all(extended_length_path(&output_path))
+                    .map_err(|err| classify_write_error(err.into(), &output_path))?;
+                owners.extend(owner.map(|owner| (output_path.clone(), owner)));
+                summary.dirs += 1;
+            } else if file.is_symlink() && cfg!(unix) {
+                let mut target = String::new();
+                file.read_to_string(&mut target)?;
+                extract_symlink(&root, &output_path, &target, &name)?;
+                owners.extend(owner.map(|owner| (output_path.clone(), owner)));
+                summary.extracted += 1;
+            } else if resume
+                .as_ref()
+                .is_some_and(|r| r.completed(&name, &output_path, file.crc32()))
+            {
+                tracing::debug!(path = %output_path.display(), "already extracted");
+                resumed += 1;
+                summary.skipped += 1;
+            } else if self.keep_existing(
+                &target.join(output_path.strip_prefix(output_dir).unwrap_or(&output_path)),
+                file.last_modified(),
+            )? {
+                tracing::debug!(path = %output_path.display(), "keeping existing file");
+                kept += 1;
+                summary.skipped += 1;
+            } else {
+                if let Some(parent) = output_path.parent() {
+                    std::fs::create_dir_all(extended_length_path(parent))
+                        .map_err(|err| classify_write_error(err.into(), &output_path))?;
+                }
+                if output_path.is_symlink() {
+                    std::fs::remove_file(&output_path)?;
+                }
+                let mut output_file = self
+                    .create_file(&extended_length_path(&output_path))
+                    .map_err(|err| classify_write_error(err, &output_path))?;
+                let written = std::io::copy(&mut file, &mut output_file);
+                match written {
+                    Ok(bytes) => summary.total_bytes += bytes,
+                    Err(err) => {
+                        drop(output_file);
+                        let _ = std::fs::remove_file(&output_path);
+                        return Err(classify_write_error(err.into(), &output_path));
+                    }
+                }
+                summary.extracted += 1;
+                if let Some(mode) = self.opts.file_mode {
+                    set_mode(&output_path, mode)?;
+                }
+                owners.extend(owner.map(|owner| (output_path.clone(), owner)));
+                if checksums.is_some() {
+                    written_files.push((name.clone(), output_path));
+                }
+                if let Some(resume) = &mut resume {
+                    resume.record(&name)?;
+                }
+            }
+            reporter.inc(1);
+        }
+        if let Some(checksums) = &checksums {
+            let mut mismatched = Vec::new();
+            for (name, path) in &written_files {
+                if let Some(expected) = checksums.get(name)
+                    && !self.calculate_file_hash(path)?.eq_ignore_ascii_case(expected)
+                {
+                    mismatched.push(name.clone());
+                }
+            }
+            if !mismatched.is_empty() {
+                return Err(ArchiveError::ChecksumMismatch { names: mismatched }.into());
+            }
+        }
+        if let Some(mode) = self.opts.dir_mode {
+            // Deepest first, so parents stay writable until their children are done
+            for dir in extracted_dirs.iter().rev() {
+                set_mode(dir, mode)?;
+            }
+        }
+        #[cfg(unix)]
+        restore_owners(&owners)?;
+        #[cfg(not(unix))]
+        drop(owners);
 
         let elapsed = start.elapsed();
-        if let Some(pb) = &pb {
-            pb.finish_with_message(format!("✓ Extracted in {:.2?}", elapsed));
+        tracing::info!(
+            entries = total,
+            kept,
+            elapsed_ms = elapsed.as_millis() as u64,
+            "extraction finished"
+        );
+        if kept > 0 {
+            reporter.message(&format!("Kept {kept} existing files"));
+        }
+        if let Some(resume) = resume {
+            resume.finish()?;
+            if resumed > 0 {
+                reporter.message(&format!("Skipped {resumed} files extracted by an earlier run"));
+            }
         }
+        reporter.message(&format!("✓ Extracted {} files in {:.2?}", summary.extracted, elapsed));
+        reporter.finish();
         if mode.json {
             crate::progress::print_json(&serde_json::json!({
-                "event":"done","op":"extract","archive": archive_path.as_ref().display().to_string(),
-                "output": output_dir.as_ref().display().to_string(), "elapsed_ms": elapsed.as_millis()
+                "event":"done","op":"extract","archive": archive_path.display().to_string(),
+                "output": target.display().to_string(), "elapsed_ms": elapsed.as_millis(),
+                "kept": kept
             }));
         }
-        Ok(())
+        Ok(summary)
+    }
+
+    /// Extract only the named entries (a name ending in `/` selects everything under it),
+    /// returning how many archive entries were selected. Fails before writing anything
+    /// if a name matches no entry.
+    pub fn extract_selected<P: AsRef<Path>>(
+        &self,
+        archive_path: P,
+        output_dir: P,
+        entries: &[&str],
+    ) -> Result<usize> {
+        let mut reporter = crate::progress::default_reporter();
+        let summary = self.extract_selected_with_summary(
+            archive_path,
+            output_dir,
+            entries,
+            reporter.as_mut(),
+        )?;
+        Ok(summary.entries())
+    }
+
+    /// Extract only the named entries, reporting progress to `reporter` and returning
+    /// what happened to the selected entries
+    pub fn extract_selected_with_summary<P: AsRef<Path>>(
+        &self,
+        archive_path: P,
+        output_dir: P,
+        entries: &[&str],
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<ExtractSummary> {
+        let archive = ZipArchive::new(BufReader::new(self.open_archive(archive_path.as_ref())?))?;
+        let missing = missing_entries(&archive, entries);
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "Entries not found in {}: {}",
+                archive_path.as_ref().display(),
+                missing.join(", ")
+            );
+        }
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            entries: entries.iter().map(|n| n.to_string()).collect(),
+            ..self.opts.clone()
+        });
+        manager.extract_archive_with_summary(archive_path, output_dir, reporter)
+    }
+
+    /// Files that extracting to `output_dir` would write over, without extracting
+    /// anything. Existing files whose contents match the entry's CRC are left out;
+    /// `overwrite` says whether the overwrite policy would replace each one.
+    pub fn preview_conflicts<P: AsRef<Path>>(
+        &self,
+        archive_path: P,
+        output_dir: P,
+    ) -> Result<Vec<ExtractConflict>> {
+        let file = self.open_archive(archive_path.as_ref())?;
+        let mut archive = ZipArchive::new(BufReader::new(file))?;
+        let mut conflicts = Vec::new();
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            let name = self.entry_name(&file).into_owned();
+            if entry_is_dir(&file)
+                || file.is_symlink()
+                || (self.opts.skip_mac_metadata && is_mac_metadata(&name))
+                || !self.selects(&name)
+            {
+                continue;
+            }
+            let path = match self.opts.flatten {
+                true => Path::new(&name).file_name().map(|base| output_dir.as_ref().join(base)),
+                false => enclosed_path(&name).map(|relative| output_dir.as_ref().join(relative)),
+            };
+            let Some(path) = path.filter(|path| path.is_file()) else {
+                continue;
+            };
+            if file_crc32(&path)? == file.crc32() {
+                continue;
+            }
+            let overwrite = !self.keep_existing(&path, file.last_modified())?;
+            conflicts.push(ExtractConflict {
+                name,
+                path,
+                overwrite,
+            });
+        }
+        Ok(conflicts)
     }
 
     /// List contents of a ZIP archive
     pub fn list_archive<P: AsRef<Path>>(&self, archive_path: P) -> Result<Vec<String>> {
-        let file = File::open(archive_path)?;
+        let file = self.open_archive(archive_path.as_ref())?;
         let mut archive = ZipArchive::new(BufReader::new(file))?;
+        self.resolve_duplicates(archive_path.as_ref(), Some(archive.len()))?;
         let mut contents = Vec::new();
 
         for i in 0..archive.len() {
-            let file = archive.by_index(i)?;
-            contents.push(file.name().to_string());
+            // Names are readable without the password
+            let file = archive.by_index_raw(i)?;
+            let name = self.entry_name(&file);
+            if self.opts.skip_mac_metadata && is_mac_metadata(&name) {
+                continue;
+            }
+            contents.push(name.into_owned());
+        }
+
+        Ok(contents)
+    }
+
+    /// Whether any entry in the archive is encrypted
+    pub fn has_encrypted_entries<P: AsRef<Path>>(&self, archive_path: P) -> Result<bool> {
+        let file = self.open_archive(archive_path.as_ref())?;
+        let mut archive = ZipArchive::new(BufReader::new(file))?;
+        for i in 0..archive.len() {
+            if archive.by_index_raw(i)?.encrypted() {
+                return Ok(true);
+            }
         }
+        Ok(false)
+    }
 
+    /// Read one entry's contents into memory without touching the filesystem
+    pub fn extract_entry<P: AsRef<Path>>(&self, archive_path: P, name: &str) -> Result<Vec<u8>> {
+        let archive_path = archive_path.as_ref();
+        let file = self.open_archive(archive_path)?;
+        let mut archive = ZipArchive::new(BufReader::new(file))?;
+        let index = archive.index_for_name(name).ok_or_else(|| {
+            anyhow::anyhow!("Entry '{}' not found in {}", name, archive_path.display())
+        })?;
+        let mut entry = self.open_entry(&mut archive, index)?;
+        if entry.is_dir() {
+            anyhow::bail!("Entry '{}' is a directory", name);
+        }
+        let mut contents = Vec::with_capacity(entry.size() as usize);
+        entry
+            .read_to_end(&mut contents)
+            .with_context(|| format!("Failed to read entry '{}'", name))?;
         Ok(contents)
     }
 
-    fn add_file_to_zip(
+    /// Expected SHA-256 by entry name from the archive's [`CHECKSUMS_ENTRY`], if present.
+    /// Lines use the `sha256sum` format: `<hash>  <name>`, or `<hash> *<name>`.
+    fn read_checksums<R: Read + Seek>(
         &self,
-        zip: &mut ZipWriter<File>,
-        file_path: &Path,
-        options: &SimpleFileOptions,
-        buf_size: usize,
+        archive: &mut ZipArchive<R>,
+    ) -> Result<Option<HashMap<String, String>>> {
+        let Some(index) = archive.index_for_name(CHECKSUMS_ENTRY) else {
+            return Ok(None);
+        };
+        let mut manifest = String::new();
+        self.open_entry(archive, index)?
+            .read_to_string(&mut manifest)
+            .with_context(|| format!("Failed to read {CHECKSUMS_ENTRY}"))?;
+        let mut checksums = HashMap::new();
+        for line in manifest.lines().filter(|l| !l.trim().is_empty()) {
+            let (hash, name) = line
+                .split_once(' ')
+                .ok_or_else(|| anyhow::anyhow!("Malformed {CHECKSUMS_ENTRY} line: {line}"))?;
+            let name = name.strip_prefix([' ', '*']).unwrap_or(name);
+            checksums.insert(name.to_string(), hash.to_string());
+        }
+        Ok(Some(checksums))
+    }
+
+    /// Open an entry for reading, decrypting it when a password is configured
+    fn open_entry<'a, R: Read + Seek>(
+        &self,
+        archive: &'a mut ZipArchive<R>,
+        index: usize,
+    ) -> Result<ZipFile<'a, R>> {
+        match &self.opts.password {
+            Some(password) => Ok(archive.by_index_decrypt(index, password.as_bytes())?),
+            None => Ok(archive.by_index(index)?),
+        }
+    }
+
+    /// Start a new file entry, encrypting it when a password is configured
+    fn start_entry<W: Write + Seek, T: FileOptionExtension>(
+        &self,
+        zip: &mut ZipWriter<W>,
+        name: &str,
+        options: FileOptions<'_, T>,
     ) -> Result<()> {
-        let name = file_path.file_name().unwrap().to_string_lossy();
-        zip.start_file(name, *options)?;
-        let mut file = File::open(file_path)?;
-        copy_buffered(&mut file, zip, buf_size)?;
+        self.check_entry_name(name)?;
+        // ZipCrypto entries are written plain and encrypted once the archive is
+        // finished (see `encrypt_zipcrypto`)
+        match (&self.opts.password, self.opts.encryption.aes_mode()) {
+            (Some(password), Some(mode)) => {
+                zip.start_file(name, options.with_aes_encryption(mode, password))?
+            }
+            _ => zip.start_file(name, options)?,
+        }
         Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
-    fn add_dir_to_zip_with_progress(
+    /// The password when entries are to be ZipCrypto encrypted after writing
+    fn zipcrypto_password(&self) -> Option<&str> {
+        match self.opts.encryption {
+            EncryptionMethod::ZipCrypto => self.opts.password.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// ZipCrypto encryption rewrites a whole finished archive, so it can't be applied
+    /// to entries added to an existing one
+    fn check_zipcrypto_unsupported(&self) -> Result<()> {
+        if self.zipcrypto_password().is_some() {
+            anyhow::bail!(
+                "ZipCrypto encryption is only available when creating an archive; use AES \
+                 to add encrypted entries"
+            );
+        }
+        Ok(())
+    }
+
+    /// Rewrite the finished archive at `archive_path` with its entries ZipCrypto encrypted
+    fn encrypt_zipcrypto(&self, archive_path: &Path, password: &str) -> Result<()> {
+        self.replace_via_temp(archive_path, |temp| {
+            let mut source = BufReader::new(self.open_file(archive_path)?);
+            let mut dest = std::io::BufWriter::new(self.create_file(temp)?);
+            crate::zipcrypto::encrypt_archive(&mut source, &mut dest, password)?;
+            dest.flush()?;
+            Ok(())
+        })
+    }
+
+    /// The writer flags every non-ASCII name as UTF-8; without `utf8_names` such names
+    /// are refused rather than left for tools that ignore the flag to garble
+    fn check_entry_name(&self, name: &str) -> Result<()> {
+        if !self.opts.utf8_names && !name.is_ascii() {
+            anyhow::bail!("Entry name '{name}' is not ASCII and --utf8-names is off");
+        }
+        Ok(())
+    }
+
+    /// Detect repeated entry names and apply the configured `DuplicatePolicy`.
+    ///
+    /// The zip index keeps a single (last) entry per name, so when the index is at hand
+    /// (`indexed` is its length), duplicates show up as fewer indexed entries than
+    /// central directory records and the scan is skipped without them. The returned
+    /// map holds, for each repeated name, the central directory position of the copy
+    /// to use.
+    fn resolve_duplicates(
         &self,
-        zip: &mut ZipWriter<File>,
-        dir_path: &Path,
-        options: &SimpleFileOptions,
-        pb: &Option<ProgressBar>,
-        json: bool,
-        total: u64,
-        processed: &mut u64,
-        opts: ArchiveOptions,
+        archive_path: &Path,
+        indexed: Option<usize>,
+    ) -> Result<HashMap<String, usize>> {
+        let mut file = BufReader::new(self.open_archive(archive_path)?);
+        if let Some(indexed) = indexed {
+            match central_dir::find_eocd(&mut file) {
+                Ok(eocd) if eocd.entry_count as usize != indexed => {}
+                _ => return Ok(HashMap::new()),
+            }
+        }
+
+        // Names in order of first appearance with the positions of their copies,
+        // indexed by name
+        let mut copies: Vec<(String, Vec<usize>)> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut i = 0;
+        central_dir::for_each_entry(&mut file, |entry| {
+            match index.entry(self.raw_entry_name(&entry).into_owned()) {
+                std::collections::hash_map::Entry::Occupied(seen) => copies[*seen.get()].1.push(i),
+                std::collections::hash_map::Entry::Vacant(new) => {
+                    copies.push((new.key().clone(), vec![i]));
+                    new.insert(copies.len() - 1);
+                }
+            }
+            i += 1;
+            Ok(())
+        })?;
+        copies.retain(|(_, at)| at.len() > 1);
+
+        let keep = match self.opts.on_duplicate {
+            DuplicatePolicy::Error => {
+                let names: Vec<&str> = copies.iter().map(|(n, _)| n.as_str()).collect();
+                return Err(anyhow::anyhow!(
+                    "Archive contains duplicate entry names: {}",
+                    names.join(", ")
+                ));
+            }
+            DuplicatePolicy::KeepFirst => "first",
+            DuplicatePolicy::KeepLast => "last",
+        };
+        for (name, at) in &copies {
+            eprintln!(
+                "warning: '{name}' appears {} times in the archive; using the {keep} copy",
+                at.len()
+            );
+        }
+
+        let first = self.opts.on_duplicate == DuplicatePolicy::KeepFirst;
+        Ok(copies
+            .into_iter()
+            .map(|(name, at)| (name, if first { at[0] } else { at[at.len() - 1] }))
+            .collect())
+    }
+
+    /// `archive` with the `DuplicatePolicy` applied: of each repeated name, only the
+    /// copy the policy picks stays in the index
+    fn apply_duplicate_policy(
+        &self,
+        archive_path: &Path,
+        archive: ZipArchive<BufReader<TrimmedArchive<VolumeReader>>>,
+    ) -> Result<ZipArchive<BufReader<TrimmedArchive<VolumeReader>>>> {
+        let kept = self.resolve_duplicates(archive_path, Some(archive.len()))?;
+        if kept.is_empty() {
+            return Ok(archive);
+        }
+        let file = archive.into_inner().into_inner().keep_entries(|i, entry| {
+            kept.get(&*self.raw_entry_name(entry)).is_none_or(|&keep| keep == i)
+        })?;
+        Ok(ZipArchive::new(BufReader::new(file))?)
+    }
+
+    /// Output path for `name` with its directories stripped, or `None` to skip the entry
+    fn flattened_path(&self, output_dir: &Path, name: &str) -> Result<Option<PathBuf>> {
+        let base = Path::new(name)
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("Entry has no file name: {name}"))?;
+        let path = output_dir.join(base);
+        if !path.exists() {
+            return Ok(Some(path));
+        }
+        match self.opts.on_conflict {
+            ConflictPolicy::Error => {
+                Err(anyhow::anyhow!("Flattening '{}' would overwrite {}", name, path.display()))
+            }
+            ConflictPolicy::Skip => {
+                eprintln!("warning: skipping '{}': {} already exists", name, path.display());
+                Ok(None)
+            }
+            ConflictPolicy::Rename => {
+                let stem = Path::new(base).file_stem().unwrap_or(base).to_string_lossy();
+                let ext = Path::new(base)
+                    .extension()
+                    .map(|e| format!(".{}", e.to_string_lossy()))
+                    .unwrap_or_default();
+                let renamed = (1..)
+                    .map(|n| output_dir.join(format!("{stem}-{n}{ext}")))
+                    .find(|candidate| !candidate.exists())
+                    .expect("unbounded range always yields a free name");
+                Ok(Some(renamed))
+            }
+        }
+    }
+
+    /// Report entry names that would clobber each other on a case-insensitive filesystem.
+    /// Collisions are emitted as JSON events, then either warned about or turned into an error.
+    fn report_case_collisions(&self, archive_path: &Path, op: &str, fail: bool) -> Result<()> {
+        let archive = ZipArchive::new(BufReader::new(self.open_archive(archive_path)?))?;
+        let collisions = find_case_collisions(archive.file_names());
+        if collisions.is_empty() {
+            return Ok(());
+        }
+        if crate::progress::output_mode().json {
+            for names in &collisions {
+                crate::progress::print_json(&serde_json::json!({
+                    "event":"case_collision","op": op,"archive": archive_path.display().to_string(),
+                    "names": names
+                }));
+            }
+        }
+        let groups: Vec<String> = collisions.iter().map(|names| names.join(" / ")).collect();
+        if fail {
+            return Err(anyhow::anyhow!(
+                "Archive contains entries that differ only by case: {}",
+                groups.join(", ")
+            ));
+        }
+        for group in groups {
+            eprintln!(
+                "warning: entries differ only by case and will clash on Windows/macOS: {group}"
+            );
+        }
+        Ok(())
+    }
+
+    /// List archive entries with their metadata (sizes, method, CRC, time, encryption)
+    pub fn list_archive_detailed<P: AsRef<Path>>(&self, archive_path: P) -> Result<Vec<EntryInfo>> {
+        let mut entries = Vec::new();
+        self.list_archive_with_callback(archive_path, |entry| {
+            entries.push(entry);
+            Ok(())
+        })?;
+        Ok(entries)
+    }
+
+    /// Hand each entry to `on_entry` as soon as it is read, so callers can show
+    /// a long listing incrementally; an error from the callback stops the listing.
+    ///
+    /// Entries come straight from the central directory, one record at a time, after a
+    /// first pass over every name applies `on_duplicate`, so a repeated name is listed
+    /// once, as the copy that extraction would write.
+    pub fn list_archive_with_callback<P, F>(&self, archive_path: P, mut on_entry: F) -> Result<()>
+    where
+        P: AsRef<Path>,
+        F: FnMut(EntryInfo) -> Result<()>,
+    {
+        let kept = self.resolve_duplicates(archive_path.as_ref(), None)?;
+        let mut reader = BufReader::new(self.open_archive(archive_path.as_ref())?);
+        let mut index = 0;
+        central_dir::for_each_entry(&mut reader, |entry| {
+            let name = self.raw_entry_name(&entry);
+            let i = index;
+            index += 1;
+            if kept.get(&*name).is_some_and(|&keep| keep != i) {
+                return Ok(());
+            }
+            if self.opts.skip_mac_metadata && is_mac_metadata(&name) {
+                return Ok(());
+            }
+            if !self.selects(&name) {
+                return Ok(());
+            }
+            #[allow(deprecated)]
+            let method = zip::CompressionMethod::from_u16(raw_entry_method(&entry));
+            on_entry(EntryInfo {
+                is_dir: name.ends_with(['/', '\\']),
+                name: name.into_owned(),
+                size: entry.uncompressed_size,
+                compressed_size: entry.compressed_size,
+                method: compression_method_name(method),
+                crc32: entry.crc32,
+                modified: zip::DateTime::try_from_msdos(entry.modified_date, entry.modified_time)
+                    .ok()
+                    .map(format_zip_datetime),
+                encryption: raw_entry_encryption(&entry),
+            })
+        })
+    }
+
+    fn add_file_to_zip<W: Write + Seek>(
+        &self,
+        zip: &mut ZipWriter<W>,
+        file_path: &Path,
+        options: SimpleFileOptions,
+        state: &mut CreateState,
     ) -> Result<()> {
-        let walkdir = WalkDir::new(dir_path);
-        let it = walkdir.into_iter();
+        let name = self.input_entry_name(file_path);
+        self.add_source(zip, file_path, &name, options, state)
+    }
 
-        // Get the directory name to preserve structure
-        let dir_name = dir_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    /// Entry name for a file named directly as an input
+    fn input_entry_name<'p>(&self, path: &'p Path) -> Cow<'p, str> {
+        match self.opts.store_full_path {
+            true => Cow::Owned(full_path_entry_name(path)),
+            false => path.file_name().unwrap().to_string_lossy(),
+        }
+    }
 
-        for entry in it {
-            let entry = entry?;
-            let path = entry.path();
-            let relative_path = path.strip_prefix(dir_path)?.to_string_lossy();
+    /// Whether the input `path` is a symlink to be archived as a link entry: one to a
+    /// file (or to nothing), unless `dereference` asks for what it points to. Links
+    /// to directories are walked like the directories themselves.
+    fn stores_link(&self, path: &Path) -> bool {
+        !self.opts.dereference && path.is_symlink() && !path.is_dir()
+    }
 
-            // Include directory name in archive path
-            let archive_path = if relative_path.is_empty() {
-                format!("{dir_name}/")
-            } else {
-                format!("{dir_name}/{relative_path}")
-            };
+    /// Store the symlink named as an input as a link entry holding its target, which
+    /// Unix extraction recreates as a link
+    fn add_link_to_zip<W: Write + Seek>(
+        &self,
+        zip: &mut ZipWriter<W>,
+        path: &Path,
+        base: SimpleFileOptions,
+    ) -> Result<()> {
+        let name = self.input_entry_name(path);
+        let target = std::fs::read_link(path)?;
+        let target = portable_entry_name(&target.to_string_lossy()).into_owned();
+        self.check_entry_name(&name)?;
+        // The link's own time; `entry_time` would read the file it points to
+        let stamp = std::fs::symlink_metadata(path)?.modified().ok();
+        let stamp = self.opts.mtime.or(stamp).and_then(system_to_zip_datetime);
+        match self.link_owner_field(path)? {
+            Some(field) => {
+                let mut options = FullFileOptions::default();
+                options.add_extra_data(UNIX_OWNER_FIELD, field, false)?;
+                let options = match stamp {
+                    Some(stamp) => options.last_modified_time(stamp),
+                    None => options,
+                };
+                zip.add_symlink(name, target, options)?;
+            }
+            None => {
+                let options = match stamp {
+                    Some(stamp) => base.last_modified_time(stamp),
+                    None => base,
+                };
+                zip.add_symlink(name, target, options)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the file at `path` into a new entry `name`. With `keep_going`, a file that
+    /// can't be opened is recorded as skipped and left out instead of failing. With
+    /// `dedup`, content seen before becomes a second central directory record for the
+    /// entry that holds it, sharing its compressed data.
+    fn add_source<W: Write + Seek>(
+        &self,
+        zip: &mut ZipWriter<W>,
+        path: &Path,
+        name: &str,
+        base: SimpleFileOptions,
+        state: &mut CreateState,
+    ) -> Result<()> {
+        let mut source = match self.open_source(path) {
+            Ok(source) => source,
+            Err(err) if self.opts.keep_going => {
+                tracing::warn!(path = %path.display(), error = %err, "skipping unreadable file");
+                state.summary.skipped.push(SkippedFile {
+                    path: path.to_path_buf(),
+                    error: format!("{:#}", err.root_cause()),
+                });
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+        if self.opts.dedup {
+            let mut hasher = Sha256::new();
+            let len = std::io::copy(&mut self.open_source(path)?, &mut hasher)?;
+            match state.seen.entry((len, hasher.finalize().into())) {
+                std::collections::hash_map::Entry::Occupied(first) => {
+                    tracing::debug!(entry = name, first = first.get().as_str(), "deduplicated");
+                    self.check_entry_name(name)?;
+                    // The copy keeps the first entry's local header, so its timestamp and
+                    // permissions, and the local header still carries the first name.
+                    // Readers that go by the central directory (as most do) extract it
+                    // as a regular file; streaming readers only see the first copy.
+                    zip.shallow_copy_file(first.get(), name)?;
+                    state.summary.duplicate_files += 1;
+                    state.summary.duplicate_bytes += len;
+                    return Ok(());
+                }
+                std::collections::hash_map::Entry::Vacant(slot) => {
+                    slot.insert(name.to_string());
+                }
+            }
+        }
+        match self.owner_field(path)? {
+            // Extra fields need the full options type. `base` only carries a method and
+            // level, which `file_options` sets again.
+            Some(field) => {
+                let options = FullFileOptions::default();
+                let mut options = self.entry_time(
+                    self.file_options(options, path, name, &mut state.summary)?,
+                    Some(path),
+                );
+                options.add_extra_data(UNIX_OWNER_FIELD, field, false)?;
+                self.start_entry(zip, name, options)?;
+            }
+            None => {
+                let options = self.entry_time(
+                    self.file_options(base, path, name, &mut state.summary)?,
+                    Some(path),
+                );
+                self.start_entry(zip, name, options)?;
+            }
+        }
+        copy_buffered(&mut source, zip, self.opts.io_buffer_size)?;
+        Ok(())
+    }
+
+    /// Entry name as displayed and extracted. Names flagged as UTF-8 (or carrying a
+    /// Unicode path field) are used as they are; the rest are decoded with the
+    /// configured `name_encoding`.
+    fn entry_name<'a, R: Read>(&self, file: &'a ZipFile<'_, R>) -> Cow<'a, str> {
+        if file.get_metadata().is_utf8 {
+            return Cow::Borrowed(file.name());
+        }
+        self.opts.name_encoding.decode(file.name_raw())
+    }
+
+    /// `entry_name` for a raw central directory record
+    fn raw_entry_name<'a>(&self, entry: &'a central_dir::RawEntry) -> Cow<'a, str> {
+        if entry.flags & UTF8_FLAG != 0 {
+            return String::from_utf8_lossy(&entry.name);
+        }
+        // Info-ZIP Unicode path field: version (1), CRC of the plain name (4), UTF-8 name
+        if let Some(name) = central_dir::extra_field(&entry.extra, UNICODE_PATH_EXTRA_ID)
+            .and_then(|data| data.get(5..))
+        {
+            return String::from_utf8_lossy(name);
+        }
+        self.opts.name_encoding.decode(&entry.name)
+    }
+
+    /// Whether `name` passes the `entries` selection
+    fn selects(&self, name: &str) -> bool {
+        self.opts.entries.is_empty() || self.opts.entries.iter().any(|n| entry_selected(name, n))
+    }
+
+    /// Whether the overwrite policy keeps the file already at `path` instead of
+    /// extracting an entry last modified at `entry_time`
+    fn keep_existing(&self, path: &Path, entry_time: Option<zip::DateTime>) -> Result<bool> {
+        if self.opts.overwrite == OverwritePolicy::Always || !path.is_file() {
+            return Ok(false);
+        }
+        if self.opts.overwrite == OverwritePolicy::Never {
+            return Ok(true);
+        }
+        // Entries without a usable timestamp can't be shown to be newer
+        let Some(entry_time) = entry_time.and_then(zip_datetime_to_system) else {
+            return Ok(true);
+        };
+        Ok(entry_time <= std::fs::metadata(path)?.modified()?)
+    }
+
+    /// Open an archive for reading, together with its leading volumes if it was split
+    fn open_archive(&self, path: &Path) -> Result<TrimmedArchive<VolumeReader>> {
+        let volumes = retry_io(self.opts.retries, RETRY_BASE_DELAY, || VolumeReader::open(path))
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        match self.opts.ignore_comment_mismatch {
+            true => TrimmedArchive::trimmed(volumes)
+                .with_context(|| format!("Failed to find the end of {}", path.display())),
+            false => Ok(TrimmedArchive::whole(volumes)?),
+        }
+    }
+
+    fn open_file(&self, path: &Path) -> Result<File> {
+        retry_io(self.opts.retries, RETRY_BASE_DELAY, || File::open(path))
+            .with_context(|| format!("Failed to open {}", path.display()))
+    }
+
+    /// Open an input file for archiving, skipping holes when `sparse` is set
+    fn open_source(&self, path: &Path) -> Result<Box<dyn Read>> {
+        let file = self.open_file(path)?;
+        if !self.opts.sparse {
+            return Ok(Box::new(file));
+        }
+        let reader = crate::sparse::SparseReader::new(file)
+            .with_context(|| format!("Failed to map data regions of {}", path.display()))?;
+        Ok(Box::new(reader))
+    }
+
+    fn create_file(&self, path: &Path) -> Result<File> {
+        retry_io(self.opts.retries, RETRY_BASE_DELAY, || File::create(path))
+            .with_context(|| format!("Failed to create {}", path.display()))
+    }
+
+    /// Choose the compression method for the file `path`, archived as `name`: the first
+    /// matching compression rule, else stored for known compressed extensions or (with
+    /// auto-store) high-entropy content, deflated otherwise
+    fn file_options<'k, T: FileOptionExtension>(
+        &self,
+        base: FileOptions<'k, T>,
+        path: &Path,
+        name: &str,
+        summary: &mut CreateSummary,
+    ) -> Result<FileOptions<'k, T>> {
+        if let Some(rule) = self.opts.compression_rules.iter().find(|r| r.matches(name)) {
+            tracing::debug!(entry = name, pattern = rule.pattern.as_str(), "compression rule");
+            // A deflate rule without a level keeps the global one
+            let level = match rule.method {
+                zip::CompressionMethod::Deflated => {
+                    rule.level.or(self.opts.compression_level.map(|level| level as i64))
+                }
+                _ => rule.level,
+            };
+            return Ok(base.compression_method(rule.method).compression_level(level));
+        }
+        let by_extension = path.extension().is_some_and(|ext| {
+            let ext = ext.to_string_lossy();
+            self.opts.store_extensions.iter().any(|e| e.eq_ignore_ascii_case(&ext))
+        });
+        if by_extension {
+            tracing::debug!(path = %path.display(), "storing by extension");
+        } else if !self.opts.force_recompress
+            && let Some(format) = archive_format(self.open_file(path)?)?
+        {
+            tracing::debug!(entry = name, format, "storing compressed data");
+            summary.stored_archives += 1;
+            summary.stored_bytes += path.metadata().map_or(0, |m| m.len());
+            return Ok(base.compression_method(zip::CompressionMethod::Stored));
+        }
+        let stored = match by_extension {
+            true => {
+                summary.stored_by_extension += 1;
+                true
+            }
+            false if self.opts.auto_store => {
+                let start = Instant::now();
+                let incompressible = is_incompressible(
+                    path,
+                    self.opts.store_entropy_threshold,
+                    self.opts.store_ratio_threshold,
+                )?;
+                summary.probe_time += start.elapsed();
+                summary.stored_by_probe += incompressible as usize;
+                incompressible
+            }
+            false => false,
+        };
+        if stored {
+            summary.stored_bytes += path.metadata().map_or(0, |m| m.len());
+            return Ok(base.compression_method(zip::CompressionMethod::Stored));
+        }
+        let options = base.compression_method(zip::CompressionMethod::Deflated);
+        Ok(match self.opts.compression_level {
+            Some(level) => options.compression_level(Some(level as i64)),
+            None => options,
+        })
+    }
+
+    /// `options` stamped with the `mtime` override, or else with the modification time
+    /// of `path`; entries with neither keep the writer's default of now
+    fn entry_time<'k, T: FileOptionExtension>(
+        &self,
+        options: FileOptions<'k, T>,
+        path: Option<&Path>,
+    ) -> FileOptions<'k, T> {
+        match (self.opts.mtime.and_then(system_to_zip_datetime), path) {
+            (Some(stamp), _) => options.last_modified_time(stamp),
+            (None, Some(path)) => with_mtime(options, path),
+            (None, None) => options,
+        }
+    }
+
+    /// Owner extra field for the file at `path` under `preserve_owner`; `None` otherwise
+    /// and on platforms without Unix owners
+    fn owner_field(&self, path: &Path) -> Result<Option<Box<[u8]>>> {
+        self.owner_field_with(path, |p| std::fs::metadata(p))
+    }
+
+    /// Owner extra field for the symlink at `path` itself rather than what it points to
+    fn link_owner_field(&self, path: &Path) -> Result<Option<Box<[u8]>>> {
+        self.owner_field_with(path, |p| std::fs::symlink_metadata(p))
+    }
+
+    fn owner_field_with(
+        &self,
+        path: &Path,
+        metadata: fn(&Path) -> std::io::Result<std::fs::Metadata>,
+    ) -> Result<Option<Box<[u8]>>> {
+        if !self.opts.preserve_owner {
+            return Ok(None);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let meta = metadata(path)?;
+            Ok(Some(owner_field(meta.uid(), meta.gid())))
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (path, metadata);
+            Ok(None)
+        }
+    }
+
+    /// Whether a file passes the modification-time and size filters
+    fn include_file(&self, path: &Path) -> Result<bool> {
+        self.include_with(path, |p| std::fs::metadata(p))
+    }
+
+    /// Whether a symlink stored as a link passes the same filters, judged by the link
+    /// itself
+    fn include_link(&self, path: &Path) -> Result<bool> {
+        self.include_with(path, |p| std::fs::symlink_metadata(p))
+    }
+
+    fn include_with(
+        &self,
+        path: &Path,
+        metadata: fn(&Path) -> std::io::Result<std::fs::Metadata>,
+    ) -> Result<bool> {
+        let o = &self.opts;
+        if o.newer_than.is_none()
+            && o.older_than.is_none()
+            && o.min_size.is_none()
+            && o.max_size.is_none()
+        {
+            return Ok(true);
+        }
+        let meta = metadata(path)?;
+        let size = meta.len();
+        if o.min_size.is_some_and(|min| size < min) || o.max_size.is_some_and(|max| size > max) {
+            return Ok(false);
+        }
+        if o.newer_than.is_none() && o.older_than.is_none() {
+            return Ok(true);
+        }
+        let modified = meta.modified()?;
+        Ok(o.newer_than.is_none_or(|t| modified > t) && o.older_than.is_none_or(|t| modified < t))
+    }
+
+    /// Walk `dir`, pruning hidden and ignored files and directories below it when
+    /// configured to. Fails if an exclude pattern is invalid.
+    fn walk_dir(
+        &self,
+        dir: &Path,
+    ) -> Result<impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> + use<>> {
+        let exclude_hidden = self.opts.exclude_hidden;
+        let mut ignores =
+            IgnoreRules::new(dir, &self.opts.exclude_patterns, self.opts.respect_gitignore)?;
+        let mut walk = WalkDir::new(dir).sort_by_file_name();
+        if let Some(depth) = self.opts.max_depth {
+            walk = walk.max_depth(depth);
+        }
+        // Sorted, so the same tree always gives entries in the same order
+        Ok(walk.into_iter().filter_entry(move |e| {
+            e.depth() == 0
+                || !(exclude_hidden && is_hidden(e)
+                    || ignores.is_ignored(e.path(), e.file_type().is_dir()))
+        }))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn add_dir_to_zip_with_progress<W: Write + Seek>(
+        &self,
+        zip: &mut ZipWriter<W>,
+        dir_path: &Path,
+        options: &SimpleFileOptions,
+        reporter: &mut dyn ProgressReporter,
+        json_progress: &mut Option<JsonProgress>,
+        processed: &mut u64,
+        state: &mut CreateState,
+        opts: ArchiveOptions,
+    ) -> Result<()> {
+        let it = self.walk_dir(dir_path)?;
+
+        // Get the directory name to preserve structure
+        let dir_name = dir_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+
+        for entry in it {
+            let entry = entry?;
+            let path = entry.path();
+            let relative_path = path.strip_prefix(dir_path)?.to_string_lossy();
+
+            // Include directory name in archive path
+            let archive_path = if relative_path.is_empty() {
+                format!("{dir_name}/")
+            } else {
+                portable_entry_name(&format!("{dir_name}/{relative_path}")).into_owned()
+            };
+
+            if reporter.is_cancelled() {
+                return Err(ArchiveError::Cancelled.into());
+            }
+            if path.is_file() {
+                if !self.include_file(path)? {
+                    continue;
+                }
+                reporter.message(&format!("Adding: {}", path.display()));
+                self.add_source(zip, path, &archive_path, *options, state)?;
+                reporter.inc(1);
+                *processed += 1;
+                if let Some(progress) = json_progress {
+                    let size = entry.metadata()?.len();
+                    let file = path.display().to_string();
+                    if let Some(event) = progress.event("create", &file, *processed, size) {
+                        crate::progress::print_json(&event);
+                    }
+                }
+            } else if path.is_dir() && opts.dir_entries {
+                // Files imply their parents, so only an empty input root needs its own entry
+                let is_empty = self.walk_dir(path)?.nth(1).is_none();
+                if (is_empty && opts.preserve_empty_dirs)
+                    || (!is_empty && !relative_path.is_empty())
+                {
+                    self.check_entry_name(&archive_path)?;
+                    let name = archive_path.trim_end_matches('/');
+                    match self.owner_field(path)? {
+                        Some(field) => {
+                            let mut options = FullFileOptions::default()
+                                .compression_method(zip::CompressionMethod::Stored);
+                            options.add_extra_data(UNIX_OWNER_FIELD, field, false)?;
+                            zip.add_directory(name, self.entry_time(options, Some(path)))?;
+                        }
+                        None => zip.add_directory(name, self.entry_time(*options, Some(path)))?,
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// What is wrong with a raw entry name that other tools may mishandle: NUL bytes,
+/// control characters, an absolute path or backslash separators. Empty when fine.
+pub fn entry_name_problems(name: &[u8]) -> Vec<&'static str> {
+    let mut problems = Vec::new();
+    if name.contains(&0) {
+        problems.push("contains a NUL byte");
+    }
+    if name.iter().any(|&b| b != 0 && (b < 0x20 || b == 0x7f)) {
+        problems.push("contains control characters");
+    }
+    let is_drive_path = name.len() >= 2 && name[0].is_ascii_alphabetic() && name[1] == b':';
+    if name.starts_with(b"/") || name.starts_with(b"\\") || is_drive_path {
+        problems.push("is an absolute path");
+    }
+    if name.contains(&b'\\') {
+        problems.push("contains backslashes");
+    }
+    problems
+}
+
+/// Whether an entry is a directory: its name ends in `/`, or, for archives written
+/// without the slash, its Unix mode or MS-DOS attributes say so
+fn entry_is_dir<R: Read>(file: &ZipFile<'_, R>) -> bool {
+    const S_IFMT: u32 = 0o170000;
+    const S_IFDIR: u32 = 0o040000;
+    file.is_dir() || file.unix_mode().is_some_and(|mode| mode & S_IFMT == S_IFDIR)
+}
+
+/// Uncompressed size of every entry, from the central directory
+fn uncompressed_total<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<u64> {
+    let mut total = 0;
+    for i in 0..archive.len() {
+        total += archive.by_index_raw(i)?.size();
+    }
+    Ok(total)
+}
+
+/// Entry name with every separator as `/`, as the ZIP spec requires; Windows paths
+/// would otherwise store backslashes that other platforms read as part of the name
+fn portable_entry_name(name: &str) -> Cow<'_, str> {
+    match name.contains('\\') {
+        true => Cow::Owned(name.replace('\\', "/")),
+        false => Cow::Borrowed(name),
+    }
+}
+
+// General purpose flag: the entry name is UTF-8
+const UTF8_FLAG: u16 = 0x0800;
+/// Info-ZIP Unicode path extra field (`up`), holding a UTF-8 copy of the entry name
+const UNICODE_PATH_EXTRA_ID: u16 = 0x7075;
+
+/// Info-ZIP "new Unix" extra field (`ux`), holding an entry's uid and gid
+const UNIX_OWNER_FIELD: u16 = 0x7875;
+
+/// Body of a [`UNIX_OWNER_FIELD`]: version 1, then the uid and gid, each preceded
+/// by its size in bytes and stored little-endian
+fn owner_field(uid: u32, gid: u32) -> Box<[u8]> {
+    let mut field = vec![1, 4];
+    field.extend(uid.to_le_bytes());
+    field.push(4);
+    field.extend(gid.to_le_bytes());
+    field.into()
+}
+
+/// The (uid, gid) in an entry's extra data, if it has a [`UNIX_OWNER_FIELD`] with ids
+/// that fit in 32 bits
+fn parse_owner_field(mut extra: &[u8]) -> Option<(u32, u32)> {
+    while extra.len() >= 4 {
+        let id = u16::from_le_bytes([extra[0], extra[1]]);
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let body = extra.get(4..4 + len)?;
+        extra = &extra[4 + len..];
+        if id != UNIX_OWNER_FIELD || body.first() != Some(&1) {
+            continue;
+        }
+        let read_id = |field: &[u8]| -> Option<(u32, usize)> {
+            let size = *field.first()? as usize;
+            let bytes = field.get(1..1 + size)?;
+            // Wider ids are fine as long as the extra bytes are zero
+            if size > 4 && bytes[4..].iter().any(|&b| b != 0) {
+                return None;
+            }
+            let mut value = [0u8; 4];
+            value[..size.min(4)].copy_from_slice(&bytes[..size.min(4)]);
+            Some((u32::from_le_bytes(value), 1 + size))
+        };
+        let (uid, used) = read_id(&body[1..])?;
+        let (gid, _) = read_id(&body[1 + used..])?;
+        return Some((uid, gid));
+    }
+    None
+}
+
+/// Give extracted paths their archived owners. Without the privilege to do so, warns
+/// once and leaves the rest owned by the current user.
+#[cfg(unix)]
+fn restore_owners(owners: &[(PathBuf, (u32, u32))]) -> Result<()> {
+    for (path, (uid, gid)) in owners {
+        match std::os::unix::fs::lchown(path, Some(*uid), Some(*gid)) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+                tracing::debug!(path = %path.display(), "not permitted to restore owner");
+                eprintln!(
+                    "warning: not permitted to restore file owners (needs root); extracted \
+                     files belong to the current user"
+                );
+                return Ok(());
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to chown {}", path.display()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `path` as an entry name: `.` and `..` resolved, then its normal components joined
+/// with `/`, so absolute paths become relative and leading `..` can't lead extraction
+/// out of the output directory
+fn full_path_entry_name(path: &Path) -> String {
+    use std::path::Component;
+    let normalized = normalize_lexically(path);
+    let parts: Vec<_> = normalized
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect();
+    parts.join("/")
+}
+
+/// Whether `name` is macOS Finder noise: anything under `__MACOSX/` or an AppleDouble `._` file
+pub fn is_mac_metadata(name: &str) -> bool {
+    name.starts_with("__MACOSX/")
+        || name
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .is_some_and(|base| base.starts_with("._"))
+}
+
+/// Group names that are equal ignoring case, in first-seen order; only groups of two or more
+pub fn find_case_collisions<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<Vec<String>> {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for name in names {
+        let key = name.to_lowercase();
+        match index.get(&key) {
+            Some(&i) => {
+                if !groups[i].1.iter().any(|n| n == name) {
+                    groups[i].1.push(name.to_string());
+                }
+            }
+            None => {
+                index.insert(key.clone(), groups.len());
+                groups.push((key, vec![name.to_string()]));
+            }
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(_, names)| names)
+        .filter(|names| names.len() > 1)
+        .collect()
+}
+
+/// Turn out-of-space I/O failures anywhere in `err`'s chain into [`ArchiveError::DiskFull`],
+/// and names too long for the platform into [`ArchiveError::PathTooLong`]
+fn classify_write_error(err: anyhow::Error, path: &Path) -> anyhow::Error {
+    let is_disk_full = |e: &std::io::Error| {
+        e.kind() == std::io::ErrorKind::StorageFull || e.kind() == std::io::ErrorKind::QuotaExceeded
+    };
+    // Windows also reports invalid characters as InvalidFilename; 206 is "name too long"
+    let is_too_long = |e: &std::io::Error| {
+        e.kind() == std::io::ErrorKind::InvalidFilename
+            && (cfg!(unix) || e.raw_os_error() == Some(206))
+    };
+    let io_error = err.chain().find_map(|cause| {
+        if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            return Some(e);
+        }
+        match cause.downcast_ref::<zip::result::ZipError>() {
+            Some(zip::result::ZipError::Io(e)) => Some(e),
+            _ => None,
+        }
+    });
+    match io_error {
+        Some(e) if is_disk_full(e) => ArchiveError::DiskFull {
+            path: path.to_path_buf(),
+            source: std::io::Error::new(e.kind(), e.to_string()),
+        }
+        .into(),
+        Some(e) if is_too_long(e) => ArchiveError::PathTooLong {
+            path: path.to_path_buf(),
+            source: std::io::Error::new(e.kind(), e.to_string()),
+        }
+        .into(),
+        _ => err,
+    }
+}
+
+fn copy_buffered<R: std::io::Read, W: std::io::Write>(
+    reader: &mut R,
+    writer: &mut W,
+    buf_size: usize,
+) -> Result<u64> {
+    let mut buf = vec![0u8; buf_size];
+    let mut total: u64 = 0;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n])?;
+        total += n as u64;
+    }
+    Ok(total)
+}
+
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(50);
+/// Longest wait between two attempts
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Errors worth retrying on network filesystems; anything else fails immediately
+fn is_transient(err: &std::io::Error) -> bool {
+    use std::io::ErrorKind::*;
+    const EIO: i32 = 5;
+    matches!(err.kind(), Interrupted | WouldBlock | TimedOut | ResourceBusy)
+        || (cfg!(unix) && err.raw_os_error() == Some(EIO))
+}
+
+/// Wait before retry number `attempt` (from 0): `base_delay` doubled each time, up to
+/// [`RETRY_MAX_DELAY`]
+fn retry_delay(base_delay: Duration, attempt: u32) -> Duration {
+    base_delay
+        .saturating_mul(2u32.saturating_pow(attempt.min(16)))
+        .min(RETRY_MAX_DELAY)
+}
+
+/// Run `op`, retrying transient failures up to `retries` times with exponential backoff
+fn retry_io<T>(
+    retries: u32,
+    base_delay: Duration,
+    mut op: impl FnMut() -> std::io::Result<T>,
+) -> std::io::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(err) if attempt < retries && is_transient(&err) => {
+                std::thread::sleep(retry_delay(base_delay, attempt));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// `path` in the `\\?\` form Windows needs for paths of 260 characters or more;
+/// unchanged elsewhere, or when short enough
+fn extended_length_path(path: &Path) -> std::borrow::Cow<'_, Path> {
+    #[cfg(windows)]
+    if path.as_os_str().len() >= 260
+        && let Ok(absolute) = std::path::absolute(path)
+        && !absolute.as_os_str().to_string_lossy().starts_with(r"\\")
+    {
+        // The prefix turns off normalization, so it needs an absolute path with `\`
+        // separators, which `absolute` provides. Network shares (`\\server\share`) and
+        // paths already prefixed are left as they are.
+        let mut extended = std::ffi::OsString::from(r"\\?\");
+        extended.push(absolute.as_os_str());
+        return std::borrow::Cow::Owned(extended.into());
+    }
+    std::borrow::Cow::Borrowed(path)
+}
+
+/// Fail if `path`, with any symlinks among its existing ancestors resolved, lies outside `root`
+fn ensure_within_root(root: &Path, path: &Path, name: &str) -> Result<()> {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    // A final component that is itself a link is replaced rather than followed
+    if path.is_symlink() {
+        rest.push(path.file_name().unwrap_or_default());
+        existing = path.parent().unwrap_or(root);
+    }
+    while !existing.exists() {
+        rest.push(existing.file_name().unwrap_or_default());
+        existing = existing.parent().unwrap_or(root);
+    }
+    let mut resolved = existing.canonicalize()?;
+    resolved.extend(rest.iter().rev());
+    if !normalize_lexically(&resolved).starts_with(root) {
+        return Err(anyhow::anyhow!(
+            "Refusing to extract '{}': path escapes the output directory through a symlink",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Create a symlink at `link` only if its target, resolved from the link's directory, stays in `root`
+fn extract_symlink(root: &Path, link: &Path, target: &str, name: &str) -> Result<()> {
+    let parent = link.parent().unwrap_or(root);
+    std::fs::create_dir_all(parent)?;
+    let resolved = normalize_lexically(&parent.canonicalize()?.join(target));
+    if !resolved.starts_with(root) {
+        return Err(anyhow::anyhow!(
+            "Refusing to extract symlink '{}': target '{}' points outside the output directory",
+            name,
+            target
+        ));
+    }
+    if link.is_symlink() {
+        std::fs::remove_file(link)?;
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, link)?;
+    Ok(())
+}
+
+/// Apply a Unix permission mode; a no-op on other platforms
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Failed to set mode {:o} on {}", mode, path.display()))?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+    Ok(())
+}
+
+/// Resolve `.` and `..` components without touching the filesystem
+fn normalize_lexically(path: &Path) -> PathBuf {
+    use std::path::Component;
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::CurDir => {}
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Dotfiles everywhere, plus entries carrying the hidden attribute on Windows
+fn is_hidden(entry: &walkdir::DirEntry) -> bool {
+    if entry.file_name().to_string_lossy().starts_with('.') {
+        return true;
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        if let Ok(meta) = entry.metadata() {
+            return meta.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0;
+        }
+    }
+    false
+}
+
+/// Progress of a resumable extraction, kept in [`RESUME_STATE_FILE`] in the output root
+struct ResumeState {
+    path: PathBuf,
+    completed: std::collections::HashSet<String>,
+    log: File,
+}
+
+impl ResumeState {
+    fn open(root: &Path) -> Result<Self> {
+        let path = root.join(RESUME_STATE_FILE);
+        let completed = match std::fs::read_to_string(&path) {
+            Ok(text) => text.lines().map(str::to_string).collect(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        let log = File::options()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        Ok(Self {
+            path,
+            completed,
+            log,
+        })
+    }
+
+    /// Whether an earlier run finished `name` and `path` still holds its contents
+    fn completed(&self, name: &str, path: &Path, crc32: u32) -> bool {
+        if !self.completed.contains(name) {
+            return false;
+        }
+        file_crc32(path).is_ok_and(|crc| crc == crc32)
+    }
+
+    /// Note that `name` is fully written. The log isn't synced: a line lost to a crash
+    /// only means the entry is extracted again, and `completed` checks the CRC anyway.
+    fn record(&mut self, name: &str) -> Result<()> {
+        writeln!(self.log, "{name}")?;
+        Ok(())
+    }
+
+    /// Remove the state file once every entry has been extracted
+    fn finish(self) -> Result<()> {
+        drop(self.log);
+        std::fs::remove_file(&self.path)
+            .with_context(|| format!("Failed to remove {}", self.path.display()))
+    }
+}
+
+/// Gitignore-style exclusions for one directory walk. Patterns given up front are
+/// rooted at the walked directory; with `respect_gitignore`, each directory's
+/// `.gitignore` applies below it, deeper files taking precedence as in git.
+struct IgnoreRules {
+    root: PathBuf,
+    patterns: Option<Gitignore>,
+    respect_gitignore: bool,
+    // `.gitignore` matchers by directory, loaded as the walk reaches them
+    gitignores: HashMap<PathBuf, Option<Gitignore>>,
+}
+
+impl IgnoreRules {
+    fn new(root: &Path, patterns: &[String], respect_gitignore: bool) -> Result<Self> {
+        let patterns = match patterns {
+            [] => None,
+            patterns => {
+                let mut builder = GitignoreBuilder::new(root);
+                for pattern in patterns {
+                    builder
+                        .add_line(None, pattern)
+                        .with_context(|| format!("Invalid exclude pattern {pattern:?}"))?;
+                }
+                Some(builder.build()?)
+            }
+        };
+        Ok(Self {
+            root: root.to_path_buf(),
+            patterns,
+            respect_gitignore,
+            gitignores: HashMap::new(),
+        })
+    }
+
+    fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let mut ignored = self.patterns.as_ref().and_then(|g| match_decision(g, path, is_dir));
+        if self.respect_gitignore {
+            let mut dirs: Vec<&Path> =
+                path.ancestors().skip(1).take_while(|d| d.starts_with(&self.root)).collect();
+            dirs.reverse();
+            for dir in dirs {
+                let gitignore = self.gitignores.entry(dir.to_path_buf()).or_insert_with(|| {
+                    let file = dir.join(".gitignore");
+                    if !file.is_file() {
+                        return None;
+                    }
+                    let (gitignore, err) = Gitignore::new(&file);
+                    if let Some(err) = err {
+                        tracing::warn!("Problem reading {}: {err}", file.display());
+                    }
+                    Some(gitignore)
+                });
+                if let Some(decision) =
+                    gitignore.as_ref().and_then(|g| match_decision(g, path, is_dir))
+                {
+                    ignored = Some(decision);
+                }
+            }
+        }
+        ignored.unwrap_or(false)
+    }
+}
+
+/// `Some(true)` when `path` is ignored, `Some(false)` when a `!` pattern re-includes it
+fn match_decision(gitignore: &Gitignore, path: &Path, is_dir: bool) -> Option<bool> {
+    match gitignore.matched(path, is_dir) {
+        ignore::Match::None => None,
+        ignore::Match::Ignore(_) => Some(true),
+        ignore::Match::Whitelist(_) => Some(false),
+    }
+}
+
+/// Magic bytes of compressed formats that deflate cannot shrink any further
+const ARCHIVE_MAGIC: &[(&[u8], &str)] = &[
+    (b"PK\x03\x04", "ZIP"),
+    (b"PK\x05\x06", "ZIP"),
+    (b"\x1f\x8b", "gzip"),
+    (b"BZh", "bzip2"),
+    (b"\xfd7zXZ\x00", "xz"),
+    (b"\x28\xb5\x2f\xfd", "zstd"),
+    (b"7z\xbc\xaf\x27\x1c", "7z"),
+    (b"Rar!\x1a\x07", "RAR"),
+];
+
+/// Name of the archive format `file` starts with, if its magic bytes give one away
+fn archive_format(file: File) -> Result<Option<&'static str>> {
+    let mut head = Vec::with_capacity(8);
+    file.take(8).read_to_end(&mut head)?;
+    Ok(ARCHIVE_MAGIC
+        .iter()
+        .find(|(magic, _)| head.starts_with(magic))
+        .map(|&(_, format)| format))
+}
+
+/// Bytes from the start of a file used to judge its compressibility
+const PROBE_SIZE: usize = 64 * 1024;
+
+/// Judge a file incompressible when its prefix has entropy of at least `entropy_threshold`
+/// bits/byte, or when deflating the prefix keeps at least `ratio_threshold` of its size
+fn is_incompressible(path: &Path, entropy_threshold: f64, ratio_threshold: f64) -> Result<bool> {
+    let f = File::open(path)?;
+    let mut buf = Vec::with_capacity(PROBE_SIZE);
+    f.take(PROBE_SIZE as u64).read_to_end(&mut buf)?;
+    if buf.is_empty() {
+        return Ok(false);
+    }
+    let mut freq = [0usize; 256];
+    for &b in &buf {
+        freq[b as usize] += 1;
+    }
+    let total = buf.len() as f64;
+    let mut entropy = 0.0f64;
+    for &count in &freq {
+        if count == 0 {
+            continue;
+        }
+        let p = count as f64 / total;
+        entropy -= p * p.log2();
+    }
+    if entropy >= entropy_threshold {
+        return Ok(true);
+    }
+    Ok(deflate_ratio(&buf)? >= ratio_threshold)
+}
+
+/// Compressed size divided by original size when deflating `data` at a fast level
+fn deflate_ratio(data: &[u8]) -> Result<f64> {
+    let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?.len() as f64 / data.len() as f64)
+}
+
+/// Determine how an encrypted entry is protected without needing its password
+fn entry_encryption<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+) -> Result<EncryptionMethod> {
+    Ok(
+        match archive.get_aes_verification_key_and_salt(index)?.map(|info| info.aes_mode) {
+            Some(AesMode::Aes128) => EncryptionMethod::Aes128,
+            Some(AesMode::Aes192) => EncryptionMethod::Aes192,
+            Some(AesMode::Aes256) => EncryptionMethod::Aes256,
+            None => EncryptionMethod::ZipCrypto,
+        },
+    )
+}
+
+/// Encryption of a raw central directory entry, read from its flags and AES extra field
+fn raw_entry_encryption(entry: &central_dir::RawEntry) -> Option<EncryptionMethod> {
+    const AES_METHOD: u16 = 99;
+    const AES_EXTRA_ID: u16 = 0x9901;
+    if entry.flags & 1 == 0 {
+        return None;
+    }
+    if entry.method != AES_METHOD {
+        return Some(EncryptionMethod::ZipCrypto);
+    }
+    // AE-x extra field: version (2), vendor "AE" (2), strength (1), actual method (2)
+    match central_dir::extra_field(&entry.extra, AES_EXTRA_ID).and_then(|d| d.get(4)) {
+        Some(1) => Some(EncryptionMethod::Aes128),
+        Some(2) => Some(EncryptionMethod::Aes192),
+        _ => Some(EncryptionMethod::Aes256),
+    }
+}
+
+/// Compression method of a raw central directory entry; AES entries record the
+/// real method in their extra field
+fn raw_entry_method(entry: &central_dir::RawEntry) -> u16 {
+    const AES_METHOD: u16 = 99;
+    const AES_EXTRA_ID: u16 = 0x9901;
+    if entry.method != AES_METHOD {
+        return entry.method;
+    }
+    central_dir::extra_field(&entry.extra, AES_EXTRA_ID)
+        .and_then(|data| data.get(5..7))
+        .map_or(entry.method, |m| u16::from_le_bytes([m[0], m[1]]))
+}
+
+fn compression_method_name(method: zip::CompressionMethod) -> String {
+    #[allow(deprecated)]
+    match method {
+        zip::CompressionMethod::Stored => "stored".to_string(),
+        zip::CompressionMethod::Deflated => "deflated".to_string(),
+        zip::CompressionMethod::Deflate64 => "deflate64".to_string(),
+        zip::CompressionMethod::Bzip2 => "bzip2".to_string(),
+        zip::CompressionMethod::Zstd => "zstd".to_string(),
+        zip::CompressionMethod::Lzma => "lzma".to_string(),
+        zip::CompressionMethod::Xz => "xz".to_string(),
+        other => other.to_string().to_lowercase(),
+    }
+}
+
+fn format_zip_datetime(dt: zip::DateTime) -> String {
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        dt.year(),
+        dt.month(),
+        dt.day(),
+        dt.hour(),
+        dt.minute(),
+        dt.second()
+    )
+}
+
+/// Whether entry `name` is picked by `selector`: an exact name, or a directory
+/// prefix ending in `/`
+fn entry_selected(name: &str, selector: &str) -> bool {
+    name == selector || (selector.ends_with('/') && name.starts_with(selector))
+}
+
+/// Selectors from `names` that pick no entry of `archive`
+fn missing_entries<'a, R: Read + Seek>(archive: &ZipArchive<R>, names: &[&'a str]) -> Vec<&'a str> {
+    names
+        .iter()
+        .copied()
+        .filter(|n| !archive.file_names().any(|name| entry_selected(name, n)))
+        .collect()
+}
+
+/// `name` as a relative path, or `None` if it is absolute or climbs above its root
+/// (the rules of `ZipFile::enclosed_name`, for names decoded here)
+fn enclosed_path(name: &str) -> Option<PathBuf> {
+    if name.contains('\0') {
+        return None;
+    }
+    let path = PathBuf::from(name);
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            std::path::Component::Prefix(_) | std::path::Component::RootDir => return None,
+            std::path::Component::ParentDir => depth = depth.checked_sub(1)?,
+            std::path::Component::Normal(_) => depth += 1,
+            std::path::Component::CurDir => {}
+        }
+    }
+    Some(path)
+}
+
+/// Hidden file next to `path` that holds its replacement while it is rebuilt
+pub(crate) fn sibling_temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{name}.rolypoly-tmp"))
+}
+
+/// Move a finished extraction from `staging` to `target`: a rename when `target` is
+/// missing or empty, otherwise each entry is moved across, replacing files and merging
+/// directories that already exist. Replaced files are set aside until the merge is
+/// complete, and a merge that fails part-way is undone.
+fn move_into_place(staging: &Path, target: &Path) -> Result<()> {
+    move_into_place_with(staging, target, &mut |from, to| std::fs::rename(from, to))
+}
+
+/// A file moved into the target by a merge, and where the file it replaced was set aside
+struct MergedFile {
+    from: PathBuf,
+    to: PathBuf,
+    replaced: Option<PathBuf>,
+}
+
+/// `move_into_place`, with the moves into the target made by `rename`
+fn move_into_place_with(
+    staging: &Path,
+    target: &Path,
+    rename: &mut dyn FnMut(&Path, &Path) -> std::io::Result<()>,
+) -> Result<()> {
+    if !target.exists() || target.read_dir()?.next().is_none() {
+        if target.exists() {
+            std::fs::remove_dir(target)?;
+        }
+        std::fs::rename(staging, target)?;
+        return Ok(());
+    }
+    let replaced_dir = staging.with_extension("rolypoly-old");
+    std::fs::create_dir(&replaced_dir)?;
+    let mut merged = Vec::new();
+    match merge_dir(staging, target, &replaced_dir, &mut merged, rename) {
+        Ok(()) => {
+            std::fs::remove_dir_all(&replaced_dir)?;
+            std::fs::remove_dir_all(staging)?;
+            Ok(())
+        }
+        Err(err) => {
+            for file in merged.iter().rev() {
+                let _ = std::fs::rename(&file.to, &file.from);
+                if let Some(replaced) = &file.replaced {
+                    let _ = std::fs::rename(replaced, &file.to);
+                }
+            }
+            // Only empty if every replaced file made it back
+            let _ = std::fs::remove_dir(&replaced_dir);
+            Err(err)
+        }
+    }
+}
+
+fn merge_dir(
+    staging: &Path,
+    target: &Path,
+    replaced_dir: &Path,
+    merged: &mut Vec<MergedFile>,
+    rename: &mut dyn FnMut(&Path, &Path) -> std::io::Result<()>,
+) -> Result<()> {
+    let is_dir = |path: &Path| path.symlink_metadata().is_ok_and(|m| m.is_dir());
+    for entry in std::fs::read_dir(staging)? {
+        let from = entry?.path();
+        let to = target.join(from.file_name().unwrap());
+        if is_dir(&from) && is_dir(&to) {
+            merge_dir(&from, &to, replaced_dir, merged, rename)?;
+            continue;
+        }
+        let replaced = match to.symlink_metadata() {
+            Ok(_) => {
+                let aside = replaced_dir.join(merged.len().to_string());
+                std::fs::rename(&to, &aside)?;
+                Some(aside)
+            }
+            Err(_) => None,
+        };
+        if let Err(err) = rename(&from, &to) {
+            if let Some(aside) = &replaced {
+                let _ = std::fs::rename(aside, &to);
+            }
+            return Err(err).with_context(|| format!("Failed to move {} into place", to.display()));
+        }
+        merged.push(MergedFile { from, to, replaced });
+    }
+    Ok(())
+}
+
+/// CRC-32 of a file's contents
+fn file_crc32(path: &Path) -> std::io::Result<u32> {
+    let mut file = File::open(path)?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = [0; 8192];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => return Ok(hasher.finalize()),
+            n => hasher.update(&buffer[..n]),
+        }
+    }
+}
+
+/// Interpret a ZIP timestamp, which carries no zone, as local time
+pub(crate) fn zip_datetime_to_system(dt: zip::DateTime) -> Option<SystemTime> {
+    use chrono::TimeZone;
+
+    let naive =
+        chrono::NaiveDate::from_ymd_opt(dt.year().into(), dt.month().into(), dt.day().into())?
+            .and_hms_opt(dt.hour().into(), dt.minute().into(), dt.second().into())?;
+    let local = chrono::Local.from_local_datetime(&naive).earliest()?;
+    Some(local.into())
+}
+
+/// ZIP timestamp for `time` in local time; `None` before 1980 or after 2107
+pub(crate) fn system_to_zip_datetime(time: SystemTime) -> Option<zip::DateTime> {
+    use chrono::{Datelike, Timelike};
+
+    let local = chrono::DateTime::<chrono::Local>::from(time).naive_local();
+    zip::DateTime::from_date_and_time(
+        local.year().try_into().ok()?,
+        local.month() as u8,
+        local.day() as u8,
+        local.hour() as u8,
+        local.minute() as u8,
+        local.second() as u8,
+    )
+    .ok()
+}
+
+/// `options` stamped with the modification time of `path`, so identical inputs give
+/// identical archives; times a ZIP can't hold keep the writer's default
+fn with_mtime<'k, T: FileOptionExtension>(
+    options: FileOptions<'k, T>,
+    path: &Path,
+) -> FileOptions<'k, T> {
+    let stamp = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    match stamp.and_then(system_to_zip_datetime) {
+        Some(stamp) => options.last_modified_time(stamp),
+        None => options,
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ArchiveStats {
+    pub file_count: usize,
+    pub dir_count: usize,
+    pub total_uncompressed_size: u64,
+    pub total_compressed_size: u64,
+    pub compression_ratio: f64,
+    pub encrypted_count: usize,
+    pub encryption_methods: Vec<EncryptionMethod>,
+    /// Archive comment; only read by [`ArchiveManager::get_archive_stats`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Bytes before the ZIP data, such as a self-extractor stub or an APK signing block
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub prepended_bytes: u64,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+impl ArchiveStats {
+    fn empty() -> Self {
+        ArchiveStats {
+            file_count: 0,
+            dir_count: 0,
+            total_uncompressed_size: 0,
+            total_compressed_size: 0,
+            compression_ratio: 0.0,
+            encrypted_count: 0,
+            encryption_methods: Vec::new(),
+            comment: None,
+            prepended_bytes: 0,
+        }
+    }
+
+    /// Aggregate already-listed entries, e.g. to summarize a filtered listing
+    pub fn from_entries(entries: &[EntryInfo]) -> Self {
+        let mut stats = Self::empty();
+        for entry in entries {
+            stats.record(entry.is_dir, entry.size, entry.compressed_size, entry.encryption);
+        }
+        stats.finish()
+    }
+
+    /// Count one entry; directories contribute no sizes
+    fn record(
+        &mut self,
+        is_dir: bool,
+        size: u64,
+        compressed_size: u64,
+        encryption: Option<EncryptionMethod>,
+    ) {
+        if is_dir {
+            self.dir_count += 1;
+        } else {
+            self.file_count += 1;
+            self.total_uncompressed_size += size;
+            self.total_compressed_size += compressed_size;
+        }
+        if let Some(method) = encryption {
+            self.encrypted_count += 1;
+            if !self.encryption_methods.contains(&method) {
+                self.encryption_methods.push(method);
+            }
+        }
+    }
+
+    fn finish(mut self) -> Self {
+        self.encryption_methods.sort();
+        if self.total_uncompressed_size > 0 {
+            self.compression_ratio =
+                (self.total_compressed_size as f64 / self.total_uncompressed_size as f64) * 100.0;
+        }
+        self
+    }
+}
+
+/// Bounds for `validate --recursive`, guarding against nested zip bombs
+#[derive(Debug, Clone)]
+pub struct NestedLimits {
+    /// How many levels of archives-within-archives to open
+    pub max_depth: usize,
+    /// Total bytes of nested archives read into memory
+    pub max_bytes: u64,
+}
+
+impl Default for NestedLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 3,
+            max_bytes: 1 << 30,
+        }
+    }
+}
+
+/// Outcome of creating an archive
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct CreateSummary {
+    /// Inputs left out because they could not be read (`keep_going`)
+    pub skipped: Vec<SkippedFile>,
+    /// Files sharing the stored data of identical content archived earlier (`dedup`)
+    pub duplicate_files: usize,
+    /// Bytes those files would otherwise have added before compression
+    pub duplicate_bytes: u64,
+    /// Files stored uncompressed because of their extension (`store_extensions`)
+    pub stored_by_extension: usize,
+    /// Files stored uncompressed because probing their content found it incompressible
+    /// (`auto_store`)
+    pub stored_by_probe: usize,
+    /// Files stored uncompressed because they already hold ZIP, gzip or other
+    /// compressed data (unless `force_recompress`)
+    pub stored_archives: usize,
+    /// Bytes of the files stored uncompressed by any of these rules
+    pub stored_bytes: u64,
+    /// Time spent probing file contents for `auto_store`
+    pub probe_time: Duration,
+}
+
+/// Outcome of extracting an archive. Entries left out by an `entries` selection are
+/// not counted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ExtractSummary {
+    /// Files and links written
+    pub extracted: usize,
+    /// Directories created
+    pub dirs: usize,
+    /// Entries not written: existing files kept by the overwrite policy, files done by
+    /// an earlier `resume` run, macOS metadata and entries `flatten` drops
+    pub skipped: usize,
+    /// Bytes of file data written
+    pub total_bytes: u64,
+}
+
+impl ExtractSummary {
+    /// Every entry accounted for, written or not
+    pub fn entries(&self) -> usize {
+        self.extracted + self.dirs + self.skipped
+    }
+}
+
+/// Bookkeeping while the entries of one archive are written
+#[derive(Default)]
+struct CreateState {
+    summary: CreateSummary,
+    // (size, SHA-256) of content archived so far -> name of the entry holding it
+    seen: HashMap<(u64, [u8; 32]), String>,
+}
+
+/// An input file left out of an archive, and why
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Validation outcome for an archive and the archives nested inside it
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ValidationReport {
+    /// Archive path for the top level, entry name for nested archives
+    pub name: String,
+    /// Whether this archive and everything nested in it passed
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub nested: Vec<ValidationReport>,
+}
+
+/// An existing file that extraction would write over, from `preview_conflicts`
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ExtractConflict {
+    pub name: String,
+    pub path: PathBuf,
+    /// Whether the overwrite policy replaces the file rather than keeping it
+    pub overwrite: bool,
+}
+
+/// One line of a checksum manifest
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ManifestEntry {
+    pub name: String,
+    /// Lowercase hex digest
+    pub hash: String,
+}
+
+/// Size and timing of one `calculate_file_digest_with_stats` run
+#[derive(Debug, Clone, Copy)]
+pub struct HashStats {
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl HashStats {
+    /// Read rate, `None` when too little time passed to measure
+    pub fn bytes_per_sec(&self) -> Option<f64> {
+        let seconds = self.elapsed.as_secs_f64();
+        (seconds > 0.0).then(|| self.bytes as f64 / seconds)
+    }
+}
+
+/// Metadata for a single archive entry, as shown by detailed listings
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EntryInfo {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub compressed_size: u64,
+    pub method: String,
+    pub crc32: u32,
+    pub modified: Option<String>,
+    pub encryption: Option<EncryptionMethod>,
+}
+
+impl EntryInfo {
+    /// Compressed size as a fraction of the original; empty entries count as 1.0
+    pub fn ratio(&self) -> f64 {
+        if self.size == 0 {
+            1.0
+        } else {
+            self.compressed_size as f64 / self.size as f64
+        }
+    }
+}
+
+/// Key for ordering detailed entry listings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum EntrySort {
+    /// Entry name, byte-wise
+    Name,
+    /// Uncompressed size
+    Size,
+    /// Compression method name
+    Method,
+    /// Compressed size divided by uncompressed size
+    Ratio,
+}
+
+/// Sort entries by `key`, ascending unless `reverse`; ties keep archive order
+pub fn sort_entries(entries: &mut [EntryInfo], key: EntrySort, reverse: bool) {
+    entries.sort_by(|a, b| {
+        let ordering = match key {
+            EntrySort::Name => a.name.cmp(&b.name),
+            EntrySort::Size => a.size.cmp(&b.size),
+            EntrySort::Method => a.method.cmp(&b.method),
+            EntrySort::Ratio => a.ratio().total_cmp(&b.ratio()),
+        };
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+/// Bytes in and out for the file entries stored with one compression method
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct MethodStats {
+    pub method: String,
+    pub files: usize,
+    pub uncompressed_bytes: u64,
+    pub compressed_bytes: u64,
+    /// Compressed size as a fraction of the original; 1.0 when nothing was stored
+    pub ratio: f64,
+}
+
+/// Totals per compression method over the file entries, most uncompressed bytes first
+pub fn method_breakdown(entries: &[EntryInfo]) -> Vec<MethodStats> {
+    let mut totals: Vec<MethodStats> = Vec::new();
+    for entry in entries.iter().filter(|e| !e.is_dir) {
+        let index = match totals.iter().position(|t| t.method == entry.method) {
+            Some(index) => index,
+            None => {
+                totals.push(MethodStats {
+                    method: entry.method.clone(),
+                    files: 0,
+                    uncompressed_bytes: 0,
+                    compressed_bytes: 0,
+                    ratio: 1.0,
+                });
+                totals.len() - 1
+            }
+        };
+        let total = &mut totals[index];
+        total.files += 1;
+        total.uncompressed_bytes += entry.size;
+        total.compressed_bytes += entry.compressed_size;
+    }
+    for total in &mut totals {
+        if total.uncompressed_bytes > 0 {
+            total.ratio = total.compressed_bytes as f64 / total.uncompressed_bytes as f64;
+        }
+    }
+    totals.sort_by_key(|t| std::cmp::Reverse(t.uncompressed_bytes));
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_create_archive_single_file() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("test.txt");
+        let archive_path = temp_dir.path().join("test.zip");
+
+        // Create a test file
+        fs::write(&test_file, "Hello, World!")?;
+
+        // Create archive
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&test_file])?;
+
+        // Verify archive was created
+        assert!(archive_path.exists());
+        assert!(archive_path.metadata()?.len() > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_archive_multiple_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file1 = temp_dir.path().join("test1.txt");
+        let test_file2 = temp_dir.path().join("test2.txt");
+        let archive_path = temp_dir.path().join("test.zip");
+
+        // Create test files
+        fs::write(&test_file1, "Hello, World!")?;
+        fs::write(&test_file2, "Goodbye, World!")?;
+
+        // Create archive
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&test_file1, &test_file2])?;
+
+        // Verify archive was created
+        assert!(archive_path.exists());
+
+        // Verify contents
+        let contents = manager.list_archive(&archive_path)?;
+        assert_eq!(contents.len(), 2);
+        assert!(contents.contains(&"test1.txt".to_string()));
+        assert!(contents.contains(&"test2.txt".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_archive_directory() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_dir = temp_dir.path().join("test_dir");
+        let archive_path = temp_dir.path().join("test.zip");
+
+        // Create test directory with files
+        fs::create_dir(&test_dir)?;
+        fs::write(test_dir.join("file1.txt"), "Content 1")?;
+        fs::write(test_dir.join("file2.txt"), "Content 2")?;
+
+        // Create archive
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&test_dir])?;
+
+        // Verify archive was created
+        assert!(archive_path.exists());
+
+        // Verify contents
+        let contents = manager.list_archive(&archive_path)?;
+        assert!(!contents.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_archive() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("test.txt");
+        let archive_path = temp_dir.path().join("test.zip");
+        let extract_dir = temp_dir.path().join("extract");
+
+        // Create a test file and archive
+        fs::write(&test_file, "Hello, World!")?;
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&test_file])?;
+
+        // Extract archive
+        fs::create_dir(&extract_dir)?;
+        manager.extract_archive(&archive_path, &extract_dir)?;
+
+        // Verify extracted file
+        let extracted_file = extract_dir.join("test.txt");
+        assert!(extracted_file.exists());
+        assert_eq!(fs::read_to_string(&extracted_file)?, "Hello, World!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_archive() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file1 = temp_dir.path().join("test1.txt");
+        let test_file2 = temp_dir.path().join("test2.txt");
+        let archive_path = temp_dir.path().join("test.zip");
+
+        // Create test files and archive
+        fs::write(&test_file1, "Hello, World!")?;
+        fs::write(&test_file2, "Goodbye, World!")?;
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&test_file1, &test_file2])?;
+
+        // List archive contents
+        let contents = manager.list_archive(&archive_path)?;
+
+        assert_eq!(contents.len(), 2);
+        assert!(contents.contains(&"test1.txt".to_string()));
+        assert!(contents.contains(&"test2.txt".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_nonexistent_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let nonexistent_file = temp_dir.path().join("nonexistent.txt");
+        let archive_path = temp_dir.path().join("test.zip");
+
+        let manager = ArchiveManager::new();
+        let result = manager.create_archive(&archive_path, &[&nonexistent_file]);
+
+        assert!(result.is_err());
+    }
+
+    /// Writer that reports a full disk once `limit` bytes have been written
+    struct FullDiskWriter {
+        inner: File,
+        written: usize,
+        limit: usize,
+    }
+
+    impl Write for FullDiskWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.written + buf.len() > self.limit {
+                return Err(std::io::Error::from(std::io::ErrorKind::StorageFull));
+            }
+            self.written += buf.len();
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    impl Seek for FullDiskWriter {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_disk_full_removes_partial_archive() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("big.txt");
+        let archive_path = temp_dir.path().join("test.zip");
+        let noise: Vec<u8> =
+            (0..64 * 1024u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+        fs::write(&test_file, noise)?;
+
+        let writer = FullDiskWriter {
+            inner: File::create(&archive_path)?,
+            written: 0,
+            limit: 512,
+        };
+        let manager = ArchiveManager::new();
+        let err = manager
+            .create_archive_into(
+                &archive_path,
+                writer,
+                &[&test_file],
+                &mut crate::progress::SilentReporter,
+            )
+            .unwrap_err();
+
+        let archive_err = err.downcast_ref::<ArchiveError>().expect("typed error");
+        assert_eq!(archive_err.code(), "DISK_FULL");
+        assert!(!archive_path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_very_deep_path() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("deep.zip");
+        // Past PATH_MAX on Unix and MAX_PATH on Windows, though each name is short
+        let name = format!("{}file.txt", "deep/".repeat(1100));
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        zip.start_file(name.as_str(), SimpleFileOptions::default())?;
+        zip.write_all(b"at the bottom")?;
+        zip.finish()?;
+
+        let out = temp_dir.path().join("out");
+        let result = ArchiveManager::new().extract_archive(&archive_path, &out);
+        if cfg!(windows) {
+            // Written through the `\\?\` prefix
+            result?;
+            let path = extended_length_path(&out.join(&name)).into_owned();
+            assert_eq!(fs::read(path)?, b"at the bottom");
+        } else {
+            let err = result.unwrap_err();
+            let archive_err = err.downcast_ref::<ArchiveError>().expect("typed error");
+            assert_eq!(archive_err.code(), "PATH_TOO_LONG");
+            assert!(err.to_string().contains("shorter output directory"), "{err}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_archive() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("test.txt");
+        let archive_path = temp_dir.path().join("test.zip");
+
+        // Create a test file and archive
+        fs::write(&test_file, "Hello, World!")?;
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&test_file])?;
+
+        // Validate the archive
+        let is_valid = manager.validate_archive(&archive_path)?;
+        assert!(is_valid);
+
+        Ok(())
+    }
+
+    /// A zip holding `entries`, stored uncompressed so tests can corrupt the data
+    fn zip_bytes(entries: &[(&str, &[u8])]) -> Result<Vec<u8>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for (name, data) in entries {
+            zip.start_file(*name, options)?;
+            zip.write_all(data)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    }
+
+    #[test]
+    fn test_validate_recursive_nested_archives() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let good = zip_bytes(&[("inner.txt", b"nested content")])?;
+        let mut bad = good.clone();
+        let at = bad.windows(7).position(|w| w == b"content").unwrap();
+        bad[at] ^= 0xff;
+        let deep = zip_bytes(&[("level2.zip", &good)])?;
+
+        let archive_path = temp_dir.path().join("outer.zip");
+        let outer = zip_bytes(&[("good.zip", &good), ("bad.zip", &bad), ("deep.zip", &deep)])?;
+        fs::write(&archive_path, outer)?;
+
+        let manager = ArchiveManager::new();
+        let report = manager.validate_archive_recursive(&archive_path, &NestedLimits::default())?;
+        assert!(!report.valid);
+        let by_name: HashMap<&str, &ValidationReport> =
+            report.nested.iter().map(|r| (r.name.as_str(), r)).collect();
+        assert!(by_name["good.zip"].valid);
+        assert!(!by_name["bad.zip"].valid);
+        assert!(by_name["bad.zip"].error.is_some());
+        assert_eq!(by_name["deep.zip"].nested.len(), 1);
+
+        // Depth 1 opens only the top-level .zip entries
+        let shallow = NestedLimits {
+            max_depth: 1,
+            ..Default::default()
+        };
+        let report = manager.validate_archive_recursive(&archive_path, &shallow)?;
+        assert!(report.nested.iter().all(|r| r.nested.is_empty()));
+
+        let tiny = NestedLimits {
+            max_bytes: 16,
+            ..Default::default()
+        };
+        let report = manager.validate_archive_recursive(&archive_path, &tiny)?;
+        assert!(report.nested.iter().all(|r| !r.valid));
+        Ok(())
+    }
+
+    #[test]
+    fn test_overwrite_if_newer() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let out = temp_dir.path().join("out");
+        fs::create_dir_all(&out)?;
+        let now = SystemTime::now();
+        let set_mtime = |path: &Path, time: SystemTime| -> Result<()> {
+            fs::write(path, "on disk")?;
+            File::options().write(true).open(path)?.set_modified(time)?;
+            Ok(())
+        };
+        // Older than the entries, so they get replaced
+        set_mtime(&out.join("stale.txt"), now - Duration::from_secs(30 * 86400))?;
+        // Newer than the entries, so they are kept
+        set_mtime(&out.join("fresh.txt"), now + Duration::from_secs(86400))?;
+
+        let archive_path = temp_dir.path().join("test.zip");
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        use chrono::{Datelike, Timelike};
+        let stamp = chrono::Local::now().naive_local() - chrono::Duration::days(1);
+        let stamp = zip::DateTime::from_date_and_time(
+            stamp.year() as u16,
+            stamp.month() as u8,
+            stamp.day() as u8,
+            stamp.hour() as u8,
+            stamp.minute() as u8,
+            stamp.second() as u8,
+        )?;
+        let options = SimpleFileOptions::default().last_modified_time(stamp);
+        for name in ["stale.txt", "fresh.txt", "new.txt"] {
+            zip.start_file(name, options)?;
+            zip.write_all(b"from archive")?;
+        }
+        zip.finish()?;
+
+        ArchiveManager::with_options(ArchiveOptions {
+            overwrite: OverwritePolicy::IfNewer,
+            ..Default::default()
+        })
+        .extract_archive(&archive_path, &out)?;
+        assert_eq!(fs::read_to_string(out.join("stale.txt"))?, "from archive");
+        assert_eq!(fs::read_to_string(out.join("fresh.txt"))?, "on disk");
+        assert_eq!(fs::read_to_string(out.join("new.txt"))?, "from archive");
+        Ok(())
+    }
+
+    #[test]
+    fn test_embed_checksums() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("dist");
+        fs::create_dir_all(dir.join("bin"))?;
+        fs::write(dir.join("README"), "read me")?;
+        fs::write(dir.join("bin/tool"), vec![42u8; 10_000])?;
+        let archive_path = temp_dir.path().join("dist.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            embed_checksums: true,
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&dir])?;
+        assert_eq!(manager.list_archive(&archive_path)?.last().unwrap(), CHECKSUMS_ENTRY);
+
+        let out = temp_dir.path().join("out");
+        manager.extract_archive(&archive_path, &out)?;
+        let sums = fs::read_to_string(out.join(CHECKSUMS_ENTRY))?;
+        let mut names = Vec::new();
+        for line in sums.lines() {
+            let (hash, name) = line.split_once("  ").unwrap();
+            assert_eq!(manager.calculate_file_hash(out.join(name))?, hash);
+            names.push(name);
+        }
+        names.sort();
+        assert_eq!(names, ["dist/README", "dist/bin/tool"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_checksums() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let readme = temp_dir.path().join("README");
+        fs::write(&readme, "read me")?;
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            embed_checksums: true,
+            ..Default::default()
+        });
+        let good = temp_dir.path().join("good.zip");
+        manager.create_archive(&good, &[&readme])?;
+
+        let verifier = ArchiveManager::with_options(ArchiveOptions {
+            verify_checksums: true,
+            ..Default::default()
+        });
+        verifier.extract_archive(&good, &temp_dir.path().join("good"))?;
+
+        // A manifest that no longer matches its file
+        let sums = temp_dir.path().join(CHECKSUMS_ENTRY);
+        fs::write(&sums, format!("{}  README\n", "0".repeat(64)))?;
+        let tampered = temp_dir.path().join("tampered.zip");
+        ArchiveManager::new().create_archive(&tampered, &[&readme, &sums])?;
+        let err = verifier.extract_archive(&tampered, &temp_dir.path().join("bad")).unwrap_err();
+        assert_eq!(err.downcast_ref::<ArchiveError>().map(|e| e.code()), Some("CHECKSUM_MISMATCH"));
+        assert!(err.to_string().contains("README"));
+
+        // Without a manifest there is nothing to check
+        let plain = temp_dir.path().join("plain.zip");
+        ArchiveManager::new().create_archive(&plain, &[&readme])?;
+        verifier.extract_archive(&plain, &temp_dir.path().join("plain"))?;
+        Ok(())
+    }
+
+    /// A one-entry stored archive whose name bytes are written as given, without the
+    /// UTF-8 flag, as legacy tools do
+    fn legacy_name_zip(name: &[u8], data: &[u8]) -> Vec<u8> {
+        let crc = crc32fast::hash(data);
+        let mut fields = Vec::new();
+        fields.extend_from_slice(&0u16.to_le_bytes()); // flags: no UTF-8 bit
+        fields.extend_from_slice(&0u16.to_le_bytes()); // stored
+        fields.extend_from_slice(&0u16.to_le_bytes()); // time
+        fields.extend_from_slice(&0x21u16.to_le_bytes()); // 1980-01-01
+        fields.extend_from_slice(&crc.to_le_bytes());
+        fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        fields.extend_from_slice(&0u16.to_le_bytes()); // extra length
+
+        let mut zip = Vec::new();
+        zip.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        zip.extend_from_slice(&20u16.to_le_bytes());
+        zip.extend_from_slice(&fields);
+        zip.extend_from_slice(name);
+        zip.extend_from_slice(data);
+        let cd_offset = zip.len() as u32;
+        zip.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        zip.extend_from_slice(&20u16.to_le_bytes()); // made by
+        zip.extend_from_slice(&20u16.to_le_bytes()); // needed
+        zip.extend_from_slice(&fields);
+        zip.extend_from_slice(&[0u8; 10]); // comment length, disk, attributes
+        zip.extend_from_slice(&0u32.to_le_bytes()); // local header offset
+        zip.extend_from_slice(name);
+        let cd_size = zip.len() as u32 - cd_offset;
+        zip.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        zip.extend_from_slice(&[0, 0, 0, 0, 1, 0, 1, 0]);
+        zip.extend_from_slice(&cd_size.to_le_bytes());
+        zip.extend_from_slice(&cd_offset.to_le_bytes());
+        zip.extend_from_slice(&0u16.to_le_bytes());
+        zip
+    }
+
+    #[test]
+    fn test_legacy_name_encodings() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let cp437 = temp_dir.path().join("cp437.zip");
+        fs::write(&cp437, legacy_name_zip(b"caf\x82.txt", b"coffee"))?;
+        let manager = ArchiveManager::new();
+        assert_eq!(manager.list_archive(&cp437)?, ["café.txt"]);
+        manager.extract_archive(&cp437, &temp_dir.path().join("out"))?;
+        assert_eq!(fs::read_to_string(temp_dir.path().join("out/café.txt"))?, "coffee");
+        // UTF-8 written without the flag is not read as CP437
+        let unflagged = temp_dir.path().join("unflagged.zip");
+        fs::write(&unflagged, legacy_name_zip("naïve.txt".as_bytes(), b"n"))?;
+        assert_eq!(manager.list_archive(&unflagged)?, ["naïve.txt"]);
+
+        // Shift_JIS "テスト.txt" is mojibake as CP437 until the encoding is given
+        let sjis = temp_dir.path().join("sjis.zip");
+        fs::write(&sjis, legacy_name_zip(b"\x83e\x83X\x83g.txt", b"test"))?;
+        assert_ne!(manager.list_archive(&sjis)?, ["テスト.txt"]);
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            name_encoding: NameEncoding::from_label("shift_jis").unwrap(),
+            ..Default::default()
+        });
+        assert_eq!(manager.list_archive(&sjis)?, ["テスト.txt"]);
+        assert_eq!(manager.list_archive(&unflagged)?, ["naïve.txt"]);
+        assert_eq!(manager.list_archive_detailed(&sjis)?[0].name, "テスト.txt");
+        manager.extract_archive(&sjis, &temp_dir.path().join("out"))?;
+        assert_eq!(fs::read_to_string(temp_dir.path().join("out/テスト.txt"))?, "test");
+        // UTF-8 names are unaffected by the override
+        let utf8_file = temp_dir.path().join("naïve.txt");
+        fs::write(&utf8_file, "n")?;
+        let utf8 = temp_dir.path().join("utf8.zip");
+        manager.create_archive(&utf8, &[&utf8_file])?;
+        assert_eq!(manager.list_archive(&utf8)?, ["naïve.txt"]);
+
+        assert_eq!(NameEncoding::from_label("CP437"), Some(NameEncoding::Cp437));
+        assert_eq!(NameEncoding::from_label("klingon"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_utf8_names() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file = temp_dir.path().join("文件.txt");
+        fs::write(&file, "内容")?;
+        let archive_path = temp_dir.path().join("utf8.zip");
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&file])?;
+
+        let entries = central_dir::read_entries(&mut File::open(&archive_path)?)?;
+        assert_eq!(entries[0].name, "文件.txt".as_bytes());
+        assert_ne!(entries[0].flags & (1 << 11), 0, "UTF-8 flag set");
+        assert_eq!(manager.list_archive(&archive_path)?, ["文件.txt"]);
+        let out = temp_dir.path().join("out");
+        manager.extract_archive(&archive_path, &out)?;
+        assert_eq!(fs::read_to_string(out.join("文件.txt"))?, "内容");
+
+        let ascii_only = ArchiveManager::with_options(ArchiveOptions {
+            utf8_names: false,
+            ..Default::default()
+        });
+        let refused = temp_dir.path().join("ascii.zip");
+        assert!(ascii_only.create_archive(&refused, &[&file]).is_err());
+        assert!(!refused.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_and_remove_entries() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("docs");
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("a.txt"), "alpha")?;
+        let extra = temp_dir.path().join("extra.txt");
+        fs::write(&extra, "extra")?;
+        let keep = temp_dir.path().join("keep.txt");
+        fs::write(&keep, "keep")?;
+
+        let archive_path = temp_dir.path().join("test.zip");
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&keep, &dir])?;
+
+        manager.add_to_archive(&archive_path, &[&extra])?;
+        let mut names = manager.list_archive(&archive_path)?;
+        names.sort();
+        assert_eq!(names, ["docs/a.txt", "extra.txt", "keep.txt"]);
+        assert_eq!(manager.extract_entry(&archive_path, "extra.txt")?, b"extra");
+
+        assert_eq!(manager.remove_from_archive(&archive_path, &["docs/", "extra.txt"])?, 2);
+        assert_eq!(manager.list_archive(&archive_path)?, ["keep.txt"]);
+        assert!(manager.validate_archive(&archive_path)?);
+
+        assert!(manager.remove_from_archive(&archive_path, &["missing.txt"]).is_err());
+        assert!(manager.add_to_archive(&archive_path, &[&keep]).is_err());
+        assert_eq!(manager.list_archive(&archive_path)?, ["keep.txt"]);
+        assert!(!temp_dir.path().join(".test.zip.rolypoly-tmp").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_summary_counts() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("tree");
+        fs::create_dir_all(dir.join("sub"))?;
+        fs::write(dir.join("a.txt"), "alpha")?;
+        fs::write(dir.join("sub/c.txt"), "charlie")?;
+        let archive_path = temp_dir.path().join("tree.zip");
+        ArchiveManager::new().create_archive(&archive_path, &[&dir])?;
+        let entries = ArchiveManager::new().list_archive(&archive_path)?.len();
+
+        let out = temp_dir.path().join("out");
+        let extract = |overwrite| {
+            ArchiveManager::with_options(ArchiveOptions {
+                overwrite,
+                ..Default::default()
+            })
+            .extract_archive_with_summary(
+                &archive_path,
+                &out,
+                &mut crate::progress::SilentReporter,
+            )
+        };
+        let summary = extract(OverwritePolicy::Always)?;
+        assert_eq!(summary.entries(), entries);
+        assert_eq!(
+            summary,
+            ExtractSummary {
+                extracted: 2,
+                dirs: entries - 2,
+                skipped: 0,
+                total_bytes: 12
+            }
+        );
+
+        // Existing files are kept and counted as skipped
+        let summary = extract(OverwritePolicy::Never)?;
+        assert_eq!(summary.entries(), entries);
+        assert_eq!((summary.extracted, summary.skipped, summary.total_bytes), (0, 2, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_selected_entries() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("tree");
+        fs::create_dir_all(dir.join("sub"))?;
+        fs::write(dir.join("a.txt"), "a")?;
+        fs::write(dir.join("b.txt"), "b")?;
+        fs::write(dir.join("sub/c.txt"), "c")?;
+        let archive_path = temp_dir.path().join("tree.zip");
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&dir])?;
+
+        let out = temp_dir.path().join("one");
+        assert_eq!(manager.extract_selected(&archive_path, &out, &["tree/b.txt"])?, 1);
+        assert_eq!(fs::read_to_string(out.join("tree/b.txt"))?, "b");
+        assert!(!out.join("tree/a.txt").exists());
+        assert!(!out.join("tree/sub").exists());
+
+        let out = temp_dir.path().join("sub");
+        assert_eq!(manager.extract_selected(&archive_path, &out, &["tree/sub/"])?, 2);
+        assert!(out.join("tree/sub/c.txt").exists());
+        assert!(!out.join("tree/a.txt").exists());
+
+        let out = temp_dir.path().join("missing");
+        assert!(manager.extract_selected(&archive_path, &out, &["tree/b.txt", "nope"]).is_err());
+        assert!(!out.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_file_hash() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("test.txt");
+
+        // Create a test file
+        fs::write(&test_file, "Hello, World!")?;
+
+        let manager = ArchiveManager::new();
+        let hash = manager.calculate_file_hash(&test_file)?;
+
+        // The hash should be consistent and not empty
+        assert!(!hash.is_empty());
+        assert_eq!(hash.len(), 64); // SHA256 produces 64 character hex string
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_crc32() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("test.txt");
+        fs::write(&test_file, "Hello, World!")?;
+
+        let manager = ArchiveManager::new();
+        let crc = manager.calculate_file_hash_with(&test_file, HashAlgorithm::Crc32)?;
+        assert_eq!(crc, "ec4ac3d0");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_archive_stats() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file1 = temp_dir.path().join("test1.txt");
+        let test_file2 = temp_dir.path().join("test2.txt");
+        let archive_path = temp_dir.path().join("test.zip");
+
+        // Create test files
+        fs::write(&test_file1, "Hello, World!")?;
+        fs::write(&test_file2, "Goodbye, World!")?;
+
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&test_file1, &test_file2])?;
+
+        // Get archive stats
+        let stats = manager.get_archive_stats(&archive_path)?;
+
+        assert_eq!(stats.file_count, 2);
+        assert!(stats.total_uncompressed_size > 0);
+        assert!(stats.total_compressed_size > 0);
+        assert!(stats.compression_ratio > 0.0);
+
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct RecordingReporter {
+        calls: Vec<String>,
+    }
+
+    impl ProgressReporter for RecordingReporter {
+        fn start(&mut self, total: u64) {
+            self.calls.push(format!("start({total})"));
+        }
+        fn inc(&mut self, n: u64) {
+            self.calls.push(format!("inc({n})"));
+        }
+        fn message(&mut self, _msg: &str) {
+            self.calls.push("message".to_string());
+        }
+        fn finish(&mut self) {
+            self.calls.push("finish".to_string());
+        }
+    }
+
+    #[test]
+    fn test_progress_reporter_call_sequence() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file1 = temp_dir.path().join("test1.txt");
+        let test_file2 = temp_dir.path().join("test2.txt");
+        let archive_path = temp_dir.path().join("test.zip");
+        fs::write(&test_file1, "Hello, World!")?;
+        fs::write(&test_file2, "Goodbye, World!")?;
+
+        let manager = ArchiveManager::new();
+        let mut reporter = RecordingReporter::default();
+        manager.create_archive_with_reporter(
+            &archive_path,
+            &[&test_file1, &test_file2],
+            &mut reporter,
+        )?;
+        assert_eq!(
+            reporter.calls,
+            vec!["start(2)", "message", "inc(1)", "message", "inc(1)", "message", "finish"]
+        );
+
+        let extract_dir = temp_dir.path().join("extract");
+        let mut reporter = RecordingReporter::default();
+        manager.extract_archive_with_reporter(
+            archive_path.as_path(),
+            extract_dir.as_path(),
+            &mut reporter,
+        )?;
+        assert_eq!(
+            reporter.calls,
+            vec!["start(2)", "message", "inc(1)", "message", "inc(1)", "message", "finish"]
+        );
+
+        let mut reporter = RecordingReporter::default();
+        assert!(manager.validate_archive_with_reporter(&archive_path, &mut reporter)?);
+        assert_eq!(reporter.calls.first().map(String::as_str), Some("start(2)"));
+        assert_eq!(reporter.calls.last().map(String::as_str), Some("finish"));
+        assert_eq!(reporter.calls.iter().filter(|c| *c == "inc(1)").count(), 2);
+
+        Ok(())
+    }
+
+    /// Build an archive holding two entries named `a.txt` ("first", then "second")
+    fn write_duplicate_archive(path: &Path) -> Result<()> {
+        let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let stored =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("a.txt", stored)?;
+        std::io::Write::write_all(&mut zip, b"first")?;
+        zip.start_file("b.txt", stored)?;
+        std::io::Write::write_all(&mut zip, b"second")?;
+        let mut bytes = zip.finish()?.into_inner();
+        // The writer rejects duplicate names, so rename b.txt in both local and central headers
+        for i in 0..bytes.len() - 4 {
+            if &bytes[i..i + 5] == b"b.txt" {
+                bytes[i] = b'a';
+            }
+        }
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    fn manager_with_duplicate_policy(policy: DuplicatePolicy) -> ArchiveManager {
+        ArchiveManager::with_options(ArchiveOptions {
+            on_duplicate: policy,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_duplicate_entry_policies() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("dup.zip");
+        write_duplicate_archive(&archive_path)?;
+
+        let last_dir = temp_dir.path().join("last");
+        manager_with_duplicate_policy(DuplicatePolicy::KeepLast)
+            .extract_archive(&archive_path, &last_dir)?;
+        assert_eq!(fs::read_to_string(last_dir.join("a.txt"))?, "second");
+
+        let first_dir = temp_dir.path().join("first");
+        manager_with_duplicate_policy(DuplicatePolicy::KeepFirst)
+            .extract_archive(&archive_path, &first_dir)?;
+        assert_eq!(fs::read_to_string(first_dir.join("a.txt"))?, "first");
+
+        let error_dir = temp_dir.path().join("error");
+        let manager = manager_with_duplicate_policy(DuplicatePolicy::Error);
+        let err = manager.extract_archive(&archive_path, &error_dir).unwrap_err();
+        assert!(err.to_string().contains("duplicate entry names: a.txt"));
+        assert!(!error_dir.join("a.txt").exists());
+
+        assert!(manager.list_archive(&archive_path).is_err());
+        let listed = manager_with_duplicate_policy(DuplicatePolicy::KeepFirst)
+            .list_archive(&archive_path)?;
+        assert_eq!(listed, vec!["a.txt".to_string()]);
+        // The streamed listing shows the copy each policy keeps
+        assert!(manager.list_archive_detailed(&archive_path).is_err());
+        for (policy, size) in [(DuplicatePolicy::KeepFirst, 5), (DuplicatePolicy::KeepLast, 6)] {
+            let detailed =
+                manager_with_duplicate_policy(policy).list_archive_detailed(&archive_path)?;
+            let sizes: Vec<u64> = detailed.iter().map(|e| e.size).collect();
+            assert_eq!(sizes, [size], "{policy:?}");
+        }
+
+        Ok(())
+    }
+
+    /// Two entries named `a.txt` ("first", then "second"), written through `zip`
+    fn duplicate_entries<W: Write + Seek>(
+        mut zip: ZipWriter<W>,
+        options: SimpleFileOptions,
+    ) -> Result<W> {
+        zip.start_file("a.txt", options)?;
+        zip.write_all(b"first")?;
+        zip.start_file("b.txt", options)?;
+        zip.write_all(b"second")?;
+        Ok(zip.finish()?)
+    }
+
+    #[test]
+    fn test_keep_first_opens_encrypted_and_streamed_copies() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        // A stream writer can't seek back, so its entries get data descriptors
+        let streamed =
+            duplicate_entries(ZipWriter::new_stream(Vec::new()), SimpleFileOptions::default())?
+                .into_inner();
+        let encrypted = duplicate_entries(
+            ZipWriter::new(std::io::Cursor::new(Vec::new())),
+            SimpleFileOptions::default().with_aes_encryption(zip::AesMode::Aes256, "secret"),
+        )?
+        .into_inner();
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            on_duplicate: DuplicatePolicy::KeepFirst,
+            password: Some("secret".to_string()),
+            ..Default::default()
+        });
+        for (label, mut bytes) in [("streamed", streamed), ("encrypted", encrypted)] {
+            for i in 0..bytes.len() - 4 {
+                if &bytes[i..i + 5] == b"b.txt" {
+                    bytes[i] = b'a';
+                }
+            }
+            let archive_path = temp_dir.path().join(format!("{label}.zip"));
+            fs::write(&archive_path, bytes)?;
+            let out = temp_dir.path().join(label);
+            manager.extract_archive(&archive_path, &out)?;
+            assert_eq!(fs::read_to_string(out.join("a.txt"))?, "first", "{label}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypted_round_trip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("secret.txt");
+        let archive_path = temp_dir.path().join("secret.zip");
+        fs::write(&test_file, "top secret")?;
+
+        let with_password = |password: &str| {
+            ArchiveManager::with_options(ArchiveOptions {
+                password: Some(password.to_string()),
+                ..Default::default()
+            })
+        };
+        with_password("hunter2").create_archive(&archive_path, &[&test_file])?;
+        assert!(ArchiveManager::new().has_encrypted_entries(&archive_path)?);
+        assert_eq!(ArchiveManager::new().list_archive(&archive_path)?, ["secret.txt"]);
+
+        let out = temp_dir.path().join("plain");
+        assert!(ArchiveManager::new().extract_archive(&archive_path, &out).is_err());
+        let out = temp_dir.path().join("wrong");
+        assert!(with_password("wrong").extract_archive(&archive_path, &out).is_err());
+
+        let out = temp_dir.path().join("right");
+        with_password("hunter2").extract_archive(&archive_path, &out)?;
+        assert_eq!(fs::read_to_string(out.join("secret.txt"))?, "top secret");
+        Ok(())
+    }
+
+    #[test]
+    fn test_encryption_method_reporting() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("secret.txt");
+        fs::write(&test_file, "top secret")?;
+
+        for method in [EncryptionMethod::Aes128, EncryptionMethod::Aes256] {
+            let archive_path = temp_dir.path().join(format!("{}.zip", method.as_str()));
+            ArchiveManager::with_options(ArchiveOptions {
+                password: Some("hunter2".to_string()),
+                encryption: method,
+                ..Default::default()
+            })
+            .create_archive(&archive_path, &[&test_file])?;
+
+            // Metadata is available without the password
+            let manager = ArchiveManager::new();
+            let entries = manager.list_archive_detailed(&archive_path)?;
+            assert_eq!(entries[0].encryption, Some(method));
+            // The real method, not the AES marker
+            let mut archive = ZipArchive::new(File::open(&archive_path)?)?;
+            let compression = archive.by_index_raw(0)?.compression();
+            assert_eq!(entries[0].method, compression_method_name(compression));
+            assert_ne!(entries[0].method, "aes");
+            let stats = manager.get_archive_stats(&archive_path)?;
+            assert_eq!(stats.encrypted_count, 1);
+            assert_eq!(stats.encryption_methods, vec![method]);
+            assert!(manager.validate_archive(&archive_path)?);
+        }
+
+        // Plain archives report no encryption
+        let plain = temp_dir.path().join("plain.zip");
+        ArchiveManager::new().create_archive(&plain, &[&test_file])?;
+        assert_eq!(ArchiveManager::new().list_archive_detailed(&plain)?[0].encryption, None);
+        assert_eq!(ArchiveManager::new().get_archive_stats(&plain)?.encrypted_count, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zipcrypto_round_trip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let input = temp_dir.path().join("data");
+        fs::create_dir_all(input.join("sub"))?;
+        fs::write(input.join("secret.txt"), "top secret ".repeat(100))?;
+        fs::write(input.join("sub/empty.txt"), "")?;
+        // Incompressible, so its data spans several of the cipher's chunks
+        let mut state = 0x2545_f491_u32;
+        let noise: Vec<u8> = (0..300_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        fs::write(input.join("noise.bin"), &noise)?;
+        let with_password = |password: &str| {
+            ArchiveManager::with_options(ArchiveOptions {
+                password: Some(password.to_string()),
+                encryption: EncryptionMethod::ZipCrypto,
+                embed_checksums: true,
+                preserve_empty_dirs: true,
+                ..Default::default()
+            })
+        };
+        let archive_path = temp_dir.path().join("legacy.zip");
+        with_password("hunter2").create_archive(&archive_path, &[&input])?;
+
+        let entries = ArchiveManager::new().list_archive_detailed(&archive_path)?;
+        let secret = entries.iter().find(|e| e.name == "data/secret.txt").unwrap();
+        assert_eq!(secret.encryption, Some(EncryptionMethod::ZipCrypto));
+        assert!(with_password("hunter2").validate_archive(&archive_path)?);
+
+        let out = temp_dir.path().join("out");
+        with_password("hunter2").extract_archive(&archive_path, &out)?;
+        assert_eq!(fs::read_to_string(out.join("data/secret.txt"))?, "top secret ".repeat(100));
+        assert_eq!(fs::read(out.join("data/sub/empty.txt"))?, b"");
+        assert_eq!(fs::read(out.join("data/noise.bin"))?, noise);
+        assert!(
+            with_password("wrong")
+                .extract_archive(&archive_path, &temp_dir.path().join("bad"))
+                .is_err()
+        );
+
+        // In memory too, and refused for entries added to an existing archive
+        let bytes = with_password("pw").create_archive_in_memory(&[("a.txt", b"abc")])?;
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let mut contents = String::new();
+        archive.by_index_decrypt(0, b"pw")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "abc");
+        let extra = temp_dir.path().join("extra.txt");
+        fs::write(&extra, "more")?;
+        assert!(with_password("hunter2").add_to_archive(&archive_path, &[&extra]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_zipcrypto_detected_from_flags() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("legacy.zip");
+        let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let stored =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("a.txt", stored)?;
+        std::io::Write::write_all(&mut zip, b"0123456789abcdef")?;
+        let mut bytes = zip.finish()?.into_inner();
+        // Set the "encrypted" general-purpose flag in the local and central headers
+        bytes[6] |= 1;
+        let cd = bytes.windows(4).position(|w| w == [0x50, 0x4b, 0x01, 0x02]).unwrap();
+        bytes[cd + 8] |= 1;
+        fs::write(&archive_path, bytes)?;
+
+        let entries = ArchiveManager::new().list_archive_detailed(&archive_path)?;
+        assert_eq!(entries[0].encryption, Some(EncryptionMethod::ZipCrypto));
+        Ok(())
+    }
+
+    #[test]
+    fn test_case_collisions_reported() -> Result<()> {
+        let collisions = find_case_collisions(["Foo.txt", "bar.txt", "foo.txt", "FOO.TXT"]);
+        assert_eq!(collisions, vec![vec!["Foo.txt", "foo.txt", "FOO.TXT"]]);
+        assert!(find_case_collisions(["a.txt", "b.txt"]).is_empty());
+
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("case.zip");
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        for name in ["Foo.txt", "foo.txt"] {
+            zip.start_file(name, SimpleFileOptions::default())?;
+            zip.write_all(name.as_bytes())?;
+        }
+        zip.finish()?;
+
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            check_case_collisions: true,
+            ..Default::default()
+        });
+        let out_dir = temp_dir.path().join("out");
+        let err = manager.extract_archive(&archive_path, &out_dir).unwrap_err();
+        assert!(err.to_string().contains("Foo.txt / foo.txt"));
+        assert!(!out_dir.join("Foo.txt").exists());
+
+        ArchiveManager::new().extract_archive(&archive_path, &out_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_flatten_rename_policy() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("nested.zip");
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        for (name, body) in [("a/notes.txt", "one"), ("b/c/notes.txt", "two"), ("b/other", "x")] {
+            zip.start_file(name, SimpleFileOptions::default())?;
+            zip.write_all(body.as_bytes())?;
+        }
+        zip.finish()?;
+
+        let flatten = |on_conflict| {
+            ArchiveManager::with_options(ArchiveOptions {
+                flatten: true,
+                on_conflict,
+                ..Default::default()
+            })
+        };
+
+        let out = temp_dir.path().join("renamed");
+        flatten(ConflictPolicy::Rename).extract_archive(&archive_path, &out)?;
+        assert_eq!(fs::read_to_string(out.join("notes.txt"))?, "one");
+        assert_eq!(fs::read_to_string(out.join("notes-1.txt"))?, "two");
+        assert!(out.join("other").exists());
+        assert!(!out.join("a").exists());
+
+        let out = temp_dir.path().join("skipped");
+        flatten(ConflictPolicy::Skip).extract_archive(&archive_path, &out)?;
+        assert_eq!(fs::read_to_string(out.join("notes.txt"))?, "one");
+        assert!(!out.join("notes-1.txt").exists());
+
+        let out = temp_dir.path().join("error");
+        assert!(flatten(ConflictPolicy::Error).extract_archive(&archive_path, &out).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_streaming_stats_match_buffered() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("tree");
+        fs::create_dir_all(dir.join("sub/empty"))?;
+        fs::write(dir.join("a.txt"), "a".repeat(1000))?;
+        fs::write(dir.join("sub/b.txt"), "hello world")?;
+        let archive_path = temp_dir.path().join("tree.zip");
+        ArchiveManager::new().create_archive(&archive_path, &[&dir])?;
+
+        let encrypted_path = temp_dir.path().join("enc.zip");
+        ArchiveManager::with_options(ArchiveOptions {
+            password: Some("pw".to_string()),
+            encryption: EncryptionMethod::Aes192,
+            ..Default::default()
+        })
+        .create_archive(&encrypted_path, &[&dir])?;
+
+        let manager = ArchiveManager::new();
+        for path in [&archive_path, &encrypted_path] {
+            let buffered = serde_json::to_value(manager.get_archive_stats(path)?)?;
+            let streaming = serde_json::to_value(manager.get_archive_stats_streaming(path)?)?;
+            assert_eq!(buffered, streaming);
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sparse_file_round_trip() -> Result<()> {
+        use std::io::{Seek, SeekFrom};
+
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("sparse.bin");
+        let mut file = File::create(&path)?;
+        file.write_all(b"head")?;
+        file.seek(SeekFrom::Start(8 << 20))?;
+        file.write_all(b"middle")?;
+        file.set_len(16 << 20)?;
+        drop(file);
+
+        let archive_path = temp_dir.path().join("sparse.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            sparse: true,
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&path])?;
+
+        let contents = manager.extract_entry(&archive_path, "sparse.bin")?;
+        assert_eq!(contents.len(), 16 << 20);
+        assert_eq!(contents, fs::read(&path)?);
+        assert!(contents[4..8 << 20].iter().all(|&b| b == 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_entry_reads_single_file() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("tree");
+        fs::create_dir_all(dir.join("sub"))?;
+        fs::write(dir.join("a.txt"), "first file")?;
+        fs::write(dir.join("sub/b.txt"), "second file".repeat(100))?;
+        let archive_path = temp_dir.path().join("tree.zip");
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&dir])?;
+
+        let bytes = manager.extract_entry(&archive_path, "tree/sub/b.txt")?;
+        assert_eq!(bytes, fs::read(dir.join("sub/b.txt"))?);
+        assert_eq!(manager.extract_entry(&archive_path, "tree/a.txt")?, b"first file");
+
+        let err = manager.extract_entry(&archive_path, "missing.txt").unwrap_err();
+        assert!(err.to_string().contains("not found"));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exclude_hidden() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("project");
+        fs::create_dir_all(dir.join(".git"))?;
+        fs::write(dir.join(".git/config"), "[core]")?;
+        fs::write(dir.join(".hidden"), "secret")?;
+        fs::write(dir.join("visible.txt"), "hello")?;
+
+        let included = temp_dir.path().join("included.zip");
+        ArchiveManager::new().create_archive(&included, &[&dir])?;
+        let names = ArchiveManager::new().list_archive(&included)?;
+        assert!(names.contains(&"project/.hidden".to_string()));
+        assert!(names.contains(&"project/.git/config".to_string()));
+
+        let excluded = temp_dir.path().join("excluded.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            exclude_hidden: true,
+            ..Default::default()
+        });
+        let mut reporter = RecordingReporter::default();
+        manager.create_archive_with_reporter(&excluded, &[&dir], &mut reporter)?;
+        let names = manager.list_archive(&excluded)?;
+        assert!(names.iter().all(|n| !n.contains("/.")), "{names:?}");
+        assert!(names.contains(&"project/visible.txt".to_string()));
+        assert_eq!(reporter.calls.first(), Some(&"start(1)".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_depth_limits_recursion() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("tree");
+        fs::create_dir_all(dir.join("a/b"))?;
+        fs::write(dir.join("top.txt"), "1")?;
+        fs::write(dir.join("a/mid.txt"), "2")?;
+        fs::write(dir.join("a/b/deep.txt"), "3")?;
+
+        let archive_path = temp_dir.path().join("shallow.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        });
+        let mut reporter = RecordingReporter::default();
+        manager.create_archive_with_reporter(&archive_path, &[&dir], &mut reporter)?;
+        let names = manager.list_archive(&archive_path)?;
+        let files: Vec<_> = names.iter().filter(|n| !n.ends_with('/')).collect();
+        assert_eq!(files, ["tree/top.txt"]);
+        assert_eq!(reporter.calls.first(), Some(&"start(1)".to_string()));
+
+        let archive_path = temp_dir.path().join("two.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&dir])?;
+        let names = manager.list_archive(&archive_path)?;
+        assert!(names.contains(&"tree/a/mid.txt".to_string()));
+        assert!(!names.contains(&"tree/a/b/deep.txt".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_patterns() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("project");
+        fs::create_dir_all(dir.join("target/debug"))?;
+        fs::create_dir_all(dir.join("src/generated"))?;
+        fs::write(dir.join("target/debug/app"), "binary")?;
+        fs::write(dir.join("src/main.rs"), "fn main() {}")?;
+        fs::write(dir.join("src/notes.log"), "log")?;
+        fs::write(dir.join("src/keep.log"), "log")?;
+        fs::write(dir.join("src/generated/out.rs"), "// generated")?;
+        fs::write(dir.join("src/.gitignore"), "generated/\n")?;
+
+        let archive_path = temp_dir.path().join("project.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            exclude_patterns: vec!["target/".into(), "*.log".into(), "!keep.log".into()],
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&dir])?;
+        let names = manager.list_archive(&archive_path)?;
+        assert!(names.iter().all(|n| !n.starts_with("project/target")), "{names:?}");
+        assert!(!names.contains(&"project/src/notes.log".to_string()));
+        assert!(names.contains(&"project/src/keep.log".to_string()));
+        assert!(names.contains(&"project/src/generated/out.rs".to_string()));
+
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            exclude_patterns: vec!["target/".into()],
+            respect_gitignore: true,
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&dir])?;
+        let names = manager.list_archive(&archive_path)?;
+        assert!(names.iter().all(|n| !n.contains("target") && !n.contains("generated")));
+        assert!(names.contains(&"project/src/main.rs".to_string()));
+        assert!(names.contains(&"project/src/.gitignore".to_string()));
+
+        // An invalid pattern fails the archive instead of excluding nothing
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            exclude_patterns: vec!["target/".into(), "*.{log".into()],
+            ..Default::default()
+        });
+        let err = manager.create_archive(&archive_path, &[&dir]).unwrap_err();
+        assert!(format!("{err:#}").contains("Invalid exclude pattern \"*.{log\""), "{err:#}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_dirs_round_trip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("tree");
+        fs::create_dir_all(dir.join("empty"))?;
+        fs::create_dir_all(dir.join("nested/deeper/also_empty"))?;
+        fs::write(dir.join("file.txt"), "content")?;
+        let bare = temp_dir.path().join("bare");
+        fs::create_dir(&bare)?;
+
+        let archive_path = temp_dir.path().join("tree.zip");
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&dir, &bare])?;
+        let names = manager.list_archive(&archive_path)?;
+        assert!(names.contains(&"bare/".to_string()), "{names:?}");
+
+        let out = temp_dir.path().join("out");
+        manager.extract_archive(&archive_path, &out)?;
+        assert!(out.join("tree/empty").is_dir());
+        assert!(out.join("tree/nested/deeper/also_empty").is_dir());
+        assert!(out.join("bare").is_dir());
+
+        let skipping = ArchiveManager::with_options(ArchiveOptions {
+            preserve_empty_dirs: false,
+            ..Default::default()
+        });
+        let archive_path = temp_dir.path().join("no_empty.zip");
+        skipping.create_archive(&archive_path, &[&dir])?;
+        let names = skipping.list_archive(&archive_path)?;
+        assert!(!names.iter().any(|n| n.contains("empty")), "{names:?}");
+        assert!(names.contains(&"tree/nested/".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_transient_errors() {
+        let flaky = |failures: u32, kind: std::io::ErrorKind| {
+            let mut calls = 0;
+            let result = retry_io(3, Duration::from_millis(1), || {
+                calls += 1;
+                if calls <= failures {
+                    Err(std::io::Error::from(kind))
+                } else {
+                    Ok(calls)
+                }
+            });
+            (result, calls)
+        };
+
+        let (result, calls) = flaky(2, std::io::ErrorKind::WouldBlock);
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(calls, 3);
+
+        let (result, calls) = flaky(2, std::io::ErrorKind::NotFound);
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+
+        let (result, calls) = flaky(10, std::io::ErrorKind::Interrupted);
+        assert!(result.is_err());
+        assert_eq!(calls, 4);
+
+        assert_eq!(retry_delay(Duration::from_millis(50), 2), Duration::from_millis(200));
+        assert_eq!(retry_delay(Duration::from_millis(50), 40), RETRY_MAX_DELAY);
+        assert_eq!(retry_delay(Duration::MAX, u32::MAX), RETRY_MAX_DELAY);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escape_rejected() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("slip.zip");
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        zip.add_symlink("link", "../../", SimpleFileOptions::default())?;
+        zip.start_file("link/passwd", SimpleFileOptions::default())?;
+        zip.write_all(b"pwned")?;
+        zip.finish()?;
+
+        let out = temp_dir.path().join("nested/out");
+        let err = ArchiveManager::new().extract_archive(&archive_path, &out).unwrap_err();
+        assert!(err.to_string().contains("outside the output directory"), "{err}");
+        assert!(!out.join("link").exists());
+        assert!(!temp_dir.path().join("passwd").exists());
+
+        // Links that stay inside the root are recreated as links
+        let archive_path = temp_dir.path().join("ok.zip");
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        zip.start_file("data/file.txt", SimpleFileOptions::default())?;
+        zip.write_all(b"hello")?;
+        zip.add_symlink("alias", "data/file.txt", SimpleFileOptions::default())?;
+        zip.finish()?;
+        let out = temp_dir.path().join("ok");
+        ArchiveManager::new().extract_archive(&archive_path, &out)?;
+        assert!(out.join("alias").is_symlink());
+        assert_eq!(fs::read_to_string(out.join("alias"))?, "hello");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parent_traversal_rejected() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("slip.zip");
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        zip.start_file("../evil.txt", SimpleFileOptions::default())?;
+        zip.write_all(b"pwned")?;
+        zip.finish()?;
+
+        let out = temp_dir.path().join("out");
+        assert!(ArchiveManager::new().extract_archive(&archive_path, &out).is_err());
+        assert!(!temp_dir.path().join("evil.txt").exists());
+        Ok(())
+    }
+
+    /// Reporter that requests cancellation once `after` items have completed
+    struct CancelAfter {
+        done: u64,
+        after: u64,
+    }
+
+    impl ProgressReporter for CancelAfter {
+        fn start(&mut self, _total: u64) {}
+        fn inc(&mut self, n: u64) {
+            self.done += n;
+        }
+        fn message(&mut self, _msg: &str) {}
+        fn finish(&mut self) {}
+        fn is_cancelled(&self) -> bool {
+            self.done >= self.after
+        }
+    }
+
+    #[test]
+    fn test_cancelled_extraction_and_creation() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let files: Vec<PathBuf> =
+            (0..5).map(|i| temp_dir.path().join(format!("f{i}.txt"))).collect();
+        for file in &files {
+            fs::write(file, "data")?;
+        }
+        let files: Vec<&PathBuf> = files.iter().collect();
+        let archive_path = temp_dir.path().join("test.zip");
+        let archive_ref = &archive_path;
+        let manager = ArchiveManager::new();
+
+        let mut reporter = CancelAfter { done: 0, after: 2 };
+        let err = manager
+            .create_archive_with_reporter(archive_ref, &files, &mut reporter)
+            .unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(ArchiveError::Cancelled)));
+        assert!(!archive_path.exists());
+
+        manager.create_archive(archive_ref, &files)?;
+        let out = temp_dir.path().join("out");
+        let mut reporter = CancelAfter { done: 0, after: 2 };
+        let err = manager
+            .extract_archive_with_reporter(&archive_path, &out, &mut reporter)
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<ArchiveError>().map(|e| e.code()), Some("CANCELLED"));
+        assert_eq!(fs::read_dir(&out)?.count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_atomic_extraction() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let files: Vec<PathBuf> =
+            (0..5).map(|i| temp_dir.path().join(format!("f{i}.txt"))).collect();
+        for file in &files {
+            fs::write(file, "new")?;
+        }
+        let archive_path = temp_dir.path().join("test.zip");
+        ArchiveManager::new().create_archive(&archive_path, &files.iter().collect::<Vec<_>>())?;
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            atomic: true,
+            ..Default::default()
+        });
+        let out = temp_dir.path().join("out");
+        fs::create_dir(&out)?;
+        fs::write(out.join("f0.txt"), "old")?;
+        fs::write(out.join("keep.txt"), "mine")?;
 
-            if path.is_file() {
-                if let Some(pb) = pb {
-                    pb.set_message(format!("Adding: {}", path.display()));
-                }
-                let method =
-                    if opts.auto_store && is_incompressible(path, opts.store_entropy_threshold)? {
-                        zip::CompressionMethod::Stored
-                    } else {
-                        zip::CompressionMethod::Deflated
-                    };
-                let mut per_file = (*options).compression_method(method);
-                if let Some(level) = opts.compression_level {
-                    per_file = per_file.compression_level(Some(level as i64));
-                }
-                zip.start_file(&archive_path, per_file)?;
-                let mut file = File::open(path)?;
-                copy_buffered(&mut file, zip, opts.io_buffer_size)?;
-                if let Some(pb) = pb {
-                    pb.inc(1);
-                }
-                *processed += 1;
-                if json {
-                    let pct = if total > 0 {
-                        (*processed as f64) / (total as f64)
-                    } else {
-                        0.0
-                    };
-                    crate::progress::print_json(&serde_json::json!({
-                        "event":"progress","op":"create","file": path.display().to_string(),
-                        "current": *processed, "total": total, "pct": pct
-                    }));
-                }
-            } else if path.is_dir() && !relative_path.is_empty() {
-                zip.add_directory(format!("{archive_path}/"), *options)?;
-            }
+        // Failing part-way leaves the output directory untouched, with nothing staged
+        let mut reporter = CancelAfter { done: 0, after: 2 };
+        let err = manager
+            .extract_archive_with_reporter(&archive_path, &out, &mut reporter)
+            .unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(ArchiveError::Cancelled)));
+        let mut names: Vec<_> = fs::read_dir(&out)?.map(|e| e.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, ["f0.txt", "keep.txt"]);
+        assert_eq!(fs::read_to_string(out.join("f0.txt"))?, "old");
+        assert!(!sibling_temp_path(&out).exists());
+
+        // A complete run merges into what is there, per the overwrite policy
+        manager.extract_archive(&archive_path, &out)?;
+        assert_eq!(fs::read_dir(&out)?.count(), 6);
+        assert_eq!(fs::read_to_string(out.join("f0.txt"))?, "new");
+        assert_eq!(fs::read_to_string(out.join("keep.txt"))?, "mine");
+        assert!(!sibling_temp_path(&out).exists());
+
+        fs::write(out.join("f1.txt"), "edited")?;
+        let keep = ArchiveManager::with_options(ArchiveOptions {
+            atomic: true,
+            overwrite: OverwritePolicy::Never,
+            ..Default::default()
+        });
+        keep.extract_archive(&archive_path, &out)?;
+        assert_eq!(fs::read_to_string(out.join("f1.txt"))?, "edited");
+
+        // A merge that fails part-way puts back what it had moved and replaced
+        let staged = temp_dir.path().join("staged");
+        fs::create_dir_all(staged.join("sub"))?;
+        for name in ["f0.txt", "f1.txt", "sub/x.txt"] {
+            fs::write(staged.join(name), "staged")?;
         }
+        let before: Vec<_> = ["f0.txt", "f1.txt"]
+            .iter()
+            .map(|name| fs::read_to_string(out.join(name)).unwrap())
+            .collect();
+        let mut moves = 0;
+        let err = move_into_place_with(&staged, &out, &mut |from, to| {
+            moves += 1;
+            match moves {
+                3 => Err(std::io::Error::other("disk full")),
+                _ => fs::rename(from, to),
+            }
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("Failed to move"));
+        assert_eq!(fs::read_to_string(out.join("f0.txt"))?, before[0]);
+        assert_eq!(fs::read_to_string(out.join("f1.txt"))?, before[1]);
+        assert!(!out.join("sub").exists());
+        assert_eq!(fs::read_dir(&out)?.count(), 6);
+        assert_eq!(fs::read_to_string(staged.join("sub/x.txt"))?, "staged");
+        assert!(!staged.with_extension("rolypoly-old").exists());
 
+        // A missing output directory is created by renaming the staged one
+        let fresh = temp_dir.path().join("fresh");
+        manager.extract_archive(&archive_path, &fresh)?;
+        assert_eq!(fs::read_dir(&fresh)?.count(), 5);
         Ok(())
     }
-}
 
-fn copy_buffered<R: std::io::Read, W: std::io::Write>(
-    reader: &mut R,
-    writer: &mut W,
-    buf_size: usize,
-) -> Result<u64> {
-    let mut buf = vec![0u8; buf_size];
-    let mut total: u64 = 0;
-    loop {
-        let n = reader.read(&mut buf)?;
-        if n == 0 {
-            break;
+    #[test]
+    fn test_portable_entry_names() -> Result<()> {
+        assert_eq!(portable_entry_name(r"docs\guide\intro.md"), "docs/guide/intro.md");
+        assert_eq!(portable_entry_name("docs/readme.md"), "docs/readme.md");
+
+        // Unix allows backslashes in file names, which stand in for Windows paths here
+        #[cfg(unix)]
+        {
+            let temp_dir = TempDir::new()?;
+            let dir = temp_dir.path().join("tree");
+            fs::create_dir(&dir)?;
+            fs::write(dir.join(r"sub\file.txt"), "data")?;
+            let archive_path = temp_dir.path().join("tree.zip");
+            ArchiveManager::new().create_archive(&archive_path, &[&dir])?;
+            let names = ArchiveManager::new().list_archive(&archive_path)?;
+            assert_eq!(names, ["tree/sub/file.txt"]);
         }
-        writer.write_all(&buf[..n])?;
-        total += n as u64;
+        Ok(())
     }
-    Ok(total)
-}
 
-fn is_incompressible(path: &Path, entropy_threshold: f64) -> Result<bool> {
-    // Simple entropy-based heuristic on the first 256 KiB
-    let mut f = File::open(path)?;
-    let mut buf = vec![0u8; 256 * 1024];
-    let n = f.read(&mut buf)?;
-    if n == 0 {
-        return Ok(false);
-    }
-    let slice = &buf[..n];
-    let mut freq = [0usize; 256];
-    for &b in slice {
-        freq[b as usize] += 1;
+    #[test]
+    fn test_store_full_path() -> Result<()> {
+        assert_eq!(full_path_entry_name(Path::new("/etc/app/config")), "etc/app/config");
+        assert_eq!(full_path_entry_name(Path::new("./a/../b.txt")), "b.txt");
+        assert_eq!(full_path_entry_name(Path::new("../../x/./y.txt")), "x/y.txt");
+
+        let temp_dir = TempDir::new()?;
+        let file = temp_dir.path().join("sub/dir/file.txt");
+        fs::create_dir_all(file.parent().unwrap())?;
+        fs::write(&file, "data")?;
+        let archive_path = temp_dir.path().join("test.zip");
+
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            store_full_path: true,
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&file])?;
+        let names = manager.list_archive(&archive_path)?;
+        assert_eq!(names, [full_path_entry_name(&file)]);
+        assert_eq!(manager.extract_entry(&archive_path, &names[0])?, b"data");
+
+        ArchiveManager::new().create_archive(&archive_path, &[&file])?;
+        assert_eq!(ArchiveManager::new().list_archive(&archive_path)?, ["file.txt"]);
+        Ok(())
     }
-    let total = n as f64;
-    let mut entropy = 0.0f64;
-    for &count in &freq {
-        if count == 0 {
-            continue;
+
+    #[test]
+    fn test_directory_entries_without_slash() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("dirs.zip");
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        zip.start_file("folder", SimpleFileOptions::default())?;
+        zip.start_file("folder/inside.txt", SimpleFileOptions::default())?;
+        zip.write_all(b"inside")?;
+        zip.finish()?;
+        let original = fs::read(&archive_path)?;
+        // The first central directory header belongs to "folder"
+        let header = original.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+
+        // (host system in "version made by", external attributes)
+        for (system, attributes) in [(3u8, 0o040755u32 << 16), (0, 0x10)] {
+            let mut bytes = original.clone();
+            bytes[header + 5] = system;
+            bytes[header + 38..header + 42].copy_from_slice(&attributes.to_le_bytes());
+            fs::write(&archive_path, &bytes)?;
+
+            let out = temp_dir.path().join(format!("out{system}"));
+            ArchiveManager::new().extract_archive(&archive_path, &out)?;
+            assert!(out.join("folder").is_dir());
+            assert_eq!(fs::read_to_string(out.join("folder/inside.txt"))?, "inside");
         }
-        let p = count as f64 / total;
-        entropy -= p * p.log2();
+        Ok(())
     }
-    Ok(entropy >= entropy_threshold)
-}
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct ArchiveStats {
-    pub file_count: usize,
-    pub dir_count: usize,
-    pub total_uncompressed_size: u64,
-    pub total_compressed_size: u64,
-    pub compression_ratio: f64,
-}
+    #[test]
+    fn test_no_dir_entries() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("tree");
+        fs::create_dir_all(dir.join("a/b"))?;
+        fs::write(dir.join("a/b/deep.txt"), "deep")?;
+        fs::write(dir.join("a/top.txt"), "top")?;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use tempfile::TempDir;
+        let archive_path = temp_dir.path().join("tree.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            dir_entries: false,
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&dir])?;
+        let mut names = manager.list_archive(&archive_path)?;
+        names.sort();
+        assert_eq!(names, ["tree/a/b/deep.txt", "tree/a/top.txt"]);
+
+        let out = temp_dir.path().join("out");
+        manager.extract_archive(&archive_path, &out)?;
+        assert_eq!(fs::read_to_string(out.join("tree/a/b/deep.txt"))?, "deep");
+        assert_eq!(fs::read_to_string(out.join("tree/a/top.txt"))?, "top");
+        Ok(())
+    }
 
     #[test]
-    fn test_create_archive_single_file() -> Result<()> {
+    fn test_strict_validation_checks_local_headers() -> Result<()> {
         let temp_dir = TempDir::new()?;
-        let test_file = temp_dir.path().join("test.txt");
-        let archive_path = temp_dir.path().join("test.zip");
+        let archive_path = temp_dir.path().join("tampered.zip");
+        let mut bytes = zip_bytes(&[("first.txt", b"first"), ("second.txt", b"second")])?;
+        // Flip the CRC in the second entry's local header, leaving the central directory
+        let second = bytes.windows(4).rposition(|w| w == b"PK\x03\x04").unwrap();
+        bytes[second + 14] ^= 0xff;
+        std::fs::write(&archive_path, &bytes)?;
 
-        // Create a test file
-        fs::write(&test_file, "Hello, World!")?;
+        assert!(ArchiveManager::new().validate_archive(&archive_path)?);
+        let strict = ArchiveManager::with_options(ArchiveOptions {
+            strict: true,
+            ..Default::default()
+        });
+        assert!(!strict.validate_archive(&archive_path)?);
+        std::fs::write(&archive_path, zip_bytes(&[("first.txt", b"first")])?)?;
+        assert!(strict.validate_archive(&archive_path)?);
+        Ok(())
+    }
 
-        // Create archive
-        let manager = ArchiveManager::new();
-        manager.create_archive(&archive_path, &[&test_file])?;
+    #[test]
+    fn test_validate_streamed_archive() -> Result<()> {
+        // Written without seeking, so each entry's CRC and sizes follow it in a data descriptor
+        let mut zip = ZipWriter::new_stream(Vec::new());
+        let stored =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("alpha.txt", stored)?;
+        zip.write_all(b"alpha data")?;
+        zip.start_file("beta.txt", SimpleFileOptions::default())?;
+        zip.write_all(&b"beta ".repeat(100))?;
+        let bytes = zip.finish()?.into_inner();
+        assert!(bytes.windows(4).any(|w| w == b"PK\x07\x08"));
 
-        // Verify archive was created
-        assert!(archive_path.exists());
-        assert!(archive_path.metadata()?.len() > 0);
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("streamed.zip");
+        std::fs::write(&archive_path, &bytes)?;
+        let strict = ArchiveManager::with_options(ArchiveOptions {
+            strict: true,
+            ..Default::default()
+        });
+        assert!(ArchiveManager::new().validate_archive(&archive_path)?);
+        assert!(strict.validate_archive(&archive_path)?);
+
+        // Damaged data no longer matches the recorded CRC
+        let mut damaged = bytes.clone();
+        let data = damaged.windows(10).position(|w| w == b"alpha data").unwrap();
+        damaged[data] ^= 0xff;
+        std::fs::write(&archive_path, &damaged)?;
+        assert!(!ArchiveManager::new().validate_archive(&archive_path)?);
 
+        // Nor does a descriptor that disagrees with the central directory
+        let mut damaged = bytes;
+        let descriptor = damaged.windows(4).position(|w| w == b"PK\x07\x08").unwrap();
+        damaged[descriptor + 4] ^= 0xff;
+        std::fs::write(&archive_path, &damaged)?;
+        assert!(!ArchiveManager::new().validate_archive(&archive_path)?);
         Ok(())
     }
 
     #[test]
-    fn test_create_archive_multiple_files() -> Result<()> {
+    fn test_archive_with_prepended_data() -> Result<()> {
         let temp_dir = TempDir::new()?;
-        let test_file1 = temp_dir.path().join("test1.txt");
-        let test_file2 = temp_dir.path().join("test2.txt");
-        let archive_path = temp_dir.path().join("test.zip");
-
-        // Create test files
-        fs::write(&test_file1, "Hello, World!")?;
-        fs::write(&test_file2, "Goodbye, World!")?;
+        let input = temp_dir.path().join("payload.txt");
+        fs::write(&input, "payload")?;
+        let plain = temp_dir.path().join("plain.zip");
+        ArchiveManager::new().create_archive(&plain, &[&input])?;
+        // A self-extractor: a stub program followed by the unmodified archive
+        let stub = b"#!/bin/sh\necho 'self-extracting'\nexit 0\n".repeat(20);
+        let sfx = temp_dir.path().join("installer.zip");
+        fs::write(&sfx, [stub.as_slice(), &fs::read(&plain)?].concat())?;
 
-        // Create archive
         let manager = ArchiveManager::new();
-        manager.create_archive(&archive_path, &[&test_file1, &test_file2])?;
+        assert_eq!(manager.prepended_bytes(&plain)?, 0);
+        assert_eq!(manager.prepended_bytes(&sfx)?, stub.len() as u64);
+        assert_eq!(manager.list_archive(&sfx)?, ["payload.txt"]);
+        let out = temp_dir.path().join("out");
+        manager.extract_archive(&sfx, &out)?;
+        assert_eq!(fs::read_to_string(out.join("payload.txt"))?, "payload");
+        assert_eq!(manager.get_archive_stats(&sfx)?.prepended_bytes, stub.len() as u64);
+        assert_eq!(manager.get_archive_stats_streaming(&sfx)?.prepended_bytes, stub.len() as u64);
+        assert!(manager.validate_archive(&sfx)?);
 
-        // Verify archive was created
-        assert!(archive_path.exists());
+        // Strict validation wants the prefix acknowledged
+        let strict = |allow_prepended| {
+            ArchiveManager::with_options(ArchiveOptions {
+                strict: true,
+                allow_prepended,
+                ..Default::default()
+            })
+        };
+        assert!(!strict(false).validate_archive(&sfx)?);
+        assert!(strict(true).validate_archive(&sfx)?);
+        assert!(strict(false).validate_archive(&plain)?);
+        Ok(())
+    }
 
-        // Verify contents
-        let contents = manager.list_archive(&archive_path)?;
-        assert_eq!(contents.len(), 2);
-        assert!(contents.contains(&"test1.txt".to_string()));
-        assert!(contents.contains(&"test2.txt".to_string()));
+    #[test]
+    fn test_suspicious_entry_names() -> Result<()> {
+        assert!(entry_name_problems(b"docs/readme.txt").is_empty());
+        assert_eq!(entry_name_problems(b"/etc/passwd"), ["is an absolute path"]);
+        assert_eq!(
+            entry_name_problems(b"C:\\temp\\x"),
+            ["is an absolute path", "contains backslashes"]
+        );
+        assert_eq!(
+            entry_name_problems(b"a\0b\tc"),
+            ["contains a NUL byte", "contains control characters"]
+        );
+
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("names.zip");
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        for name in ["ok.txt", "dir\\file.txt", "bell\x07.txt"] {
+            zip.start_file(name, SimpleFileOptions::default())?;
+            zip.write_all(b"data")?;
+        }
+        zip.finish()?;
 
+        // Suspicious names are warnings, unless strict
+        assert!(ArchiveManager::new().validate_archive(&archive_path)?);
+        let strict = ArchiveManager::with_options(ArchiveOptions {
+            strict: true,
+            ..Default::default()
+        });
+        assert!(!strict.validate_archive(&archive_path)?);
+        let mut archive = ZipArchive::new(File::open(&archive_path)?)?;
+        let flagged: Vec<(String, Vec<&str>)> = (0..archive.len())
+            .map(|i| {
+                let entry = archive.by_index_raw(i).unwrap();
+                (entry.name().to_string(), entry_name_problems(entry.name_raw()))
+            })
+            .filter(|(_, problems)| !problems.is_empty())
+            .collect();
+        assert_eq!(
+            flagged,
+            [
+                ("dir\\file.txt".to_string(), vec!["contains backslashes"]),
+                ("bell\x07.txt".to_string(), vec!["contains control characters"])
+            ]
+        );
         Ok(())
     }
 
     #[test]
-    fn test_create_archive_directory() -> Result<()> {
+    fn test_create_archive_in_memory() -> Result<()> {
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            comment: Some("built in memory".to_string()),
+            ..Default::default()
+        });
+        let bytes = manager.create_archive_in_memory(&[
+            ("docs/", b""),
+            ("docs/readme.txt", b"read me"),
+            ("data.bin", &[7u8; 4096]),
+        ])?;
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.comment(), b"built in memory");
+        assert_eq!(archive.file_names().count(), 3);
+        assert!(archive.by_name("docs/")?.is_dir());
+        let mut contents = String::new();
+        archive.by_name("docs/readme.txt")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "read me");
+        let mut data = archive.by_name("data.bin")?;
+        assert_eq!(data.compression(), zip::CompressionMethod::Deflated);
+        let mut contents = Vec::new();
+        data.read_to_end(&mut contents)?;
+        assert_eq!(contents, [7u8; 4096]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_stores_identical_files_once() -> Result<()> {
         let temp_dir = TempDir::new()?;
-        let test_dir = temp_dir.path().join("test_dir");
-        let archive_path = temp_dir.path().join("test.zip");
+        let dir = temp_dir.path().join("d");
+        std::fs::create_dir_all(dir.join("sub"))?;
+        // Pseudo-random bytes, so compression alone can't hide the duplication
+        let mut state = 0x9e37_79b9_u32;
+        let noise: Vec<u8> = (0..100_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        for name in ["a.bin", "b.bin", "sub/c.bin"] {
+            std::fs::write(dir.join(name), &noise)?;
+        }
+        std::fs::write(dir.join("other.txt"), "different")?;
 
-        // Create test directory with files
-        fs::create_dir(&test_dir)?;
-        fs::write(test_dir.join("file1.txt"), "Content 1")?;
-        fs::write(test_dir.join("file2.txt"), "Content 2")?;
+        let plain_path = temp_dir.path().join("plain.zip");
+        ArchiveManager::new().create_archive_with_reporter(
+            &plain_path,
+            &[&dir],
+            &mut crate::progress::SilentReporter,
+        )?;
+        let dedup_path = temp_dir.path().join("dedup.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            dedup: true,
+            ..Default::default()
+        });
+        let summary = manager.create_archive_with_summary(
+            &dedup_path,
+            &[&dir],
+            &mut crate::progress::SilentReporter,
+        )?;
+        assert_eq!(summary.duplicate_files, 2);
+        assert_eq!(summary.duplicate_bytes, 200_000);
+        let plain_size = std::fs::metadata(&plain_path)?.len();
+        let dedup_size = std::fs::metadata(&dedup_path)?.len();
+        assert!(dedup_size * 2 < plain_size, "{dedup_size} vs {plain_size}");
 
-        // Create archive
-        let manager = ArchiveManager::new();
-        manager.create_archive(&archive_path, &[&test_dir])?;
+        // Every copy is a regular file, however it is extracted
+        let out = temp_dir.path().join("out");
+        manager.extract_archive(&dedup_path, &out)?;
+        for name in ["a.bin", "b.bin", "sub/c.bin"] {
+            let path = out.join("d").join(name);
+            assert!(!path.is_symlink());
+            assert_eq!(std::fs::read(path)?, noise);
+        }
+        assert_eq!(std::fs::read_to_string(out.join("d/other.txt"))?, "different");
+        assert_eq!(manager.extract_entry(&dedup_path, "d/sub/c.bin")?, noise);
+        let flat = temp_dir.path().join("flat");
+        ArchiveManager::with_options(ArchiveOptions {
+            flatten: true,
+            ..Default::default()
+        })
+        .extract_archive(&dedup_path, &flat)?;
+        for name in ["a.bin", "b.bin", "c.bin"] {
+            assert_eq!(std::fs::read(flat.join(name))?, noise);
+        }
+        Ok(())
+    }
 
-        // Verify archive was created
-        assert!(archive_path.exists());
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_input_stored_or_dereferenced() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let target = temp_dir.path().join("real.txt");
+        std::fs::write(&target, "real contents")?;
+        let link = temp_dir.path().join("alias.txt");
+        std::os::unix::fs::symlink("real.txt", &link)?;
 
-        // Verify contents
-        let contents = manager.list_archive(&archive_path)?;
-        assert!(!contents.is_empty());
+        // By default the link itself is stored
+        let archive_path = temp_dir.path().join("links.zip");
+        ArchiveManager::new().create_archive_with_reporter(
+            &archive_path,
+            &[&link],
+            &mut crate::progress::SilentReporter,
+        )?;
+        let mut archive = ZipArchive::new(File::open(&archive_path)?)?;
+        let mut entry = archive.by_name("alias.txt")?;
+        assert!(entry.is_symlink());
+        let mut stored = String::new();
+        entry.read_to_string(&mut stored)?;
+        assert_eq!(stored, "real.txt");
+        drop(entry);
+
+        // With `dereference` the file it points to is stored under the link's name
+        let archive_path = temp_dir.path().join("followed.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            dereference: true,
+            ..Default::default()
+        });
+        manager.create_archive_with_reporter(
+            &archive_path,
+            &[&link],
+            &mut crate::progress::SilentReporter,
+        )?;
+        let mut archive = ZipArchive::new(File::open(&archive_path)?)?;
+        let mut entry = archive.by_name("alias.txt")?;
+        assert!(!entry.is_symlink());
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents)?;
+        assert_eq!(contents, "real contents");
+        drop(entry);
 
+        // A link to a directory is walked, and size filters apply to links too
+        let dir = temp_dir.path().join("dir");
+        fs::create_dir(&dir)?;
+        fs::write(dir.join("inner.txt"), "inner")?;
+        let dir_link = temp_dir.path().join("linkdir");
+        std::os::unix::fs::symlink("dir", &dir_link)?;
+        let archive_path = temp_dir.path().join("dirlink.zip");
+        ArchiveManager::new().create_archive_with_reporter(
+            &archive_path,
+            &[&dir_link, &link],
+            &mut crate::progress::SilentReporter,
+        )?;
+        let mut names = ArchiveManager::new().list_archive(&archive_path)?;
+        names.sort();
+        assert_eq!(names, ["alias.txt", "linkdir/inner.txt"]);
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            min_size: Some(1000),
+            ..Default::default()
+        });
+        manager.create_archive_with_reporter(
+            &archive_path,
+            &[&dir_link, &link],
+            &mut crate::progress::SilentReporter,
+        )?;
+        assert!(manager.list_archive(&archive_path)?.is_empty());
         Ok(())
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_extract_archive() -> Result<()> {
+    fn test_preserve_owner_round_trip() -> Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let mut extra = vec![0x55, 0x54, 1, 0, 0];
+        extra.extend([0x75, 0x78, 11, 0]);
+        extra.extend(owner_field(1000, 100));
+        assert_eq!(parse_owner_field(&extra), Some((1000, 100)));
+        // 8-byte ids are read when they fit in 32 bits
+        let wide = [0x75, 0x78, 19, 0, 1, 8, 7, 0, 0, 0, 0, 0, 0, 0, 8, 9, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(parse_owner_field(&wide), Some((7, 9)));
+        assert_eq!(parse_owner_field(&[0x75, 0x78, 3, 0, 1]), None);
+
         let temp_dir = TempDir::new()?;
-        let test_file = temp_dir.path().join("test.txt");
-        let archive_path = temp_dir.path().join("test.zip");
-        let extract_dir = temp_dir.path().join("extract");
+        let dir = temp_dir.path().join("data");
+        std::fs::create_dir_all(dir.join("sub"))?;
+        std::fs::write(dir.join("sub/owned.txt"), "owned")?;
+        // Root can hand the inputs to another user, which extraction should bring back
+        // SAFETY: geteuid has no preconditions
+        let is_root = unsafe { libc::geteuid() } == 0;
+        if is_root {
+            for path in [dir.join("sub"), dir.join("sub/owned.txt")] {
+                std::os::unix::fs::chown(&path, Some(65534), Some(65534))?;
+            }
+        }
+        let expected = {
+            let meta = std::fs::metadata(dir.join("sub/owned.txt"))?;
+            (meta.uid(), meta.gid())
+        };
 
-        // Create a test file and archive
-        fs::write(&test_file, "Hello, World!")?;
-        let manager = ArchiveManager::new();
-        manager.create_archive(&archive_path, &[&test_file])?;
+        let archive_path = temp_dir.path().join("owned.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            preserve_owner: true,
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&dir])?;
+        let mut archive = ZipArchive::new(File::open(&archive_path)?)?;
+        for name in ["data/sub/", "data/sub/owned.txt"] {
+            let entry = archive.by_name(name)?;
+            assert_eq!(entry.extra_data().and_then(parse_owner_field), Some(expected), "{name}");
+        }
+        drop(archive);
 
-        // Extract archive
-        fs::create_dir(&extract_dir)?;
-        manager.extract_archive(&archive_path, &extract_dir)?;
+        let out = temp_dir.path().join("out");
+        manager.extract_archive(&archive_path, &out)?;
+        if is_root {
+            for path in [out.join("data/sub"), out.join("data/sub/owned.txt")] {
+                let meta = std::fs::metadata(&path)?;
+                assert_eq!((meta.uid(), meta.gid()), (65534, 65534), "{}", path.display());
+            }
+            // Without the flag, extracted files belong to the extracting user
+            let plain = temp_dir.path().join("plain");
+            ArchiveManager::new().extract_archive(&archive_path, &plain)?;
+            assert_eq!(std::fs::metadata(plain.join("data/sub/owned.txt"))?.uid(), 0);
+        }
+        Ok(())
+    }
 
-        // Verify extracted file
-        let extracted_file = extract_dir.join("test.txt");
-        assert!(extracted_file.exists());
-        assert_eq!(fs::read_to_string(&extracted_file)?, "Hello, World!");
+    #[cfg(unix)]
+    #[test]
+    fn test_keep_going_skips_unreadable_files() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        // SAFETY: geteuid has no preconditions
+        if unsafe { libc::geteuid() } == 0 {
+            eprintln!("skipping: root reads files whatever their permissions");
+            return Ok(());
+        }
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("inputs");
+        fs::create_dir(&dir)?;
+        fs::write(dir.join("a.txt"), "a")?;
+        fs::write(dir.join("locked.txt"), "secret")?;
+        fs::write(dir.join("z.txt"), "z")?;
+        fs::set_permissions(dir.join("locked.txt"), fs::Permissions::from_mode(0o000))?;
 
+        let archive_path = temp_dir.path().join("out.zip");
+        assert!(ArchiveManager::new().create_archive(&archive_path, &[&dir]).is_err());
+
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            keep_going: true,
+            ..Default::default()
+        });
+        let summary = manager.create_archive_with_summary(
+            &archive_path,
+            &[&dir],
+            &mut crate::progress::SilentReporter,
+        )?;
+        assert_eq!(summary.skipped.len(), 1);
+        assert_eq!(summary.skipped[0].path, dir.join("locked.txt"));
+        assert!(summary.skipped[0].error.contains("denied"), "{:?}", summary.skipped);
+        let mut names = manager.list_archive(&archive_path)?;
+        names.sort();
+        assert_eq!(names, ["inputs/a.txt", "inputs/z.txt"]);
         Ok(())
     }
 
     #[test]
-    fn test_list_archive() -> Result<()> {
+    fn test_resume_extraction() -> Result<()> {
         let temp_dir = TempDir::new()?;
-        let test_file1 = temp_dir.path().join("test1.txt");
-        let test_file2 = temp_dir.path().join("test2.txt");
+        let files: Vec<PathBuf> =
+            (0..6).map(|i| temp_dir.path().join(format!("f{i}.txt"))).collect();
+        for (i, file) in files.iter().enumerate() {
+            fs::write(file, format!("contents of file {i}"))?;
+        }
+        let files: Vec<&PathBuf> = files.iter().collect();
         let archive_path = temp_dir.path().join("test.zip");
+        ArchiveManager::new().create_archive(&archive_path, &files)?;
 
-        // Create test files and archive
-        fs::write(&test_file1, "Hello, World!")?;
-        fs::write(&test_file2, "Goodbye, World!")?;
-        let manager = ArchiveManager::new();
-        manager.create_archive(&archive_path, &[&test_file1, &test_file2])?;
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            resume: true,
+            ..Default::default()
+        });
+        let out = temp_dir.path().join("out");
+        let mut reporter = CancelAfter { done: 0, after: 3 };
+        assert!(
+            manager
+                .extract_archive_with_reporter(&archive_path, &out, &mut reporter)
+                .is_err()
+        );
+        let state = fs::read_to_string(out.join(RESUME_STATE_FILE))?;
+        assert_eq!(state.lines().collect::<Vec<_>>(), ["f0.txt", "f1.txt", "f2.txt"]);
 
-        // List archive contents
-        let contents = manager.list_archive(&archive_path)?;
+        // Skipped files keep their timestamp; one changed since the interruption fails
+        // its CRC and is rewritten
+        let marker = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        File::options().write(true).open(out.join("f0.txt"))?.set_modified(marker)?;
+        fs::write(out.join("f1.txt"), "corrupted")?;
+        manager.extract_archive_with_reporter(
+            &archive_path,
+            &out,
+            &mut crate::progress::SilentReporter,
+        )?;
+        assert_eq!(fs::metadata(out.join("f0.txt"))?.modified()?, marker);
+        assert!(!out.join(RESUME_STATE_FILE).exists());
+        for i in 0..6 {
+            let path = out.join(format!("f{i}.txt"));
+            assert_eq!(fs::read_to_string(path)?, format!("contents of file {i}"));
+        }
+        Ok(())
+    }
 
-        assert_eq!(contents.len(), 2);
-        assert!(contents.contains(&"test1.txt".to_string()));
-        assert!(contents.contains(&"test2.txt".to_string()));
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_fixed_modes() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("modes.zip");
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        let options = SimpleFileOptions::default().unix_permissions(0o700);
+        zip.add_directory("bin/", options)?;
+        zip.start_file("bin/tool", options)?;
+        zip.write_all(b"#!/bin/sh")?;
+        zip.start_file("deep/nested/readme.txt", options)?;
+        zip.write_all(b"hi")?;
+        zip.finish()?;
 
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            file_mode: Some(0o644),
+            dir_mode: Some(0o755),
+            ..Default::default()
+        });
+        let out = temp_dir.path().join("out");
+        manager.extract_archive(&archive_path, &out)?;
+
+        let mode = |p: &str| -> Result<u32> {
+            Ok(fs::metadata(out.join(p))?.permissions().mode() & 0o7777)
+        };
+        assert_eq!(mode("bin/tool")?, 0o644);
+        assert_eq!(mode("deep/nested/readme.txt")?, 0o644);
+        assert_eq!(mode("bin")?, 0o755);
+        assert_eq!(mode("deep")?, 0o755);
+        assert_eq!(mode("deep/nested")?, 0o755);
         Ok(())
     }
 
     #[test]
-    fn test_archive_nonexistent_file() {
-        let temp_dir = TempDir::new().unwrap();
-        let nonexistent_file = temp_dir.path().join("nonexistent.txt");
-        let archive_path = temp_dir.path().join("test.zip");
+    fn test_skip_mac_metadata() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("mac.zip");
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        for name in ["photos/cat.jpg", "__MACOSX/photos/._cat.jpg", "photos/._cat.jpg"] {
+            zip.start_file(name, SimpleFileOptions::default())?;
+            zip.write_all(b"data")?;
+        }
+        zip.finish()?;
 
-        let manager = ArchiveManager::new();
-        let result = manager.create_archive(&archive_path, &[&nonexistent_file]);
+        assert_eq!(ArchiveManager::new().list_archive(&archive_path)?.len(), 3);
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            skip_mac_metadata: true,
+            ..Default::default()
+        });
+        assert_eq!(manager.list_archive(&archive_path)?, vec!["photos/cat.jpg"]);
+        assert_eq!(manager.list_archive_detailed(&archive_path)?.len(), 1);
 
-        assert!(result.is_err());
+        let out = temp_dir.path().join("out");
+        let summary = manager.extract_archive_with_summary(
+            &archive_path,
+            &out,
+            &mut crate::progress::SilentReporter,
+        )?;
+        // Only what was written is counted
+        assert_eq!((summary.extracted, summary.total_bytes), (1, 4));
+        assert!(out.join("photos/cat.jpg").exists());
+        assert!(!out.join("__MACOSX").exists());
+        assert!(!out.join("photos/._cat.jpg").exists());
+        Ok(())
     }
 
     #[test]
-    fn test_validate_archive() -> Result<()> {
+    fn test_mtime_filters() -> Result<()> {
         let temp_dir = TempDir::new()?;
-        let test_file = temp_dir.path().join("test.txt");
-        let archive_path = temp_dir.path().join("test.zip");
-
-        // Create a test file and archive
-        fs::write(&test_file, "Hello, World!")?;
-        let manager = ArchiveManager::new();
-        manager.create_archive(&archive_path, &[&test_file])?;
+        let dir = temp_dir.path().join("backup");
+        fs::create_dir(&dir)?;
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        for (name, age) in [("old.txt", 10), ("new.txt", 1)] {
+            let path = dir.join(name);
+            fs::write(&path, name)?;
+            File::options().write(true).open(&path)?.set_modified(now - day * age)?;
+        }
 
-        // Validate the archive
-        let is_valid = manager.validate_archive(&archive_path)?;
-        assert!(is_valid);
+        let cutoff = now - day * 5;
+        let archive_path = temp_dir.path().join("newer.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            newer_than: Some(cutoff),
+            ..Default::default()
+        });
+        let mut reporter = RecordingReporter::default();
+        manager.create_archive_with_reporter(&archive_path, &[&dir], &mut reporter)?;
+        assert_eq!(manager.list_archive(&archive_path)?, vec!["backup/new.txt"]);
+        assert_eq!(reporter.calls.first(), Some(&"start(1)".to_string()));
 
+        let archive_path = temp_dir.path().join("older.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            older_than: Some(cutoff),
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&dir.join("old.txt"), &dir.join("new.txt")])?;
+        assert_eq!(manager.list_archive(&archive_path)?, vec!["old.txt"]);
         Ok(())
     }
 
     #[test]
-    fn test_calculate_file_hash() -> Result<()> {
+    fn test_size_filters() -> Result<()> {
         let temp_dir = TempDir::new()?;
-        let test_file = temp_dir.path().join("test.txt");
+        let dir = temp_dir.path().join("media");
+        fs::create_dir(&dir)?;
+        for (name, size) in [("tiny.txt", 10), ("medium.bin", 2048), ("huge.bin", 10_000)] {
+            fs::write(dir.join(name), vec![b'x'; size])?;
+        }
 
-        // Create a test file
-        fs::write(&test_file, "Hello, World!")?;
+        let archive_path = temp_dir.path().join("sized.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            min_size: Some(100),
+            max_size: Some(4096),
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&dir])?;
+        assert_eq!(manager.list_archive(&archive_path)?, vec!["media/medium.bin"]);
+        Ok(())
+    }
 
-        let manager = ArchiveManager::new();
-        let hash = manager.calculate_file_hash(&test_file)?;
+    #[test]
+    fn test_store_extensions() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let photo = temp_dir.path().join("photo.JPG");
+        let notes = temp_dir.path().join("notes.txt");
+        // Highly compressible content, so only the extension decides
+        fs::write(&photo, "a".repeat(4096))?;
+        fs::write(&notes, "a".repeat(4096))?;
+        let archive_path = temp_dir.path().join("mixed.zip");
 
-        // The hash should be consistent and not empty
-        assert!(!hash.is_empty());
-        assert_eq!(hash.len(), 64); // SHA256 produces 64 character hex string
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            compression_level: Some(9),
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&photo, &notes])?;
+        let entries = manager.list_archive_detailed(&archive_path)?;
+        let method = |name: &str| entries.iter().find(|e| e.name == name).unwrap().method.clone();
+        assert_eq!(method("photo.JPG"), "stored");
+        assert_eq!(method("notes.txt"), "deflated");
 
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            store_extensions: Vec::new(),
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&photo])?;
+        assert_eq!(manager.list_archive_detailed(&archive_path)?[0].method, "deflated");
         Ok(())
     }
 
     #[test]
-    fn test_get_archive_stats() -> Result<()> {
+    fn test_nested_archive_is_stored() -> Result<()> {
         let temp_dir = TempDir::new()?;
-        let test_file1 = temp_dir.path().join("test1.txt");
-        let test_file2 = temp_dir.path().join("test2.txt");
-        let archive_path = temp_dir.path().join("test.zip");
-
-        // Create test files
-        fs::write(&test_file1, "Hello, World!")?;
-        fs::write(&test_file2, "Goodbye, World!")?;
+        let text = temp_dir.path().join("notes.txt");
+        fs::write(&text, "compressible text ".repeat(5_000))?;
+        let inner = temp_dir.path().join("inner.zip");
+        ArchiveManager::new().create_archive(&inner, &[&text])?;
+        // A name outside the store list, so only the magic bytes give the ZIP away
+        let nested = temp_dir.path().join("bundle.dat");
+        fs::rename(&inner, &nested)?;
 
-        let manager = ArchiveManager::new();
-        manager.create_archive(&archive_path, &[&test_file1, &test_file2])?;
+        let options = ArchiveOptions {
+            auto_store: false,
+            ..Default::default()
+        };
+        let archive_path = temp_dir.path().join("outer.zip");
+        let manager = ArchiveManager::with_options(options.clone());
+        let summary = manager.create_archive_with_summary(
+            &archive_path,
+            &[&nested, &text],
+            &mut crate::progress::SilentReporter,
+        )?;
+        assert_eq!(summary.stored_archives, 1);
+        let entries = manager.list_archive_detailed(&archive_path)?;
+        assert_eq!(entries[0].name, "bundle.dat");
+        assert_eq!(entries[0].method, "stored");
+        assert_eq!(entries[1].method, "deflated");
 
-        // Get archive stats
-        let stats = manager.get_archive_stats(&archive_path)?;
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            force_recompress: true,
+            ..options
+        });
+        manager.create_archive(&archive_path, &[&nested])?;
+        assert_eq!(manager.list_archive_detailed(&archive_path)?[0].method, "deflated");
+        Ok(())
+    }
 
-        assert_eq!(stats.file_count, 2);
-        assert!(stats.total_uncompressed_size > 0);
-        assert!(stats.total_compressed_size > 0);
-        assert!(stats.compression_ratio > 0.0);
+    #[test]
+    fn test_auto_store_probe() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let random = temp_dir.path().join("random.bin");
+        let repetitive = temp_dir.path().join("repetitive.bin");
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..200_000)
+            .map(|_| {
+                // xorshift64
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 24) as u8
+            })
+            .collect();
+        fs::write(&random, &noise)?;
+        fs::write(&repetitive, "abcabcabd".repeat(20_000))?;
+        assert!(deflate_ratio(&noise[..PROBE_SIZE])? > 0.99);
 
+        // Disable the entropy shortcut so only the deflate probe decides
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            store_entropy_threshold: 9.0,
+            store_extensions: Vec::new(),
+            ..Default::default()
+        });
+        let archive_path = temp_dir.path().join("probe.zip");
+        manager.create_archive(&archive_path, &[&random, &repetitive])?;
+        let entries = manager.list_archive_detailed(&archive_path)?;
+        assert_eq!(entries[0].method, "stored");
+        assert_eq!(entries[1].method, "deflated");
         Ok(())
     }
 }
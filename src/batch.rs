@@ -0,0 +1,127 @@
+//! Creating several archives in one invocation.
+//!
+//! A spec maps each archive to write to its inputs, as JSON: `{"docs.zip": ["docs"],
+//! "logs.zip": ["a.log", "b.log"]}`. Archives are written in name order, or by a few
+//! worker threads at once, each one created as `create` would.
+use crate::archive::ArchiveManager;
+use crate::progress::{self, ProgressReporter, SilentReporter};
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// One archive of a batch and the files and directories that go into it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchJob {
+    pub archive: PathBuf,
+    pub inputs: Vec<PathBuf>,
+}
+
+/// What happened to one archive of a batch
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchOutcome {
+    pub archive: PathBuf,
+    pub files: usize,
+    pub uncompressed_bytes: u64,
+    pub compressed_bytes: u64,
+    /// Why the archive could not be created; the others are still attempted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Read a batch spec: inline JSON when `spec` starts with `{`, otherwise the path of
+/// a file holding it
+pub fn parse_spec(spec: &str) -> Result<Vec<BatchJob>> {
+    let text = if spec.trim_start().starts_with('{') {
+        spec.to_string()
+    } else {
+        std::fs::read_to_string(spec)
+            .with_context(|| format!("Failed to read batch spec {spec}"))?
+    };
+    let map: BTreeMap<PathBuf, Vec<PathBuf>> = serde_json::from_str(&text)
+        .context("A batch spec must be a JSON object mapping each archive to a list of inputs")?;
+    if let Some((archive, _)) = map.iter().find(|(_, inputs)| inputs.is_empty()) {
+        bail!("No inputs given for {} in the batch spec", archive.display());
+    }
+    Ok(map.into_iter().map(|(archive, inputs)| BatchJob { archive, inputs }).collect())
+}
+
+/// Create every archive of `jobs`, `threads` at a time. A failed archive is recorded
+/// in its outcome rather than stopping the batch; outcomes keep the order of `jobs`.
+pub fn run_batch(manager: &ArchiveManager, jobs: &[BatchJob], threads: usize) -> Vec<BatchOutcome> {
+    if threads <= 1 {
+        let mut reporter = progress::default_reporter();
+        return jobs.iter().map(|job| create_one(manager, job, reporter.as_mut())).collect();
+    }
+    // Progress bars from several archives at once would overwrite each other
+    let next = AtomicUsize::new(0);
+    let outcomes = Mutex::new(vec![None; jobs.len()]);
+    std::thread::scope(|scope| {
+        for _ in 0..threads.min(jobs.len()) {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(job) = jobs.get(index) else { break };
+                    let outcome = create_one(manager, job, &mut SilentReporter);
+                    outcomes.lock().unwrap()[index] = Some(outcome);
+                }
+            });
+        }
+    });
+    outcomes.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+fn create_one(
+    manager: &ArchiveManager,
+    job: &BatchJob,
+    reporter: &mut dyn ProgressReporter,
+) -> BatchOutcome {
+    let mut outcome = BatchOutcome {
+        archive: job.archive.clone(),
+        files: 0,
+        uncompressed_bytes: 0,
+        compressed_bytes: 0,
+        error: None,
+    };
+    let inputs: Vec<&Path> = job.inputs.iter().map(PathBuf::as_path).collect();
+    let created = manager
+        .create_archive_with_summary(job.archive.as_path(), &inputs, reporter)
+        .and_then(|_| manager.get_archive_stats(&job.archive));
+    match created {
+        Ok(stats) => {
+            outcome.files = stats.file_count;
+            outcome.uncompressed_bytes = stats.total_uncompressed_size;
+            outcome.compressed_bytes = stats.total_compressed_size;
+        }
+        Err(err) => outcome.error = Some(format!("{err:#}")),
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_inline_spec() -> Result<()> {
+        let jobs = parse_spec(r#"{"b.zip": ["x", "y"], "a.zip": ["z"]}"#)?;
+        assert_eq!(
+            jobs,
+            [
+                BatchJob {
+                    archive: "a.zip".into(),
+                    inputs: vec!["z".into()]
+                },
+                BatchJob {
+                    archive: "b.zip".into(),
+                    inputs: vec!["x".into(), "y".into()]
+                },
+            ]
+        );
+        assert!(parse_spec(r#"{"a.zip": []}"#).is_err());
+        assert!(parse_spec(r#"{"a.zip": "z"}"#).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+//! Timing create and extract on a user's own data.
+//!
+//! `benches/performance_benchmark.rs` measures a synthetic dataset against the
+//! system `zip`; `rolypoly bench <dir>` runs the same measurements in-process on
+//! any directory and prints the same summary table.
+use crate::archive::ArchiveManager;
+use crate::progress::SilentReporter;
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use std::time::Instant;
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkResult {
+    pub operation: String,
+    pub tool: String,
+    pub file_count: usize,
+    pub total_size_mb: f64,
+    pub time_ms: u128,
+    pub throughput_mbps: f64,
+    /// Archive size as a fraction of the input size, for create
+    pub compression_ratio: Option<f64>,
+}
+
+impl BenchmarkResult {
+    pub fn new(
+        operation: String,
+        tool: String,
+        file_count: usize,
+        total_size_mb: f64,
+        time_ms: u128,
+    ) -> Self {
+        let throughput_mbps = if time_ms > 0 {
+            (total_size_mb * 1000.0) / time_ms as f64
+        } else {
+            0.0
+        };
+
+        Self {
+            operation,
+            tool,
+            file_count,
+            total_size_mb,
+            time_ms,
+            throughput_mbps,
+            compression_ratio: None,
+        }
+    }
+
+    pub fn with_compression_ratio(mut self, ratio: f64) -> Self {
+        self.compression_ratio = Some(ratio);
+        self
+    }
+}
+
+/// Archive `dir` and extract it again in a scratch directory, timing both steps
+pub fn bench_directory(manager: &ArchiveManager, dir: &Path) -> Result<Vec<BenchmarkResult>> {
+    if !dir.is_dir() {
+        anyhow::bail!("Not a directory: {}", dir.display());
+    }
+    let (file_count, total_bytes) = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .fold((0usize, 0u64), |(count, bytes), e| {
+            (count + 1, bytes + e.metadata().map_or(0, |m| m.len()))
+        });
+    let total_size_mb = total_bytes as f64 / 1024.0 / 1024.0;
+
+    let scratch = tempfile::Builder::new()
+        .prefix("rolypoly-bench-")
+        .tempdir_in(crate::temp::temp_dir())?;
+    let archive_path = scratch.path().join("bench.zip");
+    let start = Instant::now();
+    manager.create_archive_with_reporter(archive_path.as_path(), &[dir], &mut SilentReporter)?;
+    let create_ms = start.elapsed().as_millis();
+    let archive_size = std::fs::metadata(&archive_path)?.len();
+    let mut create = BenchmarkResult::new(
+        "create".to_string(),
+        "rolypoly".to_string(),
+        file_count,
+        total_size_mb,
+        create_ms,
+    );
+    if total_bytes > 0 {
+        create = create.with_compression_ratio(archive_size as f64 / total_bytes as f64);
+    }
+
+    let extract_dir = scratch.path().join("extract");
+    let start = Instant::now();
+    manager.extract_archive_with_reporter(
+        archive_path.as_path(),
+        &extract_dir,
+        &mut SilentReporter,
+    )?;
+    let extract = BenchmarkResult::new(
+        "extract".to_string(),
+        "rolypoly".to_string(),
+        file_count,
+        total_size_mb,
+        start.elapsed().as_millis(),
+    );
+    Ok(vec![create, extract])
+}
+
+/// Summary table of benchmark results followed by basic system information
+pub fn write_summary(out: &mut dyn Write, results: &[BenchmarkResult]) -> std::io::Result<()> {
+    writeln!(out, "\n{}", "=".repeat(80))?;
+    writeln!(out, "PERFORMANCE BENCHMARK SUMMARY")?;
+    writeln!(out, "{}", "=".repeat(80))?;
+
+    writeln!(
+        out,
+        "{:<12} {:<12} {:<8} {:<10} {:<8} {:<10} {:<10}",
+        "Tool", "Operation", "Files", "Size(MB)", "Time(ms)", "MB/s", "Ratio"
+    )?;
+    writeln!(out, "{}", "-".repeat(80))?;
+
+    for result in results {
+        let ratio_str = if let Some(ratio) = result.compression_ratio {
+            format!("{:.1}%", ratio * 100.0)
+        } else {
+            "-".to_string()
+        };
+
+        writeln!(
+            out,
+            "{:<12} {:<12} {:<8} {:<10.2} {:<8} {:<10.2} {:<10}",
+            result.tool,
+            result.operation,
+            result.file_count,
+            result.total_size_mb,
+            result.time_ms,
+            result.throughput_mbps,
+            ratio_str
+        )?;
+    }
+
+    writeln!(out, "\nSystem Information:")?;
+    writeln!(out, "- Platform: {}", std::env::consts::OS)?;
+    writeln!(out, "- Architecture: {}", std::env::consts::ARCH)?;
+    if let Ok(cores) = std::thread::available_parallelism() {
+        writeln!(out, "- CPU Cores: {cores}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bench_directory() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::create_dir(temp_dir.path().join("sub"))?;
+        fs::write(temp_dir.path().join("a.txt"), "A".repeat(10_000))?;
+        fs::write(temp_dir.path().join("sub/b.txt"), "B".repeat(5_000))?;
+
+        let results = bench_directory(&ArchiveManager::new(), temp_dir.path())?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].operation, "create");
+        assert_eq!(results[1].operation, "extract");
+        for result in &results {
+            assert_eq!(result.file_count, 2);
+            assert!(result.throughput_mbps >= 0.0);
+        }
+        assert!(results[0].compression_ratio.unwrap() < 1.0);
+
+        let mut out = Vec::new();
+        write_summary(&mut out, &results)?;
+        let table = String::from_utf8(out)?;
+        assert!(table.contains("PERFORMANCE BENCHMARK SUMMARY"));
+        assert!(table.lines().any(|l| l.starts_with("rolypoly     create")));
+        Ok(())
+    }
+}
@@ -3,5 +3,8 @@ use clap::Parser;
 
 fn main() -> Result<()> {
     let cli_args = rolypoly::cli::Cli::parse();
-    cli_args.run()
+    rolypoly::logging::init(cli_args.log_level.as_deref());
+    cli_args
+        .run()
+        .inspect_err(|err| tracing::debug!(error = ?err, "command failed"))
 }
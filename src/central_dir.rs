@@ -0,0 +1,847 @@
+//! Minimal reader for the raw ZIP end-of-central-directory and central directory records.
+//!
+//! The `zip` crate indexes entries by name, which hides details such as repeated
+//! names. This module walks the records directly so those cases can be detected, and
+//! compares each entry's local header with its central directory record.
+use anyhow::{Result, bail};
+use std::io::{Read, Seek, SeekFrom};
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+// General purpose flag: CRC and sizes follow the data instead of the local header
+const DATA_DESCRIPTOR_FLAG: u16 = 0x0008;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+const EOCD_MIN_SIZE: u64 = 22;
+const ZIP64_LOCATOR_SIZE: u64 = 20;
+const ZIP64_EOCD_MIN_SIZE: u64 = 56;
+// EOCD (22 bytes) plus the largest possible archive comment
+const EOCD_SEARCH_WINDOW: u64 = EOCD_MIN_SIZE + u16::MAX as u64;
+
+/// End-of-central-directory record, with ZIP64 values substituted when present
+#[derive(Debug, Clone)]
+pub struct EndOfCentralDirectory {
+    /// Absolute offset of the EOCD signature in the file
+    pub offset: u64,
+    /// Absolute offset just past the central directory (the ZIP64 record if there is one)
+    pub cd_end: u64,
+    pub entry_count: u64,
+    pub cd_size: u64,
+    /// Central directory offset as recorded (relative to the start of the ZIP data)
+    pub cd_offset: u64,
+    pub comment: Vec<u8>,
+    pub zip64: bool,
+}
+
+impl EndOfCentralDirectory {
+    /// Whether the counts and offsets came from a ZIP64 end-of-central-directory record
+    pub fn is_zip64(&self) -> bool {
+        self.zip64
+    }
+
+    /// Bytes prepended before the ZIP data (for example a self-extractor stub)
+    pub fn prefix_len(&self) -> u64 {
+        self.cd_end.saturating_sub(self.cd_size).saturating_sub(self.cd_offset)
+    }
+
+    fn needs_zip64(&self) -> bool {
+        self.entry_count == u16::MAX as u64
+            || self.cd_size == u32::MAX as u64
+            || self.cd_offset == u32::MAX as u64
+    }
+}
+
+/// A central directory file header
+#[derive(Debug, Clone)]
+pub struct RawEntry {
+    pub name: Vec<u8>,
+    pub version_made_by: u16,
+    pub flags: u16,
+    pub method: u16,
+    /// Last modification time and date, in MS-DOS format
+    pub modified_time: u16,
+    pub modified_date: u16,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub external_attributes: u32,
+    /// Local header offset as recorded (relative to the start of the ZIP data)
+    pub local_header_offset: u64,
+    pub extra: Vec<u8>,
+}
+
+impl RawEntry {
+    pub fn name_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.name).into_owned()
+    }
+}
+
+/// Locate the end-of-central-directory record by scanning backwards from the end
+pub fn find_eocd<R: Read + Seek>(reader: &mut R) -> Result<EndOfCentralDirectory> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if len < EOCD_MIN_SIZE {
+        bail!("File is too small to be a ZIP archive");
+    }
+    let window = len.min(EOCD_SEARCH_WINDOW);
+    let start = len - window;
+    reader.seek(SeekFrom::Start(start))?;
+    let mut buf = vec![0u8; window as usize];
+    reader.read_exact(&mut buf)?;
+
+    let mut pos = buf.len() - EOCD_MIN_SIZE as usize;
+    loop {
+        if le_u32(&buf[pos..]) == EOCD_SIGNATURE {
+            let rec = &buf[pos..];
+            let comment_len = le_u16(&rec[20..]) as usize;
+            let comment_end = (22 + comment_len).min(rec.len());
+            let offset = start + pos as u64;
+            let mut eocd = EndOfCentralDirectory {
+                offset,
+                cd_end: offset,
+                entry_count: le_u16(&rec[10..]) as u64,
+                cd_size: le_u32(&rec[12..]) as u64,
+                cd_offset: le_u32(&rec[16..]) as u64,
+                comment: rec[22..comment_end].to_vec(),
+                zip64: false,
+            };
+            if eocd.needs_zip64() {
+                read_zip64_record(reader, &mut eocd)?;
+            }
+            return Ok(eocd);
+        }
+        if pos == 0 {
+            bail!("Could not find EOCD (end of central directory) record");
+        }
+        pos -= 1;
+    }
+}
+
+/// Locate the end-of-central-directory record however much data follows it, for archives
+/// with trailing bytes or a comment length that runs past the end of the file. Each
+/// signature found scanning backwards is checked against the central directory it
+/// describes, so one that merely appears in the trailing data is passed over.
+pub fn find_eocd_tolerant<R: Read + Seek>(reader: &mut R) -> Result<EndOfCentralDirectory> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if len < EOCD_MIN_SIZE {
+        bail!("File is too small to be a ZIP archive");
+    }
+    // An empty archive has no central directory to check, so it only wins when
+    // nothing better is found
+    let mut empty = None;
+    let mut end = len;
+    let mut buf = Vec::new();
+    while end >= 4 {
+        let start = end.saturating_sub(EOCD_SEARCH_WINDOW);
+        buf.resize((end - start) as usize, 0);
+        reader.seek(SeekFrom::Start(start))?;
+        reader.read_exact(&mut buf)?;
+        for pos in (0..=buf.len() - 4).rev() {
+            let offset = start + pos as u64;
+            if le_u32(&buf[pos..]) != EOCD_SIGNATURE || offset + EOCD_MIN_SIZE > len {
+                continue;
+            }
+            let mut rec = [0u8; EOCD_MIN_SIZE as usize];
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_exact(&mut rec)?;
+            let comment_len = (le_u16(&rec[20..]) as u64).min(len - offset - EOCD_MIN_SIZE);
+            let mut comment = vec![0u8; comment_len as usize];
+            reader.read_exact(&mut comment)?;
+            let mut eocd = EndOfCentralDirectory {
+                offset,
+                cd_end: offset,
+                entry_count: le_u16(&rec[10..]) as u64,
+                cd_size: le_u32(&rec[12..]) as u64,
+                cd_offset: le_u32(&rec[16..]) as u64,
+                comment,
+                zip64: false,
+            };
+            if eocd.needs_zip64() && read_zip64_record(reader, &mut eocd).is_err() {
+                continue;
+            }
+            if eocd.entry_count == 0 && eocd.cd_size == 0 {
+                empty.get_or_insert(eocd);
+                continue;
+            }
+            let Some(cd_start) = eocd.cd_end.checked_sub(eocd.cd_size) else {
+                continue;
+            };
+            let mut signature = [0u8; 4];
+            reader.seek(SeekFrom::Start(cd_start))?;
+            if reader.read_exact(&mut signature).is_ok()
+                && le_u32(&signature) == CENTRAL_HEADER_SIGNATURE
+            {
+                return Ok(eocd);
+            }
+        }
+        // Overlap by three bytes so a signature split across reads is still seen
+        end = start + 3;
+        if start == 0 {
+            break;
+        }
+    }
+    empty.ok_or_else(|| anyhow::anyhow!("Could not find EOCD (end of central directory) record"))
+}
+
+/// A view of an archive that ends right after the part of its EOCD comment that is
+/// present, with the recorded comment length patched to match, so trailing bytes and
+/// cut-off comments don't stop the `zip` crate from reading it
+pub struct TrimmedArchive<R> {
+    inner: R,
+    len: u64,
+    pos: u64,
+    // Where `inner` is positioned, when known, so sequential reads don't seek
+    inner_pos: Option<u64>,
+    // Offset of the EOCD comment length field and the value to read there
+    patch: Option<(u64, [u8; 2])>,
+    // Central directory and EOCD written by `keep_entries`, and the offset they replace
+    // the original ones from
+    directory: Option<(u64, Vec<u8>)>,
+}
+
+impl<R: Read + Seek> TrimmedArchive<R> {
+    /// The whole of `inner`, unchanged
+    pub fn whole(mut inner: R) -> std::io::Result<Self> {
+        let len = inner.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            inner,
+            len,
+            pos: 0,
+            inner_pos: None,
+            patch: None,
+            directory: None,
+        })
+    }
+
+    /// `inner` up to the end of the archive found by [`find_eocd_tolerant`]
+    pub fn trimmed(mut inner: R) -> Result<Self> {
+        let eocd = find_eocd_tolerant(&mut inner)?;
+        let comment_len = eocd.comment.len() as u64;
+        let len = eocd.offset + EOCD_MIN_SIZE + comment_len;
+        let trailing = inner.seek(SeekFrom::End(0))? - len;
+        if trailing > 0 {
+            tracing::debug!(trailing, "ignoring bytes after the end of the archive");
+        }
+        Ok(Self {
+            inner,
+            len,
+            pos: 0,
+            inner_pos: None,
+            patch: Some((eocd.offset + 20, (comment_len as u16).to_le_bytes())),
+            directory: None,
+        })
+    }
+
+    /// Replace the central directory with the records `keep` accepts, given each
+    /// record's position and contents, so the `zip` crate indexes only those. It keeps
+    /// the last copy of a repeated name; dropping the later copies brings out the first.
+    /// Entry comments are left out of the new directory.
+    pub fn keep_entries(mut self, mut keep: impl FnMut(usize, &RawEntry) -> bool) -> Result<Self> {
+        let mut records = Vec::new();
+        let mut count = 0u64;
+        let mut index = 0;
+        let eocd = for_each_record(&mut self, |entry, header| {
+            if keep(index, &entry) {
+                records.extend_from_slice(&header[..32]);
+                records.extend_from_slice(&0u16.to_le_bytes()); // comment length
+                records.extend_from_slice(&header[34..]);
+                records.extend_from_slice(&entry.name);
+                records.extend_from_slice(&entry.extra);
+                count += 1;
+            }
+            index += 1;
+            Ok(())
+        })?;
+        let start = eocd.prefix_len() + eocd.cd_offset;
+        let cd_size = records.len() as u64;
+        let zip64 = eocd.zip64
+            || count >= u16::MAX as u64
+            || cd_size >= u32::MAX as u64
+            || eocd.cd_offset >= u32::MAX as u64;
+        if zip64 {
+            let record_offset = eocd.cd_offset + cd_size;
+            records.extend_from_slice(&ZIP64_EOCD_SIGNATURE.to_le_bytes());
+            records.extend_from_slice(&44u64.to_le_bytes());
+            records.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            records.extend_from_slice(&count.to_le_bytes());
+            records.extend_from_slice(&count.to_le_bytes());
+            records.extend_from_slice(&cd_size.to_le_bytes());
+            records.extend_from_slice(&eocd.cd_offset.to_le_bytes());
+            records.extend_from_slice(&ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+            records.extend_from_slice(&0u32.to_le_bytes());
+            records.extend_from_slice(&record_offset.to_le_bytes());
+            records.extend_from_slice(&1u32.to_le_bytes());
+        }
+        // With a ZIP64 record the EOCD fields are saturated to point readers at it
+        let (short_count, short_size, short_offset) = match zip64 {
+            true => (u16::MAX, u32::MAX, u32::MAX),
+            false => (count as u16, cd_size as u32, eocd.cd_offset as u32),
+        };
+        records.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+        records.extend_from_slice(&[0u8; 4]); // disk numbers
+        records.extend_from_slice(&short_count.to_le_bytes());
+        records.extend_from_slice(&short_count.to_le_bytes());
+        records.extend_from_slice(&short_size.to_le_bytes());
+        records.extend_from_slice(&short_offset.to_le_bytes());
+        records.extend_from_slice(&(eocd.comment.len() as u16).to_le_bytes());
+        records.extend_from_slice(&eocd.comment);
+
+        self.len = start + records.len() as u64;
+        self.pos = 0;
+        self.patch = None;
+        self.directory = Some((start, records));
+        Ok(self)
+    }
+}
+
+impl<R: Read + Seek> Read for TrimmedArchive<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let inner_len = match &self.directory {
+            Some((start, directory)) if self.pos >= *start => {
+                let at = ((self.pos - start) as usize).min(directory.len());
+                let n = buf.len().min(directory.len() - at);
+                buf[..n].copy_from_slice(&directory[at..at + n]);
+                self.pos += n as u64;
+                return Ok(n);
+            }
+            Some((start, _)) => *start,
+            None => self.len,
+        };
+        let want = buf.len().min(inner_len.saturating_sub(self.pos) as usize);
+        if want == 0 {
+            return Ok(0);
+        }
+        if self.inner_pos.take() != Some(self.pos) {
+            self.inner.seek(SeekFrom::Start(self.pos))?;
+        }
+        let n = self.inner.read(&mut buf[..want])?;
+        self.inner_pos = Some(self.pos + n as u64);
+        if let Some((at, bytes)) = self.patch {
+            for (i, byte) in bytes.iter().enumerate() {
+                let offset = at + i as u64;
+                if (self.pos..self.pos + n as u64).contains(&offset) {
+                    buf[(offset - self.pos) as usize] = *byte;
+                }
+            }
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for TrimmedArchive<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = target.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "seek before start")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// Replace saturated EOCD fields with the values from the ZIP64 record, if one is present
+fn read_zip64_record<R: Read + Seek>(
+    reader: &mut R,
+    eocd: &mut EndOfCentralDirectory,
+) -> Result<()> {
+    if eocd.offset < ZIP64_LOCATOR_SIZE {
+        return Ok(());
+    }
+    let locator_offset = eocd.offset - ZIP64_LOCATOR_SIZE;
+    reader.seek(SeekFrom::Start(locator_offset))?;
+    let mut locator = [0u8; ZIP64_LOCATOR_SIZE as usize];
+    reader.read_exact(&mut locator)?;
+    if le_u32(&locator) != ZIP64_LOCATOR_SIGNATURE {
+        return Ok(());
+    }
+    let recorded = le_u64(&locator[8..]);
+
+    // The record normally sits right before the locator; fall back to the recorded offset
+    let mut record = [0u8; ZIP64_EOCD_MIN_SIZE as usize];
+    let mut found = None;
+    for candidate in [locator_offset.checked_sub(ZIP64_EOCD_MIN_SIZE), Some(recorded)] {
+        let Some(candidate) = candidate else { continue };
+        reader.seek(SeekFrom::Start(candidate))?;
+        if reader.read_exact(&mut record).is_ok() && le_u32(&record) == ZIP64_EOCD_SIGNATURE {
+            found = Some(candidate);
+            break;
+        }
+    }
+    let Some(record_offset) = found else {
+        bail!("ZIP64 end of central directory record not found");
+    };
+    eocd.cd_end = record_offset;
+    eocd.entry_count = le_u64(&record[32..]);
+    eocd.cd_size = le_u64(&record[40..]);
+    eocd.cd_offset = le_u64(&record[48..]);
+    eocd.zip64 = true;
+    Ok(())
+}
+
+/// Read every central directory header, in archive order, including repeated names
+pub fn read_entries<R: Read + Seek>(reader: &mut R) -> Result<Vec<RawEntry>> {
+    let mut entries = Vec::new();
+    for_each_entry(reader, |entry| {
+        entries.push(entry);
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+/// Visit central directory headers one at a time without holding the whole directory in memory
+pub fn for_each_entry<R: Read + Seek>(
+    reader: &mut R,
+    mut visit: impl FnMut(RawEntry) -> Result<()>,
+) -> Result<()> {
+    for_each_record(reader, |entry, _| visit(entry))?;
+    Ok(())
+}
+
+/// `for_each_entry`, also handing over each record's fixed-size header; returns the
+/// EOCD the records were found through
+fn for_each_record<R: Read + Seek>(
+    reader: &mut R,
+    mut visit: impl FnMut(RawEntry, &[u8; 46]) -> Result<()>,
+) -> Result<EndOfCentralDirectory> {
+    let eocd = find_eocd(reader)?;
+    // Any bytes before the recorded central directory offset were prepended to the archive
+    reader.seek(SeekFrom::Start(eocd.prefix_len() + eocd.cd_offset))?;
+
+    let mut pos = 0u64;
+    let mut h = [0u8; 46];
+    while pos + 46 <= eocd.cd_size {
+        reader.read_exact(&mut h)?;
+        if le_u32(&h) != CENTRAL_HEADER_SIGNATURE {
+            bail!("Invalid central directory header at offset {}", pos);
+        }
+        let name_len = le_u16(&h[28..]) as usize;
+        let extra_len = le_u16(&h[30..]) as usize;
+        let comment_len = le_u16(&h[32..]) as usize;
+        let record_len = (46 + name_len + extra_len + comment_len) as u64;
+        if pos + record_len > eocd.cd_size {
+            bail!("Truncated central directory header at offset {}", pos);
+        }
+        let mut name = vec![0u8; name_len];
+        reader.read_exact(&mut name)?;
+        let mut extra = vec![0u8; extra_len];
+        reader.read_exact(&mut extra)?;
+        // Read past the comment rather than seeking, which would throw away a
+        // `BufReader`'s buffer on every record
+        std::io::copy(&mut reader.by_ref().take(comment_len as u64), &mut std::io::sink())?;
+        let mut entry = RawEntry {
+            name,
+            version_made_by: le_u16(&h[4..]),
+            flags: le_u16(&h[8..]),
+            method: le_u16(&h[10..]),
+            modified_time: le_u16(&h[12..]),
+            modified_date: le_u16(&h[14..]),
+            crc32: le_u32(&h[16..]),
+            compressed_size: le_u32(&h[20..]) as u64,
+            uncompressed_size: le_u32(&h[24..]) as u64,
+            external_attributes: le_u32(&h[38..]),
+            local_header_offset: le_u32(&h[42..]) as u64,
+            extra,
+        };
+        apply_zip64_extra(&mut entry);
+        visit(entry, &h)?;
+        pos += record_len;
+    }
+    Ok(eocd)
+}
+
+/// A local file header, with ZIP64 sizes substituted when present. Entries written
+/// with a data descriptor take their CRC and sizes from the descriptor instead.
+#[derive(Debug, Clone)]
+pub struct LocalHeader {
+    pub name: Vec<u8>,
+    pub flags: u16,
+    pub method: u16,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+}
+
+/// Read the local header of `entry`, plus its data descriptor if it has one.
+/// `prefix_len` is the number of bytes prepended before the ZIP data.
+pub fn read_local_header<R: Read + Seek>(
+    reader: &mut R,
+    entry: &RawEntry,
+    prefix_len: u64,
+) -> Result<LocalHeader> {
+    let offset = prefix_len + entry.local_header_offset;
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut h = [0u8; 30];
+    reader.read_exact(&mut h)?;
+    if le_u32(&h) != LOCAL_HEADER_SIGNATURE {
+        bail!("No local header at offset {offset}");
+    }
+    let mut name = vec![0u8; le_u16(&h[26..]) as usize];
+    reader.read_exact(&mut name)?;
+    let mut extra = vec![0u8; le_u16(&h[28..]) as usize];
+    reader.read_exact(&mut extra)?;
+    let zip64 = extra_field(&extra, ZIP64_EXTRA_ID);
+    let mut header = LocalHeader {
+        name,
+        flags: le_u16(&h[6..]),
+        method: le_u16(&h[8..]),
+        crc32: le_u32(&h[14..]),
+        compressed_size: le_u32(&h[18..]) as u64,
+        uncompressed_size: le_u32(&h[22..]) as u64,
+    };
+    // Unlike the central directory, a local ZIP64 field holds both sizes
+    if let Some(data) = zip64.filter(|data| data.len() >= 16) {
+        header.uncompressed_size = le_u64(data);
+        header.compressed_size = le_u64(&data[8..]);
+    }
+    if header.flags & DATA_DESCRIPTOR_FLAG != 0 {
+        // The descriptor follows the data, whose length only the central directory knows
+        reader.seek(SeekFrom::Current(entry.compressed_size as i64))?;
+        let size_len = if zip64.is_some() { 8 } else { 4 };
+        let mut d = [0u8; 4 + 4 + 16];
+        let d = &mut d[..8 + 2 * size_len];
+        reader.read_exact(d)?;
+        // The descriptor signature is optional
+        let d = match le_u32(d) == DATA_DESCRIPTOR_SIGNATURE {
+            true => &d[4..],
+            false => &d[..d.len() - 4],
+        };
+        let read_size = |b: &[u8]| {
+            if size_len == 8 {
+                le_u64(b)
+            } else {
+                le_u32(b) as u64
+            }
+        };
+        header.crc32 = le_u32(d);
+        header.compressed_size = read_size(&d[4..]);
+        header.uncompressed_size = read_size(&d[4 + size_len..]);
+    }
+    Ok(header)
+}
+
+/// Every way in which entries' local headers disagree with their central directory
+/// records, one description per difference
+pub fn local_header_mismatches<R: Read + Seek>(reader: &mut R) -> Result<Vec<String>> {
+    header_mismatches(reader, |_| true)
+}
+
+/// Like `local_header_mismatches`, but only for entries written with a data
+/// descriptor, as streamed archives are, whose CRC and sizes follow their data
+pub fn data_descriptor_mismatches<R: Read + Seek>(reader: &mut R) -> Result<Vec<String>> {
+    header_mismatches(reader, |entry| entry.flags & DATA_DESCRIPTOR_FLAG != 0)
+}
+
+fn header_mismatches<R: Read + Seek>(
+    reader: &mut R,
+    checked: impl Fn(&RawEntry) -> bool,
+) -> Result<Vec<String>> {
+    let prefix_len = find_eocd(reader)?.prefix_len();
+    let mut mismatches = Vec::new();
+    for entry in read_entries(reader)?.into_iter().filter(|e| checked(e)) {
+        let name = entry.name_lossy();
+        let local = match read_local_header(reader, &entry, prefix_len) {
+            Ok(local) => local,
+            Err(err) => {
+                mismatches.push(format!("{name}: {err}"));
+                continue;
+            }
+        };
+        let mut differs = |field: &str, local: String, central: String| {
+            if local != central {
+                mismatches.push(format!(
+                    "{name}: {field} is {local} in the local header but {central} in the \
+                     central directory"
+                ));
+            }
+        };
+        differs(
+            "the name",
+            format!("{:?}", String::from_utf8_lossy(&local.name)),
+            format!("{name:?}"),
+        );
+        differs("the method", local.method.to_string(), entry.method.to_string());
+        differs("the CRC-32", format!("{:08x}", local.crc32), format!("{:08x}", entry.crc32));
+        differs(
+            "the compressed size",
+            local.compressed_size.to_string(),
+            entry.compressed_size.to_string(),
+        );
+        differs(
+            "the size",
+            local.uncompressed_size.to_string(),
+            entry.uncompressed_size.to_string(),
+        );
+    }
+    Ok(mismatches)
+}
+
+/// Fill in sizes and offset that were saturated in favour of the ZIP64 extra field
+fn apply_zip64_extra(entry: &mut RawEntry) {
+    let Some(data) = extra_field(&entry.extra, ZIP64_EXTRA_ID) else {
+        return;
+    };
+    let mut values = data.chunks_exact(8).map(le_u64);
+    // Only saturated fields appear, in this fixed order
+    for field in [
+        &mut entry.uncompressed_size,
+        &mut entry.compressed_size,
+        &mut entry.local_header_offset,
+    ] {
+        if *field == u32::MAX as u64 {
+            match values.next() {
+                Some(value) => *field = value,
+                None => return,
+            }
+        }
+    }
+}
+
+/// Find the data of the extra field with the given header id
+pub fn extra_field(extra: &[u8], id: u16) -> Option<&[u8]> {
+    let mut pos = 0;
+    while pos + 4 <= extra.len() {
+        let len = le_u16(&extra[pos + 2..]) as usize;
+        let data = extra.get(pos + 4..pos + 4 + len)?;
+        if le_u16(&extra[pos..]) == id {
+            return Some(data);
+        }
+        pos += 4 + len;
+    }
+    None
+}
+
+fn le_u16(b: &[u8]) -> u16 {
+    u16::from_le_bytes([b[0], b[1]])
+}
+
+fn le_u32(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+fn le_u64(b: &[u8]) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&b[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn sample_zip() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("a.txt", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"hello").unwrap();
+        zip.start_file("dir/b.txt", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"world").unwrap();
+        zip.set_comment("note");
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_read_entries() -> Result<()> {
+        let bytes = sample_zip();
+        let entries = read_entries(&mut Cursor::new(&bytes))?;
+        let names: Vec<String> = entries.iter().map(|e| e.name_lossy()).collect();
+        assert_eq!(names, vec!["a.txt", "dir/b.txt"]);
+        assert_eq!(entries[0].uncompressed_size, 5);
+
+        let eocd = find_eocd(&mut Cursor::new(&bytes))?;
+        assert_eq!(eocd.entry_count, 2);
+        assert_eq!(eocd.comment, b"note");
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_header_mismatches() -> Result<()> {
+        let mut bytes = sample_zip();
+        assert!(local_header_mismatches(&mut Cursor::new(&bytes))?.is_empty());
+        // Streamed entries keep their CRC and sizes in a data descriptor
+        let mut zip = ZipWriter::new_stream(Vec::new());
+        zip.start_file("streamed.txt", SimpleFileOptions::default())?;
+        zip.write_all(&b"streamed ".repeat(100))?;
+        let streamed = zip.finish()?.into_inner();
+        assert!(local_header_mismatches(&mut Cursor::new(&streamed))?.is_empty());
+
+        // The first local header starts the file; its CRC is at offset 14 and name at 30
+        bytes[14] ^= 0xff;
+        bytes[30] = b'b';
+        let mismatches = local_header_mismatches(&mut Cursor::new(&bytes))?;
+        assert_eq!(mismatches.len(), 2, "{mismatches:?}");
+        assert!(mismatches[0].starts_with("a.txt: the name is \"b.txt\" in the local header"));
+        assert!(mismatches[1].starts_with("a.txt: the CRC-32 is"));
+        Ok(())
+    }
+
+    /// Central directory with `names`, ending in ZIP64 records; the first entry's
+    /// uncompressed size lives in a ZIP64 extra field
+    fn zip64_directory(names: &[&str], big_size: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (i, name) in names.iter().enumerate() {
+            let extra: Vec<u8> = if i == 0 {
+                [&ZIP64_EXTRA_ID.to_le_bytes()[..], &8u16.to_le_bytes(), &big_size.to_le_bytes()]
+                    .concat()
+            } else {
+                Vec::new()
+            };
+            let size = if i == 0 { u32::MAX } else { 1 };
+            bytes.extend_from_slice(&CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+            bytes.extend_from_slice(&[0u8; 16]); // versions, flags, method, time, date, crc
+            bytes.extend_from_slice(&1u32.to_le_bytes()); // compressed size
+            bytes.extend_from_slice(&size.to_le_bytes());
+            bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&[0u8; 10]); // comment len, disk, attributes
+            bytes.extend_from_slice(&0u32.to_le_bytes()); // local header offset
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&extra);
+        }
+        let cd_size = bytes.len() as u64;
+        let record_offset = bytes.len() as u64;
+        let count = names.len() as u64;
+        bytes.extend_from_slice(&ZIP64_EOCD_SIGNATURE.to_le_bytes());
+        bytes.extend_from_slice(&44u64.to_le_bytes());
+        bytes.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes.extend_from_slice(&cd_size.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&record_offset.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 4]);
+        bytes.extend_from_slice(&[0xff; 12]);
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_zip64_records() -> Result<()> {
+        let bytes = zip64_directory(&["big.bin", "small.txt"], 5_000_000_000);
+
+        let eocd = find_eocd(&mut Cursor::new(&bytes))?;
+        assert!(eocd.is_zip64());
+        assert_eq!(eocd.entry_count, 2);
+        assert_eq!(eocd.prefix_len(), 0);
+        let entries = read_entries(&mut Cursor::new(&bytes))?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].uncompressed_size, 5_000_000_000);
+        assert_eq!(entries[1].name_lossy(), "small.txt");
+        Ok(())
+    }
+
+    #[test]
+    fn test_trailing_data_and_cut_comment() -> Result<()> {
+        let bytes = sample_zip();
+        // Junk past the search window, holding an EOCD signature of its own
+        let mut junk = bytes.clone();
+        junk.extend_from_slice(b"PK\x05\x06");
+        junk.extend(std::iter::repeat_n(0u8, 100_000));
+        assert!(find_eocd(&mut Cursor::new(&junk)).is_err());
+        let eocd = find_eocd_tolerant(&mut Cursor::new(&junk))?;
+        assert_eq!((eocd.entry_count, eocd.comment.as_slice()), (2, &b"note"[..]));
+        let mut trimmed = TrimmedArchive::trimmed(Cursor::new(&junk))?;
+        let mut read = Vec::new();
+        trimmed.read_to_end(&mut read)?;
+        assert_eq!(read, bytes);
+
+        // A comment length pointing past the end of the file
+        let mut cut = bytes.clone();
+        let at = cut.len() - 4 - 2;
+        cut[at..at + 2].copy_from_slice(&300u16.to_le_bytes());
+        assert!(zip::ZipArchive::new(Cursor::new(&cut)).is_err());
+        let archive = zip::ZipArchive::new(TrimmedArchive::trimmed(Cursor::new(&cut))?)?;
+        assert_eq!(archive.len(), 2);
+        assert_eq!(archive.comment(), b"note");
+        Ok(())
+    }
+
+    #[test]
+    fn test_keep_entries() -> Result<()> {
+        let bytes = sample_zip();
+        let kept = TrimmedArchive::whole(Cursor::new(&bytes))?.keep_entries(|i, _| i == 1)?;
+        let mut archive = zip::ZipArchive::new(kept)?;
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), ["dir/b.txt"]);
+        assert_eq!(archive.comment(), b"note");
+        let mut contents = String::new();
+        archive.by_index(0)?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "world");
+
+        // Offsets still line up behind a prepended stub
+        let mut stubbed = vec![0u8; 100];
+        stubbed.extend_from_slice(&bytes);
+        let kept = TrimmedArchive::whole(Cursor::new(&stubbed))?
+            .keep_entries(|_, entry| entry.name == b"a.txt")?;
+        let mut archive = zip::ZipArchive::new(kept)?;
+        let mut contents = String::new();
+        archive.by_name("a.txt")?.read_to_string(&mut contents)?;
+        assert_eq!((archive.len(), contents.as_str()), (1, "hello"));
+
+        let bytes = zip64_directory(&["big.bin", "small.txt"], 5_000_000_000);
+        let mut kept = TrimmedArchive::whole(Cursor::new(&bytes))?.keep_entries(|i, _| i == 0)?;
+        let eocd = find_eocd(&mut kept)?;
+        assert!(eocd.is_zip64());
+        let entries = read_entries(&mut kept)?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].uncompressed_size, 5_000_000_000);
+        Ok(())
+    }
+
+    /// Reader that counts the seeks made on it
+    struct CountingSeeks<R> {
+        inner: R,
+        seeks: usize,
+    }
+
+    impl<R: Read> Read for CountingSeeks<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.inner.read(buf)
+        }
+    }
+
+    impl<R: Seek> Seek for CountingSeeks<R> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.seeks += 1;
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_trimmed_reads_seek_only_when_moved() -> Result<()> {
+        let bytes = sample_zip();
+        let inner = CountingSeeks {
+            inner: Cursor::new(&bytes),
+            seeks: 0,
+        };
+        let mut whole = TrimmedArchive::whole(inner)?;
+        let mut byte = [0u8];
+        for _ in 0..10 {
+            whole.read_exact(&mut byte)?;
+        }
+        // One seek to measure the length, one to go back to the start
+        assert_eq!(whole.inner.seeks, 2);
+        whole.seek(SeekFrom::Start(4))?;
+        whole.read_exact(&mut byte)?;
+        assert_eq!(byte[0], bytes[4]);
+        assert_eq!(whole.inner.seeks, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_not_a_zip() {
+        assert!(find_eocd(&mut Cursor::new(vec![0u8; 64])).is_err());
+        assert!(find_eocd(&mut Cursor::new(vec![0u8; 4])).is_err());
+        assert!(find_eocd_tolerant(&mut Cursor::new(vec![0u8; 100_000])).is_err());
+    }
+}
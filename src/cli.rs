@@ -1,10 +1,25 @@
 use crate::archive::ArchiveManager;
-use crate::archive::ArchiveOptions;
+use crate::archive::{
+    ArchiveOptions, ArchiveStats, CompressionPreset, CompressionRule, ConflictPolicy,
+    DuplicatePolicy, EncryptionMethod, EntryInfo, EntrySort, HashAlgorithm, HashEncoding,
+    MethodStats, NameEncoding, NestedLimits, OverwritePolicy, SkippedFile, ValidationReport,
+    method_breakdown, parse_compression_rules, sort_entries,
+};
+use crate::batch;
+use crate::bench;
+use crate::convert;
+use crate::fetch;
+use crate::password;
 use crate::progress;
-use anyhow::Result;
-use clap::{ArgAction, Parser, Subcommand};
+use crate::watch;
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 use serde::Serialize;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
 
 #[derive(Parser)]
 #[command(name = "rolypoly")]
@@ -14,6 +29,10 @@ pub struct Cli {
     /// Emit machine-readable JSON to stdout
     #[arg(long, global = true, action = ArgAction::SetTrue)]
     pub json: bool,
+    /// Like --json, but indent results for reading; event streams (create --verbose,
+    /// watch, progress) stay one object per line
+    #[arg(long, global = true, action = ArgAction::SetTrue)]
+    pub json_pretty: bool,
 
     /// Emit progress updates (JSON if --json, otherwise human)
     #[arg(long, global = true, action = ArgAction::SetTrue)]
@@ -21,12 +40,59 @@ pub struct Cli {
     /// Compression level (0-9). 0 = store, higher = more compression
     #[arg(long, global = true)]
     pub level: Option<i32>,
-    /// Automatically store incompressible files (faster)
-    #[arg(long, global = true, default_value_t = true)]
+    /// Automatically store incompressible files (faster); `--auto-store=false` disables
+    #[arg(
+        long,
+        global = true,
+        default_value_t = true,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        action = ArgAction::Set
+    )]
     pub auto_store: bool,
     /// Entropy threshold (0-8) above which a file is considered incompressible
     #[arg(long, global = true, default_value_t = 7.8)]
     pub store_entropy_threshold: f64,
+    /// Store a file when deflating its first 64 KiB keeps at least this fraction of its size (0-1)
+    #[arg(long, global = true, default_value_t = 0.9)]
+    pub store_ratio_threshold: f64,
+    /// Password for encrypted archives (visible in shell history; prefer --password-file)
+    #[arg(long, global = true, conflicts_with = "password_file")]
+    pub password: Option<String>,
+    /// Read the password from the first line of this file
+    #[arg(long, global = true)]
+    pub password_file: Option<PathBuf>,
+    /// Diagnostic log level or filter for stderr (e.g. debug, rolypoly=trace); defaults to RUST_LOG
+    #[arg(long, global = true)]
+    pub log_level: Option<String>,
+    /// Describe what the command would do without touching the filesystem, then exit
+    #[arg(long, global = true)]
+    pub explain: bool,
+    /// Write JSON progress events to this open file descriptor instead of stdout, which
+    /// keeps only results (Unix; stderr elsewhere)
+    #[arg(long, global = true, value_name = "FD")]
+    pub progress_fd: Option<i32>,
+    /// Emit a JSON progress event only every N entries, plus the final one (default:
+    /// about a hundred events per operation)
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub progress_every: Option<u64>,
+    /// Directory for temporary files such as piped or downloaded archives, instead of
+    /// the system one (also ROLYPOLY_TMPDIR)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub temp_dir: Option<PathBuf>,
+    /// Expect data before the ZIP, such as a self-extractor stub or APK signing block:
+    /// no note about it from list and extract, and no failure from validate --strict
+    #[arg(long, global = true)]
+    pub allow_prepended: bool,
+    /// Read archives with bytes after the end-of-central-directory record, or whose
+    /// recorded comment length runs past the end of the file ("Could not find EOCD")
+    #[arg(long, global = true)]
+    pub ignore_zip_comment_mismatch: bool,
+    /// Stop reading an archive piped to stdin or downloaded from a URL once it grows
+    /// past this size (e.g. 500M); unlimited by default
+    #[arg(long, global = true, alias = "max-download", value_parser = parse_size)]
+    pub max_input: Option<u64>,
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -35,191 +101,1799 @@ pub struct Cli {
 pub enum Commands {
     /// Create a new ZIP archive
     Create {
-        /// Name of the archive to create
+        /// Name of the archive to create. Given alone, a file or directory is archived
+        /// as `<name>.zip` next to it; an existing directory receives an archive named
+        /// after the first input
         archive: PathBuf,
-        /// Files and directories to add to the archive
+        /// Files and directories to add to the archive. Names after `--` are always
+        /// taken as files, even ones that start with `-`
         files: Vec<PathBuf>,
+        /// Replace an existing archive whose name was derived from the inputs
+        #[arg(long)]
+        force: bool,
+        /// Create the directories above the archive if they don't exist
+        #[arg(short = 'p', long)]
+        create_parents: bool,
+        /// Encrypt entries (password from --password-file, ROLYPOLY_PASSWORD, or a prompt)
+        #[arg(long)]
+        encrypt: bool,
+        /// Encryption scheme to use; implies --encrypt (default: aes256)
+        #[arg(long, value_enum)]
+        encrypt_method: Option<EncryptionMethod>,
+        /// Warn about entries whose names differ only by case
+        #[arg(long)]
+        warn_case_collisions: bool,
+        /// Skip hidden files and directories inside directory inputs
+        #[arg(long, overrides_with = "include_hidden")]
+        exclude_hidden: bool,
+        /// Include hidden files and directories (default)
+        #[arg(long, overrides_with = "exclude_hidden")]
+        include_hidden: bool,
+        /// Name file inputs by the path given (e.g. `etc/app/config`) rather than
+        /// their file name alone
+        #[arg(long)]
+        store_full_path: bool,
+        /// Skip paths matching the gitignore-style patterns in this file
+        #[arg(long)]
+        exclude_from: Option<PathBuf>,
+        /// Also skip paths ignored by `.gitignore` files inside directory inputs
+        #[arg(long)]
+        respect_gitignore: bool,
+        /// Choose the method and level per entry from this file: one `<glob> <store|deflate>
+        /// [level]` per line (e.g. `*.txt deflate 9`), first match wins
+        #[arg(long, value_name = "FILE")]
+        rules: Option<PathBuf>,
+        /// Only descend this many levels into directory inputs (1 = their direct
+        /// children)
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+        /// Store empty directories found in directory inputs
+        #[arg(long, default_value_t = true, action = ArgAction::Set)]
+        preserve_empty_dirs: bool,
+        /// Write no directory entries, leaving file paths to imply the tree; empty
+        /// directories are not kept
+        #[arg(long)]
+        no_dir_entries: bool,
+        /// Retry file opens this many times (at most 20) on transient I/O errors
+        #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u32).range(..=20))]
+        retries: u32,
+        /// Leave out files that can't be read and finish the archive, listing them and
+        /// exiting with an error afterwards
+        #[arg(long)]
+        keep_going: bool,
+        /// With --keep-going, exit successfully even when files were left out
+        #[arg(long, requires = "keep_going")]
+        ignore_errors: bool,
+        /// Store each file's owner and group (uid/gid) for `extract --preserve-owner`
+        /// (Unix)
+        #[arg(long)]
+        preserve_owner: bool,
+        /// Store the content of identical files once: later copies get their own entry
+        /// pointing at the first copy's compressed data, with its timestamp and
+        /// permissions. Entries sharing data are non-standard; Info-ZIP unzip rejects
+        /// them as overlapping and streaming readers only see the first copy.
+        #[arg(long)]
+        dedup: bool,
+        /// Archive the files that symlinks named as inputs point to. By default such a
+        /// symlink is stored as a link entry, recreated as a link on Unix extraction;
+        /// symlinks to directories are always walked.
+        #[arg(long)]
+        dereference: bool,
+        /// Only archive files modified after this RFC 3339 time (e.g. 2024-01-31T00:00:00Z)
+        #[arg(long, value_parser = parse_rfc3339)]
+        newer_than: Option<SystemTime>,
+        /// Only archive files modified before this RFC 3339 time
+        #[arg(long, value_parser = parse_rfc3339)]
+        older_than: Option<SystemTime>,
+        /// Stamp every entry with this time instead of each file's own: RFC 3339,
+        /// `@<unix seconds>` or `now`
+        #[arg(long, value_parser = parse_mtime)]
+        mtime: Option<SystemTime>,
+        /// Skip files smaller than this size (e.g. 4K, 10M)
+        #[arg(long, value_parser = parse_size)]
+        min_size: Option<u64>,
+        /// Skip files larger than this size (e.g. 500M, 2G)
+        #[arg(long, value_parser = parse_size)]
+        max_size: Option<u64>,
+        /// Comma-separated extensions to store without compression (default: common media
+        /// and archive formats; pass "" to disable)
+        #[arg(long, value_delimiter = ',')]
+        store_extensions: Option<Vec<String>>,
+        /// Skip reading the holes of sparse files (Linux); holes are archived as zeros
+        #[arg(long)]
+        sparse: bool,
+        /// Compression level by name: fast (1), balanced (6) or max (9); --level
+        /// overrides it
+        #[arg(long, value_enum)]
+        preset: Option<CompressionPreset>,
+        /// Print each added file with its original size, compressed size and ratio
+        #[arg(short, long)]
+        verbose: bool,
+        /// Deflate inputs that are already ZIP, gzip, xz, zstd, bzip2, 7z or RAR data
+        /// instead of storing them
+        #[arg(long)]
+        force_recompress: bool,
+        /// Finish with bytes in and out per compression method, to help tune --rules,
+        /// --level and --store-extensions
+        #[arg(long)]
+        compression_stats: bool,
+        /// Archive comment
+        #[arg(long, conflicts_with = "comment_file")]
+        comment: Option<String>,
+        /// Read the archive comment from this file, e.g. a build manifest
+        #[arg(long)]
+        comment_file: Option<PathBuf>,
+        /// Finish with a SHA256SUMS entry holding the SHA-256 of every file
+        #[arg(long, alias = "checksum-manifest")]
+        embed_checksums: bool,
+        /// Split the archive into volumes of at most this size (e.g. 25M): ARCHIVE.001,
+        /// ARCHIVE.002, ... and ARCHIVE itself, which extract and list read as one
+        #[arg(long, value_parser = parse_size)]
+        split: Option<u64>,
+        /// Mark non-ASCII entry names as UTF-8; `--utf8-names=false` refuses such names
+        /// so the archive stays readable by tools that ignore the flag
+        #[arg(long, default_value_t = true, action = ArgAction::Set)]
+        utf8_names: bool,
     },
     /// Extract a ZIP archive
     Extract {
-        /// Path to the archive to extract
+        /// Path to the archive to extract, or an http(s):// URL (needs the `net` feature)
         archive: PathBuf,
+        /// Only extract these entries; a name ending in `/` selects everything under it
+        entries: Vec<String>,
         /// Directory to extract to (defaults to current directory)
         #[arg(short, long, default_value = ".")]
         output: PathBuf,
+        /// Which copy to use when several entries share a name
+        #[arg(long, value_enum, default_value_t = DuplicatePolicy::KeepLast)]
+        on_duplicate: DuplicatePolicy,
+        /// Refuse to extract entries whose names differ only by case
+        #[arg(long)]
+        check_case_collisions: bool,
+        /// Write every file directly into the output directory, dropping its folders
+        #[arg(long)]
+        flatten: bool,
+        /// What to do when a flattened file name is already taken
+        #[arg(long, value_enum, default_value_t = ConflictPolicy::Error, requires = "flatten")]
+        on_conflict: ConflictPolicy,
+        /// Retry file opens this many times (at most 20) on transient I/O errors
+        #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u32).range(..=20))]
+        retries: u32,
+        /// Set every extracted file to this octal mode, e.g. 644 (Unix only)
+        #[arg(long, value_parser = parse_octal_mode)]
+        chmod_files: Option<u32>,
+        /// Set every extracted directory to this octal mode, e.g. 755 (Unix only)
+        #[arg(long, value_parser = parse_octal_mode)]
+        chmod_dirs: Option<u32>,
+        /// Skip macOS `__MACOSX/` and `._*` metadata entries
+        #[arg(long)]
+        skip_mac_metadata: bool,
+        /// Whether to replace files that already exist in the output directory
+        #[arg(long, value_enum, default_value_t = OverwritePolicy::Always)]
+        overwrite: OverwritePolicy,
+        /// Replace existing files only when the archived copy is newer (same as
+        /// `--overwrite if-newer`)
+        #[arg(long, conflicts_with = "overwrite")]
+        overwrite_if_newer: bool,
+        /// Check extracted files against the archive's SHA256SUMS entry and fail on
+        /// any mismatch (see `create --embed-checksums`)
+        #[arg(long)]
+        verify_checksums: bool,
+        /// Record finished entries in .rolypoly-resume in the output directory, so an
+        /// interrupted extraction can be re-run and skip files already written
+        #[arg(long)]
+        resume: bool,
+        /// Extract into a temporary directory beside the output and move the result
+        /// into place only once every entry has been written, so a failed run leaves
+        /// the output directory as it was
+        #[arg(long, conflicts_with = "resume")]
+        atomic: bool,
+        /// Extract nothing; list the files in the output directory that extracting would
+        /// write over (those whose contents differ from their entry) and whether the
+        /// overwrite policy replaces or keeps each
+        #[arg(long, alias = "list-only-conflicts")]
+        preview_conflicts: bool,
+        /// Give extracted files the owner and group stored by `create --preserve-owner`
+        /// (Unix; needs root, and warns instead when not permitted)
+        #[arg(long)]
+        preserve_owner: bool,
+        /// Code page of entry names stored without the UTF-8 flag, e.g. shift_jis, gbk
+        /// or windows-1251
+        #[arg(long, default_value = "cp437", value_parser = parse_encoding)]
+        encoding: NameEncoding,
     },
     /// List contents of a ZIP archive
     List {
-        /// Path to the archive to list
+        /// Path to the archive to list, or `-` to read it from stdin
         archive: PathBuf,
+        /// Show sizes, method, CRC, modification time and encryption for each entry
+        #[arg(short, long)]
+        long: bool,
+        /// Which copy to use when several entries share a name
+        #[arg(long, value_enum, default_value_t = DuplicatePolicy::KeepLast)]
+        on_duplicate: DuplicatePolicy,
+        /// Hide macOS `__MACOSX/` and `._*` metadata entries
+        #[arg(long)]
+        hide_mac_metadata: bool,
+        /// Order entries by this key instead of archive order
+        #[arg(long, value_enum)]
+        sort: Option<EntrySort>,
+        /// Sort in descending order
+        #[arg(long, requires = "sort")]
+        reverse: bool,
+        /// Append a summary of entry counts and sizes, matching `stats`
+        #[arg(long)]
+        total: bool,
+        /// With --long, show modification times as ISO 8601 instead of relative to now
+        /// (e.g. "3 days ago"); JSON output always uses ISO 8601
+        #[arg(long, requires = "long")]
+        iso: bool,
+        /// Print one line per entry from this template instead of the usual listing, e.g.
+        /// "{size}\t{name}". Fields: {name} {size} {csize} {ratio} {crc} {method} {mtime};
+        /// \t, \n and {{ }} escape. Ignored with --json
+        #[arg(long, value_parser = parse_list_format, conflicts_with = "long")]
+        format: Option<ListFormat>,
+        /// End each entry with a NUL byte instead of a newline and print nothing else,
+        /// like `find -print0`, for piping into `xargs -0`
+        #[arg(short = '0', long, conflicts_with_all = ["long", "total"])]
+        null: bool,
+        /// Code page of entry names stored without the UTF-8 flag, e.g. shift_jis, gbk
+        /// or windows-1251
+        #[arg(long, default_value = "cp437", value_parser = parse_encoding)]
+        encoding: NameEncoding,
     },
     /// Validate the integrity of a ZIP archive
     Validate {
-        /// Path to the archive to validate
+        /// Path to the archive to validate, or `-` to read it from stdin
         archive: PathBuf,
+        /// Also validate `.zip` entries, and archives nested within those
+        #[arg(long)]
+        recursive: bool,
+        /// How many levels of nested archives to open with --recursive
+        #[arg(long, default_value_t = 3, requires = "recursive")]
+        max_nesting: usize,
+        /// Total size of nested archives read into memory with --recursive (e.g. 512M)
+        #[arg(long, default_value = "1G", value_parser = parse_size, requires = "recursive")]
+        max_nested_size: u64,
+        /// Fail on suspicious entry names (control characters, NUL bytes, absolute
+        /// paths, backslashes) instead of warning about them, and compare each local
+        /// header's name, method, CRC and sizes with the central directory
+        #[arg(long)]
+        strict: bool,
     },
     /// Show statistics about a ZIP archive
     Stats {
-        /// Path to the archive to analyze
+        /// Path to the archive to analyze, or `-` to read it from stdin
+        archive: PathBuf,
+        /// Only compute aggregate numbers, streaming the central directory entry by entry
+        #[arg(long)]
+        no_entries: bool,
+    },
+    /// Print an archive's comment
+    Comment {
+        /// Path to the archive, or `-` to read it from stdin
         archive: PathBuf,
     },
+    /// Show a file (selected in its folder) or directory in the system file manager
+    Reveal {
+        /// File or directory to show, e.g. a newly created archive
+        path: PathBuf,
+    },
+    /// Create several archives at once from a JSON spec mapping each archive to its
+    /// inputs, e.g. `{"docs.zip": ["docs"], "logs.zip": ["a.log", "b.log"]}`
+    Batch {
+        /// File holding the spec, or the spec itself when it starts with `{`
+        spec: String,
+        /// Create this many archives at the same time
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+        jobs: u64,
+    },
+    /// Convert an archive to another format, e.g. `convert a.tar.gz a.zip`
+    Convert {
+        /// Archive to read (.zip, .tar, .tar.gz or .tgz)
+        input: PathBuf,
+        /// Archive to write; its extension picks the format
+        output: PathBuf,
+    },
+    /// Append files created or changed in a directory to an archive until interrupted
+    Watch {
+        /// Directory to watch (not recursive)
+        dir: PathBuf,
+        /// Archive to append to; created if missing
+        archive: PathBuf,
+        /// Wait until the directory is quiet for this many milliseconds before appending
+        #[arg(long, default_value_t = 500)]
+        debounce_ms: u64,
+    },
+    /// Time create and extract on a directory of your own files
+    #[command(hide = true)]
+    Bench {
+        /// Directory to archive and extract
+        dir: PathBuf,
+    },
     /// Calculate SHA256 hash of a file
     Hash {
         /// Path to the file to hash
         file: PathBuf,
+        /// Checksum algorithm
+        #[arg(long, value_enum, default_value_t = HashAlgorithm::Sha256)]
+        algo: HashAlgorithm,
+        /// How to render the digest
+        #[arg(long, value_enum, default_value_t = HashEncoding::Hex)]
+        encoding: HashEncoding,
+        /// Also report the file size, time taken and throughput
+        #[arg(long)]
+        stats: bool,
+    },
+    /// Print a checksum line (`<hash>  <name>`, as sha256sum does) for every file in the
+    /// given files and directories
+    Manifest {
+        /// Files and directories to checksum; directories are walked
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Name files relative to this directory (e.g. `src/main.rs`), however the paths
+        /// were given
+        #[arg(long, value_name = "DIR")]
+        relative_to: Option<PathBuf>,
+        /// Checksum algorithm
+        #[arg(long, value_enum, default_value_t = HashAlgorithm::Sha256)]
+        algo: HashAlgorithm,
     },
 }
 
-impl Cli {
-    pub fn run(self) -> Result<()> {
-        // Configure output mode for downstream operations
-        // Default: human progress bars enabled; JSON progress only when both --json and --progress are set.
-        let progress = if self.json { self.progress } else { true };
-        progress::set_output_mode(self.json, progress);
+/// Parse a permission mode such as `644` or `0o755`
+fn parse_octal_mode(value: &str) -> Result<u32, String> {
+    let digits = value.trim_start_matches("0o");
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or_else(|| format!("'{value}' is not an octal mode like 644"))
+}
+
+/// Parse a byte size such as `512`, `4K`, `1.5M` or `2GiB` (binary multiples)
+pub(crate) fn parse_size(value: &str) -> Result<u64, String> {
+    let trimmed = value.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("'{value}' has an unknown size unit")),
+    };
+    let number: f64 = number.parse().map_err(|_| format!("'{value}' is not a size like 10M"))?;
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Parse a code page label such as `cp437` or `shift_jis`
+fn parse_encoding(value: &str) -> Result<NameEncoding, String> {
+    NameEncoding::from_label(value).ok_or_else(|| format!("'{value}' is not a known encoding"))
+}
+
+/// Parse a `list --format` template
+fn parse_list_format(value: &str) -> Result<ListFormat, String> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => literal.push('\t'),
+                Some('n') => literal.push('\n'),
+                Some('\\') => literal.push('\\'),
+                other => {
+                    literal.push('\\');
+                    literal.extend(other);
+                }
+            },
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest.find('}').ok_or("unclosed '{' in format")?;
+                let field = match &rest[..end] {
+                    "name" => ListField::Name,
+                    "size" => ListField::Size,
+                    "csize" => ListField::CompressedSize,
+                    "ratio" => ListField::Ratio,
+                    "crc" => ListField::Crc,
+                    "method" => ListField::Method,
+                    "mtime" => ListField::Modified,
+                    other => return Err(format!("unknown field {{{other}}} in format")),
+                };
+                parts.push(ListPart::Literal(std::mem::take(&mut literal)));
+                parts.push(ListPart::Field(field));
+                chars = rest[end + 1..].chars();
+            }
+            '}' => return Err("unmatched '}' in format; write '}}' for a brace".to_string()),
+            c => literal.push(c),
+        }
+    }
+    parts.push(ListPart::Literal(literal));
+    parts.retain(|part| !matches!(part, ListPart::Literal(text) if text.is_empty()));
+    Ok(ListFormat(parts))
+}
+
+/// Parse an RFC 3339 timestamp such as `2024-01-31T12:00:00Z`
+fn parse_rfc3339(value: &str) -> Result<SystemTime, String> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(SystemTime::from)
+        .map_err(|e| format!("'{value}' is not an RFC 3339 time: {e}"))
+}
+
+/// Entry timestamp for `--mtime`: an RFC 3339 time, `@<unix seconds>` or `now`, within
+/// the 1980-2107 range a ZIP can hold
+fn parse_mtime(value: &str) -> Result<SystemTime, String> {
+    let time = if value.eq_ignore_ascii_case("now") {
+        SystemTime::now()
+    } else if let Some(seconds) = value.strip_prefix('@') {
+        let seconds: u64 =
+            seconds.parse().map_err(|_| format!("'{value}' is not @<unix seconds>"))?;
+        SystemTime::UNIX_EPOCH
+            .checked_add(std::time::Duration::from_secs(seconds))
+            .ok_or_else(|| format!("'{value}' is out of range"))?
+    } else {
+        parse_rfc3339(value)?
+    };
+    let out_of_range = || format!("'{value}' is outside the years 1980-2107 that a ZIP can store");
+    // Screen out far-off times before converting them to local time, which can't
+    // represent them
+    let seconds = time.duration_since(SystemTime::UNIX_EPOCH).map_err(|_| out_of_range())?;
+    if seconds.as_secs() > LATEST_ZIP_TIME {
+        return Err(out_of_range());
+    }
+    match crate::archive::system_to_zip_datetime(time) {
+        Some(_) => Ok(time),
+        None => Err(out_of_range()),
+    }
+}
+
+/// Unix time a day past the end of 2107, beyond any ZIP time in any time zone
+const LATEST_ZIP_TIME: u64 = 4_354_819_200 + 86_400;
 
-        let opts = ArchiveOptions {
+impl Cli {
+    /// Archive options from global flags plus any command-specific settings
+    fn archive_options(&self) -> ArchiveOptions {
+        let mut opts = ArchiveOptions {
             compression_level: self.level,
             auto_store: self.auto_store,
             store_entropy_threshold: self.store_entropy_threshold,
+            store_ratio_threshold: self.store_ratio_threshold,
+            allow_prepended: self.allow_prepended,
+            ignore_comment_mismatch: self.ignore_zip_comment_mismatch,
             ..Default::default()
         };
+        match &self.command {
+            Commands::Create {
+                encrypt_method,
+                warn_case_collisions,
+                exclude_hidden,
+                store_full_path,
+                respect_gitignore,
+                max_depth,
+                preserve_empty_dirs,
+                no_dir_entries,
+                retries,
+                keep_going,
+                preserve_owner,
+                dedup,
+                dereference,
+                newer_than,
+                older_than,
+                mtime,
+                min_size,
+                max_size,
+                store_extensions,
+                sparse,
+                embed_checksums,
+                split,
+                utf8_names,
+                preset,
+                create_parents,
+                force_recompress,
+                ..
+            } => {
+                if let Some(preset) = preset {
+                    opts.compression_level = opts.compression_level.or(Some(preset.level()));
+                }
+                if let Some(method) = encrypt_method {
+                    opts.encryption = *method;
+                }
+                opts.warn_case_collisions = *warn_case_collisions;
+                opts.exclude_hidden = *exclude_hidden;
+                opts.store_full_path = *store_full_path;
+                opts.respect_gitignore = *respect_gitignore;
+                opts.max_depth = *max_depth;
+                opts.preserve_empty_dirs = *preserve_empty_dirs;
+                opts.dir_entries = !*no_dir_entries;
+                opts.retries = *retries;
+                opts.keep_going = *keep_going;
+                opts.dedup = *dedup;
+                opts.dereference = *dereference;
+                opts.preserve_owner = *preserve_owner;
+                opts.newer_than = *newer_than;
+                opts.mtime = *mtime;
+                opts.older_than = *older_than;
+                opts.min_size = *min_size;
+                opts.max_size = *max_size;
+                opts.sparse = *sparse;
+                opts.embed_checksums = *embed_checksums;
+                opts.split_size = *split;
+                opts.utf8_names = *utf8_names;
+                opts.create_parents = *create_parents;
+                opts.force_recompress = *force_recompress;
+                if let Some(extensions) = store_extensions {
+                    opts.store_extensions = extensions
+                        .iter()
+                        .map(|e| e.trim().trim_start_matches('.').to_string())
+                        .filter(|e| !e.is_empty())
+                        .collect();
+                }
+            }
+            Commands::Extract {
+                on_duplicate,
+                check_case_collisions,
+                flatten,
+                on_conflict,
+                retries,
+                chmod_files,
+                chmod_dirs,
+                skip_mac_metadata,
+                overwrite,
+                overwrite_if_newer,
+                entries,
+                verify_checksums,
+                resume,
+                atomic,
+                preserve_owner,
+                encoding,
+                ..
+            } => {
+                opts.entries = entries.clone();
+                opts.name_encoding = *encoding;
+                opts.verify_checksums = *verify_checksums;
+                opts.resume = *resume;
+                opts.atomic = *atomic;
+                opts.preserve_owner = *preserve_owner;
+                opts.on_duplicate = *on_duplicate;
+                opts.check_case_collisions = *check_case_collisions;
+                opts.flatten = *flatten;
+                opts.on_conflict = *on_conflict;
+                opts.retries = *retries;
+                opts.file_mode = *chmod_files;
+                opts.dir_mode = *chmod_dirs;
+                opts.skip_mac_metadata = *skip_mac_metadata;
+                opts.overwrite = if *overwrite_if_newer {
+                    OverwritePolicy::IfNewer
+                } else {
+                    *overwrite
+                };
+            }
+            Commands::List {
+                on_duplicate,
+                hide_mac_metadata,
+                encoding,
+                ..
+            } => {
+                opts.on_duplicate = *on_duplicate;
+                opts.name_encoding = *encoding;
+                opts.skip_mac_metadata = *hide_mac_metadata;
+            }
+            Commands::Validate { strict, .. } => opts.strict = *strict,
+            _ => {}
+        }
+        opts
+    }
+
+    /// Resolve the password for commands that need one.
+    ///
+    /// Sources in order: `--password-file`/`--password`, `ROLYPOLY_PASSWORD`, then an
+    /// interactive prompt when stdin is a terminal, which `create` asks twice. Only
+    /// `create --encrypt` (or an explicit password flag) encrypts; extraction asks only
+    /// for encrypted archives.
+    /// Validation checks encrypted entries' metadata when no password is supplied.
+    fn resolve_password(&self, download: Option<&fetch::FetchedArchive>) -> Result<Option<String>> {
+        let explicit = self.password.is_some() || self.password_file.is_some();
+        let needed = match &self.command {
+            Commands::Create {
+                encrypt,
+                encrypt_method,
+                ..
+            } => *encrypt || encrypt_method.is_some() || explicit,
+            Commands::Extract { archive, .. } => {
+                let local = download.map_or(archive.as_path(), |d| d.path());
+                explicit || ArchiveManager::new().has_encrypted_entries(local).unwrap_or(false)
+            }
+            Commands::Validate { .. } => {
+                explicit || std::env::var_os(password::PASSWORD_ENV).is_some()
+            }
+            // Both write entries the way create does, so a password encrypts them
+            Commands::Batch { .. } | Commands::Watch { .. } => explicit,
+            Commands::Convert { .. } if explicit => anyhow::bail!(
+                "convert can't read or write encrypted archives; drop --password and --password-file"
+            ),
+            _ => false,
+        };
+        if !needed {
+            return Ok(None);
+        }
+        if let Some(password) =
+            password::resolve_password(self.password.as_deref(), self.password_file.as_deref())?
+        {
+            return Ok(Some(password));
+        }
+        if matches!(self.command, Commands::Validate { .. }) {
+            return Ok(None);
+        }
+        let prompted = if matches!(self.command, Commands::Create { .. }) {
+            password::prompt_new_password(&mut password::TerminalPrompt)?
+        } else {
+            password::prompt_password("Password: ")?
+        };
+        match prompted {
+            Some(password) => Ok(Some(password)),
+            None if matches!(self.command, Commands::Create { .. }) => Err(anyhow::anyhow!(
+                "Encryption requires a password: use --password-file, {} or an interactive terminal",
+                password::PASSWORD_ENV
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Comment for `create`, from `--comment` or the contents of `--comment-file`
+    fn archive_comment(&self) -> Result<Option<String>> {
+        let Commands::Create {
+            comment,
+            comment_file,
+            ..
+        } = &self.command
+        else {
+            return Ok(None);
+        };
+        match comment_file {
+            Some(path) => {
+                let text = std::fs::read_to_string(path).map_err(|e| {
+                    anyhow::anyhow!("Failed to read comment file {}: {e}", path.display())
+                })?;
+                Ok(Some(text))
+            }
+            None => Ok(comment.clone()),
+        }
+    }
+
+    /// For `list`, `validate`, `stats` and `comment` given `-`, copy stdin to a temporary archive
+    /// and point the command at it. The whole archive lands on disk first, since
+    /// reading a ZIP needs to seek.
+    fn archive_from_stdin(&mut self) -> Result<Option<fetch::FetchedArchive>> {
+        let (Commands::List { archive, .. }
+        | Commands::Validate { archive, .. }
+        | Commands::Stats { archive, .. }
+        | Commands::Comment { archive }) = &mut self.command
+        else {
+            return Ok(None);
+        };
+        if !fetch::is_stdin(archive) {
+            return Ok(None);
+        }
+        let fetched =
+            fetch::read_stdin(self.max_input).context("Failed to read the archive from stdin")?;
+        *archive = fetched.path().to_path_buf();
+        Ok(Some(fetched))
+    }
+
+    /// Download an `extract` archive given as a URL, before the password is resolved
+    /// so that its entries can be checked for encryption. The command keeps showing
+    /// the URL; the download is removed again when dropped.
+    fn download_archive(&self) -> Result<Option<fetch::FetchedArchive>> {
+        let Commands::Extract { archive, .. } = &self.command else {
+            return Ok(None);
+        };
+        match archive.to_str() {
+            Some(url) if fetch::is_url(archive) => Ok(Some(fetch::download(
+                url,
+                self.max_input,
+                progress::default_reporter().as_mut(),
+            )?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Patterns from `create --exclude-from`, skipping blank lines and `#` comments
+    fn exclude_patterns(&self) -> Result<Vec<String>> {
+        let Commands::Create {
+            exclude_from: Some(path),
+            ..
+        } = &self.command
+        else {
+            return Ok(Vec::new());
+        };
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read exclude file {}: {e}", path.display()))?;
+        Ok(text
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Compression rules from `create --rules`
+    fn compression_rules(&self) -> Result<Vec<CompressionRule>> {
+        let Commands::Create {
+            rules: Some(path), ..
+        } = &self.command
+        else {
+            return Ok(Vec::new());
+        };
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read rules file {}: {e}", path.display()))?;
+        parse_compression_rules(&text).with_context(|| format!("In {}", path.display()))
+    }
+
+    /// Plain-English description of the planned operation, built from arguments alone
+    fn explain_plan(&self) -> String {
+        let opts = self.archive_options();
+        let show = |p: &PathBuf| {
+            std::path::absolute(p).unwrap_or_else(|_| p.clone()).display().to_string()
+        };
+        let mut plan = Vec::new();
+        match &self.command {
+            Commands::Create {
+                archive,
+                files,
+                encrypt,
+                force,
+                rules,
+                ..
+            } => {
+                let (archive, files) = resolve_create_paths(archive, files, *force)
+                    .unwrap_or_else(|_| (archive.clone(), files.clone()));
+                let (archive, files) = (&archive, &files);
+                plan.push(format!("Create the ZIP archive {}", show(archive)));
+                if let Some(parent) = archive
+                    .parent()
+                    .filter(|p| opts.create_parents && !p.as_os_str().is_empty() && !p.exists())
+                {
+                    plan.push(format!("after creating the directory {}", show(&parent.into())));
+                }
+                plan.push("from these inputs (directories are added recursively):".to_string());
+                plan.extend(files.iter().map(|f| format!("  {}", show(f))));
+                if let Some(depth) = opts.max_depth {
+                    plan.push(format!("Directories are walked at most {depth} level(s) deep"));
+                }
+                let level = match opts.compression_level {
+                    Some(0) => "store without compression".to_string(),
+                    Some(level) => format!("deflate at level {level}"),
+                    None => "deflate at the default level".to_string(),
+                };
+                plan.push(format!("Compression: {level}"));
+                if let Some(rules) = rules {
+                    plan.push(format!(
+                        "Entries matching the rules in {} use their method and level",
+                        show(rules)
+                    ));
+                }
+                if !opts.force_recompress {
+                    plan.push(
+                        "Files that are already ZIP, gzip or other compressed archives are stored"
+                            .to_string(),
+                    );
+                }
+                if opts.auto_store {
+                    plan.push(format!(
+                        "Files are stored uncompressed when their first 64 KiB has entropy of at \
+                         least {} bits/byte or deflates to {:.0}% or more of its size",
+                        opts.store_entropy_threshold,
+                        opts.store_ratio_threshold * 100.0
+                    ));
+                }
+                if *encrypt || self.password.is_some() || self.password_file.is_some() {
+                    plan.push(format!("Entries are encrypted with {}", opts.encryption.as_str()));
+                }
+                if opts.embed_checksums {
+                    plan.push(format!(
+                        "A {} entry lists the SHA-256 of every file",
+                        crate::archive::CHECKSUMS_ENTRY
+                    ));
+                }
+                if let Some(mtime) = opts.mtime {
+                    let stamp = chrono::DateTime::<chrono::Local>::from(mtime);
+                    plan.push(format!(
+                        "Every entry is stamped {}",
+                        stamp.format("%Y-%m-%d %H:%M:%S (local time)")
+                    ));
+                }
+                if opts.preserve_owner {
+                    plan.push("Each entry records its owner and group".to_string());
+                }
+                if opts.dedup {
+                    plan.push(
+                        "Files with content already archived share the first copy's stored data"
+                            .to_string(),
+                    );
+                }
+                if opts.dereference {
+                    plan.push(
+                        "Symlinked inputs are archived as the files they point to".to_string(),
+                    );
+                }
+                if let Some(size) = opts.split_size {
+                    plan.push(format!("Split into volumes of at most {}", format_bytes(size)));
+                }
+            }
+            Commands::Extract {
+                archive,
+                output,
+                preview_conflicts,
+                ..
+            } => {
+                if opts.entries.is_empty() {
+                    plan.push(format!("Extract every entry of {}", show(archive)));
+                } else {
+                    plan.push(format!("Extract these entries of {}:", show(archive)));
+                    plan.extend(opts.entries.iter().map(|e| format!("  {e}")));
+                }
+                plan.push(format!("into {}", show(output)));
+                if opts.flatten {
+                    plan.push("dropping folder structure (--flatten)".to_string());
+                }
+                if opts.resume {
+                    plan.push(format!(
+                        "skipping entries an earlier run recorded in {}",
+                        crate::archive::RESUME_STATE_FILE
+                    ));
+                }
+                if opts.atomic {
+                    plan.push(
+                        "via a temporary directory, moved into place once complete".to_string(),
+                    );
+                }
+                if opts.preserve_owner {
+                    plan.push("restoring the owner and group stored for each entry".to_string());
+                }
+                if opts.verify_checksums {
+                    plan.push(format!(
+                        "then check each file against the archive's {}",
+                        crate::archive::CHECKSUMS_ENTRY
+                    ));
+                }
+                if *preview_conflicts {
+                    plan.push(
+                        "only listing files that would be written over; nothing is extracted"
+                            .to_string(),
+                    );
+                }
+                match opts.overwrite {
+                    OverwritePolicy::Always => {}
+                    OverwritePolicy::Never => {
+                        plan.push("keeping files that already exist".to_string())
+                    }
+                    OverwritePolicy::IfNewer => plan.push(
+                        "replacing existing files only when the archived copy is newer".to_string(),
+                    ),
+                }
+            }
+            Commands::List {
+                archive,
+                format,
+                null,
+                ..
+            } => {
+                plan.push(format!("List the entries of {}", show(archive)));
+                if format.is_some() {
+                    plan.push(
+                        "One line per entry, filled in from the --format template".to_string(),
+                    );
+                }
+                if *null {
+                    plan.push("Each entry ends with a NUL byte instead of a newline".to_string());
+                }
+            }
+            Commands::Validate {
+                archive,
+                recursive,
+                max_nesting,
+                ..
+            } => {
+                plan.push(format!("Check the CRC of every entry in {}", show(archive)));
+                if *recursive {
+                    plan.push(format!(
+                        "Also validate nested .zip entries, up to {max_nesting} levels deep"
+                    ));
+                }
+                if opts.strict {
+                    plan.push("Fail if any entry name looks malformed".to_string());
+                    plan.push(
+                        "Fail if any local header disagrees with the central directory".to_string(),
+                    );
+                    if !opts.allow_prepended {
+                        plan.push("Fail if any data precedes the ZIP".to_string());
+                    }
+                }
+            }
+            Commands::Stats { archive, .. } => {
+                plan.push(format!("Summarize entry counts and sizes of {}", show(archive)));
+            }
+            Commands::Comment { archive } => {
+                plan.push(format!("Print the comment of {}", show(archive)));
+            }
+            Commands::Reveal { path } => {
+                plan.push(format!("Open the system file manager at {}", show(path)));
+            }
+            Commands::Batch { spec, jobs } => {
+                match batch::parse_spec(spec) {
+                    Ok(batch_jobs) => {
+                        for job in batch_jobs {
+                            let inputs: Vec<String> = job.inputs.iter().map(&show).collect();
+                            plan.push(format!(
+                                "Create {} from {}",
+                                show(&job.archive),
+                                inputs.join(", ")
+                            ));
+                        }
+                    }
+                    Err(err) => plan.push(format!("Fail to read the batch spec: {err:#}")),
+                }
+                if *jobs > 1 {
+                    plan.push(format!("Create up to {jobs} archives at the same time"));
+                }
+            }
+            Commands::Convert { input, output } => {
+                plan.push(format!("Copy every entry of {}", show(input)));
+                plan.push(format!("into the new archive {}", show(output)));
+            }
+            Commands::Watch { dir, archive, .. } => {
+                plan.push(format!("Watch {} for new and changed files", show(dir)));
+                plan.push(format!("and append them to {} until interrupted", show(archive)));
+            }
+            Commands::Bench { dir } => {
+                plan.push(format!(
+                    "Time archiving {} and extracting it again in a temporary directory",
+                    show(dir)
+                ));
+            }
+            Commands::Hash {
+                file, algo, stats, ..
+            } => {
+                let name = match algo {
+                    HashAlgorithm::Sha256 => "SHA-256 hash",
+                    HashAlgorithm::Crc32 => "CRC-32 checksum",
+                };
+                plan.push(format!("Compute the {name} of {}", show(file)));
+                if *stats {
+                    plan.push("Report its size and how fast it was read".to_string());
+                }
+            }
+            Commands::Manifest {
+                paths,
+                relative_to,
+                algo,
+            } => {
+                plan.push(format!(
+                    "Print the {} checksum of every file in:",
+                    algo.as_str().to_uppercase()
+                ));
+                plan.extend(paths.iter().map(|p| format!("  {}", show(p))));
+                if let Some(root) = relative_to {
+                    plan.push(format!("naming each file relative to {}", show(root)));
+                }
+            }
+        }
+        plan.push("Nothing was read or written (--explain).".to_string());
+        plan.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    pub fn run(self) -> Result<()> {
+        self.run_with_output(&mut std::io::stdout())
+    }
+
+    /// Run the command, writing its human or JSON output to `out`
+    pub fn run_with_output(mut self, out: &mut dyn Write) -> Result<()> {
+        self.json |= self.json_pretty;
+        // Configure output mode for downstream operations
+        // Default: human progress bars enabled; JSON progress only when both --json and --progress are set.
+        let progress = if self.json { self.progress } else { true };
+        if let Some(fd) = self.progress_fd {
+            progress::set_progress_fd(fd)
+                .with_context(|| format!("--progress-fd {fd} is not an open file descriptor"))?;
+        }
+        progress::set_output_mode(self.json || self.progress_fd.is_some(), progress);
+        if let Some(every) = self.progress_every {
+            progress::set_progress_every(every);
+        }
+        if let Some(dir) = &self.temp_dir {
+            crate::temp::set_temp_dir(dir)?;
+        } else if let Some(dir) = std::env::var_os(crate::temp::TEMP_DIR_ENV) {
+            // A stale variable shouldn't break commands that never need temporary files
+            if let Err(err) = crate::temp::set_temp_dir(Path::new(&dir)) {
+                eprintln!("warning: ignoring {}: {err:#}", crate::temp::TEMP_DIR_ENV);
+            }
+        }
+
+        if self.explain {
+            write!(out, "{}", self.explain_plan())?;
+            return Ok(());
+        }
+
+        // Kept until the command finishes, so the temporary copy outlives its use
+        let _stdin_archive = self.archive_from_stdin()?;
+        let download = self.download_archive()?;
+        let mut opts = self.archive_options();
+        opts.password = self.resolve_password(download.as_ref())?;
+        opts.comment = self.archive_comment()?;
+        opts.exclude_patterns = self.exclude_patterns()?;
+        opts.compression_rules = self.compression_rules()?;
         let manager = ArchiveManager::with_options(opts);
+        let pretty = self.json_pretty;
+        if let Commands::List { archive, .. } | Commands::Extract { archive, .. } = &self.command
+            && !self.allow_prepended
+            && !self.json
+            && !fetch::is_url(archive)
+            && let Ok(prepended @ 1..) = manager.prepended_bytes(archive)
+        {
+            // Read errors are left for the command itself to report
+            eprintln!(
+                "note: {} starts with {prepended} bytes before the ZIP data (a self-extractor \
+                 stub or signing block?); reading the archive that follows",
+                archive.display()
+            );
+        }
+
+        if let Commands::Create {
+            encrypt_method: Some(EncryptionMethod::ZipCrypto),
+            ..
+        } = &self.command
+        {
+            eprintln!(
+                "warning: ZipCrypto encryption is weak and can be broken in minutes; use it \
+                 only for tools that can't open AES-encrypted entries"
+            );
+        }
 
         match self.command {
-            Commands::Create { archive, files } => {
-                if files.is_empty() {
-                    return Err(anyhow::anyhow!("No files specified to add to archive"));
-                }
+            Commands::Create {
+                archive,
+                files,
+                verbose,
+                force,
+                ignore_errors,
+                dedup,
+                preset,
+                compression_stats,
+                ..
+            } => {
+                let (archive, files) = resolve_create_paths(&archive, &files, force)?;
                 let file_refs: Vec<&PathBuf> = files.iter().collect();
-                manager.create_archive(&archive, &file_refs)?;
+                let start = std::time::Instant::now();
+                let mut reporter = crate::progress::default_reporter();
+                let summary =
+                    manager.create_archive_with_summary(&archive, &file_refs, reporter.as_mut())?;
+                let elapsed_ms = start.elapsed().as_millis() as u64;
+                if verbose {
+                    let level = self.level.or(preset.map(CompressionPreset::level));
+                    let preset = preset
+                        .and_then(|p| p.to_possible_value())
+                        .map(|v| v.get_name().to_string());
+                    if self.json {
+                        let event = serde_json::json!({
+                            "event": "settings",
+                            "method": if level == Some(0) { "stored" } else { "deflated" },
+                            "level": level,
+                            "preset": preset
+                        });
+                        writeln!(out, "{event}")?;
+                    } else {
+                        let mut settings = match level {
+                            Some(0) => "stored".to_string(),
+                            Some(level) => format!("deflate level {level}"),
+                            None => "deflate default level".to_string(),
+                        };
+                        if let Some(preset) = preset {
+                            settings.push_str(&format!(" (preset {preset})"));
+                        }
+                        writeln!(out, "  compression: {settings}")?;
+                    }
+                    let probe_ms = summary.probe_time.as_millis() as u64;
+                    if self.json {
+                        let event = serde_json::json!({
+                            "event": "auto_store",
+                            "stored_by_extension": summary.stored_by_extension,
+                            "stored_by_probe": summary.stored_by_probe,
+                            "stored_archives": summary.stored_archives,
+                            "stored_bytes": summary.stored_bytes,
+                            "probe_ms": probe_ms
+                        });
+                        writeln!(out, "{event}")?;
+                    } else {
+                        writeln!(
+                            out,
+                            "  stored uncompressed: {} by extension, {} already compressed, {} by \
+                             content probe ({}); probing took {probe_ms} ms",
+                            summary.stored_by_extension,
+                            summary.stored_archives,
+                            summary.stored_by_probe,
+                            format_bytes(summary.stored_bytes)
+                        )?;
+                    }
+                    // Sizes come from the finished central directory, so they are exact
+                    for entry in manager.list_archive_detailed(&archive)? {
+                        if entry.is_dir {
+                            continue;
+                        }
+                        let ratio = entry.ratio() * 100.0;
+                        if self.json {
+                            writeln!(
+                                out,
+                                "{}",
+                                serde_json::json!({
+                                    "event": "added",
+                                    "name": entry.name,
+                                    "size": entry.size,
+                                    "compressed_size": entry.compressed_size,
+                                    "ratio": ratio
+                                })
+                            )?;
+                        } else {
+                            writeln!(
+                                out,
+                                "  added: {}  {} -> {} ({ratio:.1}%)",
+                                entry.name,
+                                format_bytes(entry.size),
+                                format_bytes(entry.compressed_size)
+                            )?;
+                        }
+                    }
+                }
+                let breakdown = match compression_stats {
+                    true => Some(method_breakdown(&manager.list_archive_detailed(&archive)?)),
+                    false => None,
+                };
                 if self.json {
                     #[derive(Serialize)]
                     struct Out<'a> {
                         event: &'a str,
                         archive: String,
+                        entries: usize,
+                        files: usize,
+                        uncompressed_bytes: u64,
+                        compressed_bytes: u64,
+                        compression_ratio: f64,
+                        elapsed_ms: u64,
+                        #[serde(skip_serializing_if = "<[_]>::is_empty")]
+                        skipped: &'a [SkippedFile],
+                        #[serde(skip_serializing_if = "Option::is_none")]
+                        duplicates: Option<Duplicates>,
+                        #[serde(skip_serializing_if = "Option::is_none")]
+                        compression_stats: Option<Vec<MethodStats>>,
+                    }
+                    #[derive(Serialize)]
+                    struct Duplicates {
+                        files: usize,
+                        bytes: u64,
                     }
-                    println!(
+                    let stats = manager.get_archive_stats(&archive)?;
+                    writeln!(
+                        out,
                         "{}",
-                        serde_json::to_string(&Out {
-                            event: "created",
-                            archive: archive.display().to_string()
-                        })?
-                    );
+                        json_string(
+                            pretty,
+                            &Out {
+                                event: "created",
+                                archive: archive.display().to_string(),
+                                entries: stats.file_count + stats.dir_count,
+                                files: stats.file_count,
+                                uncompressed_bytes: stats.total_uncompressed_size,
+                                compressed_bytes: stats.total_compressed_size,
+                                compression_ratio: stats.compression_ratio,
+                                elapsed_ms,
+                                skipped: &summary.skipped,
+                                duplicates: dedup.then_some(Duplicates {
+                                    files: summary.duplicate_files,
+                                    bytes: summary.duplicate_bytes
+                                }),
+                                compression_stats: breakdown
+                            }
+                        )?
+                    )?;
+                } else {
+                    if dedup {
+                        writeln!(
+                            out,
+                            "Deduplicated {} files ({})",
+                            summary.duplicate_files,
+                            format_bytes(summary.duplicate_bytes)
+                        )?;
+                    }
+                    if summary.stored_archives > 0 {
+                        writeln!(
+                            out,
+                            "Stored {} files uncompressed that already hold ZIP, gzip or other \
+                             compressed data (--force-recompress to deflate them)",
+                            summary.stored_archives
+                        )?;
+                    }
+                    if let Some(breakdown) = &breakdown {
+                        writeln!(out, "Compression by method:")?;
+                        for method in breakdown {
+                            writeln!(
+                                out,
+                                "  {}: {} files, {} -> {} ({:.1}%)",
+                                method.method,
+                                method.files,
+                                format_bytes(method.uncompressed_bytes),
+                                format_bytes(method.compressed_bytes),
+                                method.ratio * 100.0
+                            )?;
+                        }
+                    }
+                    if !summary.skipped.is_empty() {
+                        writeln!(out, "Skipped {} unreadable files:", summary.skipped.len())?;
+                        for skipped in &summary.skipped {
+                            writeln!(out, "  {}: {}", skipped.path.display(), skipped.error)?;
+                        }
+                    }
                 }
                 // Otherwise progress and completion messages are handled by the archiver
+                if !summary.skipped.is_empty() && !ignore_errors {
+                    anyhow::bail!(
+                        "{} files could not be read and were left out of {}",
+                        summary.skipped.len(),
+                        archive.display()
+                    );
+                }
             }
-            Commands::Extract { archive, output } => {
-                manager.extract_archive(&archive, &output)?;
+            Commands::Extract {
+                archive,
+                output,
+                entries,
+                preview_conflicts,
+                ..
+            } => {
+                let local = download.as_ref().map_or(archive.as_path(), |d| d.path());
+                if preview_conflicts {
+                    let conflicts = manager.preview_conflicts(local, &output)?;
+                    if self.json {
+                        let value = serde_json::json!({
+                            "archive": archive.display().to_string(),
+                            "output": output.display().to_string(),
+                            "conflicts": conflicts
+                        });
+                        writeln!(out, "{}", json_string(pretty, &value)?)?;
+                    } else if conflicts.is_empty() {
+                        writeln!(out, "No existing files would be written over")?;
+                    } else {
+                        writeln!(
+                            out,
+                            "{} existing files differ from the archive:",
+                            conflicts.len()
+                        )?;
+                        for conflict in &conflicts {
+                            let action = if conflict.overwrite {
+                                "overwrite"
+                            } else {
+                                "keep"
+                            };
+                            writeln!(out, "  {action:<9}  {}", conflict.path.display())?;
+                        }
+                    }
+                    return Ok(());
+                }
+                let start = std::time::Instant::now();
+                let mut reporter = crate::progress::default_reporter();
+                let summary = if entries.is_empty() {
+                    manager.extract_archive_with_summary(local, &output, reporter.as_mut())?
+                } else {
+                    let names: Vec<&str> = entries.iter().map(String::as_str).collect();
+                    manager.extract_selected_with_summary(
+                        local,
+                        &output,
+                        &names,
+                        reporter.as_mut(),
+                    )?
+                };
+                let elapsed_ms = start.elapsed().as_millis() as u64;
                 if self.json {
                     #[derive(Serialize)]
                     struct Out<'a> {
                         event: &'a str,
                         archive: String,
                         output: String,
+                        files: usize,
+                        dirs: usize,
+                        skipped: usize,
+                        bytes_written: u64,
+                        elapsed_ms: u64,
                     }
-                    println!(
+                    writeln!(
+                        out,
                         "{}",
-                        serde_json::to_string(&Out {
-                            event: "extracted",
-                            archive: archive.display().to_string(),
-                            output: output.display().to_string()
-                        })?
+                        json_string(
+                            pretty,
+                            &Out {
+                                event: "extracted",
+                                archive: archive.display().to_string(),
+                                output: output.display().to_string(),
+                                files: summary.extracted,
+                                dirs: summary.dirs,
+                                skipped: summary.skipped,
+                                bytes_written: summary.total_bytes,
+                                elapsed_ms
+                            }
+                        )?
+                    )?;
+                } else {
+                    let mut line = format!(
+                        "Extracted {} files and {} directories ({})",
+                        summary.extracted,
+                        summary.dirs,
+                        format_bytes(summary.total_bytes)
                     );
+                    if summary.skipped > 0 {
+                        line.push_str(&format!(", skipped {}", summary.skipped));
+                    }
+                    writeln!(out, "{line}")?;
+                }
+            }
+            Commands::List {
+                archive,
+                format: Some(format),
+                sort,
+                reverse,
+                total,
+                null,
+                ..
+            } if !self.json => {
+                let mut entries = manager.list_archive_detailed(&archive)?;
+                if let Some(key) = sort {
+                    sort_entries(&mut entries, key, reverse);
+                }
+                let end = if null { '\0' } else { '\n' };
+                for entry in &entries {
+                    write!(out, "{}{end}", format.render(entry))?;
+                }
+                if total {
+                    writeln!(out, "{}", format_summary(&ArchiveStats::from_entries(&entries)))?;
+                }
+            }
+            Commands::List {
+                archive,
+                long: true,
+                sort,
+                reverse,
+                total,
+                iso,
+                ..
+            } => {
+                let mut entries = manager.list_archive_detailed(&archive)?;
+                if let Some(key) = sort {
+                    sort_entries(&mut entries, key, reverse);
+                }
+                let summary = total.then(|| ArchiveStats::from_entries(&entries));
+                if self.json {
+                    let mut value = serde_json::json!({
+                        "archive": archive.display().to_string(),
+                        "entries": entries
+                    });
+                    if let Some(summary) = &summary {
+                        value["summary"] = serde_json::to_value(summary)?;
+                    }
+                    writeln!(out, "{}", json_string(pretty, &value)?)?;
+                } else {
+                    writeln!(out, "Archive: {}", archive.display())?;
+                    if entries.is_empty() {
+                        writeln!(out, "Archive is empty")?;
+                    } else {
+                        writeln!(
+                            out,
+                            "  {:>12}  {:>12}  {:<9} {:<8}  {:<19}  Name",
+                            "Size", "Compressed", "Method", "CRC32", "Modified"
+                        )?;
+                        let now = chrono::Local::now().naive_local();
+                        for entry in entries {
+                            let modified = match (&entry.modified, iso) {
+                                (Some(modified), true) => modified.clone(),
+                                (Some(modified), false) => format_relative_time(modified, now),
+                                (None, _) => "-".to_string(),
+                            };
+                            let encryption = entry
+                                .encryption
+                                .map(|m| format!("  [{}]", m.as_str()))
+                                .unwrap_or_default();
+                            writeln!(
+                                out,
+                                "  {:>12}  {:>12}  {:<9} {:08x}  {:<19}  {}{}",
+                                entry.size,
+                                entry.compressed_size,
+                                entry.method,
+                                entry.crc32,
+                                modified,
+                                entry.name,
+                                encryption
+                            )?;
+                        }
+                    }
+                    if let Some(summary) = &summary {
+                        writeln!(out, "{}", format_summary(summary))?;
+                    }
                 }
-                // Otherwise progress and completion messages are handled by the archiver
             }
-            Commands::List { archive } => {
-                let contents = manager.list_archive(&archive)?;
+            Commands::List {
+                archive,
+                sort,
+                reverse,
+                total,
+                null,
+                ..
+            } => {
+                let mut summary = None;
+                let contents = if sort.is_some() || total {
+                    let mut entries = manager.list_archive_detailed(&archive)?;
+                    if let Some(key) = sort {
+                        sort_entries(&mut entries, key, reverse);
+                    }
+                    summary = total.then(|| ArchiveStats::from_entries(&entries));
+                    entries.into_iter().map(|entry| entry.name).collect()
+                } else {
+                    manager.list_archive(&archive)?
+                };
                 if self.json {
                     #[derive(Serialize)]
                     struct Out {
                         archive: String,
                         files: Vec<String>,
+                        #[serde(skip_serializing_if = "Option::is_none")]
+                        summary: Option<ArchiveStats>,
                     }
-                    println!(
+                    writeln!(
+                        out,
                         "{}",
-                        serde_json::to_string(&Out {
-                            archive: archive.display().to_string(),
-                            files: contents
-                        })?
-                    );
+                        json_string(
+                            pretty,
+                            &Out {
+                                archive: archive.display().to_string(),
+                                files: contents,
+                                summary
+                            }
+                        )?
+                    )?;
+                } else if null {
+                    for item in contents {
+                        write!(out, "{item}\0")?;
+                    }
                 } else {
-                    println!("Archive: {}", archive.display());
+                    writeln!(out, "Archive: {}", archive.display())?;
                     if contents.is_empty() {
-                        println!("Archive is empty");
+                        writeln!(out, "Archive is empty")?;
                     } else {
                         for item in contents {
-                            println!("  {item}");
+                            writeln!(out, "  {item}")?;
                         }
                     }
+                    if let Some(summary) = &summary {
+                        writeln!(out, "{}", format_summary(summary))?;
+                    }
                 }
             }
-            Commands::Validate { archive } => {
-                let is_valid = manager.validate_archive(&archive)?;
+            Commands::Validate {
+                archive,
+                recursive,
+                max_nesting,
+                max_nested_size,
+                ..
+            } => {
+                let (is_valid, nested) = if recursive {
+                    let limits = NestedLimits {
+                        max_depth: max_nesting,
+                        max_bytes: max_nested_size,
+                    };
+                    let report = manager.validate_archive_recursive(&archive, &limits)?;
+                    (report.valid, Some(report.nested))
+                } else {
+                    (manager.validate_archive(&archive)?, None)
+                };
                 if self.json {
                     #[derive(Serialize)]
                     struct Out {
                         archive: String,
                         valid: bool,
+                        #[serde(skip_serializing_if = "Option::is_none")]
+                        nested: Option<Vec<ValidationReport>>,
                     }
-                    println!(
+                    writeln!(
+                        out,
                         "{}",
-                        serde_json::to_string(&Out {
-                            archive: archive.display().to_string(),
-                            valid: is_valid
-                        })?
-                    );
-                } else if is_valid {
-                    println!("✓ Archive is valid and all files passed integrity checks");
+                        json_string(
+                            pretty,
+                            &Out {
+                                archive: archive.display().to_string(),
+                                valid: is_valid,
+                                nested
+                            }
+                        )?
+                    )?;
                 } else {
-                    println!("✗ Archive validation failed");
+                    if is_valid {
+                        writeln!(out, "✓ Archive is valid and all files passed integrity checks")?;
+                    } else {
+                        writeln!(out, "✗ Archive validation failed")?;
+                    }
+                    write_validation_tree(out, nested.as_deref().unwrap_or_default(), 1)?;
                 }
             }
-            Commands::Stats { archive } => {
-                let stats = manager.get_archive_stats(&archive)?;
+            Commands::Stats {
+                archive,
+                no_entries,
+            } => {
+                let stats = if no_entries {
+                    manager.get_archive_stats_streaming(&archive)?
+                } else {
+                    manager.get_archive_stats(&archive)?
+                };
                 if self.json {
-                    println!("{}", serde_json::to_string(&stats)?);
+                    writeln!(out, "{}", json_string(pretty, &stats)?)?;
                 } else {
-                    println!("Archive Statistics:");
-                    println!("  Files: {}", stats.file_count);
-                    println!("  Directories: {}", stats.dir_count);
-                    println!("  Uncompressed size: {} bytes", stats.total_uncompressed_size);
-                    println!("  Compressed size: {} bytes", stats.total_compressed_size);
-                    println!("  Compression ratio: {:.1}%", stats.compression_ratio);
+                    writeln!(out, "Archive Statistics:")?;
+                    writeln!(out, "  Files: {}", stats.file_count)?;
+                    writeln!(out, "  Directories: {}", stats.dir_count)?;
+                    writeln!(out, "  Uncompressed size: {} bytes", stats.total_uncompressed_size)?;
+                    writeln!(out, "  Compressed size: {} bytes", stats.total_compressed_size)?;
+                    writeln!(out, "  Compression ratio: {:.1}%", stats.compression_ratio)?;
+                    if stats.prepended_bytes > 0 {
+                        writeln!(out, "  Prepended data: {} bytes", stats.prepended_bytes)?;
+                    }
+                    if let Some(comment) = &stats.comment {
+                        writeln!(out, "  Comment:")?;
+                        for line in comment.lines() {
+                            writeln!(out, "    {line}")?;
+                        }
+                    }
+                    if stats.encrypted_count > 0 {
+                        let methods: Vec<&str> =
+                            stats.encryption_methods.iter().map(|m| m.as_str()).collect();
+                        writeln!(
+                            out,
+                            "  Encrypted entries: {} ({})",
+                            stats.encrypted_count,
+                            methods.join(", ")
+                        )?;
+                    }
                     if stats.total_uncompressed_size > 0 {
                         if stats.total_uncompressed_size > stats.total_compressed_size {
                             let space_saved =
                                 stats.total_uncompressed_size - stats.total_compressed_size;
-                            println!("  Space saved: {space_saved} bytes");
+                            writeln!(out, "  Space saved: {space_saved} bytes")?;
                         } else {
                             let space_increased =
                                 stats.total_compressed_size - stats.total_uncompressed_size;
-                            println!(
+                            writeln!(
+                                out,
                                 "  Space increased: {space_increased} bytes (due to compression overhead)"
-                            );
+                            )?;
+                        }
+                    }
+                }
+            }
+            Commands::Comment { archive } => {
+                let comment = manager.read_comment(&archive)?;
+                if self.json {
+                    let value = serde_json::json!({
+                        "archive": archive.display().to_string(),
+                        "comment": comment
+                    });
+                    writeln!(out, "{}", json_string(pretty, &value)?)?;
+                } else if !comment.is_empty() {
+                    write!(out, "{comment}")?;
+                    if !comment.ends_with('\n') {
+                        writeln!(out)?;
+                    }
+                }
+            }
+            Commands::Reveal { path } => {
+                crate::reveal::reveal_in_file_manager(&path)?;
+                if self.json {
+                    let value = serde_json::json!({"revealed": path.display().to_string()});
+                    writeln!(out, "{}", json_string(pretty, &value)?)?;
+                }
+            }
+            Commands::Batch { spec, jobs } => {
+                let batch_jobs = batch::parse_spec(&spec)?;
+                let outcomes = batch::run_batch(&manager, &batch_jobs, jobs as usize);
+                let failed = outcomes.iter().filter(|o| o.error.is_some()).count();
+                if self.json {
+                    let value = serde_json::json!({
+                        "archives": outcomes,
+                        "created": outcomes.len() - failed,
+                        "failed": failed
+                    });
+                    writeln!(out, "{}", json_string(pretty, &value)?)?;
+                } else {
+                    for outcome in &outcomes {
+                        match &outcome.error {
+                            Some(error) => {
+                                writeln!(out, "✗ {}: {error}", outcome.archive.display())?
+                            }
+                            None => writeln!(
+                                out,
+                                "✓ {}: {} files, {} -> {}",
+                                outcome.archive.display(),
+                                outcome.files,
+                                format_bytes(outcome.uncompressed_bytes),
+                                format_bytes(outcome.compressed_bytes)
+                            )?,
                         }
                     }
                 }
+                if failed > 0 {
+                    anyhow::bail!("{failed} of {} archives could not be created", outcomes.len());
+                }
+            }
+            Commands::Convert { input, output } => {
+                let summary = convert::convert_archive(&input, &output)?;
+                if self.json {
+                    #[derive(Serialize)]
+                    struct Out {
+                        event: &'static str,
+                        input: String,
+                        output: String,
+                        #[serde(flatten)]
+                        summary: convert::ConvertSummary,
+                    }
+                    writeln!(
+                        out,
+                        "{}",
+                        json_string(
+                            pretty,
+                            &Out {
+                                event: "converted",
+                                input: input.display().to_string(),
+                                output: output.display().to_string(),
+                                summary
+                            }
+                        )?
+                    )?;
+                } else {
+                    writeln!(
+                        out,
+                        "Converted {} files and {} directories from {} to {}",
+                        summary.files,
+                        summary.dirs,
+                        input.display(),
+                        output.display()
+                    )?;
+                    if summary.skipped > 0 {
+                        writeln!(out, "  Skipped {} links or special entries", summary.skipped)?;
+                    }
+                }
+            }
+            Commands::Watch {
+                dir,
+                archive,
+                debounce_ms,
+            } => {
+                // Ctrl-C lets the current batch finish so the archive is left complete
+                let stop = Arc::new(AtomicBool::new(false));
+                let handler_stop = stop.clone();
+                ctrlc::set_handler(move || handler_stop.store(true, Ordering::SeqCst))?;
+                let json = self.json;
+                let added = watch::watch_directory(
+                    &manager,
+                    &dir,
+                    &archive,
+                    Duration::from_millis(debounce_ms),
+                    &stop,
+                    &mut |path| {
+                        if json {
+                            let event = serde_json::json!({
+                                "event": "added", "file": path.display().to_string()
+                            });
+                            writeln!(out, "{event}")?;
+                        } else {
+                            writeln!(out, "added: {}", path.display())?;
+                        }
+                        Ok(out.flush()?)
+                    },
+                )?;
+                if json {
+                    let done = serde_json::json!({
+                        "event": "watched", "archive": archive.display().to_string(), "added": added
+                    });
+                    writeln!(out, "{}", json_string(pretty, &done)?)?;
+                } else {
+                    writeln!(out, "Added {added} files to {}", archive.display())?;
+                }
+            }
+            Commands::Bench { dir } => {
+                let results = bench::bench_directory(&manager, &dir)?;
+                if self.json {
+                    let report =
+                        serde_json::json!({ "dir": dir.display().to_string(), "results": results });
+                    writeln!(out, "{}", json_string(pretty, &report)?)?;
+                } else {
+                    bench::write_summary(out, &results)?;
+                }
             }
-            Commands::Hash { file } => {
-                let hash = manager.calculate_file_hash(&file)?;
+            Commands::Hash {
+                file,
+                algo,
+                encoding,
+                stats,
+            } => {
+                let (digest, hash_stats) = manager.calculate_file_digest_with_stats(&file, algo)?;
+                let hash = encoding.encode(&digest);
                 if self.json {
                     #[derive(Serialize)]
                     struct Out {
                         file: String,
                         algo: &'static str,
+                        encoding: &'static str,
                         hash: String,
+                        #[serde(flatten, skip_serializing_if = "Option::is_none")]
+                        stats: Option<Stats>,
+                    }
+                    #[derive(Serialize)]
+                    struct Stats {
+                        size: u64,
+                        elapsed_ms: u64,
+                        bytes_per_sec: Option<f64>,
                     }
-                    println!(
+                    writeln!(
+                        out,
                         "{}",
-                        serde_json::to_string(&Out {
-                            file: file.display().to_string(),
-                            algo: "sha256",
-                            hash
-                        })?
-                    );
+                        json_string(
+                            pretty,
+                            &Out {
+                                file: file.display().to_string(),
+                                algo: algo.as_str(),
+                                encoding: encoding.as_str(),
+                                hash,
+                                stats: stats.then(|| Stats {
+                                    size: hash_stats.bytes,
+                                    elapsed_ms: hash_stats.elapsed.as_millis() as u64,
+                                    bytes_per_sec: hash_stats.bytes_per_sec()
+                                })
+                            }
+                        )?
+                    )?;
+                } else {
+                    writeln!(out, "{}: {hash}", algo.as_str().to_uppercase())?;
+                    if stats {
+                        let rate = match hash_stats.bytes_per_sec() {
+                            Some(rate) => format!("{}/s", format_bytes(rate as u64)),
+                            None => "too fast to measure".to_string(),
+                        };
+                        writeln!(
+                            out,
+                            "Size: {} ({} bytes), read in {:.2?} ({rate})",
+                            format_bytes(hash_stats.bytes),
+                            hash_stats.bytes,
+                            hash_stats.elapsed
+                        )?;
+                    }
+                }
+            }
+            Commands::Manifest {
+                paths,
+                relative_to,
+                algo,
+            } => {
+                let entries = manager.checksum_manifest(&paths, relative_to.as_deref(), algo)?;
+                if self.json {
+                    let value = serde_json::json!({"algo": algo.as_str(), "files": entries});
+                    writeln!(out, "{}", json_string(pretty, &value)?)?;
                 } else {
-                    println!("SHA256: {hash}");
+                    for entry in &entries {
+                        writeln!(out, "{}  {}", entry.hash, entry.name)?;
+                    }
                 }
             }
         }
@@ -228,12 +1902,206 @@ impl Cli {
     }
 }
 
+/// Resolve `create`'s archive path and inputs. When `archive` is the only path and
+/// exists, it is the input and the archive is named after it; when it is a directory
+/// given with inputs, the archive goes inside it, named after the first input. Derived
+/// names never replace an existing file unless `force` is set.
+fn resolve_create_paths(
+    archive: &Path,
+    files: &[PathBuf],
+    force: bool,
+) -> Result<(PathBuf, Vec<PathBuf>)> {
+    let is_zip = archive.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+    let (output, inputs) = if files.is_empty() && archive.exists() && !is_zip {
+        let input = archive.to_path_buf();
+        (sibling_archive_name(&input)?, vec![input])
+    } else if !files.is_empty() && archive.is_dir() {
+        let name = sibling_archive_name(&files[0])?;
+        (archive.join(name.file_name().unwrap_or_default()), files.to_vec())
+    } else if files.is_empty() {
+        anyhow::bail!("No files specified to add to archive");
+    } else {
+        return Ok((archive.to_path_buf(), files.to_vec()));
+    };
+    if output.exists() && !force {
+        anyhow::bail!("{} already exists; pass --force to replace it", output.display());
+    }
+    Ok((output, inputs))
+}
+
+/// `<dir>.zip` or `<file stem>.zip`, next to `input`
+fn sibling_archive_name(input: &Path) -> Result<PathBuf> {
+    // Paths such as `.` or `..` have no name of their own
+    let input = match input.file_name() {
+        Some(_) => input.to_path_buf(),
+        None => input.canonicalize()?,
+    };
+    let base = if input.is_dir() {
+        input.file_name()
+    } else {
+        input.file_stem()
+    };
+    let base = base
+        .map(|b| b.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "archive".into());
+    Ok(input.with_file_name(format!("{base}.zip")))
+}
+
+/// One line per nested archive, indented by depth
+fn write_validation_tree(
+    out: &mut dyn Write,
+    reports: &[ValidationReport],
+    depth: usize,
+) -> Result<()> {
+    for report in reports {
+        let status = match &report.error {
+            Some(error) => format!("invalid ({error})"),
+            None if report.valid => "valid".to_string(),
+            None => "invalid".to_string(),
+        };
+        writeln!(out, "{}{}: {status}", "  ".repeat(depth), report.name)?;
+        write_validation_tree(out, &report.nested, depth + 1)?;
+    }
+    Ok(())
+}
+
+/// Footer for `list --total`, e.g. "42 files, 10.2 MB uncompressed, 3.1 MB compressed (30.0%)"
+fn format_summary(stats: &ArchiveStats) -> String {
+    let mut line = format!("{} files", stats.file_count);
+    if stats.dir_count > 0 {
+        line.push_str(&format!(", {} directories", stats.dir_count));
+    }
+    format!(
+        "{line}, {} uncompressed, {} compressed ({:.1}%)",
+        format_bytes(stats.total_uncompressed_size),
+        format_bytes(stats.total_compressed_size),
+        stats.compression_ratio
+    )
+}
+
+/// `value` as JSON: indented under --json-pretty, one line otherwise
+fn json_string<T: Serialize>(pretty: bool, value: &T) -> serde_json::Result<String> {
+    match pretty {
+        true => serde_json::to_string_pretty(value),
+        false => serde_json::to_string(value),
+    }
+}
+
+/// Human-readable size with decimal units
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KB", "MB", "GB", "TB", "PB"];
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 1000.0 && unit + 1 < UNITS.len() {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// A parsed `list --format` template
+#[derive(Clone, Debug)]
+pub struct ListFormat(Vec<ListPart>);
+
+#[derive(Clone, Debug)]
+enum ListPart {
+    Literal(String),
+    Field(ListField),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ListField {
+    Name,
+    Size,
+    CompressedSize,
+    Ratio,
+    Crc,
+    Method,
+    Modified,
+}
+
+impl ListFormat {
+    /// The template filled in for `entry`; sizes are in bytes, the ratio is a
+    /// percentage and missing times show as `-`
+    fn render(&self, entry: &EntryInfo) -> String {
+        let mut line = String::new();
+        for part in &self.0 {
+            match part {
+                ListPart::Literal(text) => line.push_str(text),
+                ListPart::Field(ListField::Name) => line.push_str(&entry.name),
+                ListPart::Field(ListField::Size) => line.push_str(&entry.size.to_string()),
+                ListPart::Field(ListField::CompressedSize) => {
+                    line.push_str(&entry.compressed_size.to_string())
+                }
+                ListPart::Field(ListField::Ratio) => {
+                    line.push_str(&format!("{:.1}%", entry.ratio() * 100.0))
+                }
+                ListPart::Field(ListField::Crc) => line.push_str(&format!("{:08x}", entry.crc32)),
+                ListPart::Field(ListField::Method) => line.push_str(&entry.method),
+                ListPart::Field(ListField::Modified) => {
+                    line.push_str(entry.modified.as_deref().unwrap_or("-"))
+                }
+            }
+        }
+        line
+    }
+}
+
+/// How long before `now` a listed modification time was, e.g. "3 days ago". Both are
+/// local times, as entries store them; times that don't parse are returned unchanged.
+fn format_relative_time(modified: &str, now: chrono::NaiveDateTime) -> String {
+    const UNITS: [(&str, i64); 6] = [
+        ("year", 365 * 86_400),
+        ("month", 30 * 86_400),
+        ("week", 7 * 86_400),
+        ("day", 86_400),
+        ("hour", 3_600),
+        ("minute", 60),
+    ];
+    let Ok(then) = chrono::NaiveDateTime::parse_from_str(modified, "%Y-%m-%dT%H:%M:%S") else {
+        return modified.to_string();
+    };
+    let seconds = (now - then).num_seconds();
+    let Some((unit, count)) = UNITS
+        .iter()
+        .map(|(unit, len)| (unit, seconds.abs() / len))
+        .find(|(_, n)| *n > 0)
+    else {
+        return "just now".to_string();
+    };
+    let amount = match count {
+        1 => format!("1 {unit}"),
+        _ => format!("{count} {unit}s"),
+    };
+    match seconds < 0 {
+        true => format!("in {amount}"),
+        false => format!("{amount} ago"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
     use tempfile::TempDir;
 
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("rolypoly").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn test_cli_retries_limit() {
+        let opts = parse(&["create", "a.zip", "x", "--retries", "20"]).archive_options();
+        assert_eq!(opts.retries, 20);
+        for command in ["create", "extract"] {
+            let args = ["rolypoly", command, "a.zip", "x", "--retries", "1000000"];
+            assert!(Cli::try_parse_from(args).is_err());
+        }
+    }
+
     #[test]
     fn test_cli_create_command() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -244,17 +2112,7 @@ mod tests {
         fs::write(&test_file, "Hello, World!")?;
 
         // Test create command
-        let cli = Cli {
-            json: false,
-            progress: false,
-            level: None,
-            auto_store: true,
-            store_entropy_threshold: 7.8,
-            command: Commands::Create {
-                archive: archive_path.clone(),
-                files: vec![test_file],
-            },
-        };
+        let cli = parse(&["create", archive_path.to_str().unwrap(), test_file.to_str().unwrap()]);
 
         cli.run()?;
 
@@ -265,6 +2123,139 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_cli_create_parents() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("test.txt");
+        fs::write(&test_file, "Hello, World!")?;
+        let archive_path = temp_dir.path().join("nonexistent_subdir/nested/out.zip");
+        let args = ["create", archive_path.to_str().unwrap(), test_file.to_str().unwrap()];
+
+        let err = parse(&args).run_with_output(&mut Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("pass --create-parents"), "{err}");
+        assert!(!temp_dir.path().join("nonexistent_subdir").exists());
+
+        parse(&[&args[..], &["-p"]].concat()).run_with_output(&mut Vec::new())?;
+        assert!(archive_path.parent().unwrap().is_dir());
+        assert_eq!(ArchiveManager::new().list_archive(&archive_path)?, ["test.txt"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_batch_command() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path();
+        fs::write(dir.join("a.txt"), "first")?;
+        fs::write(dir.join("b.txt"), "second")?;
+        fs::write(dir.join("c.txt"), "third")?;
+        let spec = serde_json::json!({
+            dir.join("one.zip").to_str().unwrap(): [dir.join("a.txt")],
+            dir.join("two.zip").to_str().unwrap(): [dir.join("b.txt"), dir.join("c.txt")],
+        });
+        let spec_file = dir.join("batch.json");
+        fs::write(&spec_file, spec.to_string())?;
+
+        for jobs in ["1", "2"] {
+            let mut out = Vec::new();
+            let cli = parse(&["batch", spec_file.to_str().unwrap(), "--jobs", jobs, "--json"]);
+            cli.run_with_output(&mut out)?;
+            let value: serde_json::Value = serde_json::from_slice(&out)?;
+            assert_eq!(value["created"], 2);
+
+            let manager = ArchiveManager::new();
+            assert_eq!(manager.list_archive(dir.join("one.zip"))?, ["a.txt"]);
+            let mut names = manager.list_archive(dir.join("two.zip"))?;
+            names.sort();
+            assert_eq!(names, ["b.txt", "c.txt"]);
+        }
+
+        // One missing input fails its archive and the command, not the rest
+        let spec = serde_json::json!({
+            dir.join("bad.zip").to_str().unwrap(): [dir.join("missing.txt")],
+            dir.join("good.zip").to_str().unwrap(): [dir.join("a.txt")],
+        });
+        let cli = parse(&["batch", &spec.to_string()]);
+        assert!(cli.run_with_output(&mut Vec::new()).is_err());
+        assert!(dir.join("good.zip").exists());
+
+        // The global password encrypts every archive in the batch
+        let password_file = dir.join("pw.txt");
+        fs::write(&password_file, "batch secret\n")?;
+        let spec =
+            serde_json::json!({ dir.join("enc.zip").to_str().unwrap(): [dir.join("a.txt")] });
+        let cli = parse(&[
+            "--password-file",
+            password_file.to_str().unwrap(),
+            "batch",
+            &spec.to_string(),
+        ]);
+        cli.run_with_output(&mut Vec::new())?;
+        let mut archive = zip::ZipArchive::new(fs::File::open(dir.join("enc.zip"))?)?;
+        assert!(archive.by_index_raw(0)?.encrypted());
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            password: Some("batch secret".into()),
+            ..Default::default()
+        });
+        assert_eq!(manager.extract_entry(dir.join("enc.zip"), "a.txt")?, b"first");
+
+        let cli = parse(&[
+            "--password-file",
+            password_file.to_str().unwrap(),
+            "convert",
+            dir.join("one.zip").to_str().unwrap(),
+            dir.join("one.tar").to_str().unwrap(),
+        ]);
+        assert!(cli.run_with_output(&mut Vec::new()).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_cli_extract_url_with_password() -> Result<()> {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        let temp_dir = TempDir::new()?;
+        let secret = temp_dir.path().join("secret.txt");
+        fs::write(&secret, "fetched secret")?;
+        let archive_path = temp_dir.path().join("secret.zip");
+        ArchiveManager::with_options(ArchiveOptions {
+            password: Some("hunter2".into()),
+            ..Default::default()
+        })
+        .create_archive(&archive_path, &[&secret])?;
+        let zip = fs::read(&archive_path)?;
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let server = std::thread::spawn(move || -> std::io::Result<()> {
+            let (mut stream, _) = listener.accept()?;
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line)? > 2 {
+                line.clear();
+            }
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", zip.len())?;
+            stream.write_all(&zip)
+        });
+
+        // The password file is used for the downloaded archive, not dropped for the URL
+        let password_file = temp_dir.path().join("pw.txt");
+        fs::write(&password_file, "hunter2\n")?;
+        let output = temp_dir.path().join("out");
+        let cli = parse(&[
+            "--password-file",
+            password_file.to_str().unwrap(),
+            "extract",
+            &format!("http://127.0.0.1:{port}/secret.zip"),
+            "-o",
+            output.to_str().unwrap(),
+        ]);
+        cli.run_with_output(&mut Vec::new())?;
+        server.join().unwrap()?;
+        assert_eq!(fs::read_to_string(output.join("secret.txt"))?, "fetched secret");
+        Ok(())
+    }
+
     #[test]
     fn test_cli_extract_command() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -281,17 +2272,12 @@ mod tests {
         fs::create_dir(&extract_dir)?;
 
         // Test extract command
-        let cli = Cli {
-            json: false,
-            progress: false,
-            level: None,
-            auto_store: true,
-            store_entropy_threshold: 7.8,
-            command: Commands::Extract {
-                archive: archive_path,
-                output: extract_dir.clone(),
-            },
-        };
+        let cli = parse(&[
+            "extract",
+            archive_path.to_str().unwrap(),
+            "-o",
+            extract_dir.to_str().unwrap(),
+        ]);
 
         cli.run()?;
 
@@ -317,21 +2303,539 @@ mod tests {
         manager.create_archive(&archive_path, &[&test_file1, &test_file2])?;
 
         // Test list command
-        let cli = Cli {
-            json: false,
-            progress: false,
-            level: None,
-            auto_store: true,
-            store_entropy_threshold: 7.8,
-            command: Commands::List {
-                archive: archive_path,
-            },
+        let cli = parse(&["list", archive_path.to_str().unwrap()]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+
+        assert_eq!(
+            String::from_utf8(out)?,
+            format!("Archive: {}\n  test1.txt\n  test2.txt\n", archive_path.display())
+        );
+
+        let cli = parse(&["--json", "list", archive_path.to_str().unwrap()]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let value: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(value["files"], serde_json::json!(["test1.txt", "test2.txt"]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_list_sort_by_size() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("test.zip");
+        let mut files = Vec::new();
+        for (name, len) in [("small.txt", 10), ("large.txt", 5000), ("medium.txt", 500)] {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, "x".repeat(len))?;
+            files.push(path);
+        }
+        let files: Vec<&PathBuf> = files.iter().collect();
+        ArchiveManager::new().create_archive(&archive_path, &files)?;
+
+        let cli = parse(&["list", archive_path.to_str().unwrap(), "--sort", "size", "--reverse"]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            format!(
+                "Archive: {}\n  large.txt\n  medium.txt\n  small.txt\n",
+                archive_path.display()
+            )
+        );
+
+        let cli = parse(&["list", archive_path.to_str().unwrap(), "--long", "--sort", "name"]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let text = String::from_utf8(out)?;
+        let names: Vec<&str> =
+            text.lines().skip(2).filter_map(|line| line.split_whitespace().last()).collect();
+        assert_eq!(names, ["large.txt", "medium.txt", "small.txt"]);
+
+        assert!(Cli::try_parse_from(["rolypoly", "list", "a.zip", "--reverse"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_list_total() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("test.zip");
+        let mut files = Vec::new();
+        for i in 0..4 {
+            let path = temp_dir.path().join(format!("file{i}.txt"));
+            fs::write(&path, "data ".repeat(400))?;
+            files.push(path);
+        }
+        let files: Vec<&PathBuf> = files.iter().collect();
+        ArchiveManager::new().create_archive(&archive_path, &files)?;
+
+        let cli = parse(&["list", archive_path.to_str().unwrap(), "--total"]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let text = String::from_utf8(out)?;
+        let listed = text.lines().filter(|line| line.starts_with("  ")).count();
+        let footer = text.lines().last().unwrap();
+        assert_eq!(footer.split_whitespace().next(), Some(listed.to_string().as_str()));
+        assert!(footer.starts_with("4 files, 8.0 KB uncompressed, "), "{footer}");
+
+        let cli = parse(&["--json", "list", archive_path.to_str().unwrap(), "--long", "--total"]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let value: serde_json::Value = serde_json::from_slice(&out)?;
+        let stats = serde_json::to_value(ArchiveManager::new().get_archive_stats(&archive_path)?)?;
+        assert_eq!(value["summary"], stats);
+        assert_eq!(value["entries"].as_array().map(Vec::len), Some(4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_json_pretty_output() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file = temp_dir.path().join("data.txt");
+        fs::write(&file, "pretty please")?;
+        let archive_path = temp_dir.path().join("pretty.zip");
+        let archive = archive_path.to_str().unwrap();
+        parse(&["create", archive, file.to_str().unwrap()]).run_with_output(&mut Vec::new())?;
+
+        for command in [["stats", archive], ["validate", archive]] {
+            let mut compact = Vec::new();
+            parse(&[&["--json"], &command[..]].concat()).run_with_output(&mut compact)?;
+            let mut pretty = Vec::new();
+            let cli = parse(&[&["--json-pretty"], &command[..]].concat());
+            assert!(!cli.json);
+            cli.run_with_output(&mut pretty)?;
+            let (compact, pretty) = (String::from_utf8(compact)?, String::from_utf8(pretty)?);
+            assert_eq!(compact.trim_end().lines().count(), 1, "{compact}");
+            assert!(pretty.trim_end().lines().count() > 3, "{pretty}");
+            assert!(pretty.contains("\n  \""), "{pretty}");
+            let compact: serde_json::Value = serde_json::from_str(&compact)?;
+            let pretty: serde_json::Value = serde_json::from_str(&pretty)?;
+            assert_eq!(compact, pretty);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_relative_time() {
+        let now = chrono::NaiveDateTime::parse_from_str("2024-03-10T12:00:00", "%Y-%m-%dT%H:%M:%S")
+            .unwrap();
+        let cases = [
+            ("2024-03-10T11:59:30", "just now"),
+            ("2024-03-10T11:59:00", "1 minute ago"),
+            ("2024-03-10T09:00:00", "3 hours ago"),
+            ("2024-03-07T12:00:00", "3 days ago"),
+            ("2024-02-20T12:00:00", "2 weeks ago"),
+            ("2023-12-01T12:00:00", "3 months ago"),
+            ("2021-01-01T00:00:00", "3 years ago"),
+            ("2024-03-11T12:00:00", "in 1 day"),
+            ("not a time", "not a time"),
+        ];
+        for (modified, expected) in cases {
+            assert_eq!(format_relative_time(modified, now), expected, "{modified}");
+        }
+    }
+
+    #[test]
+    fn test_cli_list_long_iso_times() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file = temp_dir.path().join("dated.txt");
+        fs::write(&file, "dated")?;
+        let archive_path = temp_dir.path().join("dated.zip");
+        let archive = archive_path.to_str().unwrap();
+        parse(&["create", archive, file.to_str().unwrap(), "--mtime", "@1700000000"])
+            .run_with_output(&mut Vec::new())?;
+        let stamp = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let iso = chrono::DateTime::<chrono::Local>::from(stamp)
+            .format("%Y-%m-%dT%H:%M:%S")
+            .to_string();
+
+        let mut out = Vec::new();
+        parse(&["list", archive, "--long", "--iso"]).run_with_output(&mut out)?;
+        let listing = String::from_utf8(out)?;
+        let line = listing.lines().find(|l| l.ends_with("dated.txt")).unwrap();
+        assert!(line.contains(&format!("  {iso}  ")), "{listing}");
+
+        // Without --iso the same entry is shown relative to now
+        let mut out = Vec::new();
+        parse(&["list", archive, "--long"]).run_with_output(&mut out)?;
+        let listing = String::from_utf8(out)?;
+        let line = listing.lines().find(|l| l.ends_with("dated.txt")).unwrap();
+        assert!(line.contains(" years ago") && !line.contains(&iso), "{listing}");
+        assert!(Cli::try_parse_from(["rolypoly", "list", archive, "--iso"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(999), "999 B");
+        assert_eq!(format_bytes(10_200_000), "10.2 MB");
+        assert_eq!(format_bytes(3_100_000_000), "3.1 GB");
+    }
+
+    #[test]
+    fn test_cli_create_verbose() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("test.zip");
+        let text_file = temp_dir.path().join("text.txt");
+        let empty_file = temp_dir.path().join("empty.txt");
+        fs::write(&text_file, "compress me ".repeat(500))?;
+        fs::write(&empty_file, "")?;
+
+        let cli = parse(&[
+            "create",
+            archive_path.to_str().unwrap(),
+            text_file.to_str().unwrap(),
+            empty_file.to_str().unwrap(),
+            "--verbose",
+        ]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+
+        let text = String::from_utf8(out)?;
+        let lines: Vec<&str> = text.lines().filter(|l| l.starts_with("  added: ")).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("  added: text.txt  6.0 KB -> "), "{}", lines[0]);
+        assert!(lines[1].starts_with("  added: empty.txt  0 B -> "), "{}", lines[1]);
+        assert!(text.contains(
+            "  stored uncompressed: 0 by extension, 0 already compressed, 0 by content probe (0 B)"
+        ));
+
+        // Files skipped by the store rules are counted
+        let photo = temp_dir.path().join("photo.jpg");
+        let noise: Vec<u8> =
+            (0..50_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+        fs::write(&photo, &noise)?;
+        let noise_file = temp_dir.path().join("noise.bin");
+        fs::write(&noise_file, &noise)?;
+        let cli = parse(&[
+            "--json",
+            "create",
+            archive_path.to_str().unwrap(),
+            photo.to_str().unwrap(),
+            noise_file.to_str().unwrap(),
+            text_file.to_str().unwrap(),
+            "--force",
+            "--verbose",
+        ]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let event = String::from_utf8(out)?
+            .lines()
+            .filter_map(|l| serde_json::from_str::<serde_json::Value>(l).ok())
+            .find(|v| v["event"] == "auto_store")
+            .unwrap();
+        assert_eq!(event["stored_by_extension"], 1);
+        assert_eq!(event["stored_by_probe"], 1);
+        assert_eq!(event["stored_bytes"], 100_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_create_presets() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let input = temp_dir.path().join("input.txt");
+        let text: String =
+            (0..20_000).map(|i| format!("line {} of {}\n", i % 977, i % 13)).collect();
+        fs::write(&input, text)?;
+
+        let mut sizes = std::collections::HashMap::new();
+        for preset in ["fast", "balanced", "max"] {
+            let archive_path = temp_dir.path().join(format!("{preset}.zip"));
+            let cli = parse(&[
+                "create",
+                archive_path.to_str().unwrap(),
+                input.to_str().unwrap(),
+                "--preset",
+                preset,
+                "--verbose",
+            ]);
+            let level = cli.archive_options().compression_level;
+            let mut out = Vec::new();
+            cli.run_with_output(&mut out)?;
+            let text = String::from_utf8(out)?;
+            let expected =
+                format!("  compression: deflate level {} (preset {preset})", level.unwrap());
+            assert!(text.lines().any(|l| l == expected), "{text}");
+            assert!(ArchiveManager::new().validate_archive(&archive_path)?);
+            sizes.insert(preset, fs::metadata(&archive_path)?.len());
+        }
+        assert!(sizes["max"] <= sizes["fast"], "{sizes:?}");
+
+        // An explicit level beats the preset
+        let opts =
+            parse(&["create", "a.zip", "x", "--preset", "max", "--level", "3"]).archive_options();
+        assert_eq!(opts.compression_level, Some(3));
+        let opts = parse(&["create", "a.zip", "x", "--preset", "fast"]).archive_options();
+        assert_eq!(opts.compression_level, Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_list_format_template() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("test.zip");
+        let file1 = temp_dir.path().join("a.txt");
+        let file2 = temp_dir.path().join("b.txt");
+        fs::write(&file1, "alpha ".repeat(200))?;
+        fs::write(&file2, "beta")?;
+        ArchiveManager::new().create_archive(&archive_path, &[&file1, &file2])?;
+
+        let cli = parse(&[
+            "list",
+            archive_path.to_str().unwrap(),
+            "--format",
+            r"{size}\t{name}\t{{{crc}}} {method} {ratio}",
+        ]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let text = String::from_utf8(out)?;
+        let entries = ArchiveManager::new().list_archive_detailed(&archive_path)?;
+        let expected: Vec<String> = entries
+            .iter()
+            .map(|e| {
+                let ratio = e.ratio() * 100.0;
+                format!("{}\t{}\t{{{:08x}}} {} {ratio:.1}%", e.size, e.name, e.crc32, e.method)
+            })
+            .collect();
+        assert_eq!(text.lines().collect::<Vec<_>>(), expected);
+        let columns: Vec<&str> = text.lines().next().unwrap().split('\t').collect();
+        assert_eq!(columns[..2], ["1200", "a.txt"]);
+
+        for bad in ["{size", "{owner}", "size}"] {
+            assert!(Cli::try_parse_from(["rolypoly", "list", "a.zip", "--format", bad]).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_list_null_separated() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("test.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&archive_path)?);
+        for name in ["plain.txt", "two\nlines.txt", "with space.txt"] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())?;
+            zip.write_all(b"data")?;
+        }
+        zip.finish()?;
+
+        for flag in ["--null", "-0"] {
+            let mut out = Vec::new();
+            parse(&["list", archive_path.to_str().unwrap(), flag]).run_with_output(&mut out)?;
+            assert_eq!(out, b"plain.txt\0two\nlines.txt\0with space.txt\0");
+        }
+        let mut out = Vec::new();
+        parse(&["list", archive_path.to_str().unwrap(), "-0", "--format", "{size} {name}"])
+            .run_with_output(&mut out)?;
+        assert_eq!(out, b"4 plain.txt\x004 two\nlines.txt\x004 with space.txt\0");
+        assert!(Cli::try_parse_from(["rolypoly", "list", "a.zip", "-0", "--long"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_json_summaries() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("test.zip");
+        let extract_dir = temp_dir.path().join("out");
+        let file1 = temp_dir.path().join("a.txt");
+        let file2 = temp_dir.path().join("b.txt");
+        fs::write(&file1, "alpha ".repeat(200))?;
+        fs::write(&file2, "beta")?;
+
+        let last_json = |out: Vec<u8>| -> Result<serde_json::Value> {
+            let text = String::from_utf8(out)?;
+            Ok(serde_json::from_str(text.lines().last().unwrap())?)
         };
 
-        // This will print to stdout, but we can't easily capture it in tests
-        // The actual functionality is tested in the archive module
-        cli.run()?;
+        let cli = parse(&[
+            "--json",
+            "create",
+            archive_path.to_str().unwrap(),
+            file1.to_str().unwrap(),
+            file2.to_str().unwrap(),
+        ]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let created = last_json(out)?;
+        assert_eq!(created["event"], "created");
+        assert_eq!(created["entries"], 2);
+        assert_eq!(created["files"], 2);
+        assert_eq!(created["uncompressed_bytes"], 1204);
+        let compressed = created["compressed_bytes"].as_f64().unwrap();
+        let ratio = created["compression_ratio"].as_f64().unwrap();
+        assert!((ratio - compressed / 1204.0 * 100.0).abs() < 1e-9);
+        assert!(created["elapsed_ms"].is_u64());
+
+        let cli = parse(&[
+            "--json",
+            "extract",
+            archive_path.to_str().unwrap(),
+            "-o",
+            extract_dir.to_str().unwrap(),
+        ]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let extracted = last_json(out)?;
+        assert_eq!(extracted["event"], "extracted");
+        assert_eq!(extracted["files"], 2);
+        assert_eq!(extracted["bytes_written"], 1204);
+        assert_eq!(extracted["skipped"], 0);
+        assert!(extracted["elapsed_ms"].is_u64());
+
+        let tar_path = temp_dir.path().join("test.tar");
+        let cli = parse(&[
+            "--json",
+            "convert",
+            archive_path.to_str().unwrap(),
+            tar_path.to_str().unwrap(),
+        ]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let converted = last_json(out)?;
+        assert_eq!(converted["event"], "converted");
+        assert_eq!(converted["files"], 2);
+
+        // A second run keeps the files already there
+        let cli = parse(&[
+            "extract",
+            archive_path.to_str().unwrap(),
+            "-o",
+            extract_dir.to_str().unwrap(),
+            "--overwrite",
+            "never",
+        ]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(out.contains("Extracted 0 files and 0 directories (0 B), skipped 2"), "{out}");
+        Ok(())
+    }
 
+    #[test]
+    fn test_cli_ignore_zip_comment_mismatch() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("test.txt");
+        fs::write(&test_file, "Hello, World!")?;
+        let clean = temp_dir.path().join("clean.zip");
+        ArchiveManager::new().create_archive(&clean, &[&test_file])?;
+        let bytes = fs::read(&clean)?;
+
+        // Trailing junk beyond the EOCD search window, and a comment length that runs
+        // past the end of the file
+        let junk = temp_dir.path().join("junk.zip");
+        fs::write(&junk, [&bytes[..], &vec![0xAA; 70_000]].concat())?;
+        let cut = temp_dir.path().join("cut.zip");
+        let mut cut_bytes = bytes.clone();
+        let at = cut_bytes.len() - 2;
+        cut_bytes[at..].copy_from_slice(&40u16.to_le_bytes());
+        fs::write(&cut, [&cut_bytes[..], b"short"].concat())?;
+        assert!(
+            parse(&["list", cut.to_str().unwrap()])
+                .run_with_output(&mut Vec::new())
+                .is_err()
+        );
+
+        for archive in [&junk, &cut] {
+            let archive = archive.to_str().unwrap();
+            let mut out = Vec::new();
+            parse(&["list", archive, "--ignore-zip-comment-mismatch"]).run_with_output(&mut out)?;
+            assert!(String::from_utf8(out)?.contains("test.txt"));
+            let valid = parse(&["validate", archive, "--ignore-zip-comment-mismatch"])
+                .run_with_output(&mut Vec::new());
+            assert!(valid.is_ok(), "{valid:?}");
+            let out_dir = temp_dir.path().join("out");
+            parse(&[
+                "extract",
+                archive,
+                "-o",
+                out_dir.to_str().unwrap(),
+                "--ignore-zip-comment-mismatch",
+            ])
+            .run_with_output(&mut Vec::new())?;
+            assert_eq!(fs::read_to_string(out_dir.join("test.txt"))?, "Hello, World!");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_extract_named_entries() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("test.zip");
+        let extract_dir = temp_dir.path().join("out");
+        let file1 = temp_dir.path().join("a.txt");
+        let file2 = temp_dir.path().join("b.txt");
+        fs::write(&file1, "alpha")?;
+        fs::write(&file2, "beta")?;
+        ArchiveManager::new().create_archive(&archive_path, &[&file1, &file2])?;
+
+        let cli = parse(&[
+            "--json",
+            "extract",
+            archive_path.to_str().unwrap(),
+            "b.txt",
+            "-o",
+            extract_dir.to_str().unwrap(),
+        ]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let value: serde_json::Value =
+            serde_json::from_str(String::from_utf8(out)?.lines().last().unwrap())?;
+        assert_eq!(value["files"], 1);
+        assert_eq!(value["bytes_written"], 4);
+        assert!(extract_dir.join("b.txt").exists());
+        assert!(!extract_dir.join("a.txt").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_create_paths() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let photos = temp_dir.path().join("photos");
+        fs::create_dir(&photos)?;
+        let report = temp_dir.path().join("report.txt");
+        fs::write(&report, "report")?;
+        let out = temp_dir.path().join("out");
+        fs::create_dir(&out)?;
+
+        // A single directory becomes <dirname>.zip next to it
+        let (archive, inputs) = resolve_create_paths(&photos, &[], false)?;
+        assert_eq!(archive, temp_dir.path().join("photos.zip"));
+        assert_eq!(inputs, std::slice::from_ref(&photos));
+
+        // A single file uses its stem
+        let (archive, _) = resolve_create_paths(&report, &[], false)?;
+        assert_eq!(archive, temp_dir.path().join("report.zip"));
+
+        // An output directory with several inputs is named after the first one
+        let inputs = vec![report.clone(), photos.clone()];
+        let (archive, resolved) = resolve_create_paths(&out, &inputs, false)?;
+        assert_eq!(archive, out.join("report.zip"));
+        assert_eq!(resolved, inputs);
+
+        // Explicit names are used as given
+        let named = temp_dir.path().join("named.zip");
+        assert_eq!(resolve_create_paths(&named, &inputs, false)?.0, named);
+
+        fs::write(out.join("report.zip"), "existing")?;
+        assert!(resolve_create_paths(&out, &inputs, false).is_err());
+        assert_eq!(resolve_create_paths(&out, &inputs, true)?.0, out.join("report.zip"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_create_names_archive_after_directory() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let photos = temp_dir.path().join("photos");
+        fs::create_dir(&photos)?;
+        fs::write(photos.join("a.txt"), "a")?;
+
+        parse(&["create", photos.to_str().unwrap()]).run_with_output(&mut Vec::new())?;
+        let archive = temp_dir.path().join("photos.zip");
+        assert_eq!(ArchiveManager::new().list_archive(&archive)?, ["photos/a.txt"]);
+
+        let again = parse(&["create", photos.to_str().unwrap()]).run_with_output(&mut Vec::new());
+        assert!(again.unwrap_err().to_string().contains("--force"));
+        parse(&["create", photos.to_str().unwrap(), "--force"]).run_with_output(&mut Vec::new())?;
         Ok(())
     }
 
@@ -340,17 +2844,7 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let archive_path = temp_dir.path().join("test.zip");
 
-        let cli = Cli {
-            json: false,
-            progress: false,
-            level: None,
-            auto_store: true,
-            store_entropy_threshold: 7.8,
-            command: Commands::Create {
-                archive: archive_path,
-                files: vec![],
-            },
-        };
+        let cli = parse(&["create", archive_path.to_str().unwrap()]);
 
         // This should return an error
         let result = cli.run();
@@ -370,16 +2864,7 @@ mod tests {
         manager.create_archive(&archive_path, &[&test_file])?;
 
         // Test validate command
-        let cli = Cli {
-            json: false,
-            progress: false,
-            level: None,
-            auto_store: true,
-            store_entropy_threshold: 7.8,
-            command: Commands::Validate {
-                archive: archive_path,
-            },
-        };
+        let cli = parse(&["validate", archive_path.to_str().unwrap()]);
 
         cli.run()?;
 
@@ -398,22 +2883,269 @@ mod tests {
         manager.create_archive(&archive_path, &[&test_file])?;
 
         // Test stats command
-        let cli = Cli {
-            json: false,
-            progress: false,
-            level: None,
-            auto_store: true,
-            store_entropy_threshold: 7.8,
-            command: Commands::Stats {
-                archive: archive_path,
-            },
-        };
+        let cli = parse(&["stats", archive_path.to_str().unwrap()]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
 
-        cli.run()?;
+        let stats = manager.get_archive_stats(&archive_path)?;
+        let text = String::from_utf8(out)?;
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "Archive Statistics:");
+        assert_eq!(lines[1], "  Files: 1");
+        assert_eq!(lines[2], "  Directories: 0");
+        assert_eq!(lines[3], "  Uncompressed size: 13 bytes");
+        assert_eq!(lines[4], format!("  Compressed size: {} bytes", stats.total_compressed_size));
+        assert!(lines[5].starts_with("  Compression ratio: "));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_create_comment_file() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("test.txt");
+        fs::write(&test_file, "Hello, World!")?;
+        let manifest = temp_dir.path().join("MANIFEST");
+        fs::write(&manifest, "build: 42\ncommit: abc123\n")?;
+        let archive_path = temp_dir.path().join("test.zip");
+        let archive = archive_path.to_str().unwrap();
+
+        parse(&[
+            "create",
+            archive,
+            test_file.to_str().unwrap(),
+            "--comment-file",
+            manifest.to_str().unwrap(),
+        ])
+        .run_with_output(&mut Vec::new())?;
+
+        let mut out = Vec::new();
+        parse(&["--json", "stats", archive]).run_with_output(&mut out)?;
+        let stats: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(stats["comment"], "build: 42\ncommit: abc123\n");
+
+        let mut out = Vec::new();
+        parse(&["stats", archive]).run_with_output(&mut out)?;
+        assert!(
+            String::from_utf8(out)?.contains("  Comment:\n    build: 42\n    commit: abc123\n")
+        );
+
+        let args = ["rolypoly", "create", "a.zip", "f", "--comment", "x", "--comment-file", "c"];
+        assert!(Cli::try_parse_from(args).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_create_exclude_from() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("project");
+        fs::create_dir_all(dir.join("target/release"))?;
+        fs::write(dir.join("target/release/app"), "binary")?;
+        fs::write(dir.join("Cargo.toml"), "[package]")?;
+        let ignore_file = temp_dir.path().join("exclude.txt");
+        fs::write(&ignore_file, "# build output\n\ntarget/\n")?;
+        let archive_path = temp_dir.path().join("project.zip");
 
+        let cli = parse(&[
+            "create",
+            archive_path.to_str().unwrap(),
+            dir.to_str().unwrap(),
+            "--exclude-from",
+            ignore_file.to_str().unwrap(),
+        ]);
+        assert_eq!(cli.exclude_patterns()?, ["target/"]);
+        cli.run_with_output(&mut Vec::new())?;
+        let names = ArchiveManager::new().list_archive(&archive_path)?;
+        assert_eq!(names, ["project/Cargo.toml"]);
         Ok(())
     }
 
+    #[test]
+    fn test_cli_create_compression_rules() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("media");
+        fs::create_dir_all(dir.join("logs"))?;
+        let text = "line of very compressible text\n".repeat(200);
+        fs::write(dir.join("notes.txt"), &text)?;
+        fs::write(dir.join("photo.jpg"), &text)?;
+        fs::write(dir.join("logs/app.log"), &text)?;
+        fs::write(dir.join("other.dat"), &text)?;
+        let rules = temp_dir.path().join("rules.txt");
+        fs::write(&rules, "# per-type settings\n*.txt deflate 9\n*.jpg store\n\n*.log store\n")?;
+        let archive_path = temp_dir.path().join("media.zip");
+
+        parse(&[
+            "create",
+            archive_path.to_str().unwrap(),
+            dir.to_str().unwrap(),
+            "--rules",
+            rules.to_str().unwrap(),
+        ])
+        .run_with_output(&mut Vec::new())?;
+        let methods: std::collections::HashMap<_, _> = ArchiveManager::new()
+            .list_archive_detailed(&archive_path)?
+            .into_iter()
+            .map(|e| (e.name, e.method))
+            .collect();
+        assert_eq!(methods["media/notes.txt"], "deflated");
+        assert_eq!(methods["media/photo.jpg"], "stored");
+        // Matches below the top level too, and wins over the default of deflating text
+        assert_eq!(methods["media/logs/app.log"], "stored");
+        assert_eq!(methods["media/other.dat"], "deflated");
+
+        fs::write(&rules, "*.txt store 3\n")?;
+        let err = parse(&[
+            "create",
+            temp_dir.path().join("bad.zip").to_str().unwrap(),
+            dir.to_str().unwrap(),
+            "--rules",
+            rules.to_str().unwrap(),
+        ])
+        .run_with_output(&mut Vec::new())
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("Rule 1: store takes no level"), "{err:#}");
+
+        // A deflate rule without a level keeps the --level given on the command line
+        let mut state = 1u32;
+        let words: Vec<String> = (0..20000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                format!("w{}", (state >> 16) % 500)
+            })
+            .collect();
+        fs::write(dir.join("notes.txt"), words.join(" "))?;
+        fs::write(&rules, "*.txt deflate\n")?;
+        let mut sizes = Vec::new();
+        for level in ["1", "9"] {
+            let archive_path = temp_dir.path().join(format!("level-{level}.zip"));
+            parse(&[
+                "create",
+                archive_path.to_str().unwrap(),
+                dir.join("notes.txt").to_str().unwrap(),
+                "--rules",
+                rules.to_str().unwrap(),
+                "--level",
+                level,
+            ])
+            .run_with_output(&mut Vec::new())?;
+            let entries = ArchiveManager::new().list_archive_detailed(&archive_path)?;
+            sizes.push(entries[0].compressed_size);
+        }
+        assert!(sizes[1] < sizes[0], "{sizes:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_create_compression_stats() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("mixed");
+        fs::create_dir(&dir)?;
+        let text = "line of very compressible text\n".repeat(200);
+        fs::write(dir.join("notes.txt"), &text)?;
+        fs::write(dir.join("more.txt"), &text)?;
+        // Stored by the default --store-extensions
+        fs::write(dir.join("photo.jpg"), &text)?;
+        let archive_path = temp_dir.path().join("mixed.zip");
+        let args = ["create", archive_path.to_str().unwrap(), dir.to_str().unwrap()];
+
+        let mut out = Vec::new();
+        parse(&[&args[..], &["--compression-stats", "--json"]].concat())
+            .run_with_output(&mut out)?;
+        let value: serde_json::Value = serde_json::from_slice(&out)?;
+        let stats = value["compression_stats"].as_array().unwrap();
+        assert_eq!(stats.len(), 2);
+        let deflated = &stats[0];
+        assert_eq!(deflated["method"], "deflated");
+        assert_eq!(deflated["files"], 2);
+        assert_eq!(deflated["uncompressed_bytes"], 2 * text.len());
+        assert!(deflated["ratio"].as_f64().unwrap() < 0.1);
+        let stored = &stats[1];
+        assert_eq!(stored["method"], "stored");
+        assert_eq!(stored["files"], 1);
+        assert_eq!(stored["compressed_bytes"], text.len());
+        assert_eq!(stored["ratio"], 1.0);
+
+        let mut out = Vec::new();
+        parse(&[&args[..], &["--compression-stats"]].concat()).run_with_output(&mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(out.contains("Compression by method:"), "{out}");
+        assert!(out.contains("  stored: 1 files, "), "{out}");
+        assert!(out.contains("  deflated: 2 files, "), "{out}");
+
+        // Left out unless asked for
+        let mut out = Vec::new();
+        parse(&[&args[..], &["--json"]].concat()).run_with_output(&mut out)?;
+        let value: serde_json::Value = serde_json::from_slice(&out)?;
+        assert!(value.get("compression_stats").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_create_fixed_mtime() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("data");
+        fs::create_dir_all(dir.join("sub"))?;
+        fs::write(dir.join("sub/a.txt"), "a")?;
+        fs::write(dir.join("b.txt"), "b")?;
+        let archive_path = temp_dir.path().join("stamped.zip");
+
+        let cli = parse(&[
+            "create",
+            archive_path.to_str().unwrap(),
+            dir.to_str().unwrap(),
+            "--mtime",
+            "@1700000000",
+        ]);
+        let mtime = cli.archive_options().mtime.unwrap();
+        assert_eq!(mtime, std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000));
+        cli.run_with_output(&mut Vec::new())?;
+        // Entries hold local time, which the detailed listing shows as written
+        let expected = chrono::DateTime::<chrono::Local>::from(mtime)
+            .format("%Y-%m-%dT%H:%M:%S")
+            .to_string();
+        let entries = ArchiveManager::new().list_archive_detailed(&archive_path)?;
+        assert_eq!(entries.len(), 3);
+        for entry in entries {
+            assert_eq!(entry.modified.as_deref(), Some(expected.as_str()), "{}", entry.name);
+        }
+
+        let opts =
+            parse(&["create", "a.zip", "x", "--mtime", "2024-01-31T12:00:00Z"]).archive_options();
+        let since_epoch = opts.mtime.unwrap().duration_since(std::time::UNIX_EPOCH)?;
+        assert_eq!(since_epoch.as_secs(), 1_706_702_400);
+        assert!(
+            parse(&["create", "a.zip", "x", "--mtime", "now"])
+                .archive_options()
+                .mtime
+                .is_some()
+        );
+        for bad in [
+            "@0",
+            "yesterday",
+            "@-5",
+            "@18446744073709551615",
+            "@9999999999",
+            "1975-06-01T00:00:00Z",
+            "2200-01-01T00:00:00Z",
+        ] {
+            assert!(
+                Cli::try_parse_from(["rolypoly", "create", "a.zip", "x", "--mtime", bad]).is_err()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_encoding_flag() {
+        let cli = parse(&["list", "a.zip", "--encoding", "Shift_JIS"]);
+        assert_eq!(
+            cli.archive_options().name_encoding,
+            NameEncoding::Legacy(encoding_rs::SHIFT_JIS)
+        );
+        assert_eq!(parse(&["list", "a.zip"]).archive_options().name_encoding, NameEncoding::Cp437);
+        assert!(Cli::try_parse_from(["rolypoly", "list", "a.zip", "--encoding", "nope"]).is_err());
+    }
+
     #[test]
     fn test_cli_hash_command() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -423,17 +3155,411 @@ mod tests {
         fs::write(&test_file, "Hello, World!")?;
 
         // Test hash command
-        let cli = Cli {
-            json: false,
-            progress: false,
-            level: None,
-            auto_store: true,
-            store_entropy_threshold: 7.8,
-            command: Commands::Hash { file: test_file },
-        };
+        let cli = parse(&["hash", test_file.to_str().unwrap()]);
 
         cli.run()?;
 
         Ok(())
     }
+
+    #[test]
+    fn test_cli_manifest_relative_to() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let project = temp_dir.path().join("project");
+        fs::create_dir_all(project.join("src/bin"))?;
+        fs::write(project.join("src/main.rs"), "fn main() {}")?;
+        fs::write(project.join("src/bin/tool.rs"), "fn main() {}")?;
+        fs::write(project.join("Cargo.toml"), "[package]")?;
+        let main_hash = ArchiveManager::new().calculate_file_hash(project.join("src/main.rs"))?;
+
+        // Named by the root however the inputs were spelled
+        let mut out = Vec::new();
+        parse(&[
+            "manifest",
+            project.join("src").to_str().unwrap(),
+            project.join("src/../Cargo.toml").to_str().unwrap(),
+            "--relative-to",
+            project.to_str().unwrap(),
+        ])
+        .run_with_output(&mut out)?;
+        let out = String::from_utf8(out)?;
+        let names: Vec<&str> =
+            out.lines().filter_map(|l| l.split_once("  ")).map(|(_, n)| n).collect();
+        assert_eq!(names, ["Cargo.toml", "src/bin/tool.rs", "src/main.rs"]);
+        assert!(out.contains(&format!("{main_hash}  src/main.rs\n")), "{out}");
+
+        let mut out = Vec::new();
+        parse(&[
+            "--json",
+            "manifest",
+            project.join("src").to_str().unwrap(),
+            "--relative-to",
+            project.join("src").to_str().unwrap(),
+        ])
+        .run_with_output(&mut out)?;
+        let value: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(value["files"][1]["name"], "main.rs");
+        assert_eq!(value["files"][1]["hash"], main_hash);
+
+        let err = parse(&[
+            "manifest",
+            project.join("Cargo.toml").to_str().unwrap(),
+            "--relative-to",
+            project.join("src").to_str().unwrap(),
+        ])
+        .run_with_output(&mut Vec::new())
+        .unwrap_err();
+        assert!(err.to_string().contains("is not inside"), "{err}");
+
+        // A symlink inside the root is named where it is, wherever it points
+        #[cfg(unix)]
+        {
+            fs::write(temp_dir.path().join("outside.txt"), "outside")?;
+            std::os::unix::fs::symlink("../outside.txt", project.join("link.txt"))?;
+            let mut out = Vec::new();
+            parse(&[
+                "manifest",
+                project.to_str().unwrap(),
+                "--relative-to",
+                project.to_str().unwrap(),
+            ])
+            .run_with_output(&mut out)?;
+            let out = String::from_utf8(out)?;
+            assert!(out.lines().any(|l| l.ends_with("  link.txt")), "{out}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_hash_crc32() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("test.txt");
+        fs::write(&test_file, "Hello, World!")?;
+
+        let cli = parse(&["--json", "hash", "--algo", "crc32", test_file.to_str().unwrap()]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let value: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(value["algo"], "crc32");
+        assert_eq!(value["hash"], "ec4ac3d0");
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_hash_base64() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("test.txt");
+        fs::write(&test_file, "Hello, World!")?;
+
+        let cli = parse(&["--json", "hash", "--encoding", "base64", test_file.to_str().unwrap()]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let value: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(value["encoding"], "base64");
+        assert_eq!(value["hash"], "3/1gIbsr1bCvZ2KQgJ7DpTGR3YHH9wpLKGiKNiGCmG8=");
+
+        let cli = parse(&["hash", "--encoding", "hex", test_file.to_str().unwrap()]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "SHA256: dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_hash_stats() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("data.bin");
+        fs::write(&test_file, vec![7u8; 300_000])?;
+        let len = fs::metadata(&test_file)?.len();
+
+        let cli = parse(&["--json", "hash", "--stats", test_file.to_str().unwrap()]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let value: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(value["size"], len);
+        assert!(value["elapsed_ms"].is_u64(), "{value}");
+        assert_eq!(value["hash"].as_str().unwrap().len(), 64);
+
+        let cli = parse(&["hash", "--stats", test_file.to_str().unwrap()]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let text = String::from_utf8(out)?;
+        assert!(text.contains(&format!("({len} bytes), read in ")), "{text}");
+
+        // Without --stats the output is unchanged
+        let cli = parse(&["--json", "hash", test_file.to_str().unwrap()]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+        let value: serde_json::Value = serde_json::from_slice(&out)?;
+        assert!(value.get("size").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_extract_preview_conflicts() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let same = temp_dir.path().join("same.txt");
+        let changed = temp_dir.path().join("changed.txt");
+        let fresh = temp_dir.path().join("fresh.txt");
+        fs::write(&same, "unchanged")?;
+        fs::write(&changed, "archived")?;
+        fs::write(&fresh, "new")?;
+        let archive_path = temp_dir.path().join("test.zip");
+        ArchiveManager::new().create_archive(&archive_path, &[&same, &changed, &fresh])?;
+
+        let out_dir = temp_dir.path().join("out");
+        fs::create_dir(&out_dir)?;
+        fs::write(out_dir.join("same.txt"), "unchanged")?;
+        fs::write(out_dir.join("changed.txt"), "edited locally")?;
+
+        let run = |extra: &[&str]| -> Result<serde_json::Value> {
+            let mut args = vec![
+                "--json",
+                "extract",
+                archive_path.to_str().unwrap(),
+                "-o",
+                out_dir.to_str().unwrap(),
+                "--preview-conflicts",
+            ];
+            args.extend(extra);
+            let mut out = Vec::new();
+            parse(&args).run_with_output(&mut out)?;
+            Ok(serde_json::from_slice(&out)?)
+        };
+        let value = run(&[])?;
+        let conflicts = value["conflicts"].as_array().unwrap();
+        assert_eq!(conflicts.len(), 1, "{value}");
+        assert_eq!(conflicts[0]["name"], "changed.txt");
+        assert_eq!(conflicts[0]["overwrite"], true);
+        let value = run(&["--overwrite", "never"])?;
+        assert_eq!(value["conflicts"][0]["overwrite"], false);
+
+        // Nothing was extracted
+        assert!(!out_dir.join("fresh.txt").exists());
+        assert_eq!(fs::read_to_string(out_dir.join("changed.txt"))?, "edited locally");
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_comment_command() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file = temp_dir.path().join("a.txt");
+        fs::write(&file, "a")?;
+        let archive_path = temp_dir.path().join("test.zip");
+        let comment = "Release 1.2\n  built by CI  \ttabbed";
+        parse(&[
+            "create",
+            archive_path.to_str().unwrap(),
+            file.to_str().unwrap(),
+            "--comment",
+            comment,
+        ])
+        .run_with_output(&mut Vec::new())?;
+
+        let mut out = Vec::new();
+        parse(&["comment", archive_path.to_str().unwrap()]).run_with_output(&mut out)?;
+        assert_eq!(String::from_utf8(out)?, format!("{comment}\n"));
+        let mut out = Vec::new();
+        parse(&["--json", "comment", archive_path.to_str().unwrap()]).run_with_output(&mut out)?;
+        let value: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(value["comment"], comment);
+
+        let plain = temp_dir.path().join("plain.zip");
+        ArchiveManager::new().create_archive(&plain, &[&file])?;
+        let mut out = Vec::new();
+        parse(&["comment", plain.to_str().unwrap()]).run_with_output(&mut out)?;
+        assert!(out.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_on_duplicate_option() {
+        let cli = parse(&["extract", "a.zip", "--on-duplicate", "keep-first"]);
+        assert_eq!(cli.archive_options().on_duplicate, DuplicatePolicy::KeepFirst);
+        let cli = parse(&["list", "a.zip", "--on-duplicate", "error"]);
+        assert_eq!(cli.archive_options().on_duplicate, DuplicatePolicy::Error);
+        let cli = parse(&["list", "a.zip"]);
+        assert_eq!(cli.archive_options().on_duplicate, DuplicatePolicy::KeepLast);
+    }
+
+    #[test]
+    fn test_cli_password_file_round_trip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let test_file = temp_dir.path().join("test.txt");
+        let archive_path = temp_dir.path().join("test.zip");
+        let password_file = temp_dir.path().join("pw.txt");
+        let extract_dir = temp_dir.path().join("extract");
+        fs::write(&test_file, "Hello, World!")?;
+        fs::write(&password_file, "hunter2\n")?;
+
+        parse(&[
+            "create",
+            archive_path.to_str().unwrap(),
+            test_file.to_str().unwrap(),
+            "--password-file",
+            password_file.to_str().unwrap(),
+        ])
+        .run()?;
+        assert!(ArchiveManager::new().has_encrypted_entries(&archive_path)?);
+
+        parse(&[
+            "extract",
+            archive_path.to_str().unwrap(),
+            "-o",
+            extract_dir.to_str().unwrap(),
+            "--password-file",
+            password_file.to_str().unwrap(),
+        ])
+        .run()?;
+        assert_eq!(fs::read_to_string(extract_dir.join("test.txt"))?, "Hello, World!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_password_flags_conflict() {
+        let args = ["rolypoly", "list", "a.zip", "--password", "x", "--password-file", "pw.txt"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_cli_encrypt_method_option() {
+        let cli = parse(&["create", "a.zip", "f.txt", "--encrypt-method", "aes128"]);
+        assert_eq!(cli.archive_options().encryption, EncryptionMethod::Aes128);
+        let cli = parse(&["create", "a.zip", "f.txt"]);
+        assert_eq!(cli.archive_options().encryption, EncryptionMethod::Aes256);
+        let cli = parse(&["create", "a.zip", "f.txt", "--encrypt-method", "zipcrypto"]);
+        assert_eq!(cli.archive_options().encryption, EncryptionMethod::ZipCrypto);
+    }
+
+    #[test]
+    fn test_cli_hidden_flags() {
+        assert!(!parse(&["create", "a.zip", "d"]).archive_options().exclude_hidden);
+        assert!(
+            parse(&["create", "a.zip", "d", "--exclude-hidden"])
+                .archive_options()
+                .exclude_hidden
+        );
+        let cli = parse(&["create", "a.zip", "d", "--exclude-hidden", "--include-hidden"]);
+        assert!(!cli.archive_options().exclude_hidden);
+    }
+
+    #[test]
+    fn test_cli_chmod_options() {
+        let opts = parse(&["extract", "a.zip", "--chmod-files", "0644", "--chmod-dirs", "0o755"])
+            .archive_options();
+        assert_eq!(opts.file_mode, Some(0o644));
+        assert_eq!(opts.dir_mode, Some(0o755));
+        assert!(
+            Cli::try_parse_from(["rolypoly", "extract", "a.zip", "--chmod-files", "999"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_overwrite_options() {
+        assert_eq!(
+            parse(&["extract", "a.zip"]).archive_options().overwrite,
+            OverwritePolicy::Always
+        );
+        let opts = parse(&["extract", "a.zip", "--overwrite-if-newer"]).archive_options();
+        assert_eq!(opts.overwrite, OverwritePolicy::IfNewer);
+        let opts = parse(&["extract", "a.zip", "--overwrite", "never"]).archive_options();
+        assert_eq!(opts.overwrite, OverwritePolicy::Never);
+        assert!(
+            Cli::try_parse_from([
+                "rolypoly",
+                "extract",
+                "a.zip",
+                "--overwrite",
+                "never",
+                "--overwrite-if-newer"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_newer_than_option() {
+        let opts = parse(&["create", "a.zip", "d", "--newer-than", "2024-01-31T00:00:00Z"])
+            .archive_options();
+        let since_epoch = opts.newer_than.unwrap().duration_since(std::time::UNIX_EPOCH).unwrap();
+        assert_eq!(since_epoch.as_secs(), 1_706_659_200);
+        assert!(
+            Cli::try_parse_from(["rolypoly", "create", "a.zip", "--older-than", "yesterday"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("4K"), Ok(4096));
+        assert_eq!(parse_size("10M"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("1.5g"), Ok(3 * 512 * 1024 * 1024));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert!(parse_size("ten").is_err());
+        assert!(parse_size("10Q").is_err());
+
+        let opts = parse(&["create", "a.zip", "d", "--min-size", "1K", "--max-size", "10M"])
+            .archive_options();
+        assert_eq!((opts.min_size, opts.max_size), (Some(1024), Some(10 << 20)));
+    }
+
+    #[test]
+    fn test_cli_explain_create() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive_path = temp_dir.path().join("plan.zip");
+        let cli = parse(&[
+            "create",
+            archive_path.to_str().unwrap(),
+            "missing-input",
+            "--level",
+            "9",
+            "--explain",
+        ]);
+        let mut out = Vec::new();
+        cli.run_with_output(&mut out)?;
+
+        let text = String::from_utf8(out)?;
+        assert!(text.starts_with(&format!("Create the ZIP archive {}\n", archive_path.display())));
+        assert!(text.contains("missing-input"));
+        assert!(text.contains("Compression: deflate at level 9"));
+        assert!(!archive_path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_store_extensions() {
+        let opts =
+            parse(&["create", "a.zip", "d", "--store-extensions", ".iso,img"]).archive_options();
+        assert_eq!(opts.store_extensions, vec!["iso", "img"]);
+        let opts = parse(&["create", "a.zip", "d", "--store-extensions", ""]).archive_options();
+        assert!(opts.store_extensions.is_empty());
+        assert!(
+            parse(&["create", "a.zip", "d"])
+                .archive_options()
+                .store_extensions
+                .contains(&"jpg".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cli_auto_store_flags() {
+        assert!(parse(&["create", "a.zip", "d"]).archive_options().auto_store);
+        assert!(parse(&["--auto-store", "create", "a.zip", "d"]).archive_options().auto_store);
+        let opts = parse(&[
+            "create",
+            "a.zip",
+            "d",
+            "--auto-store=false",
+            "--store-ratio-threshold",
+            "0.8",
+        ])
+        .archive_options();
+        assert!(!opts.auto_store);
+        assert_eq!(opts.store_ratio_threshold, 0.8);
+    }
 }
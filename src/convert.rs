@@ -0,0 +1,316 @@
+//! Converting archives between ZIP and tar formats.
+//!
+//! Entries stream from the input archive straight into the output archive, so
+//! nothing is extracted to disk on the way. Formats are inferred from file names.
+use anyhow::{Context, Result, bail};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// Archive formats `convert` reads and writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Format implied by the file name: `.zip`, `.tar`, `.tar.gz` or `.tgz`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+        }
+    }
+}
+
+/// What a conversion carried across
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ConvertSummary {
+    pub files: usize,
+    pub dirs: usize,
+    /// Links, devices and other entries the conversion does not carry
+    pub skipped: usize,
+    /// Uncompressed bytes of file data copied
+    pub bytes: u64,
+}
+
+/// Convert `input` into a new archive at `output`, formats taken from their names.
+/// A partially written output is removed on failure.
+pub fn convert_archive(input: &Path, output: &Path) -> Result<ConvertSummary> {
+    let input_format = ArchiveFormat::from_path(input)
+        .with_context(|| format!("Unsupported input format: {}", input.display()))?;
+    let output_format = ArchiveFormat::from_path(output)
+        .with_context(|| format!("Unsupported output format: {}", output.display()))?;
+    if std::path::absolute(input)? == std::path::absolute(output)? {
+        bail!("Input and output are the same file: {}", input.display());
+    }
+    let source =
+        File::open(input).with_context(|| format!("Failed to open {}", input.display()))?;
+    let dest =
+        File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    let sink = Sink::new(output_format, BufWriter::new(dest));
+    let result = match input_format {
+        ArchiveFormat::Zip => copy_zip(source, sink),
+        ArchiveFormat::Tar => copy_tar(BufReader::new(source), sink),
+        ArchiveFormat::TarGz => copy_tar(GzDecoder::new(BufReader::new(source)), sink),
+    };
+    if result.is_err() {
+        let _ = std::fs::remove_file(output);
+    }
+    result.with_context(|| format!("Failed to convert {}", input.display()))
+}
+
+/// Entry metadata carried between formats
+struct EntryMeta {
+    name: String,
+    /// Permission bits, without the file type
+    mode: Option<u32>,
+    mtime: Option<SystemTime>,
+}
+
+/// Output archive being written
+enum Sink {
+    Zip(Box<ZipWriter<BufWriter<File>>>),
+    Tar(tar::Builder<BufWriter<File>>),
+    TarGz(tar::Builder<GzEncoder<BufWriter<File>>>),
+}
+
+impl Sink {
+    fn new(format: ArchiveFormat, writer: BufWriter<File>) -> Self {
+        match format {
+            ArchiveFormat::Zip => Sink::Zip(Box::new(ZipWriter::new(writer))),
+            ArchiveFormat::Tar => Sink::Tar(tar::Builder::new(writer)),
+            ArchiveFormat::TarGz => {
+                Sink::TarGz(tar::Builder::new(GzEncoder::new(writer, Compression::default())))
+            }
+        }
+    }
+
+    fn add_dir(&mut self, meta: &EntryMeta) -> Result<()> {
+        match self {
+            Sink::Zip(zip) => zip.add_directory(meta.name.as_str(), zip_options(meta, 0))?,
+            Sink::Tar(builder) => append_tar(builder, meta, tar::EntryType::Directory, 0, &[][..])?,
+            Sink::TarGz(builder) => {
+                append_tar(builder, meta, tar::EntryType::Directory, 0, &[][..])?
+            }
+        }
+        Ok(())
+    }
+
+    fn add_file(&mut self, meta: &EntryMeta, size: u64, data: &mut dyn Read) -> Result<()> {
+        match self {
+            Sink::Zip(zip) => {
+                zip.start_file(meta.name.as_str(), zip_options(meta, size))?;
+                std::io::copy(data, zip.as_mut())?;
+            }
+            Sink::Tar(builder) => append_tar(builder, meta, tar::EntryType::Regular, size, data)?,
+            Sink::TarGz(builder) => append_tar(builder, meta, tar::EntryType::Regular, size, data)?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Sink::Zip(zip) => zip.finish()?.flush()?,
+            Sink::Tar(builder) => builder.into_inner()?.flush()?,
+            Sink::TarGz(builder) => builder.into_inner()?.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+fn zip_options(meta: &EntryMeta, size: u64) -> SimpleFileOptions {
+    let mut options = SimpleFileOptions::default().large_file(size >= u32::MAX as u64);
+    if let Some(mode) = meta.mode {
+        options = options.unix_permissions(mode);
+    }
+    // ZIP timestamps start in 1980; earlier times keep the writer's default
+    if let Some(stamp) = meta.mtime.and_then(crate::archive::system_to_zip_datetime) {
+        options = options.last_modified_time(stamp);
+    }
+    options
+}
+
+fn append_tar<W: Write>(
+    builder: &mut tar::Builder<W>,
+    meta: &EntryMeta,
+    kind: tar::EntryType,
+    size: u64,
+    data: impl Read,
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(kind);
+    header.set_size(size);
+    let default_mode = if kind.is_dir() { 0o755 } else { 0o644 };
+    header.set_mode(meta.mode.unwrap_or(default_mode));
+    let mtime = meta.mtime.and_then(|t| t.duration_since(UNIX_EPOCH).ok());
+    header.set_mtime(mtime.map_or(0, |d| d.as_secs()));
+    builder.append_data(&mut header, &meta.name, data)?;
+    Ok(())
+}
+
+fn copy_zip(source: File, mut sink: Sink) -> Result<ConvertSummary> {
+    let mut archive = ZipArchive::new(BufReader::new(source))?;
+    let mut summary = ConvertSummary::default();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let meta = EntryMeta {
+            name: entry.name().to_string(),
+            mode: entry.unix_mode().map(|mode| mode & 0o7777),
+            mtime: entry.last_modified().and_then(crate::archive::zip_datetime_to_system),
+        };
+        if entry.is_dir() {
+            sink.add_dir(&meta)?;
+            summary.dirs += 1;
+        } else if entry.is_symlink() {
+            summary.skipped += 1;
+        } else {
+            let size = entry.size();
+            sink.add_file(&meta, size, &mut entry)?;
+            summary.files += 1;
+            summary.bytes += size;
+        }
+    }
+    sink.finish()?;
+    Ok(summary)
+}
+
+fn copy_tar<R: Read>(source: R, mut sink: Sink) -> Result<ConvertSummary> {
+    let mut archive = tar::Archive::new(source);
+    let mut summary = ConvertSummary::default();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let header = entry.header();
+        let kind = header.entry_type();
+        if kind.is_pax_global_extensions() {
+            continue;
+        }
+        let mut meta = EntryMeta {
+            name: entry.path()?.to_string_lossy().into_owned(),
+            mode: header.mode().ok().map(|mode| mode & 0o7777),
+            mtime: header.mtime().ok().map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+        };
+        if kind.is_dir() {
+            if !meta.name.ends_with('/') {
+                meta.name.push('/');
+            }
+            sink.add_dir(&meta)?;
+            summary.dirs += 1;
+        } else if kind.is_file() {
+            let size = entry.size();
+            sink.add_file(&meta, size, &mut entry)?;
+            summary.files += 1;
+            summary.bytes += size;
+        } else {
+            summary.skipped += 1;
+        }
+    }
+    sink.finish()?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::ArchiveManager;
+    use tempfile::TempDir;
+
+    fn append(
+        builder: &mut tar::Builder<impl Write>,
+        name: &str,
+        kind: tar::EntryType,
+        data: &[u8],
+    ) {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(1_700_000_000);
+        builder.append_data(&mut header, name, data).unwrap();
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ArchiveFormat::from_path(Path::new("a.ZIP")), Some(ArchiveFormat::Zip));
+        assert_eq!(ArchiveFormat::from_path(Path::new("a.tar")), Some(ArchiveFormat::Tar));
+        assert_eq!(ArchiveFormat::from_path(Path::new("a.tar.gz")), Some(ArchiveFormat::TarGz));
+        assert_eq!(ArchiveFormat::from_path(Path::new("a.tgz")), Some(ArchiveFormat::TarGz));
+        assert_eq!(ArchiveFormat::from_path(Path::new("a.gz")), None);
+    }
+
+    #[test]
+    fn test_convert_tar_gz_to_zip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let tar_gz = temp_dir.path().join("a.tar.gz");
+        let encoder = GzEncoder::new(File::create(&tar_gz)?, Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        append(&mut builder, "docs/", tar::EntryType::Directory, b"");
+        append(&mut builder, "docs/readme.txt", tar::EntryType::Regular, b"read me");
+        append(&mut builder, "data.bin", tar::EntryType::Regular, &[7u8; 5000]);
+        builder.into_inner()?.finish()?;
+
+        let zip_path = temp_dir.path().join("a.zip");
+        let summary = convert_archive(&tar_gz, &zip_path)?;
+        assert_eq!(
+            summary,
+            ConvertSummary {
+                files: 2,
+                dirs: 1,
+                skipped: 0,
+                bytes: 5007
+            }
+        );
+
+        let manager = ArchiveManager::new();
+        assert_eq!(manager.list_archive(&zip_path)?, ["docs/", "docs/readme.txt", "data.bin"]);
+        assert_eq!(manager.extract_entry(&zip_path, "docs/readme.txt")?, b"read me");
+        assert_eq!(manager.extract_entry(&zip_path, "data.bin")?, vec![7u8; 5000]);
+
+        // And back again, through plain tar
+        let tar_path = temp_dir.path().join("b.tar");
+        assert_eq!(convert_archive(&zip_path, &tar_path)?, summary);
+        let mut archive = tar::Archive::new(File::open(&tar_path)?);
+        let mut names = Vec::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            names.push((entry.path()?.to_string_lossy().into_owned(), data.len()));
+        }
+        assert_eq!(
+            names,
+            [
+                ("docs/".to_string(), 0),
+                ("docs/readme.txt".to_string(), 7),
+                ("data.bin".to_string(), 5000)
+            ]
+        );
+
+        assert!(convert_archive(&zip_path, &temp_dir.path().join("c.rar")).is_err());
+        assert!(!temp_dir.path().join("c.rar").exists());
+        Ok(())
+    }
+}
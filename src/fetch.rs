@@ -0,0 +1,247 @@
+//! Fetching archives that aren't local files: URLs, for `extract http://host/a.zip`,
+//! and `-` for an archive piped to stdin.
+//!
+//! With the `net` feature, `http://` and `https://` URLs are downloaded to a temporary
+//! file with `ureq` (TLS through rustls and the bundled webpki roots), following
+//! redirects and reporting progress. Without the feature, every URL is refused and
+//! nothing touches the network.
+//!
+//! Reading a ZIP needs `Seek`, so stdin is copied to a temporary file in full before
+//! anything is read from it: piping an archive costs its size in temporary disk space.
+//! A byte limit (`--max-input`) stops either source from filling the temporary
+//! directory; the partial copy is removed.
+use crate::progress::ProgressReporter;
+use anyhow::{Result, bail};
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Whether an archive argument is a URL rather than a local path
+pub fn is_url(arg: &Path) -> bool {
+    arg.to_str().is_some_and(|s| {
+        let lower = s.to_ascii_lowercase();
+        lower.starts_with("http://") || lower.starts_with("https://")
+    })
+}
+
+/// Whether an archive argument is `-`, meaning stdin
+pub fn is_stdin(arg: &Path) -> bool {
+    arg == Path::new("-")
+}
+
+/// A fetched archive in a temporary file, removed when dropped
+#[derive(Debug)]
+pub struct FetchedArchive {
+    file: tempfile::NamedTempFile,
+}
+
+impl FetchedArchive {
+    /// Create an empty temporary file for an archive from `source`
+    fn temp(source: &str) -> Result<Self> {
+        let file = tempfile::Builder::new()
+            .prefix(&format!("rolypoly-{source}-"))
+            .suffix(".zip")
+            .tempfile_in(crate::temp::temp_dir())?;
+        Ok(Self { file })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+}
+
+/// Copy all of stdin to a temporary file, failing once more than `max_bytes` arrive
+pub fn read_stdin(max_bytes: Option<u64>) -> Result<FetchedArchive> {
+    save_stream(&mut std::io::stdin().lock(), "stdin", max_bytes)
+}
+
+/// Copy `reader` to a temporary file named after `source`
+fn save_stream(
+    reader: &mut dyn Read,
+    source: &str,
+    max_bytes: Option<u64>,
+) -> Result<FetchedArchive> {
+    let mut fetched = FetchedArchive::temp(source)?;
+    let mut file = std::io::BufWriter::new(fetched.file.as_file_mut());
+    // One byte past the limit is enough to tell the input is too large
+    let copied = match max_bytes {
+        Some(max) => std::io::copy(&mut reader.take(max.saturating_add(1)), &mut file)?,
+        None => std::io::copy(reader, &mut file)?,
+    };
+    if let Some(max) = max_bytes {
+        check_size(source, copied, max)?;
+    }
+    file.flush()?;
+    drop(file);
+    Ok(fetched)
+}
+
+/// Fail when `received` bytes from `source` exceed the `--max-input` limit
+fn check_size(source: &str, received: u64, max_bytes: u64) -> Result<()> {
+    if received > max_bytes {
+        bail!(
+            "{source} is larger than the --max-input limit of {max_bytes} bytes; \
+             raise the limit to read it"
+        );
+    }
+    Ok(())
+}
+
+/// URLs need the `net` feature
+#[cfg(not(feature = "net"))]
+pub fn download(
+    url: &str,
+    _max_bytes: Option<u64>,
+    _reporter: &mut dyn ProgressReporter,
+) -> Result<FetchedArchive> {
+    anyhow::bail!("Cannot fetch {url}: rolypoly was built without the `net` feature")
+}
+
+/// Download `url` to a temporary file, following redirects and failing once the body
+/// is larger than `max_bytes`
+#[cfg(feature = "net")]
+pub fn download(
+    url: &str,
+    max_bytes: Option<u64>,
+    reporter: &mut dyn ProgressReporter,
+) -> Result<FetchedArchive> {
+    http::download(url, max_bytes, reporter)
+}
+
+#[cfg(feature = "net")]
+mod http {
+    use super::{FetchedArchive, check_size};
+    use crate::progress::ProgressReporter;
+    use anyhow::{Context, Result, bail};
+    use std::io::{BufWriter, Read, Write};
+    use std::time::Duration;
+
+    /// Redirects followed before giving up
+    const MAX_REDIRECTS: u32 = 10;
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    pub fn download(
+        url: &str,
+        max_bytes: Option<u64>,
+        reporter: &mut dyn ProgressReporter,
+    ) -> Result<FetchedArchive> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(TIMEOUT)
+            .timeout_read(TIMEOUT)
+            .timeout_write(TIMEOUT)
+            .redirects(MAX_REDIRECTS)
+            .user_agent(concat!("rolypoly/", env!("CARGO_PKG_VERSION")))
+            .build();
+        let response = match agent.get(url).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status, _)) => bail!("HTTP {status} fetching {url}"),
+            Err(err) => return Err(err).with_context(|| format!("Failed to fetch {url}")),
+        };
+        let length = response.header("content-length").and_then(|v| v.parse::<u64>().ok());
+        // Refuse an announced oversized body before writing any of it
+        if let (Some(length), Some(max)) = (length, max_bytes) {
+            check_size(url, length, max)?;
+        }
+        let mut download = FetchedArchive::temp("download")?;
+        let mut file = BufWriter::new(download.file.as_file_mut());
+
+        reporter.start(length.unwrap_or(0));
+        reporter.message(&format!("Downloading: {url}"));
+        let mut body = response.into_reader();
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut received = 0u64;
+        loop {
+            let n = body.read(&mut buf).with_context(|| format!("Failed to download {url}"))?;
+            if n == 0 {
+                break;
+            }
+            file.write_all(&buf[..n])?;
+            received += n as u64;
+            if let Some(max) = max_bytes {
+                check_size(url, received, max)?;
+            }
+            reporter.inc(n as u64);
+        }
+        file.flush()?;
+        drop(file);
+        reporter.finish();
+        if let Some(length) = length
+            && received < length
+        {
+            bail!("Download of {url} ended after {received} of {length} bytes");
+        }
+        Ok(download)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_stream_size_cap() -> Result<()> {
+        let data = vec![7u8; 10_000];
+        let fetched = save_stream(&mut &data[..], "stdin", Some(10_000))?;
+        assert_eq!(std::fs::read(fetched.path())?, data);
+
+        let err = save_stream(&mut &data[..], "stdin", Some(4096)).unwrap_err();
+        assert!(err.to_string().contains("stdin is larger than the --max-input limit of 4096"));
+        Ok(())
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_download_follows_redirects() -> Result<()> {
+        use crate::archive::ArchiveManager;
+        use crate::progress::SilentReporter;
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+        use tempfile::TempDir;
+
+        let zip =
+            ArchiveManager::new().create_archive_in_memory(&[("hello.txt", b"hello over http")])?;
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let server = std::thread::spawn(move || -> std::io::Result<Vec<String>> {
+            let mut requested = Vec::new();
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream?;
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&stream);
+                reader.read_line(&mut request_line)?;
+                let mut line = String::new();
+                while reader.read_line(&mut line)? > 2 {
+                    line.clear();
+                }
+                let path = request_line.split_whitespace().nth(1).unwrap_or("").to_string();
+                if path == "/latest.zip" {
+                    write!(stream, "HTTP/1.1 302 Found\r\nLocation: /v1/a.zip\r\n\r\n")?;
+                } else {
+                    write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", zip.len())?;
+                    stream.write_all(&zip)?;
+                }
+                requested.push(path);
+            }
+            Ok(requested)
+        });
+
+        let url = format!("http://127.0.0.1:{port}/latest.zip");
+        assert!(is_url(Path::new(&url)));
+        let download = download(&url, None, &mut SilentReporter)?;
+        assert_eq!(server.join().unwrap()?, ["/latest.zip", "/v1/a.zip"]);
+
+        let temp_dir = TempDir::new()?;
+        ArchiveManager::new().extract_archive(download.path(), temp_dir.path())?;
+        assert_eq!(std::fs::read_to_string(temp_dir.path().join("hello.txt"))?, "hello over http");
+
+        let path = download.path().to_path_buf();
+        drop(download);
+        assert!(!path.exists());
+
+        // https URLs go to the TLS client rather than being refused up front
+        let closed = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+        let url = format!("https://127.0.0.1:{closed}/a.zip");
+        let err = super::download(&url, None, &mut SilentReporter).unwrap_err();
+        assert!(format!("{err:#}").contains("Failed to fetch https://"), "{err:#}");
+        Ok(())
+    }
+}
@@ -1,6 +1,20 @@
 pub mod archive;
+pub mod batch;
+pub mod bench;
 pub mod build_info;
+pub mod central_dir;
 pub mod cli;
+pub mod convert;
+pub mod fetch;
+pub mod logging;
 pub mod operations;
+pub mod password;
 pub mod progress;
+pub mod reveal;
+pub mod settings;
+pub mod sparse;
+pub mod split;
 pub mod state;
+pub mod temp;
+pub mod watch;
+pub mod zipcrypto;
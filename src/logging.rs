@@ -0,0 +1,20 @@
+//! Diagnostic logging via `tracing`, written to stderr.
+//!
+//! `--log-level` takes a level (`debug`) or full filter directives
+//! (`rolypoly=trace,zip=warn`); without it `RUST_LOG` is used, then `warn`.
+use std::io::IsTerminal;
+use tracing_subscriber::EnvFilter;
+
+/// Install the global subscriber; later calls are ignored
+pub fn init(level: Option<&str>) {
+    let filter = match level {
+        Some(directives) => EnvFilter::new(directives),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+    };
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false)
+        .try_init();
+}
@@ -1,44 +1,192 @@
-use crate::archive::ArchiveManager;
+use crate::archive::{ArchiveError, ArchiveManager};
+use crate::progress::ProgressReporter;
 use crate::state::{AppEvent, AppStateManager, Operation, OperationResult};
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use tokio::sync::Semaphore;
 use tokio::task::JoinHandle;
 
+/// Forwards archive progress to the GUI as `AppEvent::OperationProgress` fractions
+struct EventReporter {
+    state_manager: Arc<AppStateManager>,
+    operation: Operation,
+    total: u64,
+    current: u64,
+    // cancelled once the manager's generation moves past the one captured at start
+    cancel_generation: Arc<AtomicU64>,
+    started_generation: u64,
+}
+
+impl EventReporter {
+    fn new(
+        state_manager: Arc<AppStateManager>,
+        operation: Operation,
+        cancel_generation: Arc<AtomicU64>,
+    ) -> Self {
+        let started_generation = cancel_generation.load(Ordering::SeqCst);
+        Self {
+            state_manager,
+            operation,
+            total: 0,
+            current: 0,
+            cancel_generation,
+            started_generation,
+        }
+    }
+
+    fn emit(&self, progress: f64) {
+        self.state_manager
+            .emit_event(AppEvent::OperationProgress(self.operation.clone(), progress));
+    }
+}
+
+impl ProgressReporter for EventReporter {
+    fn start(&mut self, total: u64) {
+        self.total = total;
+        self.current = 0;
+        self.emit(0.0);
+    }
+
+    fn inc(&mut self, n: u64) {
+        self.current += n;
+        if self.total > 0 {
+            self.emit((self.current as f64 / self.total as f64).min(1.0));
+        }
+    }
+
+    fn message(&mut self, _msg: &str) {}
+
+    fn finish(&mut self) {
+        self.emit(1.0);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_generation.load(Ordering::SeqCst) != self.started_generation
+    }
+}
+
+/// Operations allowed to run at once unless configured otherwise
+pub const DEFAULT_CONCURRENCY: usize = 3;
+
+/// Entries per `AppEvent::EntriesListed` event while listing an archive
+pub const LIST_BATCH_SIZE: usize = 256;
+
+/// Counts an operation as running for as long as it is alive
+struct RunningGuard<'a>(&'a AtomicUsize);
+
+impl<'a> RunningGuard<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 pub struct OperationManager {
     archive_manager: Arc<ArchiveManager>,
     state_manager: Arc<AppStateManager>,
     operation_semaphore: Arc<Semaphore>,
     active_operations: Arc<tokio::sync::Mutex<Vec<JoinHandle<()>>>>,
+    concurrency: AtomicUsize,
+    // permits to retire as running operations finish after the limit was lowered
+    pending_shrink: AtomicUsize,
+    running: AtomicUsize,
+    // bumped by `cancel_all_operations`; operations started earlier stop at the next item
+    cancel_generation: Arc<AtomicU64>,
 }
 
 impl OperationManager {
     pub fn new(archive_manager: Arc<ArchiveManager>, state_manager: Arc<AppStateManager>) -> Self {
+        Self::with_concurrency(archive_manager, state_manager, DEFAULT_CONCURRENCY)
+    }
+
+    /// Create a manager that runs at most `limit` operations at once (at least one)
+    pub fn with_concurrency(
+        archive_manager: Arc<ArchiveManager>,
+        state_manager: Arc<AppStateManager>,
+        limit: usize,
+    ) -> Self {
+        let limit = limit.max(1);
         Self {
             archive_manager,
             state_manager,
-            operation_semaphore: Arc::new(Semaphore::new(3)), // Max 3 concurrent operations
+            operation_semaphore: Arc::new(Semaphore::new(limit)),
             active_operations: Arc::new(tokio::sync::Mutex::new(Vec::new())),
+            concurrency: AtomicUsize::new(limit),
+            pending_shrink: AtomicUsize::new(0),
+            running: AtomicUsize::new(0),
+            cancel_generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency.load(Ordering::SeqCst)
+    }
+
+    /// Change the concurrency limit; lowering it lets running operations finish first
+    pub fn set_concurrency(&self, limit: usize) {
+        let limit = limit.max(1);
+        let old = self.concurrency.swap(limit, Ordering::SeqCst);
+        if limit > old {
+            let mut grow = limit - old;
+            // Cancel out retirements that have not happened yet before adding permits
+            let _ = self.pending_shrink.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |p| {
+                let cancelled = p.min(grow);
+                grow -= cancelled;
+                Some(p - cancelled)
+            });
+            self.operation_semaphore.add_permits(grow);
+        } else if limit < old {
+            let shrink = old - limit;
+            let forgotten = self.operation_semaphore.forget_permits(shrink);
+            self.pending_shrink.fetch_add(shrink - forgotten, Ordering::SeqCst);
         }
     }
 
     pub async fn execute_operation(&self, operation: Operation) -> Result<OperationResult, String> {
         // Acquire semaphore permit for concurrency control
-        let _permit = self.operation_semaphore.acquire().await.map_err(|e| e.to_string())?;
+        let permit = self.operation_semaphore.acquire().await.map_err(|e| e.to_string())?;
+        let _running = RunningGuard::new(&self.running);
+        let result = self.run_operation(operation).await;
+
+        let retire = self
+            .pending_shrink
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |p| p.checked_sub(1))
+            .is_ok();
+        if retire {
+            permit.forget();
+        }
+        result
+    }
 
+    async fn run_operation(&self, operation: Operation) -> Result<OperationResult, String> {
+        // Made before the start is announced, so a cancel sent on seeing it still applies
+        let reporter = self.event_reporter(operation.clone());
         self.state_manager.emit_event(AppEvent::OperationStarted(operation.clone()));
 
         let result = match operation.clone() {
             Operation::CreateArchive { output, files } => {
-                self.create_archive_with_progress(output, files).await
+                self.create_archive_with_progress(output, files, reporter).await
             }
             Operation::ExtractArchive { archive, output } => {
-                self.extract_archive_with_progress(archive, output).await
+                self.extract_archive_with_progress(archive, output, reporter).await
             }
             Operation::ValidateArchive { archive } => {
-                self.validate_archive_with_progress(archive).await
+                self.validate_archive_with_progress(archive, reporter).await
             }
             Operation::CalculateHash { file } => self.calculate_hash_with_progress(file).await,
+            Operation::ListArchive { archive } => {
+                self.list_archive_in_batches(archive, reporter.started_generation).await
+            }
+            Operation::AddToArchive { archive, files } => {
+                self.add_to_archive_with_progress(archive, files, reporter).await
+            }
         };
 
         match &result {
@@ -55,40 +203,53 @@ impl OperationManager {
         result
     }
 
+    /// Progress reporter for `operation`, cancelled by any `cancel_all_operations` from
+    /// now on. It is made before the work goes to the blocking pool, so a cancel issued
+    /// while the work waits for a thread still stops it.
+    fn event_reporter(&self, operation: Operation) -> EventReporter {
+        EventReporter::new(self.state_manager.clone(), operation, self.cancel_generation.clone())
+    }
+
     async fn create_archive_with_progress(
         &self,
         output: PathBuf,
         files: Vec<PathBuf>,
+        mut reporter: EventReporter,
     ) -> Result<OperationResult, String> {
         let archive_manager = self.archive_manager.clone();
-        let state_manager = self.state_manager.clone();
-        let operation = Operation::CreateArchive {
-            output: output.clone(),
-            files: files.clone(),
-        };
         let output_clone = output.clone();
 
         // Run in blocking task to avoid blocking the async runtime
         let result = tokio::task::spawn_blocking(move || {
-            // Simulate progress updates
-            for i in 0..=100 {
-                let progress = i as f64 / 100.0;
-                state_manager.emit_event(AppEvent::OperationProgress(operation.clone(), progress));
+            let file_refs: Vec<&PathBuf> = files.iter().collect();
+            archive_manager.create_archive_with_reporter(&output, &file_refs, &mut reporter)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
 
-                if i < 100 {
-                    std::thread::sleep(std::time::Duration::from_millis(10));
-                }
-            }
+        result
+            .map(|_| OperationResult::ArchiveCreated(output_clone))
+            .map_err(|e| e.to_string())
+    }
 
-            // Perform actual archive creation
+    async fn add_to_archive_with_progress(
+        &self,
+        archive: PathBuf,
+        files: Vec<PathBuf>,
+        mut reporter: EventReporter,
+    ) -> Result<OperationResult, String> {
+        let archive_manager = self.archive_manager.clone();
+        let archive_clone = archive.clone();
+
+        let result = tokio::task::spawn_blocking(move || {
             let file_refs: Vec<&PathBuf> = files.iter().collect();
-            archive_manager.create_archive(&output, &file_refs)
+            archive_manager.add_to_archive_with_reporter(&archive, &file_refs, &mut reporter)
         })
         .await
         .map_err(|e| e.to_string())?;
 
         result
-            .map(|_| OperationResult::ArchiveCreated(output_clone))
+            .map(|_| OperationResult::ArchiveUpdated(archive_clone))
             .map_err(|e| e.to_string())
     }
 
@@ -96,28 +257,13 @@ impl OperationManager {
         &self,
         archive: PathBuf,
         output: PathBuf,
+        mut reporter: EventReporter,
     ) -> Result<OperationResult, String> {
         let archive_manager = self.archive_manager.clone();
-        let state_manager = self.state_manager.clone();
-        let operation = Operation::ExtractArchive {
-            archive: archive.clone(),
-            output: output.clone(),
-        };
         let output_clone = output.clone();
 
         let result = tokio::task::spawn_blocking(move || {
-            // Simulate progress updates
-            for i in 0..=100 {
-                let progress = i as f64 / 100.0;
-                state_manager.emit_event(AppEvent::OperationProgress(operation.clone(), progress));
-
-                if i < 100 {
-                    std::thread::sleep(std::time::Duration::from_millis(10));
-                }
-            }
-
-            // Perform actual extraction
-            archive_manager.extract_archive(&archive, &output)
+            archive_manager.extract_archive_with_reporter(&archive, &output, &mut reporter)
         })
         .await
         .map_err(|e| e.to_string())?;
@@ -130,26 +276,12 @@ impl OperationManager {
     async fn validate_archive_with_progress(
         &self,
         archive: PathBuf,
+        mut reporter: EventReporter,
     ) -> Result<OperationResult, String> {
         let archive_manager = self.archive_manager.clone();
-        let state_manager = self.state_manager.clone();
-        let operation = Operation::ValidateArchive {
-            archive: archive.clone(),
-        };
 
         let result = tokio::task::spawn_blocking(move || {
-            // Simulate progress updates
-            for i in 0..=100 {
-                let progress = i as f64 / 100.0;
-                state_manager.emit_event(AppEvent::OperationProgress(operation.clone(), progress));
-
-                if i < 100 {
-                    std::thread::sleep(std::time::Duration::from_millis(5));
-                }
-            }
-
-            // Perform actual validation
-            archive_manager.validate_archive(&archive)
+            archive_manager.validate_archive_with_reporter(&archive, &mut reporter)
         })
         .await
         .map_err(|e| e.to_string())?;
@@ -182,23 +314,63 @@ impl OperationManager {
         result.map(OperationResult::HashCalculated).map_err(|e| e.to_string())
     }
 
+    /// List an archive off the async runtime, emitting entries in batches as they are read.
+    /// A cancel moving the generation past `started_generation` stops it.
+    async fn list_archive_in_batches(
+        &self,
+        archive: PathBuf,
+        started_generation: u64,
+    ) -> Result<OperationResult, String> {
+        let archive_manager = self.archive_manager.clone();
+        let state_manager = self.state_manager.clone();
+        let cancel_generation = self.cancel_generation.clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            let mut batch = Vec::with_capacity(LIST_BATCH_SIZE);
+            let mut count = 0;
+            archive_manager.list_archive_with_callback(&archive, |entry| {
+                if cancel_generation.load(Ordering::SeqCst) != started_generation {
+                    return Err(ArchiveError::Cancelled.into());
+                }
+                batch.push(entry);
+                count += 1;
+                if batch.len() == LIST_BATCH_SIZE {
+                    let full = std::mem::replace(&mut batch, Vec::with_capacity(LIST_BATCH_SIZE));
+                    state_manager.emit_event(AppEvent::EntriesListed(archive.clone(), full));
+                }
+                Ok(())
+            })?;
+            if !batch.is_empty() {
+                state_manager.emit_event(AppEvent::EntriesListed(archive.clone(), batch));
+            }
+            Ok::<_, anyhow::Error>(count)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+        result.map(OperationResult::ArchiveListed).map_err(|e| e.to_string())
+    }
+
+    /// Stop every running operation; archive work ends with a cancellation error
     pub async fn cancel_all_operations(&self) {
+        self.cancel_generation.fetch_add(1, Ordering::SeqCst);
         let mut operations = self.active_operations.lock().await;
         for handle in operations.drain(..) {
             handle.abort();
         }
     }
 
+    /// Operations currently running (holding a concurrency slot)
     pub async fn get_active_operation_count(&self) -> usize {
         let operations = self.active_operations.lock().await;
-        operations.len()
+        operations.len().max(self.running.load(Ordering::SeqCst))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::state::AppStateManager;
+    use crate::state::{AppState, AppStateManager};
     use std::sync::Arc;
     use tempfile::TempDir;
 
@@ -235,4 +407,210 @@ mod tests {
             panic!("Expected HashCalculated result");
         }
     }
+
+    #[tokio::test]
+    async fn test_create_emits_real_progress() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        std::fs::write(&test_file, "Hello, World!").unwrap();
+
+        let archive_manager = Arc::new(ArchiveManager::new());
+        let state_manager = Arc::new(AppStateManager::new());
+        let mut receiver = state_manager.subscribe();
+        let op_manager = OperationManager::new(archive_manager, state_manager);
+
+        let operation = Operation::CreateArchive {
+            output: temp_dir.path().join("test.zip"),
+            files: vec![test_file],
+        };
+        op_manager.execute_operation(operation).await.unwrap();
+
+        let mut progress = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let AppEvent::OperationProgress(_, p) = event {
+                progress.push(p);
+            }
+        }
+        assert_eq!(progress.first(), Some(&0.0));
+        assert_eq!(progress.last(), Some(&1.0));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrency_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        std::fs::write(&test_file, "Hello, World!").unwrap();
+
+        let op_manager = Arc::new(OperationManager::with_concurrency(
+            Arc::new(ArchiveManager::new()),
+            Arc::new(AppStateManager::new()),
+            2,
+        ));
+        assert_eq!(op_manager.concurrency(), 2);
+
+        let handles: Vec<_> = (0..5)
+            .map(|_| {
+                let op_manager = op_manager.clone();
+                let operation = Operation::CalculateHash {
+                    file: test_file.clone(),
+                };
+                tokio::spawn(async move { op_manager.execute_operation(operation).await })
+            })
+            .collect();
+
+        let mut max_active = 0;
+        while !handles.iter().all(|h| h.is_finished()) {
+            max_active = max_active.max(op_manager.get_active_operation_count().await);
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        for handle in handles {
+            assert!(handle.await.unwrap().is_ok());
+        }
+        assert!(max_active >= 1);
+        assert!(max_active <= 2, "{max_active} operations ran at once");
+        assert_eq!(op_manager.get_active_operation_count().await, 0);
+
+        op_manager.set_concurrency(1);
+        assert_eq!(op_manager.operation_semaphore.available_permits(), 1);
+        op_manager.set_concurrency(4);
+        assert_eq!(op_manager.operation_semaphore.available_permits(), 4);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_cancel_extraction() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_path = temp_dir.path().join("large.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive_path).unwrap());
+        for i in 0..5000 {
+            zip.start_file(format!("f{i}.txt"), zip::write::SimpleFileOptions::default())
+                .unwrap();
+        }
+        zip.finish().unwrap();
+
+        let state_manager = Arc::new(AppStateManager::new());
+        let mut receiver = state_manager.subscribe();
+        let op_manager =
+            Arc::new(OperationManager::new(Arc::new(ArchiveManager::new()), state_manager));
+        let operation = Operation::ExtractArchive {
+            archive: archive_path,
+            output: temp_dir.path().join("out"),
+        };
+        let handle = {
+            let op_manager = op_manager.clone();
+            tokio::spawn(async move { op_manager.execute_operation(operation).await })
+        };
+
+        // Cancel as soon as extraction reports its first progress
+        while !matches!(receiver.recv().await, Ok(AppEvent::OperationProgress(..))) {}
+        op_manager.cancel_all_operations().await;
+
+        let error = handle.await.unwrap().unwrap_err();
+        assert_eq!(error, "Operation cancelled");
+        let extracted = std::fs::read_dir(temp_dir.path().join("out")).unwrap().count();
+        assert!(extracted < 5000);
+    }
+
+    #[test]
+    fn test_cancel_before_work_starts() {
+        // One blocking thread, held busy so the extraction waits in the queue
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .max_blocking_threads(1)
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let temp_dir = TempDir::new().unwrap();
+            let archive_path = temp_dir.path().join("small.zip");
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive_path).unwrap());
+            zip.start_file("a.txt", zip::write::SimpleFileOptions::default()).unwrap();
+            zip.finish().unwrap();
+
+            let state_manager = Arc::new(AppStateManager::new());
+            let mut receiver = state_manager.subscribe();
+            let op_manager =
+                Arc::new(OperationManager::new(Arc::new(ArchiveManager::new()), state_manager));
+            let (release, blocked) = std::sync::mpsc::channel::<()>();
+            let blocker = tokio::task::spawn_blocking(move || blocked.recv());
+            let operation = Operation::ExtractArchive {
+                archive: archive_path,
+                output: temp_dir.path().join("out"),
+            };
+            let handle = {
+                let op_manager = op_manager.clone();
+                tokio::spawn(async move { op_manager.execute_operation(operation).await })
+            };
+
+            while !matches!(receiver.recv().await, Ok(AppEvent::OperationStarted(..))) {}
+            op_manager.cancel_all_operations().await;
+            release.send(()).unwrap();
+            blocker.await.unwrap().unwrap();
+
+            let error = handle.await.unwrap().unwrap_err();
+            assert_eq!(error, "Operation cancelled");
+        });
+    }
+
+    #[tokio::test]
+    async fn test_list_archive_streams_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_path = temp_dir.path().join("many.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive_path).unwrap());
+        let count = LIST_BATCH_SIZE * 2 + 10;
+        for i in 0..count {
+            zip.start_file(format!("f{i}.txt"), zip::write::SimpleFileOptions::default())
+                .unwrap();
+        }
+        zip.finish().unwrap();
+
+        let state_manager = Arc::new(AppStateManager::new());
+        let mut receiver = state_manager.subscribe();
+        let op_manager = OperationManager::new(Arc::new(ArchiveManager::new()), state_manager);
+        let result = op_manager
+            .execute_operation(Operation::ListArchive {
+                archive: archive_path,
+            })
+            .await
+            .unwrap();
+        assert!(matches!(result, OperationResult::ArchiveListed(n) if n == count));
+
+        let mut batches = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let AppEvent::EntriesListed(_, entries) = event {
+                batches.push(entries);
+            }
+        }
+        let sizes: Vec<usize> = batches.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [LIST_BATCH_SIZE, LIST_BATCH_SIZE, 10]);
+        assert_eq!(batches[2].last().unwrap().name, format!("f{}.txt", count - 1));
+    }
+
+    #[tokio::test]
+    async fn test_add_to_archive_operation() {
+        let temp_dir = TempDir::new().unwrap();
+        let existing = temp_dir.path().join("existing.txt");
+        let dropped = temp_dir.path().join("dropped.txt");
+        std::fs::write(&existing, "existing").unwrap();
+        std::fs::write(&dropped, "dropped").unwrap();
+        let archive_path = temp_dir.path().join("open.zip");
+        let archive_manager = Arc::new(ArchiveManager::new());
+        archive_manager.create_archive(&archive_path, &[&existing]).unwrap();
+
+        let state_manager = Arc::new(AppStateManager::new());
+        state_manager
+            .transition_to(AppState::ArchiveLoaded(archive_path.clone()))
+            .unwrap();
+        let operation = state_manager.files_dropped(vec![dropped]).unwrap().unwrap();
+        state_manager.transition_to(AppState::Processing(operation.clone())).unwrap();
+
+        let op_manager = OperationManager::new(archive_manager.clone(), state_manager.clone());
+        let result = op_manager.execute_operation(operation).await.unwrap();
+        assert!(matches!(result, OperationResult::ArchiveUpdated(ref p) if *p == archive_path));
+        state_manager
+            .transition_to(AppState::ArchiveLoaded(archive_path.clone()))
+            .unwrap();
+
+        let mut names = archive_manager.list_archive(&archive_path).unwrap();
+        names.sort();
+        assert_eq!(names, ["dropped.txt", "existing.txt"]);
+    }
 }
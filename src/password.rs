@@ -0,0 +1,153 @@
+//! Password sources for encrypted archives.
+//!
+//! Passwords given on the command line end up in shell history and `ps` output,
+//! so a password file or the `ROLYPOLY_PASSWORD` environment variable are preferred.
+use anyhow::{Context, Result, bail};
+use std::io::IsTerminal;
+use std::path::Path;
+
+/// Environment variable consulted when no password flag or file is given
+pub const PASSWORD_ENV: &str = "ROLYPOLY_PASSWORD";
+
+/// Read a password from the first line of `path`, trimming surrounding whitespace
+pub fn read_password_file(path: &Path) -> Result<String> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read password file {}", path.display()))?;
+    let password = contents.lines().next().unwrap_or("").trim();
+    if password.is_empty() {
+        bail!("Password file {} is empty", path.display());
+    }
+    Ok(password.to_string())
+}
+
+/// Resolve a non-interactive password: password file, then explicit value, then environment
+pub fn resolve_password(explicit: Option<&str>, file: Option<&Path>) -> Result<Option<String>> {
+    resolve_from(explicit, file, std::env::var(PASSWORD_ENV).ok())
+}
+
+fn resolve_from(
+    explicit: Option<&str>,
+    file: Option<&Path>,
+    env: Option<String>,
+) -> Result<Option<String>> {
+    if let Some(file) = file {
+        return read_password_file(file).map(Some);
+    }
+    if let Some(password) = explicit {
+        return Ok(Some(password.to_string()));
+    }
+    Ok(env.filter(|p| !p.is_empty()))
+}
+
+/// Source of interactively entered passwords
+pub trait PasswordPrompt {
+    /// Ask for a password; `None` when no one can answer
+    fn prompt(&mut self, prompt: &str) -> Result<Option<String>>;
+}
+
+/// Prompts on the terminal without echo
+pub struct TerminalPrompt;
+
+impl PasswordPrompt for TerminalPrompt {
+    fn prompt(&mut self, prompt: &str) -> Result<Option<String>> {
+        if !std::io::stdin().is_terminal() {
+            return Ok(None);
+        }
+        Ok(Some(rpassword::prompt_password(prompt)?))
+    }
+}
+
+/// Prompt for a password without echo; `None` when stdin is not a terminal
+pub fn prompt_password(prompt: &str) -> Result<Option<String>> {
+    TerminalPrompt.prompt(prompt)
+}
+
+/// Prompt for a new password twice; a mistyped password would lock the archive
+/// for good, so the entries must match
+pub fn prompt_new_password(prompter: &mut dyn PasswordPrompt) -> Result<Option<String>> {
+    let Some(password) = prompter.prompt("Password: ")? else {
+        return Ok(None);
+    };
+    let confirmation = prompter.prompt("Confirm password: ")?;
+    if confirmation.as_deref() != Some(password.as_str()) {
+        bail!("Passwords do not match");
+    }
+    Ok(Some(password))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_password_file_reads_first_line_trimmed() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("pw.txt");
+        fs::write(&path, "  s3cret \nsecond line\n")?;
+        assert_eq!(read_password_file(&path)?, "s3cret");
+
+        fs::write(&path, "\n")?;
+        assert!(read_password_file(&path).is_err());
+        Ok(())
+    }
+
+    /// Answers prompts from a script, recording what was asked
+    struct ScriptedPrompt {
+        answers: Vec<Option<&'static str>>,
+        asked: Vec<String>,
+    }
+
+    impl PasswordPrompt for ScriptedPrompt {
+        fn prompt(&mut self, prompt: &str) -> Result<Option<String>> {
+            self.asked.push(prompt.to_string());
+            Ok(self.answers.remove(0).map(String::from))
+        }
+    }
+
+    fn scripted(answers: &[Option<&'static str>]) -> ScriptedPrompt {
+        ScriptedPrompt {
+            answers: answers.to_vec(),
+            asked: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_new_password_requires_confirmation() -> Result<()> {
+        let mut prompt = scripted(&[Some("hunter2"), Some("hunter2")]);
+        assert_eq!(prompt_new_password(&mut prompt)?.as_deref(), Some("hunter2"));
+        assert_eq!(prompt.asked, ["Password: ", "Confirm password: "]);
+
+        let mut prompt = scripted(&[Some("hunter2"), Some("hunter3")]);
+        let err = prompt_new_password(&mut prompt).unwrap_err();
+        assert!(err.to_string().contains("do not match"));
+
+        // No terminal: nothing to confirm
+        let mut prompt = scripted(&[None]);
+        assert_eq!(prompt_new_password(&mut prompt)?, None);
+        assert_eq!(prompt.asked.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_password_precedence() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("pw.txt");
+        fs::write(&path, "from-file\n")?;
+        let env = Some("from-env".to_string());
+
+        assert_eq!(
+            resolve_from(Some("from-flag"), Some(&path), env.clone())?.as_deref(),
+            Some("from-file")
+        );
+        assert_eq!(
+            resolve_from(Some("from-flag"), None, env.clone())?.as_deref(),
+            Some("from-flag")
+        );
+        assert_eq!(resolve_from(None, None, env)?.as_deref(), Some("from-env"));
+        assert_eq!(resolve_from(None, None, Some(String::new()))?, None);
+        assert_eq!(resolve_from(None, None, None)?, None);
+        Ok(())
+    }
+}
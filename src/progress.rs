@@ -1,4 +1,8 @@
-use std::sync::OnceLock;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 #[derive(Copy, Clone)]
 pub struct OutputMode {
@@ -20,8 +24,286 @@ pub fn output_mode() -> OutputMode {
     })
 }
 
+/// Where JSON events go instead of stdout, once `set_progress_fd` has been called
+static PROGRESS_SINK: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+/// Send JSON events to the open file descriptor `fd` rather than stdout, leaving stdout
+/// for results. Platforms without file descriptors fall back to stderr.
+pub fn set_progress_fd(fd: i32) -> std::io::Result<()> {
+    #[cfg(unix)]
+    let sink: Box<dyn Write + Send> = {
+        use std::os::fd::BorrowedFd;
+        if fd < 0 {
+            return Err(std::io::Error::from_raw_os_error(libc::EBADF));
+        }
+        // SAFETY: the descriptor is only borrowed long enough to duplicate it; one that
+        // isn't open makes the duplication fail with EBADF
+        let owned = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+        Box::new(std::fs::File::from(owned))
+    };
+    #[cfg(not(unix))]
+    let sink: Box<dyn Write + Send> = {
+        let _ = fd;
+        Box::new(std::io::stderr())
+    };
+    let _ = PROGRESS_SINK.set(Mutex::new(sink));
+    Ok(())
+}
+
 pub fn print_json<T: serde::Serialize>(value: &T) {
-    if let Ok(s) = serde_json::to_string(value) {
-        println!("{}", s);
+    let Ok(s) = serde_json::to_string(value) else {
+        return;
+    };
+    match PROGRESS_SINK.get() {
+        Some(sink) => {
+            let mut sink = sink.lock().unwrap_or_else(|e| e.into_inner());
+            // A reader that went away shouldn't stop the operation
+            let _ = writeln!(sink, "{s}").and_then(|()| sink.flush());
+        }
+        None => println!("{}", s),
+    }
+}
+
+/// Emit JSON progress for every this many items, once set by `set_progress_every`
+static PROGRESS_EVERY: OnceLock<u64> = OnceLock::new();
+
+/// Limit JSON progress to one event per `every` items (plus the final one) instead of
+/// the default chosen from the total
+pub fn set_progress_every(every: u64) {
+    let _ = PROGRESS_EVERY.set(every.max(1));
+}
+
+/// Default interval between JSON progress events: every item, up to about a hundred
+/// events per operation
+fn auto_progress_every(total: u64) -> u64 {
+    (total / 100).max(1)
+}
+
+/// Span of recent progress that throughput is averaged over
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(5);
+
+/// Bytes per second over a rolling window of progress samples, so one slow or
+/// fast item doesn't swing the rate
+pub struct Throughput {
+    window: Duration,
+    // (time, total bytes done by then), oldest first
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl Throughput {
+    /// Start measuring at `start` with nothing done
+    pub fn new(window: Duration, start: Instant) -> Self {
+        Self {
+            window,
+            samples: VecDeque::from([(start, 0)]),
+        }
+    }
+
+    /// Record that `done` bytes in total have been processed as of `now`
+    pub fn record(&mut self, now: Instant, done: u64) {
+        self.samples.push_back((now, done));
+        // Keep the newest sample at least a window old as the baseline
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= self.window {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Average rate across the window, `None` until time has passed
+    pub fn bytes_per_sec(&self) -> Option<f64> {
+        let (&(first_at, first), &(last_at, last)) = (self.samples.front()?, self.samples.back()?);
+        let elapsed = last_at.duration_since(first_at).as_secs_f64();
+        (elapsed > 0.0).then(|| last.saturating_sub(first) as f64 / elapsed)
+    }
+
+    /// Seconds left for `remaining` bytes at the current rate
+    pub fn eta_seconds(&self, remaining: u64) -> Option<f64> {
+        self.bytes_per_sec()
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| remaining as f64 / rate)
+    }
+}
+
+/// State behind an operation's JSON `progress` events: item counts plus byte
+/// throughput and the estimated time left
+pub struct JsonProgress {
+    total: u64,
+    // items between emitted events
+    every: u64,
+    total_bytes: u64,
+    bytes_done: u64,
+    throughput: Throughput,
+}
+
+impl JsonProgress {
+    pub fn new(total: u64, total_bytes: u64) -> Self {
+        Self {
+            total,
+            every: PROGRESS_EVERY.get().copied().unwrap_or_else(|| auto_progress_every(total)),
+            total_bytes,
+            bytes_done: 0,
+            throughput: Throughput::new(THROUGHPUT_WINDOW, Instant::now()),
+        }
+    }
+
+    /// Event for item number `current`, counting its `bytes` as processed. `None`
+    /// between intervals; the last item always gets one.
+    pub fn event(
+        &mut self,
+        op: &str,
+        file: &str,
+        current: u64,
+        bytes: u64,
+    ) -> Option<serde_json::Value> {
+        self.bytes_done += bytes;
+        self.throughput.record(Instant::now(), self.bytes_done);
+        if !current.is_multiple_of(self.every) && current < self.total {
+            return None;
+        }
+        let pct = if self.total > 0 {
+            current as f64 / self.total as f64
+        } else {
+            0.0
+        };
+        Some(serde_json::json!({
+            "event": "progress", "op": op, "file": file,
+            "current": current, "total": self.total, "pct": pct,
+            "bytes_per_sec": self.throughput.bytes_per_sec(),
+            "eta_seconds": self.throughput.eta_seconds(self.total_bytes.saturating_sub(self.bytes_done))
+        }))
+    }
+}
+
+/// Receives progress updates from long-running archive operations.
+///
+/// Archive logic only talks to this trait, so callers decide how progress is
+/// surfaced: a terminal bar for the CLI, events for the GUI, or nothing at all.
+pub trait ProgressReporter {
+    /// Called once before any work with the number of units to process
+    fn start(&mut self, total: u64);
+    /// Advance progress by `n` units
+    fn inc(&mut self, n: u64);
+    /// Describe the item currently being processed
+    fn message(&mut self, msg: &str);
+    /// Called once when the operation has finished
+    fn finish(&mut self);
+    /// Polled between items; returning `true` stops the operation with a cancellation error
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Reporter that discards all progress updates
+#[derive(Default)]
+pub struct SilentReporter;
+
+impl ProgressReporter for SilentReporter {
+    fn start(&mut self, _total: u64) {}
+    fn inc(&mut self, _n: u64) {}
+    fn message(&mut self, _msg: &str) {}
+    fn finish(&mut self) {}
+}
+
+/// Reporter that renders an `indicatif` progress bar on the terminal
+#[derive(Default)]
+pub struct IndicatifReporter {
+    pb: Option<ProgressBar>,
+}
+
+impl IndicatifReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ProgressReporter for IndicatifReporter {
+    fn start(&mut self, total: u64) {
+        let pb = ProgressBar::new(total);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    "{spinner:.green} [{elapsed_precise}] {wide_bar:.cyan/blue} {pos:>5}/{len:<5} {percent:>3}% {eta_precise} | {msg}"
+                )
+                .unwrap()
+                .progress_chars("█· "),
+        );
+        self.pb = Some(pb);
+    }
+
+    fn inc(&mut self, n: u64) {
+        if let Some(pb) = &self.pb {
+            pb.inc(n);
+        }
+    }
+
+    fn message(&mut self, msg: &str) {
+        if let Some(pb) = &self.pb {
+            pb.set_message(msg.to_string());
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(pb) = &self.pb {
+            pb.finish();
+        }
+    }
+}
+
+/// Reporter matching the process-wide output mode: a progress bar for
+/// interactive human output, silence otherwise (JSON progress is emitted separately).
+pub fn default_reporter() -> Box<dyn ProgressReporter> {
+    let mode = output_mode();
+    if mode.progress && !mode.json {
+        Box::new(IndicatifReporter::new())
+    } else {
+        Box::new(SilentReporter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throughput_window() {
+        let start = Instant::now();
+        let mut throughput = Throughput::new(Duration::from_secs(5), start);
+        assert_eq!(throughput.bytes_per_sec(), None);
+
+        // 1 MB/s steadily for ten seconds, then a spike of 50 MB in one second
+        for second in 1..=10 {
+            throughput.record(start + Duration::from_secs(second), second * 1_000_000);
+        }
+        let steady = throughput.bytes_per_sec().unwrap();
+        assert!(steady.is_finite() && (steady - 1_000_000.0).abs() < 1.0, "{steady}");
+        assert_eq!(throughput.eta_seconds(3_000_000).map(f64::round), Some(3.0));
+
+        throughput.record(start + Duration::from_secs(11), 60_000_000);
+        let smoothed = throughput.bytes_per_sec().unwrap();
+        assert!(smoothed.is_finite() && smoothed > steady, "{smoothed}");
+        assert!(smoothed < 50_000_000.0, "spike not smoothed: {smoothed}");
+    }
+
+    #[test]
+    fn test_json_progress_event() {
+        let mut progress = JsonProgress::new(2, 2_000);
+        std::thread::sleep(Duration::from_millis(5));
+        let event = progress.event("extract", "a.txt", 1, 1_000).unwrap();
+        assert_eq!(event["current"], 1);
+        assert_eq!(event["pct"], 0.5);
+        let rate = event["bytes_per_sec"].as_f64().unwrap();
+        assert!(rate > 0.0 && rate.is_finite());
+        assert!(event["eta_seconds"].as_f64().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_json_progress_interval() {
+        assert_eq!(auto_progress_every(50), 1);
+        assert_eq!(auto_progress_every(250_000), 2_500);
+
+        let mut progress = JsonProgress::new(25, 0);
+        progress.every = 10;
+        let emitted: Vec<u64> =
+            (1..=25).filter(|&i| progress.event("create", "f", i, 0).is_some()).collect();
+        assert_eq!(emitted, [10, 20, 25]);
     }
 }
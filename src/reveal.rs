@@ -0,0 +1,72 @@
+//! Showing a finished operation's result in the platform file manager.
+//!
+//! A file is selected in its folder where the file manager supports that (Finder,
+//! Explorer); elsewhere its folder is opened. A directory is opened itself.
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use std::process::Command;
+
+/// Open the file manager at `path`, which must exist. Returns once the file manager
+/// has been started, without waiting for it.
+pub fn reveal_in_file_manager(path: &Path) -> Result<()> {
+    if !path.exists() {
+        bail!("Cannot reveal {}: it does not exist", path.display());
+    }
+    let path = std::path::absolute(path)?;
+    reveal_command(&path, path.is_dir())
+        .spawn()
+        .with_context(|| format!("Failed to open a file manager at {}", path.display()))?;
+    Ok(())
+}
+
+/// The platform command that shows `path`, an absolute path
+fn reveal_command(path: &Path, is_dir: bool) -> Command {
+    let mut command;
+    if cfg!(target_os = "macos") {
+        command = Command::new("open");
+        if !is_dir {
+            command.arg("-R");
+        }
+        command.arg(path);
+    } else if cfg!(windows) {
+        command = Command::new("explorer");
+        match is_dir {
+            true => command.arg(path),
+            // Explorer only understands the switch and path as one argument
+            false => command.arg(format!("/select,{}", path.display())),
+        };
+    } else {
+        // xdg-open has no way to select a file, so open the folder holding it
+        command = Command::new("xdg-open");
+        command.arg(match is_dir {
+            true => path,
+            false => path.parent().unwrap_or(path),
+        });
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_reveal_missing_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing = temp_dir.path().join("gone.zip");
+        let err = reveal_in_file_manager(&missing).unwrap_err();
+        assert!(err.to_string().contains("does not exist"), "{err}");
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_reveal_command_opens_folder() {
+        let file = Path::new("/data/out/archive.zip");
+        let command = reveal_command(file, false);
+        assert_eq!(command.get_program(), "xdg-open");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["/data/out"]);
+        let command = reveal_command(Path::new("/data/out"), true);
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["/data/out"]);
+    }
+}
@@ -0,0 +1,150 @@
+//! User preferences persisted between runs.
+//!
+//! Settings live as JSON in the platform config directory (for example
+//! `~/.config/rolypoly/settings.json` on Linux). A missing file, or missing
+//! fields, fall back to the defaults, which match the behavior without settings.
+use crate::archive::ArchiveOptions;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// File name inside the config directory
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// How new archives compress their entries by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionMethod {
+    #[default]
+    Deflated,
+    Stored,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Follow the operating system
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Deflate level 0-9; `None` uses the library default
+    pub compression_level: Option<i32>,
+    pub compression_method: CompressionMethod,
+    pub theme: Theme,
+    /// Where extracted files go when no output directory is chosen; `None` means
+    /// the current directory
+    pub extract_dir: Option<PathBuf>,
+    /// Recently opened or created archives, newest first
+    pub recent_archives: Vec<PathBuf>,
+}
+
+impl Settings {
+    /// Platform location of the settings file, if a home directory can be found
+    pub fn default_path() -> Option<PathBuf> {
+        directories::ProjectDirs::from("", "", "rolypoly")
+            .map(|dirs| dirs.config_dir().join(SETTINGS_FILE))
+    }
+
+    /// Load settings from the platform location, or defaults when there are none
+    pub fn load_default() -> Result<Self> {
+        match Self::default_path() {
+            Some(path) => Self::load(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load settings from `path`; a missing file yields the defaults
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid settings file {}", path.display()))
+    }
+
+    /// Write settings to `path`, creating its directory; the old file is replaced
+    /// only once the new one is fully written
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", temp.display()))?;
+        std::fs::rename(&temp, path)
+            .with_context(|| format!("Failed to replace {}", path.display()))
+    }
+
+    /// Apply the compression defaults to archive options
+    pub fn apply(&self, opts: &mut ArchiveOptions) {
+        opts.compression_level = match self.compression_method {
+            CompressionMethod::Stored => Some(0),
+            CompressionMethod::Deflated => self.compression_level,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_settings_serde() -> Result<()> {
+        let settings = Settings {
+            compression_level: Some(9),
+            compression_method: CompressionMethod::Deflated,
+            theme: Theme::Dark,
+            extract_dir: Some(PathBuf::from("/tmp/out")),
+            recent_archives: vec![PathBuf::from("/tmp/a.zip")],
+        };
+        let json = serde_json::to_value(&settings)?;
+        assert_eq!(json["theme"], "dark");
+        assert_eq!(json["compression_method"], "deflated");
+        assert_eq!(serde_json::from_value::<Settings>(json)?, settings);
+
+        // Fields missing from the file keep their defaults
+        let partial: Settings = serde_json::from_str(r#"{"theme":"light"}"#)?;
+        assert_eq!(
+            partial,
+            Settings {
+                theme: Theme::Light,
+                ..Default::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_settings_file_round_trip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("config/rolypoly").join(SETTINGS_FILE);
+        assert_eq!(Settings::load(&path)?, Settings::default());
+
+        let settings = Settings {
+            compression_method: CompressionMethod::Stored,
+            ..Default::default()
+        };
+        settings.save(&path)?;
+        assert_eq!(Settings::load(&path)?, settings);
+
+        let mut opts = ArchiveOptions::default();
+        settings.apply(&mut opts);
+        assert_eq!(opts.compression_level, Some(0));
+
+        std::fs::write(&path, "not json")?;
+        assert!(Settings::load(&path).is_err());
+        Ok(())
+    }
+}
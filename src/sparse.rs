@@ -0,0 +1,107 @@
+//! Reading sparse files without materializing their holes.
+//!
+//! On Linux, `SEEK_DATA`/`SEEK_HOLE` map out which byte ranges are backed by
+//! storage; holes are synthesized as zeros instead of being read from disk.
+//! Other platforms (and filesystems without hole support) read normally.
+use std::fs::File;
+use std::io::{self, Read};
+
+/// A reader over a file that yields zeros for holes without touching the disk
+pub struct SparseReader {
+    file: File,
+    /// Data regions as `(start, end)` byte offsets, in file order
+    segments: Vec<(u64, u64)>,
+    len: u64,
+    pos: u64,
+    /// Offset the underlying file is positioned at; `None` until the first seek
+    file_pos: Option<u64>,
+}
+
+impl SparseReader {
+    /// Map the data regions of `file`; the whole file counts as data when holes
+    /// cannot be detected
+    pub fn new(file: File) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+        let segments = data_segments(&file, len)?;
+        Ok(SparseReader {
+            file,
+            segments,
+            len,
+            pos: 0,
+            file_pos: None,
+        })
+    }
+}
+
+impl Read for SparseReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let remaining = (self.len - self.pos).min(buf.len() as u64);
+        // Segments are sorted and disjoint, so the first one ending past `pos` is found
+        // by binary search
+        let index = self.segments.partition_point(|&(_, end)| end <= self.pos);
+        match self.segments.get(index) {
+            Some(&(start, end)) if start <= self.pos => {
+                if self.file_pos != Some(self.pos) {
+                    io::Seek::seek(&mut self.file, io::SeekFrom::Start(self.pos))?;
+                }
+                let want = remaining.min(end - self.pos) as usize;
+                let read = self.file.read(&mut buf[..want])?;
+                self.pos += read as u64;
+                self.file_pos = Some(self.pos);
+                Ok(read)
+            }
+            next => {
+                // Inside a hole: zeros up to the next data region or end of file
+                let hole_end = next.map_or(self.len, |&(start, _)| start);
+                let zeros = remaining.min(hole_end - self.pos) as usize;
+                buf[..zeros].fill(0);
+                self.pos += zeros as u64;
+                Ok(zeros)
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn data_segments(file: &File, len: u64) -> io::Result<Vec<(u64, u64)>> {
+    use std::os::fd::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let seek = |offset: u64, whence: libc::c_int| -> io::Result<Option<u64>> {
+        // SAFETY: lseek on a descriptor owned by `file`, which outlives this call
+        let result = unsafe { libc::lseek(fd, offset as libc::off_t, whence) };
+        if result >= 0 {
+            return Ok(Some(result as u64));
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            // No more data past `offset`
+            Some(libc::ENXIO) => Ok(None),
+            _ => Err(err),
+        }
+    };
+
+    let mut segments = Vec::new();
+    let mut offset = 0;
+    while offset < len {
+        let start = match seek(offset, libc::SEEK_DATA) {
+            Ok(Some(start)) => start,
+            Ok(None) => break,
+            // Filesystem without hole support
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL) => return Ok(vec![(0, len)]),
+            Err(err) => return Err(err),
+        };
+        let end = seek(start, libc::SEEK_HOLE)?.unwrap_or(len).min(len);
+        segments.push((start, end));
+        offset = end;
+    }
+    Ok(segments)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn data_segments(_file: &File, len: u64) -> io::Result<Vec<(u64, u64)>> {
+    Ok(vec![(0, len)])
+}
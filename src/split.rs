@@ -0,0 +1,299 @@
+//! Size-limited archive volumes.
+//!
+//! A split archive is an ordinary archive cut into pieces: `out.zip.001`, `out.zip.002`,
+//! ... hold the leading bytes and `out.zip` the rest, including the central directory.
+//! Offsets stay relative to the joined bytes, so the set reads as one archive once
+//! the volumes are put back end to end (`cat out.zip.0* out.zip` rebuilds it), which is
+//! what [`VolumeReader`] does. This is not PKZIP's spanned format: the `zip` crate
+//! can neither write nor read per-disk offsets.
+//!
+//! The last volume records the split itself: its central directory sits further into
+//! the joined stream than into the file, by exactly the bytes of the leading volumes.
+//! Numbered files beside an archive are only joined or removed when their sizes add
+//! up to that gap, so files this tool didn't write are left alone.
+use crate::central_dir;
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Path of volume `n` (1-based) of the split archive `archive`
+pub fn volume_path(archive: &Path, n: usize) -> PathBuf {
+    let mut name = archive.as_os_str().to_owned();
+    name.push(format!(".{n:03}"));
+    PathBuf::from(name)
+}
+
+/// Bytes that precede `archive` in the joined stream, going by where its central
+/// directory says it is; 0 for a plain archive or a file that isn't one
+fn missing_prefix(archive: &Path) -> u64 {
+    let Ok(file) = File::open(archive) else {
+        return 0;
+    };
+    match central_dir::find_eocd(&mut BufReader::new(file)) {
+        Ok(eocd) => (eocd.cd_offset + eocd.cd_size).saturating_sub(eocd.cd_end),
+        Err(_) => 0,
+    }
+}
+
+/// Leading volumes of `archive` found on disk, in order; empty for a plain archive.
+/// Fails when `archive` is the last volume of a split whose other volumes are missing
+/// or don't fit it.
+pub fn leading_volumes(archive: &Path) -> io::Result<Vec<PathBuf>> {
+    let missing = missing_prefix(archive);
+    let mut volumes = Vec::new();
+    let mut total = 0;
+    while total < missing {
+        let path = volume_path(archive, volumes.len() + 1);
+        let Some(meta) = std::fs::metadata(&path).ok().filter(|m| m.is_file()) else {
+            break;
+        };
+        total += meta.len();
+        volumes.push(path);
+    }
+    if total != missing {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} is the last volume of a split archive, but its leading volumes ({} ...) \
+                 are missing or don't belong to it",
+                archive.display(),
+                volume_path(archive, 1).display()
+            ),
+        ));
+    }
+    Ok(volumes)
+}
+
+/// Remove the leading volumes of the split archive at `archive` before it is replaced.
+/// Numbered files that aren't part of its split are kept.
+pub fn remove_volumes(archive: &Path) -> Result<()> {
+    for volume in leading_volumes(archive).unwrap_or_default() {
+        std::fs::remove_file(&volume)
+            .with_context(|| format!("Failed to remove {}", volume.display()))?;
+    }
+    Ok(())
+}
+
+/// Fail when `archive` is split, for changes that can only rewrite a single file
+pub fn ensure_single_volume(archive: &Path) -> Result<()> {
+    if !leading_volumes(archive)?.is_empty() {
+        anyhow::bail!(
+            "{} is split into volumes and can't be modified in place; create it again instead",
+            archive.display()
+        );
+    }
+    Ok(())
+}
+
+/// Cut the finished archive at `archive` into volumes of at most `volume_size` bytes,
+/// returning how many there are. `archive` keeps the last volume; an archive that
+/// already fits is left alone.
+pub fn split_archive(archive: &Path, volume_size: u64) -> Result<usize> {
+    if volume_size == 0 {
+        anyhow::bail!("Split size must be greater than zero");
+    }
+    let len = std::fs::metadata(archive)?.len();
+    let count = len.div_ceil(volume_size).max(1) as usize;
+    if count == 1 {
+        return Ok(1);
+    }
+    let mut written = Vec::new();
+    let result = write_volumes(archive, volume_size, count, &mut written);
+    if result.is_err() {
+        for volume in written {
+            let _ = std::fs::remove_file(volume);
+        }
+    }
+    result.with_context(|| format!("Failed to split {}", archive.display()))?;
+    Ok(count)
+}
+
+fn write_volumes(
+    archive: &Path,
+    volume_size: u64,
+    count: usize,
+    written: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut source = File::open(archive)?;
+    for n in 1..count {
+        let path = volume_path(archive, n);
+        let mut volume = BufWriter::new(File::create(&path)?);
+        written.push(path);
+        io::copy(&mut (&mut source).take(volume_size), &mut volume)?;
+        volume.flush()?;
+    }
+    // The last volume replaces the archive through a temporary copy
+    let temp = crate::archive::sibling_temp_path(archive);
+    let mut last = BufWriter::new(File::create(&temp)?);
+    io::copy(&mut source, &mut last)?;
+    last.flush()?;
+    drop(last);
+    std::fs::rename(&temp, archive).inspect_err(|_| {
+        let _ = std::fs::remove_file(&temp);
+    })?;
+    Ok(())
+}
+
+/// Reads a split archive's volumes as one stream; a plain archive is a single volume
+pub struct VolumeReader {
+    // (file, offset of its first byte in the joined stream)
+    volumes: Vec<(File, u64)>,
+    len: u64,
+    pos: u64,
+}
+
+impl VolumeReader {
+    /// Open `archive` together with any leading volumes beside it
+    pub fn open(archive: &Path) -> io::Result<Self> {
+        let mut volumes = Vec::new();
+        let mut len = 0;
+        for path in leading_volumes(archive)?.iter().map(PathBuf::as_path).chain([archive]) {
+            let file = File::open(path)?;
+            let size = file.metadata()?.len();
+            volumes.push((file, len));
+            len += size;
+        }
+        Ok(Self {
+            volumes,
+            len,
+            pos: 0,
+        })
+    }
+
+    /// Number of volumes in the set
+    pub fn volume_count(&self) -> usize {
+        self.volumes.len()
+    }
+}
+
+impl Read for VolumeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let index = self.volumes.partition_point(|(_, start)| *start <= self.pos) - 1;
+        let end = self.volumes.get(index + 1).map_or(self.len, |(_, start)| *start);
+        let (file, start) = &mut self.volumes[index];
+        file.seek(SeekFrom::Start(self.pos - *start))?;
+        let want = buf.len().min((end - self.pos) as usize);
+        let n = file.read(&mut buf[..want])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for VolumeReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the archive")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{ArchiveManager, ArchiveOptions};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_split_and_extract() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let input = temp_dir.path().join("data");
+        fs::create_dir(&input)?;
+        // Pseudo-random bytes so deflate can't shrink them below the split size
+        let mut state = 0x2545_f491_u32;
+        let noise: Vec<u8> = (0..150_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        fs::write(input.join("noise.bin"), &noise)?;
+        fs::write(input.join("small.txt"), "small")?;
+
+        let archive_path = temp_dir.path().join("out.zip");
+        let manager = ArchiveManager::with_options(ArchiveOptions {
+            split_size: Some(64 * 1024),
+            ..Default::default()
+        });
+        manager.create_archive(&archive_path, &[&input])?;
+        assert_eq!(leading_volumes(&archive_path)?.len(), 2);
+        assert!(volume_path(&archive_path, 1).ends_with("out.zip.001"));
+        for n in 1..=2 {
+            assert_eq!(fs::metadata(volume_path(&archive_path, n))?.len(), 64 * 1024);
+        }
+        assert_eq!(VolumeReader::open(&archive_path)?.volume_count(), 3);
+
+        let mut names = manager.list_archive(&archive_path)?;
+        names.sort();
+        assert_eq!(names, ["data/noise.bin", "data/small.txt"]);
+        let out = temp_dir.path().join("out");
+        manager.extract_archive(&archive_path, &out)?;
+        assert_eq!(fs::read(out.join("data/noise.bin"))?, noise);
+        assert_eq!(fs::read_to_string(out.join("data/small.txt"))?, "small");
+
+        // Joining the volumes gives back an ordinary archive
+        let joined = temp_dir.path().join("joined.zip");
+        let mut bytes = Vec::new();
+        VolumeReader::open(&archive_path)?.read_to_end(&mut bytes)?;
+        fs::write(&joined, bytes)?;
+        assert!(manager.validate_archive(&joined)?);
+
+        // Recreating without a split drops the old volumes
+        ArchiveManager::new().create_archive(&archive_path, &[&input])?;
+        assert!(leading_volumes(&archive_path)?.is_empty());
+        assert!(!volume_path(&archive_path, 1).exists());
+        assert!(manager.validate_archive(&archive_path)?);
+
+        // A split archive can't be appended to as if it were one file
+        manager.create_archive(&archive_path, &[&input])?;
+        let err = manager.add_to_archive(&archive_path, &[&input]).unwrap_err();
+        assert!(err.to_string().contains("split into volumes"));
+
+        // With a leading volume missing the set is reported rather than misread
+        fs::remove_file(volume_path(&archive_path, 2))?;
+        let err = manager.list_archive(&archive_path).unwrap_err();
+        assert!(format!("{err:#}").contains("missing or don't belong"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unrelated_numbered_files_are_kept() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let input = temp_dir.path().join("a.txt");
+        fs::write(&input, "hello")?;
+        let archive_path = temp_dir.path().join("out.zip");
+        let stray = [
+            archive_path.with_extension("z01"),
+            volume_path(&archive_path, 1),
+            volume_path(&archive_path, 2),
+        ];
+        for path in &stray {
+            fs::write(path, "not a volume")?;
+        }
+
+        // Neither creating nor reading the archive touches files it didn't write
+        let manager = ArchiveManager::new();
+        manager.create_archive(&archive_path, &[&input])?;
+        manager.create_archive(&archive_path, &[&input])?;
+        for path in &stray {
+            assert_eq!(fs::read_to_string(path)?, "not a volume");
+        }
+        assert_eq!(VolumeReader::open(&archive_path)?.volume_count(), 1);
+        assert_eq!(manager.list_archive(&archive_path)?, ["a.txt"]);
+        manager.add_to_archive(&archive_path, &[&temp_dir.path().join("out.zip.001")])?;
+        assert_eq!(manager.list_archive(&archive_path)?.len(), 2);
+        Ok(())
+    }
+}
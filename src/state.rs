@@ -1,3 +1,6 @@
+use crate::archive::EntryInfo;
+use crate::settings::Settings;
+use std::collections::VecDeque;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use tokio::sync::broadcast;
@@ -11,6 +14,8 @@ pub enum AppEvent {
     OperationCompleted(Operation, OperationResult),
     OperationFailed(Operation, String),
     StateChanged(AppState),
+    /// A batch of entries from a running `ListArchive` operation, in archive order
+    EntriesListed(PathBuf, Vec<EntryInfo>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -29,14 +34,24 @@ pub enum Operation {
     CalculateHash {
         file: PathBuf,
     },
+    ListArchive {
+        archive: PathBuf,
+    },
+    AddToArchive {
+        archive: PathBuf,
+        files: Vec<PathBuf>,
+    },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum OperationResult {
     ArchiveCreated(PathBuf),
     ArchiveExtracted(PathBuf),
     ArchiveValidated(bool),
     HashCalculated(String),
+    /// Number of entries delivered through `AppEvent::EntriesListed`
+    ArchiveListed(usize),
+    ArchiveUpdated(PathBuf),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -45,11 +60,21 @@ pub enum AppState {
     FilesSelected(Vec<PathBuf>),
     ArchiveLoaded(PathBuf),
     Processing(Operation),
+    /// An operation just succeeded; keeps its result (e.g. the archive written) so the
+    /// GUI can offer follow-ups such as opening the containing folder
+    Completed(OperationResult),
     Error(String),
 }
 
+/// Most archives remembered in the recent list
+pub const MAX_RECENT: usize = 10;
+
 pub struct AppStateManager {
     state: Arc<Mutex<AppState>>,
+    // most recently opened or created archives, newest first
+    recent: Arc<Mutex<VecDeque<PathBuf>>>,
+    // settings file the recent list is saved to, if any
+    settings_path: Option<PathBuf>,
     event_sender: broadcast::Sender<AppEvent>,
     _event_receiver: broadcast::Receiver<AppEvent>,
 }
@@ -59,11 +84,26 @@ impl AppStateManager {
         let (event_sender, event_receiver) = broadcast::channel(100);
         Self {
             state: Arc::new(Mutex::new(AppState::Empty)),
+            recent: Arc::new(Mutex::new(VecDeque::with_capacity(MAX_RECENT))),
+            settings_path: None,
             event_sender,
             _event_receiver: event_receiver,
         }
     }
 
+    /// A manager whose recent list starts from the settings file at `path` (see
+    /// [`Settings::default_path`]) and is written back to it whenever it changes
+    pub fn with_settings_file(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let recent = Settings::load(&path)?.recent_archives;
+        let manager = Self {
+            settings_path: Some(path),
+            ..Self::new()
+        };
+        manager.set_recent(recent);
+        Ok(manager)
+    }
+
     pub fn get_state(&self) -> AppState {
         self.state.lock().unwrap().clone()
     }
@@ -81,22 +121,98 @@ impl AppStateManager {
     }
 
     pub fn emit_event(&self, event: AppEvent) {
+        match &event {
+            AppEvent::ArchiveOpened(path)
+            | AppEvent::OperationCompleted(_, OperationResult::ArchiveCreated(path)) => {
+                self.push_recent(path.clone());
+            }
+            _ => {}
+        }
         let _ = self.event_sender.send(event);
     }
 
+    /// Move `path` to the front of the recent list, dropping the oldest past the cap,
+    /// and save the list to the settings file
+    pub fn push_recent(&self, path: PathBuf) {
+        self.remember(path);
+        self.save_recent();
+    }
+
+    /// Replace the recent list, e.g. with the one saved in the settings file. Nothing
+    /// is saved until the next `push_recent`.
+    pub fn set_recent(&self, paths: impl IntoIterator<Item = PathBuf>) {
+        self.recent.lock().unwrap().clear();
+        let paths: Vec<PathBuf> = paths.into_iter().collect();
+        for path in paths.into_iter().rev() {
+            self.remember(path);
+        }
+    }
+
+    fn remember(&self, path: PathBuf) {
+        let mut recent = self.recent.lock().unwrap();
+        recent.retain(|p| *p != path);
+        recent.push_front(path);
+        recent.truncate(MAX_RECENT);
+    }
+
+    /// Write the recent list into the settings file, keeping its other settings. A
+    /// failure is logged rather than failing the operation that opened the archive.
+    fn save_recent(&self) {
+        let Some(path) = &self.settings_path else {
+            return;
+        };
+        let recent: Vec<PathBuf> = self.recent.lock().unwrap().iter().cloned().collect();
+        let saved = Settings::load(path).and_then(|mut settings| {
+            settings.recent_archives = recent;
+            settings.save(path)
+        });
+        if let Err(err) = saved {
+            tracing::warn!(path = %path.display(), error = %format!("{err:#}"), "failed to save recent archives");
+        }
+    }
+
+    /// Recent archives, newest first, leaving out any that no longer exist
+    pub fn recent_archives(&self) -> Vec<PathBuf> {
+        let mut recent = self.recent.lock().unwrap();
+        recent.retain(|p| p.exists());
+        recent.iter().cloned().collect()
+    }
+
+    /// Move to `new_state` if the GUI can get there from the current state:
+    ///
+    /// | from            | to                                             |
+    /// |-----------------|------------------------------------------------|
+    /// | `Empty`         | `FilesSelected`, `ArchiveLoaded`               |
+    /// | `FilesSelected` | `FilesSelected`, `ArchiveLoaded`, `Processing` |
+    /// | `ArchiveLoaded` | `ArchiveLoaded`, `FilesSelected`, `Processing` |
+    /// | `Processing`    | any state (`Processing`: the next queued one)  |
+    /// | `Completed`     | `Empty`, `FilesSelected`, `ArchiveLoaded`      |
+    /// | `Error`         | `Empty`, `FilesSelected`, `ArchiveLoaded`      |
+    ///
+    /// Switching between `FilesSelected` and `ArchiveLoaded` is changing between
+    /// compress and extract mode. [`reset`](Self::reset) returns to `Empty` from anywhere.
     pub fn transition_to(&self, new_state: AppState) -> Result<(), String> {
         let current_state = self.get_state();
 
         // Validate state transitions
         match (&current_state, &new_state) {
             (AppState::Empty, AppState::FilesSelected(_)) => Ok(()),
+            (AppState::FilesSelected(_), AppState::FilesSelected(_)) => Ok(()),
+            (AppState::FilesSelected(_), AppState::ArchiveLoaded(_)) => Ok(()),
             (AppState::Empty, AppState::ArchiveLoaded(_)) => Ok(()),
+            (AppState::ArchiveLoaded(_), AppState::ArchiveLoaded(_)) => Ok(()),
+            (AppState::ArchiveLoaded(_), AppState::FilesSelected(_)) => Ok(()),
             (AppState::FilesSelected(_), AppState::Processing(_)) => Ok(()),
             (AppState::ArchiveLoaded(_), AppState::Processing(_)) => Ok(()),
+            (AppState::Processing(_), AppState::Processing(_)) => Ok(()),
             (AppState::Processing(_), AppState::Empty) => Ok(()),
             (AppState::Processing(_), AppState::FilesSelected(_)) => Ok(()),
             (AppState::Processing(_), AppState::ArchiveLoaded(_)) => Ok(()),
+            (AppState::Processing(_), AppState::Completed(_)) => Ok(()),
             (AppState::Processing(_), AppState::Error(_)) => Ok(()),
+            (AppState::Completed(_), AppState::Empty) => Ok(()),
+            (AppState::Completed(_), AppState::FilesSelected(_)) => Ok(()),
+            (AppState::Completed(_), AppState::ArchiveLoaded(_)) => Ok(()),
             (AppState::Error(_), AppState::Empty) => Ok(()),
             (AppState::Error(_), AppState::FilesSelected(_)) => Ok(()),
             (AppState::Error(_), AppState::ArchiveLoaded(_)) => Ok(()),
@@ -108,6 +224,23 @@ impl AppStateManager {
         self.set_state(new_state);
         Ok(())
     }
+
+    /// Drop the selection, loaded archive or error and start over
+    pub fn reset(&self) {
+        self.set_state(AppState::Empty);
+    }
+
+    /// Handle files dropped onto the window. With an archive loaded this returns the
+    /// `AddToArchive` operation to offer the user; otherwise the files become the
+    /// new selection.
+    pub fn files_dropped(&self, files: Vec<PathBuf>) -> Result<Option<Operation>, String> {
+        if let AppState::ArchiveLoaded(archive) = self.get_state() {
+            return Ok(Some(Operation::AddToArchive { archive, files }));
+        }
+        self.transition_to(AppState::FilesSelected(files.clone()))?;
+        self.emit_event(AppEvent::FilesAdded(files));
+        Ok(None)
+    }
 }
 
 impl Default for AppStateManager {
@@ -152,6 +285,165 @@ mod tests {
         assert!(state_manager.transition_to(AppState::Processing(operation)).is_err());
     }
 
+    #[test]
+    fn test_mode_switch_and_queued_transitions() {
+        let state_manager = AppStateManager::new();
+        let archive = PathBuf::from("open.zip");
+        let files = vec![PathBuf::from("test.txt")];
+
+        // Extract mode to compress mode and back
+        assert!(state_manager.transition_to(AppState::ArchiveLoaded(archive.clone())).is_ok());
+        assert!(state_manager.transition_to(AppState::FilesSelected(files.clone())).is_ok());
+        assert!(state_manager.transition_to(AppState::ArchiveLoaded(archive.clone())).is_ok());
+        let other = PathBuf::from("other.zip");
+        assert!(state_manager.transition_to(AppState::ArchiveLoaded(other)).is_ok());
+
+        // A queued operation follows the running one directly
+        let extract = Operation::ExtractArchive {
+            archive: archive.clone(),
+            output: PathBuf::from("out"),
+        };
+        let validate = Operation::ValidateArchive { archive };
+        assert!(state_manager.transition_to(AppState::Processing(extract)).is_ok());
+        assert!(state_manager.transition_to(AppState::Processing(validate.clone())).is_ok());
+        assert_eq!(state_manager.get_state(), AppState::Processing(validate));
+
+        state_manager.reset();
+        assert_eq!(state_manager.get_state(), AppState::Empty);
+        state_manager.set_state(AppState::FilesSelected(files));
+        state_manager.reset();
+        assert_eq!(state_manager.get_state(), AppState::Empty);
+
+        // Still rejected, leaving the state alone
+        let error = AppState::Error("boom".to_string());
+        assert!(state_manager.transition_to(error.clone()).is_err());
+        state_manager.set_state(error.clone());
+        let operation = Operation::CalculateHash {
+            file: PathBuf::from("test.txt"),
+        };
+        assert!(state_manager.transition_to(AppState::Processing(operation)).is_err());
+        assert!(state_manager.transition_to(error.clone()).is_err());
+        assert_eq!(state_manager.get_state(), error);
+    }
+
+    #[test]
+    fn test_completed_state() {
+        let state_manager = AppStateManager::new();
+        let files = vec![PathBuf::from("test.txt")];
+        let output = PathBuf::from("test.zip");
+        let done = AppState::Completed(OperationResult::ArchiveCreated(output.clone()));
+
+        // Only a running operation can complete
+        assert!(state_manager.transition_to(done.clone()).is_err());
+        state_manager.transition_to(AppState::FilesSelected(files.clone())).unwrap();
+        assert!(state_manager.transition_to(done.clone()).is_err());
+        let operation = Operation::CreateArchive {
+            output: output.clone(),
+            files: files.clone(),
+        };
+        state_manager.transition_to(AppState::Processing(operation.clone())).unwrap();
+        assert!(state_manager.transition_to(done.clone()).is_ok());
+        assert_eq!(state_manager.get_state(), done);
+
+        // From the success panel: start over, pick new files or open the new archive
+        for next in [
+            AppState::Empty,
+            AppState::FilesSelected(files.clone()),
+            AppState::ArchiveLoaded(output.clone()),
+        ] {
+            state_manager.set_state(done.clone());
+            assert!(state_manager.transition_to(next).is_ok());
+        }
+        for next in [AppState::Processing(operation), done.clone(), AppState::Error("x".into())] {
+            state_manager.set_state(done.clone());
+            assert!(state_manager.transition_to(next).is_err());
+        }
+    }
+
+    #[test]
+    fn test_files_dropped() {
+        let state_manager = AppStateManager::new();
+        let files = vec![PathBuf::from("new.txt")];
+
+        assert_eq!(state_manager.files_dropped(files.clone()), Ok(None));
+        assert_eq!(state_manager.get_state(), AppState::FilesSelected(files.clone()));
+
+        let archive = PathBuf::from("open.zip");
+        state_manager.set_state(AppState::ArchiveLoaded(archive.clone()));
+        let operation = state_manager.files_dropped(files.clone()).unwrap();
+        assert_eq!(
+            operation,
+            Some(Operation::AddToArchive {
+                archive: archive.clone(),
+                files
+            })
+        );
+        assert_eq!(state_manager.get_state(), AppState::ArchiveLoaded(archive));
+    }
+
+    #[test]
+    fn test_recent_archives() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = |i: usize| temp_dir.path().join(format!("a{i}.zip"));
+        for i in 0..12 {
+            std::fs::write(path(i), "").unwrap();
+        }
+
+        let state_manager = AppStateManager::new();
+        for i in 0..12 {
+            state_manager.emit_event(AppEvent::ArchiveOpened(path(i)));
+        }
+        // Reopening moves an archive to the front instead of duplicating it
+        let created = Operation::CreateArchive {
+            output: path(5),
+            files: vec![],
+        };
+        state_manager.emit_event(AppEvent::OperationCompleted(
+            created,
+            OperationResult::ArchiveCreated(path(5)),
+        ));
+
+        let recent = state_manager.recent_archives();
+        assert_eq!(recent.len(), MAX_RECENT);
+        assert_eq!(recent[0], path(5));
+        assert_eq!(recent[1], path(11));
+        assert_eq!(recent.iter().filter(|p| **p == path(5)).count(), 1);
+        assert!(!recent.contains(&path(0)) && !recent.contains(&path(1)));
+
+        std::fs::remove_file(path(11)).unwrap();
+        assert!(!state_manager.recent_archives().contains(&path(11)));
+
+        state_manager.set_recent(vec![path(2), path(3), path(2)]);
+        assert_eq!(state_manager.recent_archives(), [path(2), path(3)]);
+    }
+
+    #[test]
+    fn test_recent_archives_saved_with_settings() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let settings_path = temp_dir.path().join("config").join(crate::settings::SETTINGS_FILE);
+        let settings = Settings {
+            theme: crate::settings::Theme::Dark,
+            ..Default::default()
+        };
+        settings.save(&settings_path).unwrap();
+        let path = |i: usize| temp_dir.path().join(format!("a{i}.zip"));
+        for i in 0..2 {
+            std::fs::write(path(i), "").unwrap();
+        }
+
+        let state_manager = AppStateManager::with_settings_file(&settings_path).unwrap();
+        assert!(state_manager.recent_archives().is_empty());
+        state_manager.emit_event(AppEvent::ArchiveOpened(path(0)));
+        state_manager.emit_event(AppEvent::ArchiveOpened(path(1)));
+
+        // A later run starts from the saved list, and the other settings are kept
+        let reopened = AppStateManager::with_settings_file(&settings_path).unwrap();
+        assert_eq!(reopened.recent_archives(), [path(1), path(0)]);
+        let saved = Settings::load(&settings_path).unwrap();
+        assert_eq!(saved.theme, crate::settings::Theme::Dark);
+        assert_eq!(saved.recent_archives, [path(1), path(0)]);
+    }
+
     #[test]
     fn test_event_system() {
         let state_manager = AppStateManager::new();
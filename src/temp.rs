@@ -0,0 +1,29 @@
+//! Where operations put temporary files and directories.
+//!
+//! Piped or downloaded archives and benchmark scratch space go to the system temp
+//! directory unless `--temp-dir` or `ROLYPOLY_TMPDIR` names another. Files written
+//! beside their target to be renamed over it (see `sibling_temp_path`) stay there, so
+//! the rename never crosses filesystems.
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Environment variable read when `--temp-dir` isn't given
+pub const TEMP_DIR_ENV: &str = "ROLYPOLY_TMPDIR";
+
+static TEMP_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use `dir`, which must be an existing directory, for temporary files from now on
+pub fn set_temp_dir(dir: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        bail!("Temporary directory {} does not exist", dir.display());
+    }
+    // ignore if already set within process; subsequent calls are no-ops
+    let _ = TEMP_DIR.set(dir.to_path_buf());
+    Ok(())
+}
+
+/// The configured temporary directory, or the system one
+pub fn temp_dir() -> PathBuf {
+    TEMP_DIR.get().cloned().unwrap_or_else(std::env::temp_dir)
+}
@@ -0,0 +1,167 @@
+//! Appending files to an archive as they appear in a directory.
+//!
+//! File system events are collected until the directory has been quiet for the
+//! debounce interval, then the batch is added in one pass. New files are appended in
+//! place, so each batch costs what it adds rather than a copy of the whole archive; a
+//! batch that changes files already archived rewrites it through a temporary copy.
+//! Either way the archive on disk is complete between batches and stopping never
+//! leaves it half written.
+use crate::archive::{ArchiveManager, sibling_temp_path};
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// How often the stop flag is checked while no events arrive
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Watch `dir` and append files created or modified in it to `archive` until `stop`
+/// is set, calling `on_added` for each appended file. The archive is created if
+/// needed. A modified file replaces its earlier entry. Returns how many files were
+/// added, counting each replacement.
+pub fn watch_directory(
+    manager: &ArchiveManager,
+    dir: &Path,
+    archive: &Path,
+    debounce: Duration,
+    stop: &AtomicBool,
+    on_added: &mut dyn FnMut(&Path) -> Result<()>,
+) -> Result<usize> {
+    if !dir.is_dir() {
+        anyhow::bail!("Not a directory: {}", dir.display());
+    }
+    if !archive.exists() {
+        let file = File::create(archive)
+            .with_context(|| format!("Failed to create {}", archive.display()))?;
+        zip::ZipWriter::new(file).finish()?;
+    }
+    let archive = std::path::absolute(archive)?;
+    let ignored = [archive.clone(), sibling_temp_path(&archive)];
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {}", dir.display()))?;
+
+    let mut pending = BTreeSet::new();
+    let mut last_event = Instant::now();
+    let mut added = 0;
+    loop {
+        let stopping = stop.load(Ordering::SeqCst);
+        let quiet = last_event.elapsed() >= debounce;
+        if !pending.is_empty() && (quiet || stopping) {
+            let batch: Vec<PathBuf> = std::mem::take(&mut pending)
+                .into_iter()
+                .filter(|path: &PathBuf| path.is_file())
+                .collect();
+            if !batch.is_empty() {
+                let files: Vec<&Path> = batch.iter().map(PathBuf::as_path).collect();
+                let mut reporter = crate::progress::default_reporter();
+                manager.update_archive(&archive, &files, reporter.as_mut())?;
+                for path in &batch {
+                    on_added(path)?;
+                }
+                added += batch.len();
+            }
+        }
+        if stopping {
+            return Ok(added);
+        }
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(event) => {
+                let event = event?;
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        let path = std::path::absolute(&path)?;
+                        if !ignored.contains(&path) {
+                            pending.insert(path);
+                        }
+                    }
+                    last_event = Instant::now();
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(added),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_watch_appends_new_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let logs = temp_dir.path().join("logs");
+        fs::create_dir(&logs)?;
+        // Kept inside the watched directory to check the archive ignores itself
+        let archive = logs.join("logs.zip");
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let watcher = {
+            let (logs, archive, stop) = (logs.clone(), archive.clone(), stop.clone());
+            std::thread::spawn(move || {
+                let mut names = Vec::new();
+                let added = watch_directory(
+                    &ArchiveManager::new(),
+                    &logs,
+                    &archive,
+                    Duration::from_millis(200),
+                    &stop,
+                    &mut |path| {
+                        names.push(path.file_name().unwrap().to_string_lossy().into_owned());
+                        Ok(())
+                    },
+                );
+                added.map(|added| (added, names))
+            })
+        };
+
+        // Give the watcher a moment to register before writing
+        let manager = ArchiveManager::new();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !archive.exists() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        std::thread::sleep(Duration::from_millis(200));
+        fs::write(logs.join("app.log"), "started\n")?;
+        // New files are appended in place, so a poll can catch the archive mid-write
+        let entries = |archive: &Path| manager.list_archive(archive).unwrap_or_default();
+        while entries(&archive).is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        // Writing the same file again replaces its entry instead of stopping the watch
+        fs::write(logs.join("app.log"), "started\nstopped\n")?;
+        while manager.extract_entry(&archive, "app.log").ok().as_deref()
+            != Some(&b"started\nstopped\n"[..])
+            && Instant::now() < deadline
+        {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        fs::write(logs.join("other.log"), "more\n")?;
+        while entries(&archive).len() < 2 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        stop.store(true, Ordering::SeqCst);
+        let (added, names) = watcher.join().unwrap()?;
+        // A write can arrive as more than one batch, so app.log may be added again
+        assert_eq!(added, names.len());
+        let (last, earlier) = names.split_last().unwrap();
+        assert_eq!(last, "other.log");
+        assert!(earlier.len() >= 2 && earlier.iter().all(|name| name == "app.log"));
+        assert_eq!(manager.list_archive(&archive)?, ["app.log", "other.log"]);
+        assert_eq!(manager.extract_entry(&archive, "app.log")?, b"started\nstopped\n");
+        Ok(())
+    }
+}
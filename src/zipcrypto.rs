@@ -0,0 +1,190 @@
+//! Writing legacy PKWARE ("ZipCrypto") encrypted entries.
+//!
+//! The `zip` crate reads ZipCrypto but keeps its writer private, so archives are
+//! written unencrypted first and then rewritten here: each file's compressed data is
+//! prefixed with the 12-byte encryption header and run through the cipher, and the
+//! headers are patched for the 12 extra bytes and the encrypted flag. ZipCrypto is
+//! weak (known-plaintext attacks recover the keys in minutes); it exists for tools
+//! that can't open AES entries.
+use anyhow::{Context, Result, bail};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::central_dir;
+
+/// Bytes of the encryption header written before each entry's data
+const HEADER_LEN: u64 = 12;
+// General purpose flag: the entry is encrypted
+const ENCRYPTED_FLAG: u16 = 0x0001;
+// General purpose flag: CRC and sizes follow the data instead of the local header
+const DATA_DESCRIPTOR_FLAG: u16 = 0x0008;
+const CENTRAL_HEADER_LEN: usize = 46;
+const LOCAL_HEADER_LEN: usize = 30;
+/// Entry data is copied and encrypted this many bytes at a time
+const CHUNK_LEN: usize = 64 * 1024;
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+fn crc32_update(crc: u32, byte: u8) -> u32 {
+    CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+}
+
+/// The three cipher keys, initialised from a password
+struct Keys([u32; 3]);
+
+impl Keys {
+    fn new(password: &[u8]) -> Self {
+        let mut keys = Self([0x1234_5678, 0x2345_6789, 0x3456_7890]);
+        for &byte in password {
+            keys.update(byte);
+        }
+        keys
+    }
+
+    fn update(&mut self, plain: u8) {
+        let [k0, k1, k2] = &mut self.0;
+        *k0 = crc32_update(*k0, plain);
+        *k1 = k1.wrapping_add(*k0 & 0xff).wrapping_mul(134_775_813).wrapping_add(1);
+        *k2 = crc32_update(*k2, (*k1 >> 24) as u8);
+    }
+
+    fn encrypt(&mut self, data: &mut [u8]) {
+        for byte in data {
+            let temp = (self.0[2] | 2) as u16;
+            let mask = (temp.wrapping_mul(temp ^ 1) >> 8) as u8;
+            let plain = *byte;
+            *byte ^= mask;
+            self.update(plain);
+        }
+    }
+}
+
+/// Copy the archive in `source` to `dest` with every file entry encrypted under
+/// `password`. Directory entries are copied as they are. Entries written with a data
+/// descriptor or ZIP64 sizes are refused.
+pub fn encrypt_archive<R: Read + Seek, W: Write>(
+    source: &mut R,
+    dest: &mut W,
+    password: &str,
+) -> Result<()> {
+    let eocd = central_dir::find_eocd(source)?;
+    if eocd.is_zip64() {
+        bail!("ZipCrypto encryption is not supported for ZIP64 archives");
+    }
+    let cd_start = eocd.cd_end - eocd.cd_size;
+    let mut cd = vec![0u8; eocd.cd_size as usize];
+    source.seek(SeekFrom::Start(cd_start))?;
+    source.read_exact(&mut cd)?;
+    let mut tail = Vec::new();
+    source.seek(SeekFrom::Start(eocd.offset))?;
+    source.read_to_end(&mut tail)?;
+
+    let mut written = 0u64;
+    let mut chunk = vec![0u8; CHUNK_LEN];
+    let mut pos = 0;
+    while pos < cd.len() {
+        if cd.len() - pos < CENTRAL_HEADER_LEN {
+            bail!("Truncated central directory");
+        }
+        let record = &mut cd[pos..];
+        let name_len = le_u16(&record[28..]) as usize;
+        let extra_len = le_u16(&record[30..]) as usize;
+        let comment_len = le_u16(&record[32..]) as usize;
+        let name = String::from_utf8_lossy(&record[46..46 + name_len]).into_owned();
+        let flags = le_u16(&record[8..]);
+        let crc = le_u32(&record[16..]);
+        let compressed = le_u32(&record[20..]);
+        let offset = le_u32(&record[42..]);
+        if compressed == u32::MAX || offset == u32::MAX {
+            bail!("{name} is too large for ZipCrypto encryption");
+        }
+        if flags & DATA_DESCRIPTOR_FLAG != 0 {
+            bail!("{name} has a data descriptor, which ZipCrypto encryption doesn't support");
+        }
+        let encrypt = !name.ends_with('/') && flags & ENCRYPTED_FLAG == 0;
+        let new_size = compressed as u64 + if encrypt { HEADER_LEN } else { 0 };
+        let new_offset: u32 = written
+            .try_into()
+            .ok()
+            .filter(|&o| o != u32::MAX && new_size < u32::MAX as u64)
+            .with_context(|| format!("{name} is too large for ZipCrypto encryption"))?;
+
+        // Local header, name and extra field, then the data
+        source.seek(SeekFrom::Start(offset as u64))?;
+        let mut local = [0u8; LOCAL_HEADER_LEN];
+        source.read_exact(&mut local)?;
+        let local_extra = le_u16(&local[26..]) as u64 + le_u16(&local[28..]) as u64;
+        let mut rest = vec![0u8; local_extra as usize];
+        source.read_exact(&mut rest)?;
+        if encrypt {
+            let local_flags = le_u16(&local[6..]) | ENCRYPTED_FLAG;
+            local[6..8].copy_from_slice(&local_flags.to_le_bytes());
+            local[18..22].copy_from_slice(&(new_size as u32).to_le_bytes());
+            record[8..10].copy_from_slice(&(flags | ENCRYPTED_FLAG).to_le_bytes());
+            record[20..24].copy_from_slice(&(new_size as u32).to_le_bytes());
+        }
+        record[42..46].copy_from_slice(&new_offset.to_le_bytes());
+        dest.write_all(&local)?;
+        dest.write_all(&rest)?;
+        let mut keys = encrypt.then(|| Keys::new(password.as_bytes()));
+        if let Some(keys) = &mut keys {
+            let mut header = [0u8; HEADER_LEN as usize];
+            getrandom::fill(&mut header[..11])
+                .map_err(|err| anyhow::anyhow!("No random bytes for ZipCrypto: {err}"))?;
+            // Readers check a password against the high byte of the CRC
+            header[11] = (crc >> 24) as u8;
+            keys.encrypt(&mut header);
+            dest.write_all(&header)?;
+        }
+        // The cipher carries its state from chunk to chunk
+        let mut remaining = compressed as u64;
+        while remaining > 0 {
+            let len = remaining.min(CHUNK_LEN as u64) as usize;
+            source.read_exact(&mut chunk[..len])?;
+            if let Some(keys) = &mut keys {
+                keys.encrypt(&mut chunk[..len]);
+            }
+            dest.write_all(&chunk[..len])?;
+            remaining -= len as u64;
+        }
+        written += (LOCAL_HEADER_LEN as u64) + local_extra + new_size;
+        pos += CENTRAL_HEADER_LEN + name_len + extra_len + comment_len;
+    }
+
+    let cd_offset: u32 = written
+        .try_into()
+        .ok()
+        .filter(|&o| o != u32::MAX)
+        .context("The archive is too large for ZipCrypto encryption")?;
+    dest.write_all(&cd)?;
+    tail[16..20].copy_from_slice(&cd_offset.to_le_bytes());
+    dest.write_all(&tail)?;
+    Ok(())
+}
+
+fn le_u16(b: &[u8]) -> u16 {
+    u16::from_le_bytes([b[0], b[1]])
+}
+
+fn le_u32(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
@@ -292,6 +292,7 @@ fn test_special_characters_and_unicode() -> Result<()> {
         ("unicode_🦀.txt", "Hello 🦀 Rust! 你好世界"),
         ("spaces in name.txt", "File with spaces"),
         ("special!@#$%^&*().txt", "Special characters"),
+        ("文件.txt", "中文文件名"),
     ];
 
     for (filename, content) in &files {
@@ -318,6 +319,13 @@ fn test_special_characters_and_unicode() -> Result<()> {
         String::from_utf8_lossy(&output.stderr)
     );
 
+    // Names are listed exactly as given
+    let output = run_rp_command(&["list", archive_path.to_str().unwrap()])?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    for (filename, _) in &files {
+        assert!(listing.contains(filename), "{filename} missing from listing:\n{listing}");
+    }
+
     // Extract and verify
     fs::create_dir(&extract_dir)?;
     let output = run_rp_command(&[
@@ -484,3 +492,403 @@ fn test_cli_help_and_version() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_password_from_environment() -> Result<()> {
+    // Ensure release binary exists
+    if !Path::new("./target/release/rolypoly").exists() {
+        Command::new("cargo").args(["build", "--release"]).status()?;
+    }
+
+    let temp_dir = TempDir::new()?;
+    let work_dir = temp_dir.path();
+    let secret = work_dir.join("secret.txt");
+    fs::write(&secret, "env secret")?;
+    let archive_path = work_dir.join("env.zip");
+    let extract_dir = work_dir.join("extracted");
+
+    let output = Command::new("./target/release/rolypoly")
+        .args(["create", archive_path.to_str().unwrap(), secret.to_str().unwrap(), "--encrypt"])
+        .env("ROLYPOLY_PASSWORD", "from-env")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Encrypted create failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Without a password (and no terminal to prompt on) extraction must fail
+    let output = Command::new("./target/release/rolypoly")
+        .args(["extract", archive_path.to_str().unwrap(), "-o", extract_dir.to_str().unwrap()])
+        .env_remove("ROLYPOLY_PASSWORD")
+        .output()?;
+    assert!(!output.status.success(), "Extraction without a password should fail");
+
+    let output = Command::new("./target/release/rolypoly")
+        .args(["extract", archive_path.to_str().unwrap(), "-o", extract_dir.to_str().unwrap()])
+        .env("ROLYPOLY_PASSWORD", "from-env")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Extraction with env password failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(fs::read_to_string(extract_dir.join("secret.txt"))?, "env secret");
+
+    Ok(())
+}
+
+#[test]
+fn test_log_level_debug_writes_diagnostics() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let test_file = temp_dir.path().join("data.txt");
+    fs::write(&test_file, "log me")?;
+    let archive_path = temp_dir.path().join("logged.zip");
+
+    let output = run_rp_command(&[
+        "--log-level",
+        "debug",
+        "create",
+        archive_path.to_str().unwrap(),
+        test_file.to_str().unwrap(),
+    ])?;
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("DEBUG"), "missing debug output: {stderr}");
+    assert!(stderr.contains("creating archive"), "missing create event: {stderr}");
+    assert!(stderr.contains("archive created"), "missing completion event: {stderr}");
+
+    // Without the flag (and with RUST_LOG unset) nothing is logged
+    let output = Command::new("./target/release/rolypoly")
+        .args(["validate", archive_path.to_str().unwrap()])
+        .env_remove("RUST_LOG")
+        .output()?;
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("INFO"));
+    Ok(())
+}
+
+#[test]
+fn test_store_full_path_keeps_relative_directories() -> Result<()> {
+    if !Path::new("./target/release/rolypoly").exists() {
+        Command::new("cargo").args(["build", "--release"]).status()?;
+    }
+    let binary = fs::canonicalize("./target/release/rolypoly")?;
+
+    let temp_dir = TempDir::new()?;
+    let work_dir = temp_dir.path();
+    fs::create_dir_all(work_dir.join("sub/dir"))?;
+    fs::write(work_dir.join("sub/dir/file.txt"), "nested")?;
+
+    let output = Command::new(&binary)
+        .args(["create", "full.zip", "sub/dir/file.txt", "--store-full-path"])
+        .current_dir(work_dir)
+        .output()?;
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let output = Command::new(&binary)
+        .args(["list", "full.zip"])
+        .current_dir(work_dir)
+        .output()?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    assert!(listing.lines().any(|l| l.trim() == "sub/dir/file.txt"), "{listing}");
+
+    let output = Command::new(&binary)
+        .args(["create", "base.zip", "sub/dir/file.txt"])
+        .current_dir(work_dir)
+        .output()?;
+    assert!(output.status.success());
+    let output = Command::new(&binary)
+        .args(["list", "base.zip"])
+        .current_dir(work_dir)
+        .output()?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    assert!(listing.lines().any(|l| l.trim() == "file.txt"), "{listing}");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_progress_fd_receives_json_events() -> Result<()> {
+    use std::io::Read;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+
+    if !Path::new("./target/release/rolypoly").exists() {
+        Command::new("cargo").args(["build", "--release"]).status()?;
+    }
+    let temp_dir = TempDir::new()?;
+    create_test_files(temp_dir.path())?;
+    let archive_path = temp_dir.path().join("progress.zip");
+
+    // Both ends are close-on-exec, so commands spawned by tests running alongside this
+    // one can't keep the write end open; only this child gets it
+    let (mut read_end, write_end) = std::io::pipe()?;
+    let write_fd = write_end.as_raw_fd();
+    let mut command = Command::new("./target/release/rolypoly");
+    command
+        .args(["--progress-fd", &write_fd.to_string(), "create"])
+        .arg(&archive_path)
+        .arg(temp_dir.path().join("small.txt"))
+        .arg(temp_dir.path().join("medium.txt"))
+        .stdout(std::process::Stdio::piped());
+    // SAFETY: fcntl is async-signal-safe and only touches a descriptor this process owns
+    unsafe {
+        command.pre_exec(move || match libc::fcntl(write_fd, libc::F_SETFD, 0) {
+            -1 => Err(std::io::Error::last_os_error()),
+            _ => Ok(()),
+        });
+    }
+    let child = command.spawn()?;
+    // Only the child may hold the write end, so reading ends when it exits
+    drop(write_end);
+    let mut events = String::new();
+    read_end.read_to_string(&mut events)?;
+    let output = child.wait_with_output()?;
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let events: Vec<serde_json::Value> =
+        events.lines().map(serde_json::from_str).collect::<Result<_, _>>()?;
+    let kinds: Vec<&str> = events.iter().filter_map(|e| e["event"].as_str()).collect();
+    assert_eq!(kinds, ["start", "progress", "progress", "done"]);
+    assert_eq!(events[2]["current"], 2);
+    // Results stay on stdout, with no progress events mixed in
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("\"event\""), "{stdout}");
+
+    let output = run_rp_command(&["--progress-fd", "999", "list", "missing.zip"])?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not an open file descriptor"));
+    Ok(())
+}
+
+#[test]
+fn test_progress_every_throttles_json_events() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path().join("many");
+    fs::create_dir(&dir)?;
+    for i in 0..1005 {
+        fs::write(dir.join(format!("file_{i:04}.txt")), "x")?;
+    }
+    let archive_path = temp_dir.path().join("many.zip");
+
+    let output = run_rp_command(&[
+        "--json",
+        "--progress",
+        "--progress-every",
+        "100",
+        "create",
+        archive_path.to_str().unwrap(),
+        dir.to_str().unwrap(),
+    ])?;
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let progress: Vec<serde_json::Value> = stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|event| event["event"] == "progress")
+        .collect();
+    let current: Vec<u64> = progress.iter().filter_map(|e| e["current"].as_u64()).collect();
+    // One event per hundred entries, then the final one at 100%
+    let mut expected: Vec<u64> = (1..=10).map(|i| i * 100).collect();
+    expected.push(1005);
+    assert_eq!(current, expected);
+    assert_eq!(progress.last().unwrap()["pct"], 1.0);
+
+    // Extracting a single early entry skips the rest, which still count toward progress
+    let output = run_rp_command(&[
+        "--json",
+        "--progress",
+        "--progress-every",
+        "100",
+        "extract",
+        archive_path.to_str().unwrap(),
+        "many/file_0000.txt",
+        "-o",
+        temp_dir.path().join("out").to_str().unwrap(),
+    ])?;
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let events: Vec<serde_json::Value> = stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .collect();
+    let total = events.iter().find(|e| e["event"] == "start").unwrap()["total"]
+        .as_u64()
+        .unwrap();
+    let current: Vec<u64> = events
+        .iter()
+        .filter(|event| event["event"] == "progress")
+        .filter_map(|e| e["current"].as_u64())
+        .collect();
+    let mut expected: Vec<u64> = (1..=total / 100).map(|i| i * 100).collect();
+    expected.push(total);
+    assert_eq!(current, expected);
+
+    let output = run_rp_command(&["--progress-every", "0", "list", "missing.zip"])?;
+    assert!(!output.status.success());
+    Ok(())
+}
+
+#[test]
+fn test_list_reads_archive_from_stdin() -> Result<()> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let temp_dir = TempDir::new()?;
+    create_test_files(temp_dir.path())?;
+    let archive_path = temp_dir.path().join("piped.zip");
+    let output = run_rp_command(&[
+        "create",
+        archive_path.to_str().unwrap(),
+        temp_dir.path().join("small.txt").to_str().unwrap(),
+        temp_dir.path().join("subdir").to_str().unwrap(),
+    ])?;
+    assert!(output.status.success());
+
+    for command in ["list", "stats", "validate"] {
+        let mut child = Command::new("./target/release/rolypoly")
+            .args([command, "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child.stdin.take().unwrap().write_all(&fs::read(&archive_path)?)?;
+        let output = child.wait_with_output()?;
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        match command {
+            "list" => {
+                assert!(stdout.lines().any(|l| l.trim() == "small.txt"), "{stdout}");
+                assert!(stdout.lines().any(|l| l.trim() == "subdir/nested.txt"), "{stdout}");
+            }
+            "stats" => assert!(stdout.contains("Files: 3"), "{stdout}"),
+            _ => assert!(stdout.contains("Archive is valid"), "{stdout}"),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_max_input_stops_oversized_stdin() -> Result<()> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    // Ensure release binary exists
+    if !Path::new("./target/release/rolypoly").exists() {
+        Command::new("cargo").args(["build", "--release"]).status()?;
+    }
+    let mut child = Command::new("./target/release/rolypoly")
+        .args(["list", "-", "--max-input", "64K"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // rolypoly may stop reading before all of it is written
+    let _ = child.stdin.take().unwrap().write_all(&vec![0u8; 1 << 20]);
+    let output = child.wait_with_output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("larger than the --max-input limit of 65536 bytes"), "{stderr}");
+    Ok(())
+}
+
+#[test]
+fn test_create_files_named_like_flags() -> Result<()> {
+    if !Path::new("./target/release/rolypoly").exists() {
+        Command::new("cargo").args(["build", "--release"]).status()?;
+    }
+    let binary = fs::canonicalize("./target/release/rolypoly")?;
+
+    let temp_dir = TempDir::new()?;
+    let work_dir = temp_dir.path();
+    for name in ["-weird.txt", "--json", "realfile"] {
+        fs::write(work_dir.join(name), name)?;
+    }
+
+    let output = Command::new(&binary)
+        .args(["create", "out.zip", "--", "--json", "-weird.txt", "realfile"])
+        .current_dir(work_dir)
+        .output()?;
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // `--json` went into the archive rather than switching the output to JSON
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("→ Creating"));
+    let output = Command::new(&binary).args(["list", "out.zip"]).current_dir(work_dir).output()?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    for name in ["-weird.txt", "--json", "realfile"] {
+        assert!(listing.lines().any(|l| l.trim() == name), "{name}: {listing}");
+    }
+
+    // Without the separator the name is still read as a flag
+    let output = Command::new(&binary)
+        .args(["create", "flag.zip", "-weird.txt"])
+        .current_dir(work_dir)
+        .output()?;
+    assert!(!output.status.success());
+    assert!(!work_dir.join("flag.zip").exists());
+    Ok(())
+}
+
+#[test]
+fn test_temp_dir_holds_piped_archive() -> Result<()> {
+    use std::io::Write;
+    use std::process::Stdio;
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new()?;
+    create_test_files(temp_dir.path())?;
+    let archive_path = temp_dir.path().join("piped.zip");
+    let output = run_rp_command(&[
+        "create",
+        archive_path.to_str().unwrap(),
+        temp_dir.path().join("small.txt").to_str().unwrap(),
+    ])?;
+    assert!(output.status.success());
+
+    for use_env in [false, true] {
+        let scratch = temp_dir.path().join(format!("scratch-{use_env}"));
+        fs::create_dir(&scratch)?;
+        let mut command = Command::new("./target/release/rolypoly");
+        match use_env {
+            true => command.env("ROLYPOLY_TMPDIR", &scratch).args(["list", "-"]),
+            false => command.args(["list", "-", "--temp-dir", scratch.to_str().unwrap()]),
+        };
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(&fs::read(&archive_path)?)?;
+        // The copy of stdin is created in the scratch directory while stdin is still open
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while fs::read_dir(&scratch)?.next().is_none() {
+            assert!(Instant::now() < deadline, "no temporary file in {}", scratch.display());
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        drop(stdin);
+        let output = child.wait_with_output()?;
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert!(String::from_utf8_lossy(&output.stdout).contains("small.txt"));
+        // and removed once the command is done
+        assert_eq!(fs::read_dir(&scratch)?.count(), 0);
+    }
+
+    let output = run_rp_command(&[
+        "list",
+        archive_path.to_str().unwrap(),
+        "--temp-dir",
+        temp_dir.path().join("missing").to_str().unwrap(),
+    ])?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not exist"));
+
+    // A missing directory in the environment is only a warning
+    let output = Command::new("./target/release/rolypoly")
+        .env("ROLYPOLY_TMPDIR", temp_dir.path().join("missing"))
+        .args(["list", archive_path.to_str().unwrap()])
+        .output()?;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("small.txt"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("warning: ignoring ROLYPOLY_TMPDIR"));
+    Ok(())
+}
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+use rolypoly::archive::ArchiveManager;
 use std::fs;
 use std::path::Path;
 use tempfile::TempDir;
@@ -10,27 +11,15 @@ pub fn create_test_file(dir: &TempDir, name: &str, content: &str) -> std::path::
     file_path
 }
 
+/// Write `test.zip` holding `files` as `(name, contents)` pairs, built in memory
 pub fn create_test_archive(dir: &TempDir, files: &[(&str, &str)]) -> std::path::PathBuf {
     let archive_path = dir.path().join("test.zip");
-
-    // Create test files
-    let mut file_paths = Vec::new();
-    for (name, content) in files {
-        let file_path = create_test_file(dir, name, content);
-        file_paths.push(file_path);
-    }
-
-    // Create archive using our CLI
-    let output = std::process::Command::new("cargo")
-        .args(["run", "--bin", "rolypoly", "--", "create", archive_path.to_str().unwrap()])
-        .args(file_paths.iter().map(|p| p.to_str().unwrap()))
-        .output()
+    let entries: Vec<(&str, &[u8])> =
+        files.iter().map(|(name, content)| (*name, content.as_bytes())).collect();
+    let bytes = ArchiveManager::new()
+        .create_archive_in_memory(&entries)
         .expect("Failed to create test archive");
-
-    if !output.status.success() {
-        panic!("Failed to create test archive: {}", String::from_utf8_lossy(&output.stderr));
-    }
-
+    fs::write(&archive_path, bytes).unwrap();
     archive_path
 }
 
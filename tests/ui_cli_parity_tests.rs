@@ -5,6 +5,42 @@ use tempfile::TempDir;
 
 mod test_helpers;
 
+/// The CLI and the library share one write path, so the same inputs give the same bytes
+#[test]
+fn test_cli_gui_parity_archive_bytes() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("project");
+    fs::create_dir_all(input.join("src/nested"))?;
+    fs::create_dir_all(input.join("empty"))?;
+    fs::write(input.join("README.md"), "# Project\n".repeat(50))?;
+    fs::write(input.join("src/main.rs"), "fn main() {}\n")?;
+    fs::write(
+        input.join("src/nested/data.bin"),
+        (0..=255u8).cycle().take(10_000).collect::<Vec<_>>(),
+    )?;
+    let single = temp_dir.path().join("notes.txt");
+    fs::write(&single, "loose file")?;
+
+    let cli_archive = temp_dir.path().join("cli.zip");
+    let cli_output = Command::new("cargo")
+        .args(["run", "--bin", "rolypoly", "--", "create"])
+        .arg(&cli_archive)
+        .arg(&input)
+        .arg(&single)
+        .output()?;
+    assert!(
+        cli_output.status.success(),
+        "CLI archive creation failed: {}",
+        String::from_utf8_lossy(&cli_output.stderr)
+    );
+
+    let gui_archive = temp_dir.path().join("gui.zip");
+    rolypoly::archive::ArchiveManager::new().create_archive(&gui_archive, &[&input, &single])?;
+
+    assert_eq!(fs::read(&cli_archive)?, fs::read(&gui_archive)?, "archive bytes differ");
+    Ok(())
+}
+
 /// Test that CLI and GUI (through ArchiveManager) produce identical results
 #[tokio::test]
 async fn test_cli_gui_parity_create_archive() -> Result<()> {